@@ -0,0 +1,120 @@
+use crate::{GPUSprite, Vec2};
+
+// Most samples taken along each axis of the overlap by pixels_overlap, however small the texels.
+const MAX_SAMPLES: f32 = 256.0;
+
+// Which texels of an image are solid, one bit each, for pixel-perfect collision. Made from the
+// RgbaImage load_texture hands back alongside the texture, and given to the sprite groups drawing
+// that texture with SpriteRender::set_group_alpha_mask.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlphaMask {
+    width: u32,
+    height: u32,
+    // Row by row from the top, like the image.
+    bits: Vec<u64>,
+}
+
+impl AlphaMask {
+    // Texels with an alpha of at least `threshold` are solid. 128 matches what the default shader
+    // leaves undrawn.
+    pub fn from_image(img: &image::RgbaImage, threshold: u8) -> Self {
+        let (width, height) = img.dimensions();
+        let mut bits = vec![0; (width as usize * height as usize).div_ceil(64)];
+        for (i, pixel) in img.pixels().enumerate() {
+            if pixel[3] >= threshold {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+        Self {
+            width,
+            height,
+            bits,
+        }
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn is_solid(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let i = y as usize * self.width as usize + x as usize;
+        self.bits[i / 64] & (1 << (i % 64)) != 0
+    }
+    // At texture coordinates, like GPUSprite::sheet_region's: 0 to 1 from the top left. Outside
+    // that they wrap around, like a repeating sampler.
+    pub fn is_solid_at(&self, uv: impl Into<Vec2>) -> bool {
+        let uv = uv.into();
+        let x = (uv.x.rem_euclid(1.0) * self.width as f32) as u32;
+        let y = (uv.y.rem_euclid(1.0) * self.height as f32) as u32;
+        self.is_solid(
+            x.min(self.width.saturating_sub(1)),
+            y.min(self.height.saturating_sub(1)),
+        )
+    }
+}
+
+// Whether two sprites overlap where they're both solid. A sprite given a mask (that of the texture
+// its sheet_region is in) is only solid where the mask is; one without is solid all over, so this
+// also works for a masked sprite against a plain hitbox. The shared area is sampled at the size of
+// the smaller texels.
+pub fn pixels_overlap(
+    a: &GPUSprite,
+    mask_a: Option<&AlphaMask>,
+    b: &GPUSprite,
+    mask_b: Option<&AlphaMask>,
+) -> bool {
+    let Some(overlap) = a.bounds().intersection(&b.bounds()) else {
+        return false;
+    };
+    if mask_a.is_none() && mask_b.is_none() {
+        return true;
+    }
+    let (texel_a, texel_b) = (texel_size(a, mask_a), texel_size(b, mask_b));
+    let step_x = texel_a[0]
+        .min(texel_b[0])
+        .clamp(overlap.width / MAX_SAMPLES, overlap.width);
+    let step_y = texel_a[1]
+        .min(texel_b[1])
+        .clamp(overlap.height / MAX_SAMPLES, overlap.height);
+    let mut y = overlap.y + step_y / 2.0;
+    while y < overlap.top() {
+        let mut x = overlap.x + step_x / 2.0;
+        while x < overlap.right() {
+            if solid_at(a, mask_a, [x, y]) && solid_at(b, mask_b, [x, y]) {
+                return true;
+            }
+            x += step_x;
+        }
+        y += step_y;
+    }
+    false
+}
+
+// How big one of the sprite's texels is in the world; unmasked sprites don't have any.
+fn texel_size(sprite: &GPUSprite, mask: Option<&AlphaMask>) -> [f32; 2] {
+    let Some(mask) = mask else {
+        return [f32::INFINITY; 2];
+    };
+    let [_, _, w, h] = sprite.screen_region;
+    let [_, _, sheet_w, sheet_h] = sprite.sheet_region;
+    [
+        (w / (sheet_w * mask.width as f32)).abs(),
+        (h / (sheet_h * mask.height as f32)).abs(),
+    ]
+}
+
+fn solid_at(sprite: &GPUSprite, mask: Option<&AlphaMask>, point: [f32; 2]) -> bool {
+    let Some(mask) = mask else {
+        return true;
+    };
+    // Dividing by the signed size keeps flipped sprites the right way round.
+    let [x, y, w, h] = sprite.screen_region;
+    let (fx, fy) = ((point[0] - x) / w, (point[1] - y) / h);
+    let [sheet_x, sheet_y, sheet_w, sheet_h] = sprite.sheet_region;
+    // Texture coordinates run down from the top, the world up from the bottom.
+    mask.is_solid_at([sheet_x + fx * sheet_w, sheet_y + (1.0 - fy) * sheet_h])
+}
@@ -0,0 +1,87 @@
+use crate::{AnimationClip, AnimationFrame, TextureError};
+use image::{AnimationDecoder, ImageFormat, RgbaImage};
+use std::io::Cursor;
+use std::path::Path;
+
+// Transparent texels between frames on the atlas, so linear filtering doesn't bleed one frame's
+// edge into the next.
+const PADDING: u32 = 1;
+// Browsers play GIF frames with a delay shorter than this at 100ms, and GIFs are made to look
+// right in browsers.
+const MIN_GIF_DELAY_MS: f32 = 20.0;
+const DEFAULT_GIF_DELAY_MS: f32 = 100.0;
+
+// Decodes every frame of an animated GIF or PNG onto one atlas, left to right and then down, and
+// makes a looping clip that steps through them. Anything else (including a PNG that isn't
+// animated) comes out as a clip of its one frame.
+pub(crate) fn decode_animation(
+    bytes: &[u8],
+    path: &Path,
+) -> Result<(RgbaImage, AnimationClip), TextureError> {
+    let format = image::guess_format(bytes).or_else(|_| ImageFormat::from_path(path));
+    let error = |e| TextureError::from_image(path, e);
+    let frames: Vec<image::Frame> = match format {
+        Ok(ImageFormat::Gif) => image::codecs::gif::GifDecoder::new(Cursor::new(bytes))
+            .and_then(|decoder| decoder.into_frames().collect_frames())
+            .map_err(error)?,
+        Ok(ImageFormat::Png) => {
+            let decoder = image::codecs::png::PngDecoder::new(Cursor::new(bytes)).map_err(error)?;
+            if !decoder.is_apng() {
+                return Ok(single_frame(decode_still(bytes, path)?));
+            }
+            decoder
+                .apng()
+                .into_frames()
+                .collect_frames()
+                .map_err(error)?
+        }
+        _ => return Ok(single_frame(decode_still(bytes, path)?)),
+    };
+    if frames.is_empty() {
+        return Ok(single_frame(decode_still(bytes, path)?));
+    }
+    let is_gif = matches!(format, Ok(ImageFormat::Gif));
+    let (width, height) = frames[0].buffer().dimensions();
+    let columns = (frames.len() as f32).sqrt().ceil() as u32;
+    let rows = (frames.len() as u32).div_ceil(columns);
+    let atlas_width = columns * (width + PADDING) - PADDING;
+    let atlas_height = rows * (height + PADDING) - PADDING;
+    let mut atlas = RgbaImage::new(atlas_width, atlas_height);
+    let mut clip = AnimationClip::new(Vec::with_capacity(frames.len()), true);
+    for (i, frame) in frames.iter().enumerate() {
+        let (x, y) = (
+            (i as u32 % columns) * (width + PADDING),
+            (i as u32 / columns) * (height + PADDING),
+        );
+        image::imageops::replace(&mut atlas, frame.buffer(), x as i64, y as i64);
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let mut delay = numer as f32 / denom.max(1) as f32;
+        if is_gif && delay < MIN_GIF_DELAY_MS {
+            delay = DEFAULT_GIF_DELAY_MS;
+        }
+        clip.frames.push(AnimationFrame {
+            sheet_region: [
+                x as f32 / atlas_width as f32,
+                y as f32 / atlas_height as f32,
+                width as f32 / atlas_width as f32,
+                height as f32 / atlas_height as f32,
+            ],
+            duration: delay / 1000.0,
+        });
+    }
+    Ok((atlas, clip))
+}
+
+fn decode_still(bytes: &[u8], path: &Path) -> Result<RgbaImage, TextureError> {
+    crate::gpu::decode_image(bytes, path)
+        .map(|img| img.to_rgba8())
+        .map_err(|e| TextureError::from_image(path, e))
+}
+
+fn single_frame(img: RgbaImage) -> (RgbaImage, AnimationClip) {
+    let frame = AnimationFrame {
+        sheet_region: [0.0, 0.0, 1.0, 1.0],
+        duration: 1.0,
+    };
+    (img, AnimationClip::new(vec![frame], true))
+}
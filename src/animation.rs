@@ -0,0 +1,240 @@
+use crate::{SpriteHandle, SpriteRender};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// Shortest a frame can last, so a clip of zero-length frames can't spin forever.
+const MIN_FRAME_TIME: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnimationFrame {
+    // Becomes the sprite's sheet_region while the frame shows.
+    pub sheet_region: [f32; 4],
+    // Seconds.
+    pub duration: f32,
+}
+
+// Something that happens on a frame of a clip, e.g. "footstep" on frame 3 of a walk cycle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameEvent {
+    pub frame: usize,
+    pub name: String,
+}
+
+// A sequence of sheet regions for Animator to flip a sprite through. Share one between sprites
+// by wrapping it in an Arc.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnimationClip {
+    pub frames: Vec<AnimationFrame>,
+    // Goes back to the first frame after the last; otherwise stops on the last.
+    pub looping: bool,
+    pub events: Vec<FrameEvent>,
+}
+
+impl AnimationClip {
+    pub fn new(frames: Vec<AnimationFrame>, looping: bool) -> Self {
+        Self {
+            frames,
+            looping,
+            events: Vec::new(),
+        }
+    }
+    // `count` frames of the same size laid out left to right on a sprite sheet, starting at
+    // `first`, each shown for `frame_duration` seconds.
+    pub fn from_strip(first: [f32; 4], count: usize, frame_duration: f32, looping: bool) -> Self {
+        let frames = (0..count)
+            .map(|i| AnimationFrame {
+                sheet_region: [first[0] + first[2] * i as f32, first[1], first[2], first[3]],
+                duration: frame_duration,
+            })
+            .collect();
+        Self::new(frames, looping)
+    }
+    // Fires `name` every time the clip reaches `frame`.
+    pub fn add_event(&mut self, frame: usize, name: impl Into<String>) {
+        self.events.push(FrameEvent {
+            frame,
+            name: name.into(),
+        });
+    }
+    // Seconds for one pass through all the frames.
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+// One of a clip's events, as Animator::take_events hands it out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnimationEvent {
+    pub sprite: SpriteHandle,
+    pub name: String,
+    pub frame: usize,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Playback {
+    clip: Arc<AnimationClip>,
+    frame: usize,
+    // Seconds into the current frame.
+    time: f32,
+    speed: f32,
+    finished: bool,
+    // The frame hasn't been put on the sprite (nor its events fired) yet.
+    entered: bool,
+}
+
+// Plays clips on sprites by setting their sheet_region as time goes on. The Engine keeps one
+// (Engine::animator), steps it once a frame after the fixed updates, and hands the frame events
+// to Game::animation_event before update (playing any sound Audio::set_event_sound gave them).
+//
+// With the serde feature it saves what's playing where, and how far along. Each playback saves
+// its own copy of its clip, so a restored one is no longer the same Arc as the game's and
+// play_if_new will start the game's over once.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Animator {
+    #[cfg_attr(feature = "serde", serde(with = "playing_as_list"))]
+    playing: BTreeMap<SpriteHandle, Playback>,
+    // Not saved; they'd be handed out again after loading.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    events: Vec<AnimationEvent>,
+}
+
+impl Animator {
+    // Starts `clip` on `sprite` from its first frame, instead of whatever it was playing.
+    pub fn play(&mut self, sprite: SpriteHandle, clip: Arc<AnimationClip>) {
+        self.playing.insert(
+            sprite,
+            Playback {
+                clip,
+                frame: 0,
+                time: 0.0,
+                speed: 1.0,
+                finished: false,
+                entered: false,
+            },
+        );
+    }
+    // Like play, but leaves the animation alone if `clip` is already on `sprite`, so it can be
+    // called every frame with whichever clip the sprite's state calls for.
+    pub fn play_if_new(&mut self, sprite: SpriteHandle, clip: &Arc<AnimationClip>) {
+        let same = self
+            .playing
+            .get(&sprite)
+            .is_some_and(|playback| Arc::ptr_eq(&playback.clip, clip));
+        if !same {
+            self.play(sprite, clip.clone());
+        }
+    }
+    // Leaves the sprite on whatever frame it's showing.
+    pub fn stop(&mut self, sprite: SpriteHandle) {
+        self.playing.remove(&sprite);
+    }
+    pub fn clear(&mut self) {
+        self.playing.clear();
+    }
+    // 2.0 plays twice as fast; 0 holds the current frame.
+    pub fn set_speed(&mut self, sprite: SpriteHandle, speed: f32) {
+        if let Some(playback) = self.playing.get_mut(&sprite) {
+            playback.speed = speed.max(0.0);
+        }
+    }
+    pub fn clip(&self, sprite: SpriteHandle) -> Option<&Arc<AnimationClip>> {
+        self.playing.get(&sprite).map(|playback| &playback.clip)
+    }
+    pub fn current_frame(&self, sprite: SpriteHandle) -> Option<usize> {
+        self.playing.get(&sprite).map(|playback| playback.frame)
+    }
+    // Whether a clip that doesn't loop has reached its last frame and stayed there.
+    pub fn is_finished(&self, sprite: SpriteHandle) -> bool {
+        self.playing
+            .get(&sprite)
+            .is_some_and(|playback| playback.finished)
+    }
+    // Moves every animation on by `dt` seconds, setting the sheet_region of sprites whose frame
+    // changed. Sprites that don't exist (any more) are skipped, not forgotten.
+    pub fn update(&mut self, sprites: &mut SpriteRender, dt: f32) {
+        for (&sprite, playback) in &mut self.playing {
+            if playback.clip.frames.is_empty()
+                || sprite.group >= sprites.group_count()
+                || sprite.index >= sprites.get_sprites(sprite.group).len()
+            {
+                continue;
+            }
+            let mut changed = !playback.entered;
+            if !playback.entered {
+                playback.entered = true;
+                fire(&mut self.events, sprite, &playback.clip, 0);
+            }
+            playback.time += dt * playback.speed;
+            while !playback.finished {
+                let duration = playback.clip.frames[playback.frame]
+                    .duration
+                    .max(MIN_FRAME_TIME);
+                if playback.time < duration {
+                    break;
+                }
+                if playback.frame + 1 == playback.clip.frames.len() {
+                    if !playback.clip.looping {
+                        playback.finished = true;
+                        break;
+                    }
+                    playback.frame = 0;
+                } else {
+                    playback.frame += 1;
+                }
+                playback.time -= duration;
+                changed = true;
+                fire(&mut self.events, sprite, &playback.clip, playback.frame);
+            }
+            if changed {
+                sprites.get_sprites_mut(sprite.group, sprite.index..sprite.index + 1)[0]
+                    .sheet_region = playback.clip.frames[playback.frame].sheet_region;
+            }
+        }
+    }
+    // The events of frames reached since the last call, oldest first.
+    pub fn take_events(&mut self) -> Vec<AnimationEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+// As a list of pairs, since formats like JSON only have maps with string keys.
+#[cfg(feature = "serde")]
+mod playing_as_list {
+    use super::{Playback, SpriteHandle};
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::BTreeMap;
+
+    pub(super) fn serialize<S: Serializer>(
+        playing: &BTreeMap<SpriteHandle, Playback>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(playing)
+    }
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<SpriteHandle, Playback>, D::Error> {
+        let list = Vec::<(SpriteHandle, Playback)>::deserialize(deserializer)?;
+        Ok(list.into_iter().collect())
+    }
+}
+
+fn fire(
+    events: &mut Vec<AnimationEvent>,
+    sprite: SpriteHandle,
+    clip: &AnimationClip,
+    frame: usize,
+) {
+    for event in clip.events.iter().filter(|event| event.frame == frame) {
+        events.push(AnimationEvent {
+            sprite,
+            name: event.name.clone(),
+            frame,
+        });
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{AssetId, AssetKind, AssetLoader, AssetManifest, WGPU};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+enum Loaded {
+    Bytes(Vec<u8>),
+    Texture(wgpu::Texture, image::RgbaImage),
+    #[cfg(feature = "audio")]
+    Sound(crate::AudioClip),
+    // The bytes went to Audio, for play_music to find by path.
+    #[cfg(feature = "audio")]
+    Music,
+}
+
+struct Pending {
+    name: String,
+    path: PathBuf,
+    kind: AssetKind,
+    id: AssetId,
+}
+
+// Assets loaded from an AssetManifest, looked up by the names it gives them. The Engine keeps one
+// (Engine::assets) and collects what's finished loading at the start of every frame, so a game
+// preloads a batch, draws a loading screen until is_loading turns false, and from then on asks
+// for "player" rather than keeping the texture around itself:
+//
+//     engine.assets.preload(&manifest, None); // in init: everything not in a bundle
+//     engine.assets.preload(&manifest, Some("castle")); // when the castle level starts
+//     ...
+//     let player = engine.assets.texture("player").unwrap();
+//
+// Sounds and music also go into Engine::audio's cache, so play_sound and play_music can use
+// their paths (see path) without loading them again.
+//
+// Each preload counts as a use of the names in it and each release gives one back; a name is
+// unloaded when nothing uses it, so assets two levels share stay loaded between them:
+//
+//     engine.assets.release(&manifest, Some("castle")); // when the castle level ends
+//
+// Unloaded textures are freed with Engine::unload_texture.
+pub struct Assets {
+    loader: AssetLoader,
+    pending: Vec<Pending>,
+    loaded: HashMap<String, (PathBuf, Loaded)>,
+    failed: Vec<String>,
+    // Preloads not yet released, by name.
+    refs: HashMap<String, usize>,
+    // Unloaded since the Engine last took them, to be let go of properly.
+    evicted: Vec<(PathBuf, Loaded)>,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Assets {
+    pub fn new() -> Self {
+        Self {
+            loader: AssetLoader::new(),
+            pending: Vec::new(),
+            loaded: HashMap::new(),
+            failed: Vec::new(),
+            refs: HashMap::new(),
+            evicted: Vec::new(),
+        }
+    }
+
+    // Starts loading the manifest's entries in `bundle` (or those in no bundle, for None) in the
+    // background. Names already loaded from the same path, or on their way, are skipped; one
+    // loaded from a different path is replaced once the new file is in.
+    pub fn preload(&mut self, manifest: &AssetManifest, bundle: Option<&str>) {
+        if self.pending.is_empty() {
+            // So progress counts this batch alone.
+            self.loader = AssetLoader::new();
+        }
+        for entry in manifest.bundle(bundle) {
+            *self.refs.entry(entry.name.clone()).or_default() += 1;
+            let loaded = self
+                .loaded
+                .get(&entry.name)
+                .is_some_and(|(path, _)| *path == entry.path);
+            let on_the_way = self
+                .pending
+                .iter()
+                .any(|pending| pending.name == entry.name && pending.path == entry.path);
+            if loaded || on_the_way {
+                continue;
+            }
+            let id = match entry.kind {
+                AssetKind::Bytes => self.loader.queue_bytes(&entry.path),
+                AssetKind::Texture => self.loader.queue_image(&entry.path),
+                #[cfg(feature = "audio")]
+                AssetKind::Sound => self.loader.queue_sound(&entry.path),
+                #[cfg(feature = "audio")]
+                AssetKind::Music => self.loader.queue_bytes(&entry.path),
+            };
+            self.failed.retain(|name| *name != entry.name);
+            // A name queued from another path before now gets this one instead.
+            self.pending.retain(|pending| pending.name != entry.name);
+            self.pending.push(Pending {
+                name: entry.name.clone(),
+                path: entry.path.clone(),
+                kind: entry.kind,
+                id,
+            });
+        }
+    }
+    // Gives back the uses a preload of the same bundle took, unloading the names nothing else
+    // uses any more.
+    pub fn release(&mut self, manifest: &AssetManifest, bundle: Option<&str>) {
+        for entry in manifest.bundle(bundle) {
+            let Some(refs) = self.refs.get_mut(&entry.name) else {
+                continue;
+            };
+            *refs -= 1;
+            if *refs == 0 {
+                self.unload(&entry.name);
+            }
+        }
+    }
+    // Unloads `name` whatever still uses it, or stops it loading.
+    pub fn unload(&mut self, name: &str) {
+        self.refs.remove(name);
+        self.pending.retain(|pending| pending.name != name);
+        self.failed.retain(|failed| failed != name);
+        if let Some(loaded) = self.loaded.remove(name) {
+            self.evicted.push(loaded);
+        }
+    }
+    // Unloads everything.
+    pub fn clear(&mut self) {
+        let names: Vec<String> = self
+            .loaded
+            .keys()
+            .chain(self.refs.keys())
+            .cloned()
+            .collect();
+        for name in names {
+            self.unload(&name);
+        }
+        self.pending.clear();
+    }
+    // How many preloads not yet released `name` was in.
+    pub fn refs(&self, name: &str) -> usize {
+        self.refs.get(name).copied().unwrap_or(0)
+    }
+
+    // How much of what's being preloaded has arrived, from 0 to 1 (1 when nothing is).
+    pub fn progress(&self) -> f32 {
+        if self.pending.is_empty() {
+            1.0
+        } else {
+            self.loader.progress()
+        }
+    }
+    pub fn is_loading(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    // Names whose files couldn't be loaded (the errors are logged), until they're preloaded again.
+    pub fn failed(&self) -> &[String] {
+        &self.failed
+    }
+
+    // Whether `name` has been loaded.
+    pub fn contains(&self, name: &str) -> bool {
+        self.loaded.contains_key(name)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.loaded.keys().map(String::as_str)
+    }
+    // The path `name` was loaded from, e.g. for Audio::play_music.
+    pub fn path(&self, name: &str) -> Option<&Path> {
+        self.loaded.get(name).map(|(path, _)| path.as_path())
+    }
+    pub fn texture(&self, name: &str) -> Option<&wgpu::Texture> {
+        match self.loaded.get(name)? {
+            (_, Loaded::Texture(texture, _)) => Some(texture),
+            _ => None,
+        }
+    }
+    // The pixels a texture was made from, e.g. for AlphaMask.
+    pub fn image(&self, name: &str) -> Option<&image::RgbaImage> {
+        match self.loaded.get(name)? {
+            (_, Loaded::Texture(_, img)) => Some(img),
+            _ => None,
+        }
+    }
+    pub fn bytes(&self, name: &str) -> Option<&[u8]> {
+        match self.loaded.get(name)? {
+            (_, Loaded::Bytes(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+    #[cfg(feature = "audio")]
+    pub fn sound(&self, name: &str) -> Option<&crate::AudioClip> {
+        match self.loaded.get(name)? {
+            (_, Loaded::Sound(clip)) => Some(clip),
+            _ => None,
+        }
+    }
+
+    // The textures of what was unloaded since the last call, for Engine::unload_texture. Sounds
+    // and music are taken out of Audio's cache here, unless another name has the same file.
+    pub(crate) fn take_evicted(
+        &mut self,
+        #[cfg(feature = "audio")] audio: &mut crate::Audio,
+    ) -> Vec<wgpu::Texture> {
+        let mut textures = Vec::new();
+        for evicted in std::mem::take(&mut self.evicted) {
+            match evicted {
+                (_, Loaded::Texture(texture, _)) => textures.push(texture),
+                #[cfg(feature = "audio")]
+                (path, Loaded::Sound(_) | Loaded::Music) => {
+                    if !self.loaded.values().any(|(other, _)| *other == path) {
+                        audio.unload(&path);
+                    }
+                }
+                (_, Loaded::Bytes(_)) => {}
+            }
+        }
+        textures
+    }
+    // Takes in whatever finished loading since the last call.
+    pub(crate) fn update(
+        &mut self,
+        gpu: &WGPU,
+        #[cfg(feature = "audio")] audio: &mut crate::Audio,
+    ) {
+        if self.pending.is_empty() {
+            return;
+        }
+        self.loader.poll();
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.retain(|asset| {
+            let outcome = match asset.kind {
+                AssetKind::Bytes => self
+                    .loader
+                    .take_bytes(asset.id)
+                    .map(|bytes| bytes.map(Loaded::Bytes).map_err(|e| e.to_string())),
+                AssetKind::Texture => self
+                    .loader
+                    .take_texture(asset.id, gpu, Some(&asset.name))
+                    .map(|texture| {
+                        texture
+                            .map(|(texture, img)| Loaded::Texture(texture, img))
+                            .map_err(|e| e.to_string())
+                    }),
+                #[cfg(feature = "audio")]
+                AssetKind::Sound => self.loader.take_sound(asset.id).map(|clip| {
+                    clip.map(|clip| {
+                        audio.insert_clip(&asset.path, clip.clone());
+                        Loaded::Sound(clip)
+                    })
+                    .map_err(|e| e.to_string())
+                }),
+                #[cfg(feature = "audio")]
+                AssetKind::Music => self.loader.take_bytes(asset.id).map(|bytes| {
+                    bytes
+                        .map_err(|e| e.to_string())
+                        .and_then(|bytes| {
+                            audio
+                                .insert_music(&asset.path, bytes.into())
+                                .map_err(|e| e.to_string())
+                        })
+                        .map(|()| Loaded::Music)
+                }),
+            };
+            match outcome {
+                None => true,
+                Some(Ok(loaded)) => {
+                    self.loaded
+                        .insert(asset.name.clone(), (asset.path.clone(), loaded));
+                    false
+                }
+                Some(Err(e)) => {
+                    log::error!(
+                        "couldn't preload {} from {}: {e}",
+                        asset.name,
+                        asset.path.display()
+                    );
+                    self.failed.push(asset.name.clone());
+                    false
+                }
+            }
+        });
+        self.pending = pending;
+    }
+}
@@ -0,0 +1,219 @@
+// Reads a whole asset file. On native `path` is a filesystem path; on the web it's a URL
+// relative to the page, fetched over HTTP, since there's no filesystem to read from.
+//
+// Relative paths are looked for in the mounted folders and archives (newest first, so a mods
+// folder mounted after the base game's wins), then under the asset root, then as given (relative
+// to the working directory, as before there was a root). Absolute paths are read as they are.
+// The settings are global, since sounds and loader threads read assets as well as the Engine.
+use crate::PakArchive;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+enum Mount {
+    Dir(PathBuf),
+    Archive(Arc<PakArchive>),
+}
+
+struct Vfs {
+    root: PathBuf,
+    mounts: Vec<Mount>,
+}
+
+// Where an asset was found.
+pub(crate) enum Location {
+    File(PathBuf),
+    // The archive and the file's name in it.
+    Archive(Arc<PakArchive>, String),
+}
+
+impl Location {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn read(&self) -> io::Result<Vec<u8>> {
+        match self {
+            Location::File(path) => std::fs::read(path),
+            Location::Archive(archive, name) => archive.read(name),
+        }
+    }
+}
+
+static VFS: RwLock<Vfs> = RwLock::new(Vfs {
+    root: PathBuf::new(),
+    mounts: Vec::new(),
+});
+
+// Where assets are unless EngineConfig::asset_root says otherwise: the project's assets folder
+// under `cargo run`, which sets CARGO_MANIFEST_DIR, or else the one next to the executable. On
+// the web, the page's own folder.
+pub(crate) fn default_root() -> PathBuf {
+    if cfg!(target_arch = "wasm32") {
+        return PathBuf::new();
+    }
+    if let Some(dir) = std::env::var_os("CARGO_MANIFEST_DIR") {
+        return PathBuf::from(dir).join("assets");
+    }
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("assets")))
+        .unwrap_or_else(|| PathBuf::from("assets"))
+}
+
+pub(crate) fn set_root(root: PathBuf) {
+    VFS.write().unwrap().root = root;
+}
+pub(crate) fn root() -> PathBuf {
+    VFS.read().unwrap().root.clone()
+}
+pub(crate) fn mount(dir: PathBuf) {
+    unmount(&dir);
+    VFS.write().unwrap().mounts.push(Mount::Dir(dir));
+}
+pub(crate) fn mount_archive(archive: PakArchive) {
+    unmount(archive.source());
+    VFS.write()
+        .unwrap()
+        .mounts
+        .push(Mount::Archive(Arc::new(archive)));
+}
+// A folder, or an archive by its PakArchive::source.
+pub(crate) fn unmount(path: &Path) {
+    VFS.write().unwrap().mounts.retain(|mount| match mount {
+        Mount::Dir(dir) => dir != path,
+        Mount::Archive(archive) => archive.source() != path,
+    });
+}
+
+// `path` with "." and "x/.." parts taken out and, on the web, backslashes made into slashes. Any
+// ".." that would climb out of the folder it's in is kept.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let path = if cfg!(target_arch = "wasm32") {
+        PathBuf::from(path.to_string_lossy().replace('\\', "/"))
+    } else {
+        path.to_owned()
+    };
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+// Everywhere `path` could be, in the order they're tried. Archives only come into it if they
+// have the file.
+fn candidates(path: &Path) -> Vec<Location> {
+    let path = normalize(path);
+    if path.is_absolute() {
+        return vec![Location::File(path)];
+    }
+    let name = path
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let vfs = VFS.read().unwrap();
+    let mut candidates = Vec::new();
+    for mount in vfs.mounts.iter().rev() {
+        match mount {
+            Mount::Dir(dir) => candidates.push(Location::File(dir.join(&path))),
+            Mount::Archive(archive) if archive.contains(&name) => {
+                candidates.push(Location::Archive(archive.clone(), name.clone()));
+            }
+            Mount::Archive(_) => {}
+        }
+    }
+    let under_root = vfs.root.join(&path);
+    if under_root != path {
+        candidates.push(Location::File(under_root));
+    }
+    candidates.push(Location::File(path));
+    candidates
+}
+
+// Where `path` is: the first candidate that exists, or under the root if none do (so errors name
+// the place it was expected). Without a filesystem to look at, on the web, files are always
+// taken to be under the root.
+pub(crate) fn locate(path: &Path) -> Location {
+    let mut candidates = candidates(path);
+    let exists = |location: &Location| match location {
+        Location::File(path) => cfg!(not(target_arch = "wasm32")) && path.exists(),
+        Location::Archive(..) => true,
+    };
+    if let Some(found) = candidates.iter().position(exists) {
+        return candidates.swap_remove(found);
+    }
+    let path = normalize(path);
+    if path.is_absolute() {
+        Location::File(path)
+    } else {
+        Location::File(VFS.read().unwrap().root.join(path))
+    }
+}
+
+// The file on disk `path` refers to, for things that watch or stream files. One in an archive
+// has none, so this is where it would be under the root.
+pub(crate) fn resolve(path: &Path) -> PathBuf {
+    match locate(path) {
+        Location::File(path) => path,
+        Location::Archive(..) => VFS.read().unwrap().root.join(normalize(path)),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn read_bytes(path: &Path) -> io::Result<Vec<u8>> {
+    locate(path).read()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn read_bytes(path: &Path) -> io::Result<Vec<u8>> {
+    let mut result = Err(io::Error::from(io::ErrorKind::NotFound));
+    for candidate in candidates(path) {
+        result = match candidate {
+            Location::File(url) => fetch(&url).await,
+            Location::Archive(archive, name) => archive.read(&name),
+        };
+        match &result {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            _ => break,
+        }
+    }
+    result
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn fetch(path: &Path) -> io::Result<Vec<u8>> {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let url = path.to_string_lossy();
+    let js_err = |e: wasm_bindgen::JsValue| io::Error::new(io::ErrorKind::Other, format!("{e:?}"));
+    let window = web_sys::window()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no browser window"))?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(&url))
+        .await
+        .map_err(js_err)?
+        .dyn_into()
+        .map_err(js_err)?;
+    if !response.ok() {
+        let kind = if response.status() == 404 {
+            io::ErrorKind::NotFound
+        } else {
+            io::ErrorKind::Other
+        };
+        return Err(io::Error::new(
+            kind,
+            format!("fetching {url} failed with HTTP {}", response.status()),
+        ));
+    }
+    let buffer = JsFuture::from(response.array_buffer().map_err(js_err)?)
+        .await
+        .map_err(js_err)?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -0,0 +1,953 @@
+use crate::audio_clip::{BoxedSource, Encoded};
+use crate::{AudioClip, AudioError, GPUCamera, Vec2};
+use rodio::{OutputStream, OutputStreamHandle, Sample, Sink, Source};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+// How far past the edge of the listener's view a positional sound fades out, in half-views
+// (half the larger of the camera's screen_size dimensions).
+const FALLOFF: f32 = 2.0;
+// Slower than this and a sound would take all but forever to finish.
+const MIN_PITCH: f32 = 0.01;
+// Frames a play_stream callback fills at a time.
+const STREAM_BLOCK: usize = 512;
+
+// Volume groups a settings menu can turn up, down or off without touching the sounds in them.
+// Everything goes through Master as well as its own bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bus {
+    Master,
+    Music,
+    Sfx,
+    Voice,
+}
+
+impl Bus {
+    pub const ALL: [Bus; 4] = [Bus::Master, Bus::Music, Bus::Sfx, Bus::Voice];
+}
+
+#[derive(Clone, Copy)]
+struct BusLevel {
+    volume: f32,
+    muted: bool,
+}
+
+// How a sound effect plays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundOptions {
+    pub bus: Bus,
+    // Relative to the bus; 1.0 is as loud as it was recorded.
+    pub volume: f32,
+    // Plays over and over until stopped.
+    pub looping: bool,
+    // Where the sound comes from, in the listener camera's world space. Positional sounds are
+    // panned toward their side of the view and fade out away from it, following the camera as it
+    // moves; see Audio::set_listener_group. None plays it as it is, e.g. for UI sounds.
+    pub position: Option<Vec2>,
+    // Playback speed, which shifts the pitch with it: 2.0 plays an octave up in half the time.
+    pub pitch: f32,
+    // Each play picks a pitch up to this much either side of `pitch`, so that a sound played over
+    // and over (footsteps, hits) doesn't sound the same every time. Around 0.05 to 0.15 works.
+    pub pitch_variance: f32,
+    // Rises from silence over this long instead of starting at full volume.
+    pub fade_in: Duration,
+}
+
+impl Default for SoundOptions {
+    fn default() -> Self {
+        Self {
+            bus: Bus::Sfx,
+            volume: 1.0,
+            looping: false,
+            position: None,
+            pitch: 1.0,
+            pitch_variance: 0.0,
+            fade_in: Duration::ZERO,
+        }
+    }
+}
+
+// A sound started by Audio::play_sound. Stays valid (but does nothing) once the sound ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SoundId(u64);
+
+// How a piece of music plays. By default it loops the whole file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MusicOptions {
+    pub looping: bool,
+    // Where each repeat starts from, e.g. just after an intro that should only play once.
+    pub loop_start: Duration,
+    // Where it goes back to loop_start; None plays to the end of the file first. Ignored unless
+    // it's after loop_start.
+    pub loop_end: Option<Duration>,
+    // Rises from silence over this long instead of starting at full volume.
+    pub fade_in: Duration,
+}
+
+impl Default for MusicOptions {
+    fn default() -> Self {
+        Self {
+            looping: true,
+            loop_start: Duration::ZERO,
+            loop_end: None,
+            fade_in: Duration::ZERO,
+        }
+    }
+}
+
+// Sound output, at Engine::audio. Music is streamed from disk as it plays rather than decoded up
+// front, while sound effects can be decoded once and kept (load_sound). Without a sound device
+// everything still works, just silently.
+pub struct Audio {
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    unlocked: bool,
+    // Asked for before unlock.
+    pending_music: Option<(PathBuf, MusicOptions)>,
+    // Set while pause_all has everything held, to whether the music should stay paused after.
+    held: Option<bool>,
+    music: Option<Music>,
+    // Tracks on their way out after crossfade_music or fade_out_music.
+    fading_music: Vec<Music>,
+    sounds: HashMap<SoundId, Sound>,
+    next_sound: u64,
+    buses: [BusLevel; 4],
+    listener_group: usize,
+    listener: Option<GPUCamera>,
+    // Decoded by load_sound.
+    clips: HashMap<PathBuf, AudioClip>,
+    // Still compressed, read in by load_music.
+    music_files: HashMap<PathBuf, Arc<[u8]>>,
+    // By AnimationEvent name.
+    event_sounds: HashMap<String, (PathBuf, SoundOptions)>,
+}
+
+struct Sound {
+    sink: Sink,
+    bus: Bus,
+    volume: f32,
+    // For positional sounds: where they are, and the pan the mixer reads as f32 bits.
+    position: Option<(Vec2, Arc<AtomicU32>)>,
+    // From the distance to the listener; always 1 for other sounds.
+    attenuation: f32,
+    fade: Fade,
+}
+
+// A volume ramp, stepped along by the Engine's frame time.
+#[derive(Clone, Copy)]
+struct Fade {
+    level: f32,
+    target: f32,
+    // Change in level per second.
+    speed: f32,
+    // Stop the sound once the level gets to the target.
+    stop: bool,
+}
+
+impl Fade {
+    fn new(from: f32, to: f32, duration: Duration, stop: bool) -> Self {
+        let seconds = duration.as_secs_f32();
+        if seconds <= 0.0 {
+            return Fade {
+                level: to,
+                target: to,
+                speed: 0.0,
+                stop,
+            };
+        }
+        Fade {
+            level: from,
+            target: to,
+            speed: (to - from).abs() / seconds,
+            stop,
+        }
+    }
+    fn fade_in(duration: Duration) -> Self {
+        Self::new(0.0, 1.0, duration, false)
+    }
+    // From wherever it is now down to silence, then stop.
+    fn fade_out(&self, duration: Duration) -> Self {
+        Self::new(self.level, 0.0, duration, true)
+    }
+    fn advance(&mut self, dt: f32) {
+        let step = self.speed * dt;
+        if (self.target - self.level).abs() <= step {
+            self.level = self.target;
+        } else {
+            self.level += step.copysign(self.target - self.level);
+        }
+    }
+    fn finished(&self) -> bool {
+        self.stop && self.level == self.target
+    }
+}
+
+impl Sound {
+    fn spatialize(&mut self, listener: Option<&GPUCamera>) {
+        let Some((position, pan)) = &self.position else {
+            return;
+        };
+        let (new_pan, attenuation) = match listener {
+            Some(camera) => spatial(*position, camera),
+            None => (0.0, 1.0),
+        };
+        pan.store(new_pan.to_bits(), Ordering::Relaxed);
+        self.attenuation = attenuation;
+    }
+    // `gain` is the sound's bus's.
+    fn apply_volume(&self, gain: f32) {
+        self.sink
+            .set_volume(self.volume * self.attenuation * self.fade.level * gain);
+    }
+}
+
+struct Music {
+    sink: Sink,
+    fade: Fade,
+    path: PathBuf,
+    options: MusicOptions,
+}
+
+impl Audio {
+    pub(crate) fn new() -> Self {
+        let mut audio = Self {
+            output: None,
+            unlocked: false,
+            pending_music: None,
+            held: None,
+            music: None,
+            fading_music: Vec::new(),
+            sounds: HashMap::new(),
+            next_sound: 0,
+            buses: [BusLevel {
+                volume: 1.0,
+                muted: false,
+            }; 4],
+            listener_group: 0,
+            listener: None,
+            clips: HashMap::new(),
+            music_files: HashMap::new(),
+            event_sounds: HashMap::new(),
+        };
+        // Browsers only let a page make sound once the player has interacted with it.
+        if cfg!(not(target_arch = "wasm32")) {
+            audio.unlock();
+        }
+        audio
+    }
+    // Opens the sound device. On the web the Engine does this on the first key press, click or
+    // touch, since an AudioContext made before then stays suspended. Sounds played before that
+    // are dropped, but the latest play_music is remembered and starts then.
+    pub(crate) fn unlock(&mut self) {
+        if self.unlocked {
+            return;
+        }
+        self.unlocked = true;
+        self.output = match OutputStream::try_default() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                log::warn!("Audio unavailable: {e}");
+                None
+            }
+        };
+        if let Some((path, options)) = self.pending_music.take() {
+            if let Err(e) = self.play_music(&path, options) {
+                log::error!("{e}");
+            }
+        }
+    }
+    // Lets go of sounds that have finished, steps fades along by `dt` seconds and moves
+    // positional sounds along with `listener`, the camera of the listener group. The Engine calls
+    // this every frame after Game::update.
+    pub(crate) fn update(&mut self, listener: Option<GPUCamera>, dt: f32) {
+        self.listener = listener;
+        self.sounds.retain(|_, sound| {
+            sound.fade.advance(dt);
+            sound.spatialize(listener.as_ref());
+            if sound.fade.finished() {
+                sound.sink.stop();
+            }
+            !sound.sink.empty()
+        });
+        for music in self.music.iter_mut().chain(&mut self.fading_music) {
+            music.fade.advance(dt);
+            if music.fade.finished() {
+                music.sink.stop();
+            }
+        }
+        if self
+            .music
+            .as_ref()
+            .is_some_and(|music| music.fade.finished())
+        {
+            self.music = None;
+        }
+        self.fading_music.retain(|music| !music.sink.empty());
+        self.apply_gains();
+    }
+    // The sprite group whose camera positional sounds are heard through. Group 0 unless set.
+    pub fn set_listener_group(&mut self, group: usize) {
+        self.listener_group = group;
+    }
+    pub fn listener_group(&self) -> usize {
+        self.listener_group
+    }
+
+    // Reads and decodes a sound effect the same way Engine::load_texture reads textures (from disk,
+    // or fetched relative to the page on the web), keeping it so that play_sound with the same
+    // path plays it from memory. Loading it again just returns the kept clip.
+    pub async fn load_sound(&mut self, path: impl AsRef<Path>) -> Result<AudioClip, AudioError> {
+        let path = path.as_ref();
+        if let Some(clip) = self.clips.get(path) {
+            return Ok(clip.clone());
+        }
+        let bytes = read(path).await?;
+        let clip = AudioClip::decode(bytes, path)?;
+        self.clips.insert(path.to_owned(), clip.clone());
+        Ok(clip)
+    }
+    // Reads a piece of music into memory still compressed, for play_music to decode as it plays.
+    // Music has to be loaded this way on the web, where play_music can't read files itself; on
+    // native it saves going to the disk during play.
+    pub async fn load_music(&mut self, path: impl AsRef<Path>) -> Result<(), AudioError> {
+        let path = path.as_ref();
+        if self.music_files.contains_key(path) {
+            return Ok(());
+        }
+        let bytes = read(path).await?;
+        self.insert_music(path, bytes)
+    }
+    // What load_sound and load_music keep, for sounds and music loaded some other way (Assets).
+    pub(crate) fn insert_clip(&mut self, path: &Path, clip: AudioClip) {
+        self.clips.insert(path.to_owned(), clip);
+    }
+    pub(crate) fn insert_music(&mut self, path: &Path, bytes: Arc<[u8]>) -> Result<(), AudioError> {
+        // Finds out now, rather than when it's played, if it's not a format we can decode.
+        Encoded::Memory(path.to_owned(), bytes.clone()).open()?;
+        self.music_files.insert(path.to_owned(), bytes);
+        Ok(())
+    }
+    // Forgets what load_sound or load_music kept for `path`. Sounds already playing it carry on.
+    pub fn unload(&mut self, path: impl AsRef<Path>) {
+        self.clips.remove(path.as_ref());
+        self.music_files.remove(path.as_ref());
+    }
+
+    // Plays `path` (through play_sound, so load_sound it first on the web) whenever an animation
+    // reaches a frame with an event called `event`, e.g. a footstep for "footstep".
+    pub fn set_event_sound(
+        &mut self,
+        event: impl Into<String>,
+        path: impl Into<PathBuf>,
+        options: SoundOptions,
+    ) {
+        self.event_sounds
+            .insert(event.into(), (path.into(), options));
+    }
+    pub fn remove_event_sound(&mut self, event: &str) {
+        self.event_sounds.remove(event);
+    }
+    pub(crate) fn play_event_sound(&mut self, event: &str) {
+        let Some((path, options)) = self.event_sounds.get(event).cloned() else {
+            return;
+        };
+        if let Err(e) = self.play_sound(&path, options) {
+            log::error!("{e}");
+        }
+    }
+
+    // Switches the music to `path`, stopping whatever was playing. Asking for the track that's
+    // already playing, with the same options, leaves it going, so each level can just ask for
+    // its music.
+    pub fn play_music(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: MusicOptions,
+    ) -> Result<(), AudioError> {
+        self.switch_music(path.as_ref(), options, None)
+    }
+    // Like play_music, but the old track fades out while the new one fades in, both over
+    // `duration` (which takes the place of options.fade_in).
+    pub fn crossfade_music(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: MusicOptions,
+        duration: Duration,
+    ) -> Result<(), AudioError> {
+        self.switch_music(path.as_ref(), options, Some(duration))
+    }
+    // Fades the music out over `duration`, then stops it.
+    pub fn fade_out_music(&mut self, duration: Duration) {
+        if let Some(mut music) = self.music.take() {
+            music.fade = music.fade.fade_out(duration);
+            self.fading_music.push(music);
+        }
+    }
+    fn switch_music(
+        &mut self,
+        path: &Path,
+        mut options: MusicOptions,
+        crossfade: Option<Duration>,
+    ) -> Result<(), AudioError> {
+        if let Some(music) = &self.music {
+            if music.path == path && music.options == options && !music.sink.empty() {
+                return Ok(());
+            }
+        }
+        match crossfade {
+            Some(duration) => {
+                self.fade_out_music(duration);
+                options.fade_in = duration;
+            }
+            None => self.stop_music(),
+        }
+        let encoded = match self.music_files.get(path) {
+            Some(bytes) => Encoded::Memory(path.to_owned(), bytes.clone()),
+            None => locate(path)?,
+        };
+        let track = LoopingTrack::new(encoded, options)?;
+        if !self.unlocked {
+            self.pending_music = Some((path.to_owned(), options));
+        }
+        let Some((_, handle)) = &self.output else {
+            return Ok(());
+        };
+        let sink = match Sink::try_new(handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                log::warn!("Couldn't play {}: {e}", path.display());
+                return Ok(());
+            }
+        };
+        let fade = Fade::fade_in(options.fade_in);
+        sink.set_volume(self.gain(Bus::Music) * fade.level);
+        sink.append(track);
+        if self.held.is_some() {
+            sink.pause();
+        }
+        self.music = Some(Music {
+            sink,
+            fade,
+            path: path.to_owned(),
+            options,
+        });
+        Ok(())
+    }
+    // Stops the music straight away, including tracks still fading out.
+    pub fn stop_music(&mut self) {
+        self.pending_music = None;
+        for music in self
+            .music
+            .take()
+            .into_iter()
+            .chain(self.fading_music.drain(..))
+        {
+            music.sink.stop();
+        }
+    }
+    pub fn pause_music(&mut self) {
+        if let Some(held) = &mut self.held {
+            *held = true;
+        }
+        if let Some(music) = &self.music {
+            music.sink.pause();
+        }
+    }
+    // While pause_all is holding everything, the music resumes along with the rest.
+    pub fn resume_music(&mut self) {
+        if let Some(held) = &mut self.held {
+            *held = false;
+            return;
+        }
+        if let Some(music) = &self.music {
+            music.sink.play();
+        }
+    }
+    // The file of the music that's playing (or paused), if any. Music that doesn't loop stops
+    // being current once it ends.
+    pub fn current_music(&self) -> Option<&Path> {
+        self.music
+            .as_ref()
+            .filter(|music| !music.sink.empty())
+            .map(|music| music.path.as_path())
+    }
+    pub fn is_music_paused(&self) -> bool {
+        self.music
+            .as_ref()
+            .is_some_and(|music| self.held.unwrap_or_else(|| music.sink.is_paused()))
+    }
+    // Pauses the music and every sound where they are, e.g. while the game is in the background.
+    // Anything started before resume_all starts paused too. The Engine does this when the window
+    // loses focus with EngineConfig::pause_when_unfocused.
+    pub fn pause_all(&mut self) {
+        if self.held.is_some() {
+            return;
+        }
+        self.held = Some(self.is_music_paused());
+        for music in self.music.iter().chain(&self.fading_music) {
+            music.sink.pause();
+        }
+        for sound in self.sounds.values() {
+            sound.sink.pause();
+        }
+    }
+    // Carries on from pause_all. Music paused with pause_music stays paused.
+    pub fn resume_all(&mut self) {
+        let Some(music_paused) = self.held.take() else {
+            return;
+        };
+        for music in &self.fading_music {
+            music.sink.play();
+        }
+        if let Some(music) = self.music.as_ref().filter(|_| !music_paused) {
+            music.sink.play();
+        }
+        for sound in self.sounds.values() {
+            sound.sink.play();
+        }
+    }
+    pub fn is_all_paused(&self) -> bool {
+        self.held.is_some()
+    }
+
+    // Starts a sound effect (or line of dialogue, with Bus::Voice) from a file: from memory if
+    // load_sound has loaded it, otherwise streamed from disk (which the web can't do). Any number
+    // can play at once.
+    pub fn play_sound(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: SoundOptions,
+    ) -> Result<SoundId, AudioError> {
+        let path = path.as_ref();
+        if let Some(clip) = self.clips.get(path) {
+            let source = Box::new(clip.source(options.looping));
+            return Ok(self.start_sound(source, options));
+        }
+        let source = locate(path)?.open()?;
+        let source: BoxedSource = if options.looping {
+            Box::new(source.repeat_infinite())
+        } else {
+            source
+        };
+        Ok(self.start_sound(source, options))
+    }
+    pub fn play_clip(&mut self, clip: &AudioClip, options: SoundOptions) -> SoundId {
+        self.start_sound(Box::new(clip.source(options.looping)), options)
+    }
+    // Plays sound made up as it goes: `fill` is called on the mixer's thread for each block of
+    // interleaved samples (-1 to 1, `channels` per frame, `sample_rate` frames a second) and
+    // returns false when that block is the sound's last. Good for chiptune-style effects and
+    // synthesizers. options.looping means nothing here; the sound lasts until `fill` says so or
+    // it's stopped.
+    pub fn play_stream(
+        &mut self,
+        channels: u16,
+        sample_rate: u32,
+        options: SoundOptions,
+        fill: impl FnMut(&mut [f32]) -> bool + Send + 'static,
+    ) -> SoundId {
+        let channels = channels.max(1);
+        let source = Procedural {
+            fill: Box::new(fill),
+            block: vec![0.0; STREAM_BLOCK * channels as usize],
+            position: STREAM_BLOCK * channels as usize,
+            done: false,
+            channels,
+            sample_rate,
+        };
+        self.start_sound(Box::new(source), options)
+    }
+    fn start_sound(&mut self, source: BoxedSource, options: SoundOptions) -> SoundId {
+        let id = SoundId(self.next_sound);
+        self.next_sound += 1;
+        let Some((_, handle)) = &self.output else {
+            return id;
+        };
+        let sink = match Sink::try_new(handle) {
+            Ok(sink) => sink,
+            Err(e) => {
+                log::warn!("Couldn't play a sound: {e}");
+                return id;
+            }
+        };
+        let pitch = options.pitch + options.pitch_variance * variation(id.0);
+        sink.set_speed(pitch.max(MIN_PITCH));
+        let pan = options.position.map(|_| Arc::new(AtomicU32::new(0)));
+        append(&sink, source, pan.clone());
+        if self.held.is_some() {
+            sink.pause();
+        }
+        let mut sound = Sound {
+            sink,
+            bus: options.bus,
+            volume: options.volume.max(0.0),
+            position: options.position.zip(pan),
+            attenuation: 1.0,
+            fade: Fade::fade_in(options.fade_in),
+        };
+        sound.spatialize(self.listener.as_ref());
+        sound.apply_volume(self.gain(sound.bus));
+        self.sounds.insert(id, sound);
+        id
+    }
+    // Moves a positional sound, e.g. to follow the sprite making it. Takes effect by the next
+    // frame. Does nothing to sounds that were started without a position.
+    pub fn set_sound_position(&mut self, id: SoundId, position: impl Into<Vec2>) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            if let Some((old, _)) = &mut sound.position {
+                *old = position.into();
+            }
+        }
+    }
+    // Changes a playing sound's speed and pitch together, like SoundOptions::pitch.
+    pub fn set_sound_pitch(&mut self, id: SoundId, pitch: f32) {
+        if let Some(sound) = self.sounds.get(&id) {
+            sound.sink.set_speed(pitch.max(MIN_PITCH));
+        }
+    }
+    // Fades a sound out over `duration`, then stops it.
+    pub fn fade_out_sound(&mut self, id: SoundId, duration: Duration) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.fade = sound.fade.fade_out(duration);
+        }
+    }
+    pub fn stop_sound(&mut self, id: SoundId) {
+        if let Some(sound) = self.sounds.remove(&id) {
+            sound.sink.stop();
+        }
+    }
+    pub fn stop_all_sounds(&mut self) {
+        for (_, sound) in self.sounds.drain() {
+            sound.sink.stop();
+        }
+    }
+    pub fn is_sound_playing(&self, id: SoundId) -> bool {
+        self.sounds
+            .get(&id)
+            .is_some_and(|sound| !sound.sink.empty())
+    }
+    pub fn set_sound_volume(&mut self, id: SoundId, volume: f32) {
+        let gains = self.gains();
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.volume = volume.max(0.0);
+            sound.apply_volume(gains[sound.bus as usize]);
+        }
+    }
+
+    // 1.0 leaves the bus's sounds as loud as they're played.
+    pub fn set_bus_volume(&mut self, bus: Bus, volume: f32) {
+        self.buses[bus as usize].volume = volume.max(0.0);
+        self.apply_gains();
+    }
+    pub fn bus_volume(&self, bus: Bus) -> f32 {
+        self.buses[bus as usize].volume
+    }
+    // Silences the bus without forgetting its volume.
+    pub fn set_bus_muted(&mut self, bus: Bus, muted: bool) {
+        self.buses[bus as usize].muted = muted;
+        self.apply_gains();
+    }
+    pub fn is_bus_muted(&self, bus: Bus) -> bool {
+        self.buses[bus as usize].muted
+    }
+
+    // How loud `bus` plays, after its own volume and mute and Master's.
+    fn gain(&self, bus: Bus) -> f32 {
+        let level = |bus: Bus| {
+            let level = self.buses[bus as usize];
+            if level.muted {
+                0.0
+            } else {
+                level.volume
+            }
+        };
+        if bus == Bus::Master {
+            level(Bus::Master)
+        } else {
+            level(Bus::Master) * level(bus)
+        }
+    }
+    fn gains(&self) -> [f32; 4] {
+        Bus::ALL.map(|bus| self.gain(bus))
+    }
+    fn apply_gains(&mut self) {
+        let gains = self.gains();
+        for music in self.music.iter().chain(&self.fading_music) {
+            music
+                .sink
+                .set_volume(gains[Bus::Music as usize] * music.fade.level);
+        }
+        for sound in self.sounds.values() {
+            sound.apply_volume(gains[sound.bus as usize]);
+        }
+    }
+}
+
+// The pan (-1 left to 1 right) and volume of a sound at `position` heard through `camera`: full
+// volume anywhere in view, fading to nothing FALLOFF half-views past its edge.
+fn spatial(position: Vec2, camera: &GPUCamera) -> (f32, f32) {
+    let half = camera.size() / 2.0;
+    let offset = position - (camera.position() + half);
+    let pan = if half.x > 0.0 {
+        (offset.x / half.x).clamp(-1.0, 1.0)
+    } else {
+        0.0
+    };
+    let reach = half.x.abs().max(half.y.abs()).max(f32::EPSILON);
+    let distance = (offset.abs() - half.abs()).max(Vec2::ZERO).length() / reach;
+    (pan, (1.0 - distance / FALLOFF).max(0.0))
+}
+
+fn append(sink: &Sink, source: BoxedSource, pan: Option<Arc<AtomicU32>>) {
+    match pan {
+        Some(pan) => sink.append(Panned {
+            channels: source.channels(),
+            source,
+            pan,
+            frame: None,
+        }),
+        None => sink.append(source),
+    }
+}
+
+// Turns a source into stereo, turning down the side it's panned away from. The pan can change
+// while it plays.
+struct Panned<S: Source>
+where
+    S::Item: Sample,
+{
+    source: S,
+    channels: u16,
+    pan: Arc<AtomicU32>,
+    // The right sample of the frame whose left sample was just played.
+    frame: Option<S::Item>,
+}
+
+impl<S: Source> Iterator for Panned<S>
+where
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if let Some(right) = self.frame.take() {
+            return Some(right);
+        }
+        let left = self.source.next()?;
+        let right = if self.channels == 1 {
+            left
+        } else {
+            let right = self.source.next()?;
+            // Anything past stereo is dropped.
+            for _ in 2..self.channels {
+                self.source.next();
+            }
+            right
+        };
+        let pan = f32::from_bits(self.pan.load(Ordering::Relaxed));
+        self.frame = Some(right.amplify((1.0 + pan).min(1.0)));
+        Some(left.amplify((1.0 - pan).min(1.0)))
+    }
+}
+
+impl<S: Source> Source for Panned<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        2
+    }
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+type FillFn = Box<dyn FnMut(&mut [f32]) -> bool + Send>;
+
+struct Procedural {
+    fill: FillFn,
+    block: Vec<f32>,
+    // Next sample of `block` to play; at the end, it's time to fill another.
+    position: usize,
+    // `fill` returned false, so `block` is the last.
+    done: bool,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl Iterator for Procedural {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.position == self.block.len() {
+            if self.done {
+                return None;
+            }
+            self.block.fill(0.0);
+            self.done = !(self.fill)(&mut self.block);
+            self.position = 0;
+        }
+        let sample = self.block[self.position];
+        self.position += 1;
+        Some((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+    }
+}
+
+impl Source for Procedural {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// A pseudo-random number from -1 to 1, different for each sound.
+fn variation(seed: u64) -> f32 {
+    let mut n = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    n ^= n >> 31;
+    n = n.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    n ^= n >> 29;
+    (n >> 40) as f32 / (1u64 << 24) as f32 * 2.0 - 1.0
+}
+
+// Where to stream `path` from: its file, or if it's in a mounted archive, its bytes from there.
+fn locate(path: &Path) -> Result<Encoded, AudioError> {
+    match crate::assets::locate(path) {
+        crate::assets::Location::File(file) => Ok(Encoded::File(file)),
+        crate::assets::Location::Archive(archive, name) => {
+            let bytes = archive.read(&name).map_err(|source| AudioError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+            Ok(Encoded::Memory(path.to_owned(), bytes.into()))
+        }
+    }
+}
+
+async fn read(path: &Path) -> Result<Arc<[u8]>, AudioError> {
+    crate::assets::read_bytes(path)
+        .await
+        .map(Arc::from)
+        .map_err(|source| AudioError::Io {
+            path: path.to_owned(),
+            source,
+        })
+}
+
+// A decoder for `encoded` that has already played its first `samples` samples.
+fn open_at(encoded: &Encoded, samples: u64) -> Option<BoxedSource> {
+    let mut decoder = encoded.open().ok()?;
+    decoder.by_ref().take(samples as usize).for_each(drop);
+    Some(decoder)
+}
+
+// Streams a file, going back to the loop start whenever it reaches the loop end. Not every format
+// can seek, so each repeat plays from a fresh decoder wound forward to the loop start; that's
+// done on another thread during the current pass, so the seam is sample-exact and doesn't stall
+// the mixer.
+struct LoopingTrack {
+    encoded: Encoded,
+    decoder: BoxedSource,
+    // Samples so far from the start of the file, counting each channel.
+    position: u64,
+    loop_start: u64,
+    loop_end: Option<u64>,
+    looping: bool,
+    next: Option<mpsc::Receiver<Option<BoxedSource>>>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl LoopingTrack {
+    fn new(encoded: Encoded, options: MusicOptions) -> Result<Self, AudioError> {
+        let decoder = encoded.open()?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        let samples = |time: Duration| {
+            (time.as_secs_f64() * sample_rate as f64).round() as u64 * channels as u64
+        };
+        let loop_start = samples(options.loop_start);
+        let loop_end = options
+            .loop_end
+            .map(samples)
+            .filter(|&end| end > loop_start);
+        let mut track = Self {
+            encoded,
+            decoder,
+            position: 0,
+            loop_start,
+            loop_end,
+            looping: options.looping,
+            next: None,
+            channels,
+            sample_rate,
+        };
+        track.prepare_next();
+        Ok(track)
+    }
+    fn prepare_next(&mut self) {
+        if !self.looping {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let (encoded, start) = (self.encoded.clone(), self.loop_start);
+        let prepare = move || {
+            let _ = sender.send(open_at(&encoded, start));
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::spawn(prepare);
+        #[cfg(target_arch = "wasm32")]
+        prepare();
+        self.next = Some(receiver);
+    }
+}
+
+impl Iterator for LoopingTrack {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let at_loop_end = self.loop_end.is_some_and(|end| self.position >= end);
+        if !at_loop_end {
+            if let Some(sample) = self.decoder.next() {
+                self.position += 1;
+                return Some(sample);
+            }
+        }
+        // Only waits if the file is so short that the next pass isn't ready yet.
+        self.decoder = self.next.take()?.recv().ok().flatten()?;
+        self.position = self.loop_start;
+        self.prepare_next();
+        let sample = self.decoder.next()?;
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for LoopingTrack {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
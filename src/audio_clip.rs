@@ -0,0 +1,153 @@
+use crate::AudioError;
+use rodio::{Decoder, Source};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub(crate) type BoxedSource = Box<dyn Source<Item = i16> + Send>;
+
+// A sound decoded into memory, ready to play any number of times at once without touching the
+// disk again. Clones share the samples. Get one from Audio::load_sound, which caches it by path.
+#[derive(Clone)]
+pub struct AudioClip {
+    // Interleaved, one per channel per frame.
+    samples: Arc<[i16]>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl AudioClip {
+    // Decodes a whole WAV, OGG, MP3 or FLAC file that's already in memory, e.g. one compiled in
+    // with include_bytes!. Errors name it by `label`.
+    pub fn from_bytes(
+        bytes: impl Into<Arc<[u8]>>,
+        label: Option<&str>,
+    ) -> Result<Self, AudioError> {
+        let path = in_memory_source(label);
+        Self::decode(bytes.into(), &path)
+    }
+    // For sounds made some other way, e.g. generated. `samples` are interleaved.
+    pub fn from_samples(samples: impl Into<Arc<[i16]>>, channels: u16, sample_rate: u32) -> Self {
+        Self {
+            samples: samples.into(),
+            channels: channels.max(1),
+            sample_rate,
+        }
+    }
+    pub(crate) fn decode(bytes: Arc<[u8]>, path: &Path) -> Result<Self, AudioError> {
+        let decoder = Decoder::new(Cursor::new(bytes)).map_err(|source| AudioError::Decode {
+            path: path.to_owned(),
+            source,
+        })?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        Ok(Self::from_samples(
+            decoder.collect::<Vec<i16>>(),
+            channels,
+            sample_rate,
+        ))
+    }
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() / self.channels as usize;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate.max(1) as f64)
+    }
+    pub(crate) fn source(&self, looping: bool) -> ClipSource {
+        ClipSource {
+            clip: self.clone(),
+            position: 0,
+            looping,
+        }
+    }
+}
+
+impl fmt::Debug for AudioClip {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AudioClip")
+            .field("channels", &self.channels)
+            .field("sample_rate", &self.sample_rate)
+            .field("duration", &self.duration())
+            .finish()
+    }
+}
+
+pub(crate) struct ClipSource {
+    clip: AudioClip,
+    position: usize,
+    looping: bool,
+}
+
+impl Iterator for ClipSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.position == self.clip.samples.len() && self.looping {
+            self.position = 0;
+        }
+        let sample = *self.clip.samples.get(self.position)?;
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for ClipSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        self.clip.channels
+    }
+    fn sample_rate(&self) -> u32 {
+        self.clip.sample_rate
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        (!self.looping).then(|| self.clip.duration())
+    }
+}
+
+// A compressed sound to decode as it plays: a file on disk, or one read into memory up front
+// (Audio::load_music), which is the only option on the web.
+#[derive(Clone)]
+pub(crate) enum Encoded {
+    File(PathBuf),
+    Memory(PathBuf, Arc<[u8]>),
+}
+
+impl Encoded {
+    pub(crate) fn path(&self) -> &Path {
+        match self {
+            Encoded::File(path) | Encoded::Memory(path, _) => path,
+        }
+    }
+    pub(crate) fn open(&self) -> Result<BoxedSource, AudioError> {
+        let decode_error = |source| AudioError::Decode {
+            path: self.path().to_owned(),
+            source,
+        };
+        match self {
+            Encoded::File(path) => {
+                let file = File::open(path).map_err(|source| AudioError::Io {
+                    path: path.clone(),
+                    source,
+                })?;
+                let decoder = Decoder::new(BufReader::new(file)).map_err(decode_error)?;
+                Ok(Box::new(decoder))
+            }
+            Encoded::Memory(_, bytes) => {
+                let decoder = Decoder::new(Cursor::new(bytes.clone())).map_err(decode_error)?;
+                Ok(Box::new(decoder))
+            }
+        }
+    }
+}
+
+fn in_memory_source(label: Option<&str>) -> PathBuf {
+    format!("<{}>", label.unwrap_or("in-memory sound")).into()
+}
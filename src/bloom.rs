@@ -0,0 +1,138 @@
+use crate::postprocess::{create_params_buffer, PostParams, PostProcess};
+use crate::WGPU;
+
+// How many times the bright parts get halved at most. Each level blurs twice as wide.
+const MAX_LEVELS: usize = 5;
+
+// The blur chain behind PostProcess::add_bloom.
+pub(crate) struct Bloom {
+    threshold: wgpu::RenderPipeline,
+    down: wgpu::RenderPipeline,
+    // Adds onto the target instead of replacing it.
+    up: wgpu::RenderPipeline,
+    // Each a half the size of the one before, starting at half the frame.
+    levels: Vec<(wgpu::TextureView, (u32, u32))>,
+    // One per draw, since every draw needs its own resolution and writes all land before any of
+    // them run.
+    params: Vec<wgpu::Buffer>,
+    frame_size: (u32, u32),
+}
+
+impl Bloom {
+    pub(crate) fn new(post: &PostProcess, gpu: &WGPU) -> Self {
+        let source = include_str!("postprocess_bloom.wgsl");
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::OVER,
+        };
+        Self {
+            threshold: post.create_pipeline(gpu, "bloom threshold", source, "fs_threshold", None),
+            down: post.create_pipeline(gpu, "bloom down", source, "fs_down", None),
+            up: post.create_pipeline(gpu, "bloom up", source, "fs_up", Some(additive)),
+            levels: Vec::new(),
+            params: Vec::new(),
+            frame_size: (0, 0),
+        }
+    }
+    pub(crate) fn resize(
+        &mut self,
+        gpu: &WGPU,
+        frame_size: (u32, u32),
+        format: wgpu::TextureFormat,
+    ) {
+        if self.frame_size == frame_size {
+            return;
+        }
+        self.frame_size = frame_size;
+        self.levels.clear();
+        let mut size = frame_size;
+        // At least one level, however small the frame.
+        while self.levels.is_empty() || (self.levels.len() < MAX_LEVELS && size.0 > 1 && size.1 > 1)
+        {
+            size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+            let view = gpu
+                .device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("bloom"),
+                    size: wgpu::Extent3d {
+                        width: size.0,
+                        height: size.1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            self.levels.push((view, size));
+        }
+        // Down into every level, then back up into all but the last.
+        let draws = (2 * self.levels.len()).saturating_sub(1);
+        self.params = (0..draws)
+            .map(|_| create_params_buffer(gpu, "bloom"))
+            .collect();
+    }
+    // Blurs the bright parts of `frame` and returns them at half size, ready to be added on.
+    pub(crate) fn run<'a>(
+        &'a self,
+        post: &'a PostProcess,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &'a wgpu::TextureView,
+        values: [f32; 4],
+    ) -> &'a wgpu::TextureView {
+        let mut params = self.params.iter();
+        let mut draw = |pipeline, source, source_size: (u32, u32), target, load| {
+            let buffer = params.next().expect("a params buffer for every draw");
+            let step = PostParams {
+                resolution: [source_size.0 as f32, source_size.1 as f32],
+                time: 0.0,
+                _padding: 0.0,
+                values,
+                color: [0.0; 4],
+            };
+            gpu.queue.write_buffer(buffer, 0, bytemuck::bytes_of(&step));
+            post.draw_fullscreen(
+                gpu,
+                encoder,
+                "bloom",
+                pipeline,
+                [source, post.no_texture()],
+                buffer,
+                target,
+                load,
+            );
+        };
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        draw(
+            &self.threshold,
+            frame,
+            self.frame_size,
+            &self.levels[0].0,
+            clear,
+        );
+        let levels = &self.levels;
+        for i in 1..levels.len() {
+            draw(
+                &self.down,
+                &levels[i - 1].0,
+                levels[i - 1].1,
+                &levels[i].0,
+                clear,
+            );
+        }
+        for i in (1..levels.len()).rev() {
+            let load = wgpu::LoadOp::Load;
+            draw(&self.up, &levels[i].0, levels[i].1, &levels[i - 1].0, load);
+        }
+        &self.levels[0].0
+    }
+}
@@ -0,0 +1,147 @@
+// System clipboard access. Native builds go through arboard. In the browser, writes go to
+// navigator.clipboard, but reads are async and permission-gated there, so they can only arrive a
+// frame or more later: Engine::clipboard_request asks for the text, which comes back as a
+// ClipboardText event (on the next frame, on native).
+#[cfg(target_arch = "wasm32")]
+use std::{cell::RefCell, rc::Rc};
+
+// The clipboard's text, published to Engine::events once Engine::clipboard_request gets it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClipboardText(pub String);
+
+pub(crate) struct Clipboard {
+    #[cfg(not(target_arch = "wasm32"))]
+    system: Option<arboard::Clipboard>,
+    #[cfg(not(target_arch = "wasm32"))]
+    requested: Option<String>,
+    // What the game last copied, which is all get can see in the browser.
+    #[cfg(target_arch = "wasm32")]
+    contents: Option<String>,
+    // Filled in by readText's promise.
+    #[cfg(target_arch = "wasm32")]
+    requested: Rc<RefCell<Option<String>>>,
+}
+
+#[cfg(target_arch = "wasm32")]
+mod browser {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = ["navigator", "clipboard"], js_name = writeText, catch)]
+        pub(super) fn write_text(text: &str) -> Result<js_sys::Promise, JsValue>;
+        #[wasm_bindgen(js_namespace = ["navigator", "clipboard"], js_name = readText, catch)]
+        pub(super) fn read_text() -> Result<js_sys::Promise, JsValue>;
+    }
+}
+
+impl Clipboard {
+    pub(crate) fn new() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let system = match arboard::Clipboard::new() {
+                Ok(clipboard) => Some(clipboard),
+                Err(e) => {
+                    log::warn!("Clipboard unavailable: {e}");
+                    None
+                }
+            };
+            Self {
+                system,
+                requested: None,
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Self {
+                contents: None,
+                requested: Rc::default(),
+            }
+        }
+    }
+    pub(crate) fn get(&mut self) -> Option<String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            match self.system.as_mut()?.get_text() {
+                Ok(text) => Some(text),
+                Err(arboard::Error::ContentNotAvailable) => None,
+                Err(e) => {
+                    log::warn!("Couldn't read clipboard: {e}");
+                    None
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.contents.clone()
+        }
+    }
+    pub(crate) fn set(&mut self, text: String) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let Some(system) = self.system.as_mut() else {
+                return false;
+            };
+            match system.set_text(text) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("Couldn't write clipboard: {e}");
+                    false
+                }
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let promise = match browser::write_text(&text) {
+                Ok(promise) => promise,
+                Err(e) => {
+                    log::warn!("Clipboard unavailable: {e:?}");
+                    return false;
+                }
+            };
+            self.contents = Some(text);
+            // Whether the browser allowed it is only known later.
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) = wasm_bindgen_futures::JsFuture::from(promise).await {
+                    log::warn!("Couldn't write clipboard: {e:?}");
+                }
+            });
+            true
+        }
+    }
+    // Starts reading the clipboard for take_requested.
+    pub(crate) fn request(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.requested = self.get();
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let promise = match browser::read_text() {
+                Ok(promise) => promise,
+                Err(e) => {
+                    log::warn!("Clipboard unavailable: {e:?}");
+                    return;
+                }
+            };
+            let requested = self.requested.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match wasm_bindgen_futures::JsFuture::from(promise).await {
+                    Ok(text) => *requested.borrow_mut() = text.as_string(),
+                    Err(e) => log::warn!("Couldn't read clipboard: {e:?}"),
+                }
+            });
+        }
+    }
+    // The text a request has got back since the last call, if any.
+    pub(crate) fn take_requested(&mut self) -> Option<String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.requested.take()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.requested.borrow_mut().take()
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{GPUSprite, Rect, Vec2};
+
+// The usual name for it in collision code.
+pub type Aabb = Rect;
+
+// Where a moving rectangle first runs into another, from sweep and friends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    // How far along the velocity it gets before touching, from 0 (already touching or overlapping)
+    // to 1 (only just at the end).
+    pub time: f32,
+    // Points out of the surface that was hit, back toward the moving rectangle: [0, 1] for
+    // landing on top of something. For rectangles that start out overlapping, it's the shortest
+    // way out.
+    pub normal: Vec2,
+    // The moving rectangle's bottom-left corner at that time.
+    pub position: Vec2,
+}
+
+// Moves `moving` by `velocity` (the whole step, not per second) and finds when it first touches
+// `target`, however far it goes in one step, so fast bullets don't pass through thin walls.
+// Rectangles that only touch and aren't moving toward each other don't count, so a character can
+// slide along a wall or floor it rests against.
+pub fn sweep(moving: &Rect, velocity: impl Into<Vec2>, target: &Rect) -> Option<Contact> {
+    let velocity = velocity.into();
+    if moving.overlaps(target) {
+        // Push out along whichever axis is the shortest way out.
+        let left = moving.right() - target.x;
+        let right = target.right() - moving.x;
+        let down = moving.top() - target.y;
+        let up = target.top() - moving.y;
+        let shortest = left.min(right).min(down).min(up);
+        let normal = if shortest == left {
+            -Vec2::X
+        } else if shortest == right {
+            Vec2::X
+        } else if shortest == down {
+            -Vec2::Y
+        } else {
+            Vec2::Y
+        };
+        return Some(Contact {
+            time: 0.0,
+            normal,
+            position: moving.position(),
+        });
+    }
+    // When along each axis the two start and stop overlapping on that axis alone.
+    let axis = |position: f32, size: f32, target_position: f32, target_size: f32, speed: f32| {
+        if speed > 0.0 {
+            Some((
+                (target_position - (position + size)) / speed,
+                (target_position + target_size - position) / speed,
+            ))
+        } else if speed < 0.0 {
+            Some((
+                (target_position + target_size - position) / speed,
+                (target_position - (position + size)) / speed,
+            ))
+        } else if position + size <= target_position || position >= target_position + target_size {
+            None
+        } else {
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        }
+    };
+    let (entry_x, exit_x) = axis(moving.x, moving.width, target.x, target.width, velocity.x)?;
+    let (entry_y, exit_y) = axis(moving.y, moving.height, target.y, target.height, velocity.y)?;
+    let entry = entry_x.max(entry_y);
+    let exit = exit_x.min(exit_y);
+    if entry > exit || !(0.0..=1.0).contains(&entry) || exit <= 0.0 {
+        return None;
+    }
+    let normal = if entry_x > entry_y {
+        Vec2::new(-velocity.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, -velocity.y.signum())
+    };
+    Some(Contact {
+        time: entry,
+        normal,
+        position: moving.position() + velocity * entry,
+    })
+}
+
+// The first of `targets` that `moving` runs into, with the item it came with.
+pub fn sweep_rects(
+    moving: &Rect,
+    velocity: impl Into<Vec2>,
+    targets: impl IntoIterator<Item = (usize, Rect)>,
+) -> Option<(usize, Contact)> {
+    let velocity = velocity.into();
+    targets
+        .into_iter()
+        .filter_map(|(item, target)| Some((item, sweep(moving, velocity, &target)?)))
+        .min_by(|(_, a), (_, b)| a.time.total_cmp(&b.time))
+}
+
+// The first solid cell of a grid `cell_size` units square (cell [0, 0] has its bottom-left corner
+// at the origin) that `moving` runs into, for tile maps: `solid` says which cells are walls. Only
+// the cells along the way are asked about.
+pub fn sweep_grid(
+    moving: &Rect,
+    velocity: impl Into<Vec2>,
+    cell_size: f32,
+    mut solid: impl FnMut([i32; 2]) -> bool,
+) -> Option<([i32; 2], Contact)> {
+    let velocity = velocity.into();
+    let end = moving.translate(velocity);
+    let reach = moving.union(&end);
+    let cell = |at: f32| (at / cell_size).floor() as i32;
+    let mut first: Option<([i32; 2], Contact)> = None;
+    for cx in cell(reach.x)..=cell(reach.right()) {
+        for cy in cell(reach.y)..=cell(reach.top()) {
+            if !solid([cx, cy]) {
+                continue;
+            }
+            let tile = Rect {
+                x: cx as f32 * cell_size,
+                y: cy as f32 * cell_size,
+                width: cell_size,
+                height: cell_size,
+            };
+            if let Some(contact) = sweep(moving, velocity, &tile) {
+                if first.is_none_or(|(_, best)| contact.time < best.time) {
+                    first = Some(([cx, cy], contact));
+                }
+            }
+        }
+    }
+    first
+}
+
+// Every pair of sprites in `sprites` whose screen_regions overlap, as (lower index, higher index),
+// sorted. Sweeps along x, so it's quick unless many sprites share the same column.
+pub fn collisions_within(sprites: &[GPUSprite]) -> Vec<(usize, usize)> {
+    let bounds = sorted_bounds(sprites);
+    let mut pairs = Vec::new();
+    for (i, (a, rect)) in bounds.iter().enumerate() {
+        for (b, other) in &bounds[i + 1..] {
+            if other.x >= rect.right() {
+                break;
+            }
+            if rect.overlaps(other) {
+                pairs.push(((*a).min(*b), (*a).max(*b)));
+            }
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+// Every (index in a, index in b) of sprites that overlap, sorted, e.g. bullets against enemies.
+// The two slices need to be in the same world space, which for two groups means sharing a camera
+// (or at least its units).
+pub fn collisions_between(a: &[GPUSprite], b: &[GPUSprite]) -> Vec<(usize, usize)> {
+    let (a, b) = (sorted_bounds(a), sorted_bounds(b));
+    // Rectangles from each side that the sweep has passed the left edge of but not yet the right.
+    let mut active_a: Vec<(usize, Rect)> = Vec::new();
+    let mut active_b: Vec<(usize, Rect)> = Vec::new();
+    let (mut next_a, mut next_b) = (0, 0);
+    let mut pairs = Vec::new();
+    while next_a < a.len() || next_b < b.len() {
+        let from_a = next_b == b.len() || (next_a < a.len() && a[next_a].1.x <= b[next_b].1.x);
+        if from_a {
+            let (i, rect) = a[next_a];
+            next_a += 1;
+            active_b.retain(|(_, other)| other.right() > rect.x);
+            pairs.extend(
+                active_b
+                    .iter()
+                    .filter(|(_, other)| rect.overlaps(other))
+                    .map(|&(j, _)| (i, j)),
+            );
+            active_a.push((i, rect));
+        } else {
+            let (j, rect) = b[next_b];
+            next_b += 1;
+            active_a.retain(|(_, other)| other.right() > rect.x);
+            pairs.extend(
+                active_a
+                    .iter()
+                    .filter(|(_, other)| rect.overlaps(other))
+                    .map(|&(i, _)| (i, j)),
+            );
+            active_b.push((j, rect));
+        }
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+// The indices of the sprites overlapping `rect`, in order.
+pub fn sprites_overlapping(sprites: &[GPUSprite], rect: &Rect) -> Vec<usize> {
+    sprites
+        .iter()
+        .enumerate()
+        .filter(|(_, sprite)| sprite.bounds().overlaps(rect))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// Each sprite's index and bounds, by left edge.
+fn sorted_bounds(sprites: &[GPUSprite]) -> Vec<(usize, Rect)> {
+    let mut bounds: Vec<(usize, Rect)> =
+        sprites.iter().map(GPUSprite::bounds).enumerate().collect();
+    bounds.sort_by(|(_, a), (_, b)| a.x.total_cmp(&b.x));
+    bounds
+}
@@ -0,0 +1,163 @@
+// A color as it's usually written down: sRGB channels from 0 to 1, the way image editors and hex
+// codes give them, with straight (not premultiplied) alpha. Tints, outlines, text, debug drawing
+// and the clear color all take one, or an [r, g, b, a] byte array, which is read the same way:
+//
+//     GPUSprite::solid([0.0, 0.0, 32.0, 32.0], Color::from_hex("#e04040").unwrap())
+//     engine.set_clear_color(Color::from_rgb8(20, 24, 40));
+//
+// The sprite shaders do their blending in linear light, so colors are converted on the GPU (or by
+// to_wgpu, for clears); a 50% gray tint comes out as the 50% gray an editor shows rather than a
+// lighter one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::WHITE
+    }
+}
+
+impl Color {
+    pub const WHITE: Self = Self::new(1.0, 1.0, 1.0, 1.0);
+    pub const BLACK: Self = Self::new(0.0, 0.0, 0.0, 1.0);
+    pub const GRAY: Self = Self::new(0.5, 0.5, 0.5, 1.0);
+    pub const TRANSPARENT: Self = Self::new(0.0, 0.0, 0.0, 0.0);
+    pub const RED: Self = Self::new(1.0, 0.0, 0.0, 1.0);
+    pub const GREEN: Self = Self::new(0.0, 1.0, 0.0, 1.0);
+    pub const BLUE: Self = Self::new(0.0, 0.0, 1.0, 1.0);
+    pub const YELLOW: Self = Self::new(1.0, 1.0, 0.0, 1.0);
+    pub const CYAN: Self = Self::new(0.0, 1.0, 1.0, 1.0);
+    pub const MAGENTA: Self = Self::new(1.0, 0.0, 1.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self::new(r, g, b, 1.0)
+    }
+    pub fn from_rgb8(r: u8, g: u8, b: u8) -> Self {
+        Self::from_rgba8(r, g, b, 255)
+    }
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        let channel = |c: u8| c as f32 / 255.0;
+        Self::new(channel(r), channel(g), channel(b), channel(a))
+    }
+    // "#rrggbb" or "#rrggbbaa", with or without the '#'. None if it isn't one of those.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+            return None;
+        }
+        let mut rgba = [255; 4];
+        for (i, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+            *channel = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+        }
+        Some(rgba.into())
+    }
+    // From channels already in linear light, e.g. a color worked out by lighting math.
+    pub fn from_linear(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::new(linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a)
+    }
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+    // [r, g, b, a] in linear light, for blending math and shaders that want it.
+    pub fn to_linear(self) -> [f32; 4] {
+        [
+            srgb_to_linear(self.r),
+            srgb_to_linear(self.g),
+            srgb_to_linear(self.b),
+            self.a,
+        ]
+    }
+    // [r, g, b, a] as they are, still sRGB.
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+    pub fn to_rgba8(self) -> [u8; 4] {
+        self.to_array()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+    // "#rrggbbaa".
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = self.to_rgba8();
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+    // `t` of the way from this to `other`, mixed in linear light so the middle doesn't go murky.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let (from, to) = (self.to_linear(), other.to_linear());
+        let [r, g, b, a] = std::array::from_fn(|i| from[i] + (to[i] - from[i]) * t);
+        Self::from_linear(r, g, b, a)
+    }
+    // For clearing or drawing into a texture of `format` yourself. sRGB formats expect linear
+    // values and encode them on write; other formats store what they're given, so they get the
+    // sRGB channels as they are.
+    pub fn to_wgpu(self, format: wgpu::TextureFormat) -> wgpu::Color {
+        let [r, g, b, a] = if format.is_srgb() || is_float(format) {
+            self.to_linear()
+        } else {
+            self.to_array()
+        };
+        wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        }
+    }
+
+    // The sRGB bytes packed little-endian, how GPUSprite and DebugDraw send colors to the GPU.
+    pub(crate) fn packed(self) -> u32 {
+        u32::from_le_bytes(self.to_rgba8())
+    }
+}
+
+// Float targets (the HDR ones) hold linear light like the sRGB ones, just without encoding it.
+fn is_float(format: wgpu::TextureFormat) -> bool {
+    matches!(
+        format,
+        wgpu::TextureFormat::Rgba16Float
+            | wgpu::TextureFormat::Rgba32Float
+            | wgpu::TextureFormat::Rg11b10Float
+    )
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl From<[u8; 4]> for Color {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Self::from_rgba8(r, g, b, a)
+    }
+}
+
+impl From<[u8; 3]> for Color {
+    fn from([r, g, b]: [u8; 3]) -> Self {
+        Self::from_rgb8(r, g, b)
+    }
+}
+
+impl From<Color> for [u8; 4] {
+    fn from(color: Color) -> Self {
+        color.to_rgba8()
+    }
+}
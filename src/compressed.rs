@@ -0,0 +1,192 @@
+// KTX2 textures kept in their GPU-compressed form (BCn on desktop, ETC2/ASTC on mobile), which
+// take a quarter or less of the VRAM of Rgba8 and upload without any decoding on our side.
+// Nothing is transcoded: Basis Universal (ETC1S or UASTC) files have to be transcoded to a
+// format the target GPUs support ahead of time, e.g. with `ktx transcode --target bc7`, and
+// shipped in one KTX2 file per format.
+use crate::{TextureError, WGPU};
+use std::path::Path;
+use wgpu::util::DeviceExt;
+
+impl WGPU {
+    // Mip levels stored in the file are uploaded too. Only plain 2D textures in a block or Rgba8
+    // format are supported, optionally Zstandard-supercompressed, and the GPU has to support the
+    // format (see WGPU::features). Basis Universal files get a TextureError::Unsupported.
+    pub async fn load_compressed_texture(
+        &self,
+        path: &Path,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        let bytes = crate::assets::read_bytes(path)
+            .await
+            .map_err(|source| TextureError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        self.compressed_texture(&bytes, path, label)
+    }
+    pub fn load_compressed_texture_from_bytes(
+        &self,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        self.compressed_texture(bytes, &crate::gpu::in_memory_source(label), label)
+    }
+
+    fn compressed_texture(
+        &self,
+        bytes: &[u8],
+        source: &Path,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        let unsupported = |reason: String| TextureError::Unsupported {
+            path: source.to_owned(),
+            reason,
+        };
+        let reader = ktx2::Reader::new(bytes).map_err(|e| decode_error(source, e))?;
+        let header = reader.header();
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            return Err(unsupported(
+                "only 2D textures are supported, not 3D, arrays, or cube maps".into(),
+            ));
+        }
+        if header.format.is_none()
+            || header.supercompression_scheme == Some(ktx2::SupercompressionScheme::BasisLZ)
+        {
+            return Err(unsupported(
+                "Basis Universal textures aren't transcoded; transcode it to BC7, ETC2 or ASTC \
+                 ahead of time"
+                    .into(),
+            ));
+        }
+        let format = header
+            .format
+            .and_then(wgpu_format)
+            .ok_or_else(|| unsupported(format!("KTX2 format {:?}", header.format)))?;
+        let missing = format.required_features() - self.device.features();
+        if !missing.is_empty() {
+            return Err(unsupported(format!(
+                "{format:?} needs {missing:?}, which this GPU doesn't have"
+            )));
+        }
+        let (width, height) = (header.pixel_width, header.pixel_height.max(1));
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(TextureError::TooLarge {
+                path: source.to_owned(),
+                width,
+                height,
+                max,
+            });
+        }
+
+        let level_count = header.level_count.max(1);
+        let (block_width, block_height) = format.block_dimensions();
+        let block_bytes = format.block_size(None).unwrap_or(4) as usize;
+        let mut data = Vec::new();
+        for (level, level_bytes) in reader.levels().enumerate() {
+            let level_bytes = match header.supercompression_scheme {
+                None => level_bytes.to_vec(),
+                Some(ktx2::SupercompressionScheme::Zstandard) => {
+                    inflate_zstd(level_bytes).map_err(|e| decode_error(source, e))?
+                }
+                Some(scheme) => {
+                    return Err(unsupported(format!("{scheme:?} supercompression")));
+                }
+            };
+            let level_width = (width >> level).max(1);
+            let level_height = (height >> level).max(1);
+            let expected = level_width.div_ceil(block_width) as usize
+                * level_height.div_ceil(block_height) as usize
+                * block_bytes;
+            if level_bytes.len() < expected {
+                return Err(decode_error(
+                    source,
+                    format!("mip level {level} is truncated"),
+                ));
+            }
+            data.extend_from_slice(&level_bytes[..expected]);
+        }
+
+        let texture = self.device.create_texture_with_data(
+            &self.queue,
+            &wgpu::TextureDescriptor {
+                label,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            &data,
+        );
+        self.remember_label(
+            &texture,
+            &label.map_or_else(|| source.display().to_string(), str::to_owned),
+        );
+        Ok(texture)
+    }
+}
+
+fn decode_error(
+    source: &Path,
+    e: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> TextureError {
+    TextureError::Decode {
+        path: source.to_owned(),
+        source: image::ImageError::Decoding(image::error::DecodingError::new(
+            image::error::ImageFormatHint::Name("KTX2".into()),
+            e,
+        )),
+    }
+}
+
+fn inflate_zstd(compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut decoder = ruzstd::StreamingDecoder::new(compressed)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out)?;
+    Ok(out)
+}
+
+fn wgpu_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat as W};
+    Some(match format {
+        K::R8G8B8A8_UNORM => W::Rgba8Unorm,
+        K::R8G8B8A8_SRGB => W::Rgba8UnormSrgb,
+        K::B8G8R8A8_UNORM => W::Bgra8Unorm,
+        K::B8G8R8A8_SRGB => W::Bgra8UnormSrgb,
+        K::BC1_RGBA_UNORM_BLOCK | K::BC1_RGB_UNORM_BLOCK => W::Bc1RgbaUnorm,
+        K::BC1_RGBA_SRGB_BLOCK | K::BC1_RGB_SRGB_BLOCK => W::Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => W::Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => W::Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => W::Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => W::Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => W::Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => W::Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => W::Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => W::Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => W::Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => W::Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => W::Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => W::Bc7RgbaUnormSrgb,
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => W::Etc2Rgba8Unorm,
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => W::Etc2Rgba8UnormSrgb,
+        K::ASTC_4x4_UNORM_BLOCK => W::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::Unorm,
+        },
+        K::ASTC_4x4_SRGB_BLOCK => W::Astc {
+            block: AstcBlock::B4x4,
+            channel: AstcChannel::UnormSrgb,
+        },
+        _ => return None,
+    })
+}
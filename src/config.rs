@@ -0,0 +1,142 @@
+// How the Engine drives frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopMode {
+    // Game-style: render frames back to back (subject to vsync and fps_cap).
+    Poll,
+    // Editor-style: sleep until an event arrives (input, resize...) and render one frame per batch
+    // of events.
+    Wait,
+    // Tool-style: sleep until an event arrives, like Wait, and then only run a frame if one is
+    // needed: a window event came in, the sprites changed (SpriteRender::has_changes), textures
+    // are waiting to upload, Engine::assets is loading, or the game asked for one with
+    // Engine::request_redraw, e.g. from Game::update to keep an animation going. Gamepads are only
+    // polled during frames, so they don't wake it up.
+    OnDemand,
+}
+
+// How to wait out the rest of a frame when an fps cap is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameLimiter {
+    // Let the OS sleep the thread. Cheap, but can overshoot by a millisecond or two.
+    Sleep,
+    // Busy-wait. Precise, but burns a core.
+    Spin,
+    // Sleep for most of the wait, then spin the last couple of milliseconds.
+    SleepSpin,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FullscreenMode {
+    Windowed,
+    // A borderless window covering the current monitor. Alt-tabs nicely.
+    Borderless,
+    // Takes over the current monitor at its best video mode.
+    Exclusive,
+}
+
+// How the Engine should build its window when it creates one itself (see Engine::launch).
+// Sizes are in logical pixels, so they come out the same physical size on high-DPI screens.
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub title: String,
+    pub size: [u32; 2],
+    pub min_size: Option<[u32; 2]>,
+    pub max_size: Option<[u32; 2]>,
+    pub resizable: bool,
+    pub decorations: bool,
+    pub icon: Option<image::RgbaImage>,
+    pub fullscreen: FullscreenMode,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Game".to_owned(),
+            size: [1024, 768],
+            min_size: None,
+            max_size: None,
+            resizable: true,
+            decorations: true,
+            icon: None,
+            fullscreen: FullscreenMode::Windowed,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub(crate) fn builder(&self) -> winit::window::WindowBuilder {
+        use winit::dpi::LogicalSize;
+        let size = |[w, h]: [u32; 2]| LogicalSize::new(w, h);
+        let mut builder = winit::window::WindowBuilder::new()
+            .with_title(&self.title)
+            .with_inner_size(size(self.size))
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations);
+        if let Some(min) = self.min_size {
+            builder = builder.with_min_inner_size(size(min));
+        }
+        if let Some(max) = self.max_size {
+            builder = builder.with_max_inner_size(size(max));
+        }
+        if let Some(icon) = &self.icon {
+            let (width, height) = icon.dimensions();
+            match winit::window::Icon::from_rgba(icon.as_raw().clone(), width, height) {
+                Ok(icon) => builder = builder.with_window_icon(Some(icon)),
+                Err(e) => log::warn!("Ignoring window icon: {e}"),
+            }
+        }
+        builder
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EngineConfig {
+    pub window: WindowConfig,
+    pub gpu: crate::GpuOptions,
+    pub loop_mode: LoopMode,
+    // Upper bound on frames per second, on top of whatever the present mode does. Ignored on wasm,
+    // where the browser already paces frames.
+    pub fps_cap: Option<f32>,
+    pub frame_limiter: FrameLimiter,
+    // Skip Game::update and pause the audio while the window doesn't have focus. Frames are
+    // still drawn.
+    pub pause_when_unfocused: bool,
+    // What the main window is cleared to before sprites are drawn. None skips the clear, which
+    // saves a little fill rate when a background group covers the whole screen anyway; with
+    // nothing covering it the leftover contents of the surface are undefined.
+    pub clear_color: Option<crate::Color>,
+    // On the web, size the canvas to the whole browser window and follow it as it resizes (or
+    // the page is zoomed). Turn off to lay the canvas out yourself. Ignored on native.
+    pub fill_browser_window: bool,
+    // Reload files watched with SpriteRender::watch_shader when they change on disk, and in debug
+    // builds the textures loaded with load_texture too. On by default in debug builds.
+    pub hot_reload: bool,
+    // Shows and hides the debug overlay (see Engine::set_debug_overlay). F3 in debug builds, none
+    // in release builds.
+    pub debug_overlay_key: Option<crate::Key>,
+    // Seconds between Game::fixed_update calls (and Engine::kinematics steps), 1/60 by default.
+    pub fixed_timestep: f32,
+    // Where relative asset paths are looked for (after any Engine::mount_assets folders). None
+    // uses the assets folder of the project under `cargo run`, or else the one beside the
+    // executable; on the web, the page's folder.
+    pub asset_root: Option<std::path::PathBuf>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            gpu: crate::GpuOptions::default(),
+            loop_mode: LoopMode::Poll,
+            fps_cap: None,
+            frame_limiter: FrameLimiter::SleepSpin,
+            pause_when_unfocused: false,
+            clear_color: Some(crate::Color::GREEN),
+            fill_browser_window: true,
+            hot_reload: cfg!(debug_assertions),
+            debug_overlay_key: cfg!(debug_assertions).then_some(crate::Key::F3),
+            fixed_timestep: 1.0 / 60.0,
+            asset_root: None,
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use crate::{
+    BlendMode, DynamicText, FontError, FontId, GPUCamera, GPUSprite, GlyphAtlas, SpriteRender,
+    TextLayout, VAlign, WGPU,
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode};
+
+// Lines kept for scrolling back through, log lines and command output together.
+const MAX_LINES: usize = 200;
+// Glyphs the console can show at once.
+const CAPACITY: usize = 8192;
+const FONT_SIZE: f32 = 16.0;
+const PADDING: f32 = 6.0;
+// How much of the window, from the top, the console covers.
+const HEIGHT_FRACTION: f32 = 0.4;
+const BACKGROUND: [u8; 4] = [16, 16, 24, 220];
+
+// Log lines waiting for the console to pick them up; the logger can be called from any thread.
+static LOG_LINES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+// env_logger, plus a copy of every line it prints for the console.
+struct CapturingLogger(env_logger::Logger);
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0.enabled(metadata) || metadata.level() <= log::Level::Warn
+    }
+    fn log(&self, record: &log::Record) {
+        // Warnings and errors reach the console even when RUST_LOG hides them from stderr.
+        if self.enabled(record.metadata()) {
+            if let Ok(mut lines) = LOG_LINES.lock() {
+                lines.push_back(format!("[{}] {}", record.level(), record.args()));
+                if lines.len() > MAX_LINES {
+                    lines.pop_front();
+                }
+            }
+        }
+        self.0.log(record);
+    }
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+// Stands in for env_logger::init (RUST_LOG works the same) so the console can show the log.
+pub(crate) fn init_logger() {
+    let logger = env_logger::Builder::from_default_env().build();
+    let max_level = logger.filter().max(log::LevelFilter::Warn);
+    if log::set_boxed_logger(Box::new(CapturingLogger(logger))).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+// A drop-down console over the top of the window, opened and closed with the ` (~) key. It shows
+// the log and runs commands typed into it. Built in are `help`, `clear` and `set <name>
+// [value]` (read back with var); anything registered with register goes to Game::console_command
+// with its arguments split at spaces. While it's open it takes all keyboard input, so the game
+// sees no keys held. Turn it on with Engine::enable_console.
+pub struct Console {
+    sprites: SpriteRender,
+    atlas: GlyphAtlas,
+    font: FontId,
+    text: DynamicText,
+    open: bool,
+    lines: VecDeque<String>,
+    input: String,
+    // Earlier commands for Up and Down, and which one is showing (None for what's being typed).
+    history: Vec<String>,
+    history_at: Option<usize>,
+    // Names and help text of the game's commands.
+    commands: Vec<(String, String)>,
+    vars: HashMap<String, String>,
+    // Commands run since the Engine last picked them up.
+    pending: Vec<(String, Vec<String>)>,
+    size: (u32, u32),
+    dirty: bool,
+}
+
+impl Console {
+    pub(crate) fn new(gpu: &WGPU, font: Vec<u8>) -> Result<Self, FontError> {
+        let mut sprites = SpriteRender::new(gpu);
+        let mut atlas = GlyphAtlas::new(gpu, 512);
+        let font = atlas.add_font(font)?;
+        let camera = GPUCamera::fixed(1.0, 1.0);
+        sprites.add_rect_group(gpu, vec![GPUSprite::solid([0.0; 4], BACKGROUND)], camera);
+        sprites.set_group_blend(gpu, 0, BlendMode::Alpha);
+        let layout = TextLayout::new(font, FONT_SIZE, [0.0; 4]);
+        let text = DynamicText::new(gpu, &mut sprites, &atlas, camera, layout, CAPACITY);
+        Ok(Self {
+            sprites,
+            atlas,
+            font,
+            text,
+            open: false,
+            lines: VecDeque::new(),
+            input: String::new(),
+            history: Vec::new(),
+            history_at: None,
+            commands: Vec::new(),
+            vars: HashMap::new(),
+            pending: Vec::new(),
+            size: (0, 0),
+            dirty: true,
+        })
+    }
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+    }
+    // Makes `name` a command; `help` is what the help command says about it.
+    pub fn register(&mut self, name: impl Into<String>, help: impl Into<String>) {
+        let name = name.into();
+        self.commands.retain(|(existing, _)| *existing != name);
+        self.commands.push((name, help.into()));
+    }
+    // Adds a line to the console, e.g. a command's result.
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.lines.push_back(line.into());
+        if self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.dirty = true;
+    }
+    pub fn var(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+    pub fn set_var(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.vars.insert(name.into(), value.into());
+    }
+    // Returns whether the console used the key, in which case the game shouldn't see it.
+    pub(crate) fn handle_key(&mut self, key: KeyboardInput) -> bool {
+        let Some(code) = key.virtual_keycode else {
+            return self.open;
+        };
+        if key.state == ElementState::Released {
+            return self.open;
+        }
+        match code {
+            VirtualKeyCode::Grave => self.open = !self.open,
+            _ if !self.open => return false,
+            VirtualKeyCode::Escape => self.open = false,
+            VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => self.submit(),
+            VirtualKeyCode::Back => {
+                self.input.pop();
+            }
+            VirtualKeyCode::Up if !self.history.is_empty() => {
+                let at = self
+                    .history_at
+                    .map_or(self.history.len() - 1, |at| at.saturating_sub(1));
+                self.history_at = Some(at);
+                self.input = self.history[at].clone();
+            }
+            VirtualKeyCode::Down => {
+                self.history_at = self
+                    .history_at
+                    .map(|at| at + 1)
+                    .filter(|at| *at < self.history.len());
+                self.input = self
+                    .history_at
+                    .map_or_else(String::new, |at| self.history[at].clone());
+            }
+            _ => {}
+        }
+        self.dirty = true;
+        true
+    }
+    pub(crate) fn handle_char(&mut self, c: char) -> bool {
+        if !self.open {
+            return false;
+        }
+        // The key that opened the console types one of these too.
+        if !c.is_control() && c != '`' && c != '~' {
+            self.input.push(c);
+            self.dirty = true;
+        }
+        true
+    }
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.history_at = None;
+        let mut words = line.split_whitespace().map(str::to_owned);
+        let Some(name) = words.next() else {
+            return;
+        };
+        let args: Vec<String> = words.collect();
+        self.print(format!("> {line}"));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        match name.as_str() {
+            "help" => {
+                self.print("help, clear, set <name> [value]");
+                for i in 0..self.commands.len() {
+                    let (name, help) = &self.commands[i];
+                    let line = format!("{name}: {help}");
+                    self.print(line);
+                }
+            }
+            "clear" => self.lines.clear(),
+            "set" => match args.as_slice() {
+                [name] => {
+                    let value = self.var(name).unwrap_or("(not set)").to_owned();
+                    self.print(format!("{name} = {value}"));
+                }
+                [name, value @ ..] if !value.is_empty() => {
+                    let value = value.join(" ");
+                    self.print(format!("{name} = {value}"));
+                    self.vars.insert(name.clone(), value);
+                }
+                _ => self.print("usage: set <name> [value]"),
+            },
+            _ if self.commands.iter().any(|(command, _)| *command == name) => {
+                self.pending.push((name, args));
+            }
+            _ => self.print(format!("unknown command: {name} (try help)")),
+        }
+    }
+    pub(crate) fn take_commands(&mut self) -> Vec<(String, Vec<String>)> {
+        std::mem::take(&mut self.pending)
+    }
+    // Picks up new log lines and, if anything changed, lays the console out again for a window
+    // of `size` pixels and copies the new sprites over in `encoder`.
+    pub(crate) fn prepare(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        size: (u32, u32),
+    ) {
+        if let Ok(mut log) = LOG_LINES.lock() {
+            for line in log.drain(..) {
+                self.print(line);
+            }
+        }
+        if !self.open {
+            return;
+        }
+        let (width, height) = (size.0 as f32, size.1 as f32);
+        let top = height * HEIGHT_FRACTION;
+        if size != self.size {
+            self.size = size;
+            self.sprites.set_target_size(size.0, size.1);
+            self.sprites
+                .set_camera_all(gpu, GPUCamera::fixed(width, height));
+            self.sprites.get_sprite_mut(0, 0).screen_region = [0.0, height - top, width, top];
+            self.sprites
+                .set_group_clip(self.text.group(), Some([0, 0, size.0, top.ceil() as u32]));
+            let layout = TextLayout::new(
+                self.font,
+                FONT_SIZE,
+                [
+                    PADDING,
+                    height - top + PADDING,
+                    width - PADDING * 2.0,
+                    top - PADDING * 2.0,
+                ],
+            )
+            .with_align(Default::default(), VAlign::Bottom);
+            self.text
+                .set_layout(gpu, &mut self.sprites, &mut self.atlas, layout);
+        }
+        if self.dirty {
+            self.dirty = false;
+            // The newest lines are at the bottom, by the prompt; older ones fall off the top.
+            let shown = (top / self.atlas.line_height(self.font, FONT_SIZE)) as usize + 1;
+            let mut text: String = self
+                .lines
+                .iter()
+                .skip(self.lines.len().saturating_sub(shown))
+                .flat_map(|line| [line.as_str(), "\n"])
+                .collect();
+            text.push_str("> ");
+            text.push_str(&self.input);
+            text.push('_');
+            self.text
+                .set_text(gpu, &mut self.sprites, &mut self.atlas, &text);
+        }
+        self.sprites.flush(gpu, encoder);
+    }
+    // Draws into the main sprite pass, over the game's sprites.
+    pub(crate) fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        if self.open {
+            self.sprites.render(rpass);
+        }
+    }
+    pub(crate) fn collect_memory(
+        &self,
+        textures: &mut std::collections::HashMap<wgpu::Id<wgpu::Texture>, crate::TextureMemory>,
+    ) -> u64 {
+        self.sprites.collect_memory(textures)
+    }
+    pub(crate) fn atlas_usage(&self) -> crate::AtlasUsage {
+        self.atlas.usage()
+    }
+    pub(crate) fn recreate(&mut self, gpu: &WGPU) {
+        self.sprites.recreate(gpu);
+        self.atlas.recreate(gpu);
+        self.sprites
+            .set_group_texture(gpu, self.text.group(), self.atlas.texture());
+    }
+}
@@ -0,0 +1,458 @@
+use crate::{Color, GPUCamera, Rect, SpriteRender, Vec2, WGPU};
+use std::borrow::Cow;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+struct DebugVertex {
+    // Clip space; shapes are put through the camera as they're added.
+    position: [f32; 2],
+    // Packed sRGB, like GPUSprite::outline_color.
+    color: u32,
+}
+
+const DEBUG_VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+    wgpu::vertex_attr_array![0 => Float32x2, 1 => Uint32];
+
+// Segments per circle.
+const CIRCLE_SEGMENTS: usize = 32;
+// Half the size of the cross drawn for a point, in pixels.
+const POINT_RADIUS: f32 = 3.0;
+// Label glyphs are seven-segment digits this many pixels wide and twice as tall.
+const GLYPH_WIDTH: f32 = 4.0;
+
+// Which of a digit's segments are lit: top, top right, bottom right, bottom, bottom left,
+// top left, middle.
+const SEGMENTS: [u8; 10] = [0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f];
+
+// Screen pixels per pixel of the font text() writes in, and how far apart its glyphs start.
+const TEXT_SCALE: usize = 2;
+const TEXT_ADVANCE: f32 = 8.0;
+
+// A 3x5 pixel font for text(), rows from the top, with the leftmost pixel in the highest bit.
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        '?' => [0b111, 0b001, 0b010, 0b000, 0b010],
+        _ => return None,
+    })
+}
+
+// Outline colors for show_sprite_bounds, one per group (wrapping around).
+const GROUP_COLORS: [[u8; 4]; 6] = [
+    [255, 64, 64, 255],
+    [64, 255, 64, 255],
+    [64, 160, 255, 255],
+    [255, 255, 64, 255],
+    [255, 64, 255, 255],
+    [64, 255, 255, 255],
+];
+
+// Lines, rectangles, circles and points drawn over the finished frame (after post-processing),
+// for seeing hitboxes, paths and velocities. Shapes only last one frame, so draw them again from
+// every update you want them in. On by default in debug builds only; in release builds every
+// call returns right away unless the game turns it on with set_enabled.
+pub struct DebugDraw {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    // Two vertices per line.
+    lines: Vec<DebugVertex>,
+    // None draws in window pixels, with (0, 0) at the bottom left.
+    camera: Option<GPUCamera>,
+    // Size of the last frame, for pixel-sized things and the default camera.
+    target_size: (u32, u32),
+    show_sprite_bounds: bool,
+    enabled: bool,
+}
+
+impl DebugDraw {
+    pub(crate) fn new(gpu: &WGPU, format: wgpu::TextureFormat) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("debug draw"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("debug_draw.wgsl"))),
+            });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("debug draw"),
+                bind_group_layouts: &[],
+                push_constant_ranges: &[],
+            });
+        let pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("debug draw"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<DebugVertex>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &DEBUG_VERTEX_ATTRIBUTES,
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+        Self {
+            pipeline,
+            vertex_buffer: Self::create_vertex_buffer(gpu, 256),
+            format,
+            lines: Vec::new(),
+            camera: None,
+            target_size: (gpu.config.width, gpu.config.height),
+            show_sprite_bounds: false,
+            enabled: cfg!(debug_assertions),
+        }
+    }
+    fn create_vertex_buffer(gpu: &WGPU, vertices: usize) -> wgpu::Buffer {
+        gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug draw vertices"),
+            size: (vertices * std::mem::size_of::<DebugVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    // Shapes added after this are in the camera's world space, usually the one the sprites
+    // they're about use. Without one they're in window pixels.
+    pub fn set_camera(&mut self, camera: Option<GPUCamera>) {
+        self.camera = camera;
+    }
+    pub fn camera(&self) -> Option<GPUCamera> {
+        self.camera
+    }
+    // Outlines every sprite's screen_region each frame, marks its origin (the bottom-left corner
+    // it's positioned by), and labels it "group.index". Each group gets its own color.
+    pub fn show_sprite_bounds(&mut self, show: bool) {
+        self.show_sprite_bounds = show;
+    }
+    pub fn shows_sprite_bounds(&self) -> bool {
+        self.show_sprite_bounds
+    }
+    fn to_clip(&self, at: impl Into<Vec2>) -> [f32; 2] {
+        let at = at.into();
+        let camera = self.camera.unwrap_or(GPUCamera::fixed(
+            self.target_size.0 as f32,
+            self.target_size.1 as f32,
+        ));
+        [
+            (at.x - camera.screen_pos[0]) / (camera.screen_size[0] / 2.0) - 1.0,
+            (at.y - camera.screen_pos[1]) / (camera.screen_size[1] / 2.0) - 1.0,
+        ]
+    }
+    // How big a pixel is in clip space.
+    fn pixel(&self) -> [f32; 2] {
+        [
+            2.0 / self.target_size.0.max(1) as f32,
+            2.0 / self.target_size.1.max(1) as f32,
+        ]
+    }
+    fn clip_line(&mut self, from: [f32; 2], to: [f32; 2], color: u32) {
+        self.lines.extend([
+            DebugVertex {
+                position: from,
+                color,
+            },
+            DebugVertex {
+                position: to,
+                color,
+            },
+        ]);
+    }
+    // Colors are sRGB, as a Color or [r, g, b, a] bytes, like GPUSprite::with_outline.
+    pub fn line(&mut self, from: impl Into<Vec2>, to: impl Into<Vec2>, color: impl Into<Color>) {
+        if !self.enabled {
+            return;
+        }
+        self.clip_line(self.to_clip(from), self.to_clip(to), color.into().packed());
+    }
+    // An outline of a Rect, or of an [x, y, width, height] such as GPUSprite::screen_region.
+    pub fn rect(&mut self, rect: impl Into<Rect>, color: impl Into<Color>) {
+        let [x, y, w, h] = rect.into().region();
+        let color = color.into();
+        let corners = [[x, y], [x + w, y], [x + w, y + h], [x, y + h]];
+        for i in 0..4 {
+            self.line(corners[i], corners[(i + 1) % 4], color);
+        }
+    }
+    pub fn circle(&mut self, center: impl Into<Vec2>, radius: f32, color: impl Into<Color>) {
+        let (center, color) = (center.into(), color.into());
+        let at = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(at(i), at(i + 1), color);
+        }
+    }
+    // A small cross that stays the same size on screen whatever the camera's zoom.
+    pub fn point(&mut self, at: impl Into<Vec2>, color: impl Into<Color>) {
+        if !self.enabled {
+            return;
+        }
+        let [x, y] = self.to_clip(at);
+        let [px, py] = self.pixel();
+        let (dx, dy) = (px * POINT_RADIUS, py * POINT_RADIUS);
+        let color = color.into().packed();
+        self.clip_line([x - dx, y], [x + dx, y], color);
+        self.clip_line([x, y - dy], [x, y + dy], color);
+    }
+    // An arrow from `from` along `vector`, e.g. a velocity.
+    pub fn arrow(
+        &mut self,
+        from: impl Into<Vec2>,
+        vector: impl Into<Vec2>,
+        color: impl Into<Color>,
+    ) {
+        let (from, vector, color) = (from.into(), vector.into(), color.into());
+        let to = from + vector;
+        self.line(from, to, color);
+        if vector == Vec2::ZERO {
+            return;
+        }
+        // The head is a quarter of the arrow's length, swept back 30 degrees each way.
+        let back = -vector / 4.0;
+        for angle in [std::f32::consts::FRAC_PI_6, -std::f32::consts::FRAC_PI_6] {
+            self.line(to, to + back.rotate(angle), color);
+        }
+    }
+    // Writes digits, '.' and '-' in small seven-segment glyphs that stay the same size on
+    // screen, starting at `at` (the bottom left of the first glyph). Anything else leaves a gap.
+    pub fn label(&mut self, at: impl Into<Vec2>, text: &str, color: impl Into<Color>) {
+        if !self.enabled {
+            return;
+        }
+        let [x, y] = self.to_clip(at);
+        let [px, py] = self.pixel();
+        let (w, h) = (GLYPH_WIDTH * px, GLYPH_WIDTH * py);
+        let color = color.into().packed();
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i as f32 * (GLYPH_WIDTH + 2.0) * px;
+            let lit = match c {
+                '0'..='9' => SEGMENTS[c as usize - '0' as usize],
+                '-' => 0x40,
+                '.' => {
+                    let mid = left + w / 2.0;
+                    self.clip_line([mid, y], [mid, y + py], color);
+                    continue;
+                }
+                _ => continue,
+            };
+            let (right, top, middle) = (left + w, y + 2.0 * h, y + h);
+            let segments = [
+                ([left, top], [right, top]),
+                ([right, top], [right, middle]),
+                ([right, middle], [right, y]),
+                ([left, y], [right, y]),
+                ([left, middle], [left, y]),
+                ([left, top], [left, middle]),
+                ([left, middle], [right, middle]),
+            ];
+            for (bit, (from, to)) in segments.into_iter().enumerate() {
+                if lit & (1 << bit) != 0 {
+                    self.clip_line(from, to, color);
+                }
+            }
+        }
+    }
+    // Writes `text` in a small blocky font (capitals only; lowercase is shown as uppercase) that
+    // stays the same size on screen, starting at `at` (the bottom left of the first glyph). Glyphs
+    // are 6 pixels wide and 10 tall, 8 apart. Characters the font doesn't have leave a gap.
+    pub fn text(&mut self, at: impl Into<Vec2>, text: &str, color: impl Into<Color>) {
+        if !self.enabled {
+            return;
+        }
+        let [x, y] = self.to_clip(at);
+        let [px, py] = self.pixel();
+        let color = color.into().packed();
+        for (i, c) in text.chars().enumerate() {
+            let Some(rows) = glyph(c.to_ascii_uppercase()) else {
+                continue;
+            };
+            let left = x + i as f32 * TEXT_ADVANCE * px;
+            for (row, bits) in rows.into_iter().enumerate() {
+                let bottom = y + ((4 - row) * TEXT_SCALE) as f32 * py;
+                let lit = |column: usize| column < 3 && bits & (0b100 >> column) != 0;
+                // One line per screen pixel row of each run of lit font pixels.
+                let mut column = 0;
+                while column < 3 {
+                    if !lit(column) {
+                        column += 1;
+                        continue;
+                    }
+                    let start = column;
+                    while lit(column) {
+                        column += 1;
+                    }
+                    let from = left + (start * TEXT_SCALE) as f32 * px;
+                    let to = left + (column * TEXT_SCALE) as f32 * px;
+                    for line in 0..TEXT_SCALE {
+                        let y = bottom + (line as f32 + 0.5) * py;
+                        self.clip_line([from, y], [to, y], color);
+                    }
+                }
+            }
+        }
+    }
+    // A filled Rect (or [x, y, width, height]), drawn as one line per screen pixel row, so keep it
+    // to panel backgrounds and the like.
+    pub fn fill_rect(&mut self, rect: impl Into<Rect>, color: impl Into<Color>) {
+        if !self.enabled {
+            return;
+        }
+        let [x, y, w, h] = rect.into().region();
+        let [left, bottom] = self.to_clip([x, y]);
+        let [right, top] = self.to_clip([x + w, y + h]);
+        let py = self.pixel()[1];
+        let color = color.into().packed();
+        // Rows off screen would only be clipped away.
+        let mut row = bottom.max(-1.0) + py / 2.0;
+        while row < top.min(1.0) {
+            self.clip_line([left, row], [right, row], color);
+            row += py;
+        }
+    }
+    // Drops everything drawn since the last frame without showing it.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+    pub(crate) fn add_sprite_bounds(&mut self, sprites: &SpriteRender) {
+        if !self.show_sprite_bounds || !self.enabled {
+            return;
+        }
+        let camera = self.camera;
+        for group in 0..sprites.group_count() {
+            let color = GROUP_COLORS[group % GROUP_COLORS.len()];
+            self.camera = Some(sprites.get_camera(group));
+            for (index, sprite) in sprites.get_sprites(group).iter().enumerate() {
+                let [x, y, _, _] = sprite.screen_region;
+                self.rect(sprite.screen_region, color);
+                self.point([x, y], color);
+                self.label([x, y], &format!("{group}.{index}"), color);
+            }
+        }
+        self.camera = camera;
+    }
+    // Draws this frame's shapes over `view` and forgets them.
+    pub(crate) fn render(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size: (u32, u32),
+    ) {
+        self.target_size = size;
+        if self.lines.is_empty() {
+            return;
+        }
+        let needed = std::mem::size_of_val(self.lines.as_slice()) as u64;
+        if needed > self.vertex_buffer.size() {
+            self.vertex_buffer =
+                Self::create_vertex_buffer(gpu, self.lines.len().next_power_of_two());
+        }
+        gpu.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.lines));
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("debug draw"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..needed));
+            rpass.draw(0..self.lines.len() as u32, 0..1);
+        }
+        self.lines.clear();
+    }
+    pub(crate) fn recreate(&mut self, gpu: &WGPU) {
+        let lines = std::mem::take(&mut self.lines);
+        let (camera, show_sprite_bounds, enabled) =
+            (self.camera, self.show_sprite_bounds, self.enabled);
+        *self = Self::new(gpu, self.format);
+        self.lines = lines;
+        self.camera = camera;
+        self.show_sprite_bounds = show_sprite_bounds;
+        self.enabled = enabled;
+    }
+}
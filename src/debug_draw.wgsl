@@ -0,0 +1,20 @@
+// Lines for DebugDraw, already in clip space.
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: u32) -> VertexOutput {
+    let rgba = unpack4x8unorm(color);
+    return VertexOutput(vec4(position, 0.0, 1.0), vec4(srgb_to_linear(rgba.rgb), rgba.a));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
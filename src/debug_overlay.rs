@@ -0,0 +1,115 @@
+use crate::{DebugDraw, FrameStats, MemoryReport, SpriteRender};
+use std::collections::VecDeque;
+
+// In window pixels. DebugDraw::text is 10 pixels tall and 8 wide per character.
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 6.0;
+const ROW_HEIGHT: f32 = 14.0;
+const WIDTH: f32 = 340.0;
+const GRAPH_HEIGHT: f32 = 40.0;
+// The frame time the top of the graph stands for: two frames at 60Hz.
+const GRAPH_MAX: f32 = 2.0 / 60.0;
+// Groups listed a row each before the rest are summed up in one.
+const MAX_GROUPS: usize = 8;
+
+const BACKGROUND: [u8; 4] = [0, 0, 0, 160];
+const TEXT: [u8; 4] = [255, 255, 255, 255];
+const GOOD: [u8; 4] = [64, 255, 64, 255];
+const SLOW: [u8; 4] = [255, 255, 64, 255];
+const BAD: [u8; 4] = [255, 64, 64, 255];
+
+const MB: f64 = 1024.0 * 1024.0;
+
+// Draws the overlay EngineConfig::debug_overlay_key toggles into the top left of a window `size`
+// pixels big, through `debug`: FPS and frame times with a graph of the recent ones, what the last
+// frame drew, each group's sprite count and camera position, and GPU memory.
+pub(crate) fn draw(
+    debug: &mut DebugDraw,
+    stats: &FrameStats,
+    frame_times: &VecDeque<f32>,
+    sprites: &SpriteRender,
+    memory: &MemoryReport,
+    size: (u32, u32),
+) {
+    let ms = |seconds: f32| seconds * 1000.0;
+    let mut above_graph = vec![
+        format!(
+            "FPS {:.1}  {:.1} MS  P99 {:.1} MS",
+            stats.fps,
+            ms(stats.frame_time_avg),
+            ms(stats.frame_time_p99)
+        ),
+        format!(
+            "UPDATE {:.2} MS  RENDER {:.2} MS",
+            ms(stats.update_time),
+            ms(stats.render_time)
+        ),
+    ];
+    for pass in &stats.gpu_passes {
+        above_graph.push(format!("GPU {} {:.2} MS", pass.label, ms(pass.seconds)));
+    }
+    let mut below_graph = vec![format!(
+        "DRAW CALLS {}  SPRITES {}",
+        stats.draw_calls, stats.sprites
+    )];
+    for group in 0..sprites.group_count().min(MAX_GROUPS) {
+        let [x, y] = sprites.get_camera(group).screen_pos;
+        below_graph.push(format!(
+            "GROUP {group}  {}  CAMERA {x:.0},{y:.0}",
+            sprites.get_sprites(group).len()
+        ));
+    }
+    if sprites.group_count() > MAX_GROUPS {
+        let rest: usize = (MAX_GROUPS..sprites.group_count())
+            .map(|group| sprites.get_sprites(group).len())
+            .sum();
+        below_graph.push(format!(
+            "{} MORE GROUPS  {rest}",
+            sprites.group_count() - MAX_GROUPS
+        ));
+    }
+    below_graph.push(format!(
+        "TEXTURES {:.1} MB  BUFFERS {:.1} MB",
+        memory.texture_bytes() as f64 / MB,
+        memory.sprite_buffer_bytes as f64 / MB
+    ));
+
+    let camera = debug.camera();
+    debug.set_camera(None);
+    let rows = (above_graph.len() + below_graph.len()) as f32;
+    let height = rows * ROW_HEIGHT + GRAPH_HEIGHT + 3.0 * PADDING;
+    let top = size.1 as f32 - MARGIN;
+    debug.fill_rect([MARGIN, top - height, WIDTH, height], BACKGROUND);
+
+    let left = MARGIN + PADDING;
+    // The bottom of the next row of text.
+    let mut y = top - PADDING - ROW_HEIGHT;
+    for row in &above_graph {
+        debug.text([left, y], row, TEXT);
+        y -= ROW_HEIGHT;
+    }
+    // Bars for the recent frame times, oldest on the left, under a line at 60Hz.
+    let graph_bottom = y - GRAPH_HEIGHT + ROW_HEIGHT;
+    let graph_width = WIDTH - 2.0 * PADDING;
+    let bar = graph_width / crate::stats::WINDOW as f32;
+    for (i, &seconds) in frame_times.iter().enumerate() {
+        let x = left + (i as f32 + 0.5) * bar;
+        let color = if seconds <= 1.05 / 60.0 {
+            GOOD
+        } else if seconds <= 1.05 / 30.0 {
+            SLOW
+        } else {
+            BAD
+        };
+        let height = (seconds / GRAPH_MAX).min(1.0) * GRAPH_HEIGHT;
+        debug.line([x, graph_bottom], [x, graph_bottom + height], color);
+    }
+    let target = graph_bottom + GRAPH_HEIGHT / 2.0;
+    debug.line([left, target], [left + graph_width, target], TEXT);
+    y = graph_bottom - ROW_HEIGHT - PADDING;
+    for row in &below_graph {
+        debug.text([left, y], row, TEXT);
+        y -= ROW_HEIGHT;
+    }
+    debug.set_camera(camera);
+}
@@ -0,0 +1,128 @@
+use crate::{
+    BlendMode, Color, GPUCamera, GPUSprite, GlyphAtlas, SamplerOptions, ShaderId, SpriteRender,
+    TextLayout, TextSpan, WGPU,
+};
+
+// Text that changes all the time, like a score or a timer, in a sprite group of its own with
+// room for `capacity` glyphs. Setting new text only sends the glyphs that actually changed to the
+// GPU, so a counter ticking up usually costs a sprite or two.
+pub struct DynamicText {
+    group: usize,
+    layout: TextLayout,
+    color: Option<Color>,
+    text: String,
+    // What the group holds now; always `capacity` sprites, the unused ones empty.
+    current: Vec<GPUSprite>,
+}
+
+// Takes up no pixels, for the unused end of the group.
+fn blank() -> GPUSprite {
+    GPUSprite::new([0.0; 4], [0.0; 4])
+}
+
+impl DynamicText {
+    // Adds the group (smoothly sampled and alpha blended, with the SDF shader for SDF atlases).
+    // Text longer than `capacity` glyphs is cut off.
+    pub fn new(
+        gpu: &WGPU,
+        sprites: &mut SpriteRender,
+        atlas: &GlyphAtlas,
+        camera: GPUCamera,
+        layout: TextLayout,
+        capacity: usize,
+    ) -> Self {
+        let current = vec![blank(); capacity];
+        sprites.add_sprite_group_with_sampler(
+            gpu,
+            atlas.texture(),
+            current.clone(),
+            camera,
+            SamplerOptions::SMOOTH,
+        );
+        let group = sprites.group_count() - 1;
+        sprites.set_group_blend(gpu, group, BlendMode::Alpha);
+        if atlas.is_sdf() {
+            sprites.set_group_shader(gpu, group, ShaderId::SDF_TEXT);
+        }
+        Self {
+            group,
+            layout,
+            color: None,
+            text: String::new(),
+            current,
+        }
+    }
+    pub fn group(&self) -> usize {
+        self.group
+    }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    pub fn set_text(
+        &mut self,
+        gpu: &WGPU,
+        sprites: &mut SpriteRender,
+        atlas: &mut GlyphAtlas,
+        text: &str,
+    ) {
+        if text == self.text {
+            return;
+        }
+        self.text = text.to_owned();
+        self.update(gpu, sprites, atlas);
+    }
+    // Tints the whole text; None leaves it white.
+    pub fn set_color(
+        &mut self,
+        gpu: &WGPU,
+        sprites: &mut SpriteRender,
+        atlas: &mut GlyphAtlas,
+        color: Option<Color>,
+    ) {
+        if color == self.color {
+            return;
+        }
+        self.color = color;
+        self.update(gpu, sprites, atlas);
+    }
+    // Moves, rewraps or realigns the text, e.g. after the window was resized.
+    pub fn set_layout(
+        &mut self,
+        gpu: &WGPU,
+        sprites: &mut SpriteRender,
+        atlas: &mut GlyphAtlas,
+        layout: TextLayout,
+    ) {
+        if layout == self.layout {
+            return;
+        }
+        self.layout = layout;
+        self.update(gpu, sprites, atlas);
+    }
+    fn update(&mut self, gpu: &WGPU, sprites: &mut SpriteRender, atlas: &mut GlyphAtlas) {
+        let capacity = self.current.len();
+        let mut span = TextSpan::new(self.text.as_str());
+        span.color = self.color;
+        let mut laid_out = atlas.layout_spans(&self.layout, &[span]).sprites;
+        laid_out.resize(capacity, blank());
+        // A grown atlas is a new texture with every glyph's sheet_region moved, so everything
+        // has to go. (Other groups drawing from the same atlas need the new texture too.)
+        let changed = if atlas.upload(gpu) {
+            sprites.set_group_texture(gpu, self.group, atlas.texture());
+            0..capacity
+        } else {
+            let differs = |i: &usize| {
+                bytemuck::bytes_of(&laid_out[*i]) != bytemuck::bytes_of(&self.current[*i])
+            };
+            let Some(first) = (0..capacity).find(differs) else {
+                return;
+            };
+            let last = (0..capacity).rev().find(differs).unwrap_or(first);
+            first..last + 1
+        };
+        sprites
+            .get_sprites_mut(self.group, changed.clone())
+            .copy_from_slice(&laid_out[changed]);
+        self.current = laid_out;
+    }
+}
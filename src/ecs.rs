@@ -0,0 +1,90 @@
+use crate::{GPUSprite, SpriteHandle, SpriteRender, Vec2};
+use bevy_ecs::prelude::*;
+
+// Which sprite an entity draws as. Engine::spawn_sprite adds it; keep it rather than changing
+// it, since Engine::despawn_sprite relies on the indices being the Engine's to fix up.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpriteRef(pub SpriteHandle);
+
+// Where the entity's sprite is drawn, in the group camera's coordinates: the sprite's
+// screen_region, which the Engine sets from this every frame.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    // Bottom-left corner.
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl Transform {
+    pub fn from_region(region: [f32; 4]) -> Self {
+        Self {
+            position: Vec2::new(region[0], region[1]),
+            size: Vec2::new(region[2], region[3]),
+        }
+    }
+    pub fn region(&self) -> [f32; 4] {
+        [self.position.x, self.position.y, self.size.x, self.size.y]
+    }
+}
+
+// Units per second the Engine moves the entity's Transform by, every fixed update.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Velocity(pub Vec2);
+
+pub(crate) fn spawn_sprite(
+    world: &mut World,
+    sprites: &mut SpriteRender,
+    group: usize,
+    sprite: GPUSprite,
+) -> Entity {
+    let index = sprites.push_sprite(group, sprite);
+    world
+        .spawn((
+            SpriteRef(SpriteHandle::new(group, index)),
+            Transform::from_region(sprite.screen_region),
+        ))
+        .id()
+}
+
+// Removes the entity and its sprite, moving the SpriteRefs of the sprites after it down an index
+// to match. False if it doesn't exist.
+pub(crate) fn despawn_sprite(
+    world: &mut World,
+    sprites: &mut SpriteRender,
+    entity: Entity,
+) -> bool {
+    let Some(SpriteRef(removed)) = world.get::<SpriteRef>(entity).copied() else {
+        return world.despawn(entity);
+    };
+    world.despawn(entity);
+    sprites.remove_sprite(removed.group, removed.index);
+    for mut sprite in world.query::<&mut SpriteRef>().iter_mut(world) {
+        if sprite.0.group == removed.group && sprite.0.index > removed.index {
+            sprite.0.index -= 1;
+        }
+    }
+    true
+}
+
+pub(crate) fn apply_velocities(world: &mut World, dt: f32) {
+    for (mut transform, velocity) in world.query::<(&mut Transform, &Velocity)>().iter_mut(world) {
+        transform.position += velocity.0 * dt;
+    }
+}
+
+// Copies every Transform into its sprite's screen_region, touching only the sprites that moved so
+// the rest aren't uploaded again. Entities whose sprite is gone are skipped.
+pub(crate) fn sync_sprites(world: &mut World, sprites: &mut SpriteRender) {
+    for (sprite, transform) in world.query::<(&SpriteRef, &Transform)>().iter(world) {
+        let SpriteHandle { group, index } = sprite.0;
+        let region = transform.region();
+        let Some(current) = sprites.try_get_sprites(group).and_then(|s| s.get(index)) else {
+            continue;
+        };
+        if current.screen_region != region {
+            sprites.get_sprites_mut(group, index..index + 1)[0].screen_region = region;
+        }
+    }
+}
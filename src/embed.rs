@@ -0,0 +1,50 @@
+// Builds a texture from an image compiled into the binary, for wasm and single-file builds that
+// have no asset folder to load from. `$loader` is anything with load_texture_from_bytes (an
+// Engine or a WGPU), and the path is relative to the file the macro is used in, like
+// include_bytes!. The file name doubles as the texture's label.
+//
+//     let (tex, img) = engine::include_texture!(engine, "../assets/king.png")?;
+#[macro_export]
+macro_rules! include_texture {
+    ($loader:expr, $path:literal) => {
+        $loader.load_texture_from_bytes(include_bytes!($path), Some($path))
+    };
+}
+
+// For a game's build.rs: packs everything under `dir` (e.g. "assets") into a pak in OUT_DIR for
+// embed_assets! to compile in, and has cargo run the build script again when anything in it
+// changes.
+//
+//     // build.rs, with engine under [build-dependencies] as well
+//     fn main() {
+//         engine::embed_assets_dir("assets").unwrap();
+//     }
+pub fn embed_assets_dir(dir: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    let dir = dir.as_ref();
+    let out_dir = std::env::var_os("OUT_DIR")
+        .ok_or_else(|| std::io::Error::other("embed_assets_dir has to run in a build script"))?;
+    println!("cargo:rerun-if-changed={}", dir.display());
+    crate::pack_dir(
+        dir,
+        std::path::Path::new(&out_dir).join("embedded_assets.pak"),
+    )
+}
+
+// The assets embed_assets_dir packed, as a PakArchive (or an io::Error if the pak is broken).
+// Given the Engine, mounts them instead, so every asset path finds its file inside the binary
+// and the game runs with no files next to it, on the web as well:
+//
+//     engine::embed_assets!(engine)?; // in Game::init
+//     let (tex, img) = engine.load_texture("king.png", None).await?;
+#[macro_export]
+macro_rules! embed_assets {
+    () => {
+        $crate::PakArchive::from_bytes(
+            &include_bytes!(concat!(env!("OUT_DIR"), "/embedded_assets.pak"))[..],
+            "embedded assets",
+        )
+    };
+    ($engine:expr) => {
+        $crate::embed_assets!().map(|archive| $engine.mount_archive(archive))
+    };
+}
@@ -1,63 +1,301 @@
-use crate::{input, sprite::SpriteRender, GPUCamera, Game, WGPU};
+use crate::gpu::BuiltinPass;
+use crate::{
+    input, sprite::SpriteRender, Animator, DebugDraw, DebugView, EngineConfig, EngineError,
+    FullscreenMode, GPUCamera, Game, Kinematics, LoopMode, PassContext, PostProcess, RenderGraph,
+    SecondaryWindow, SecondaryWindowId, TextureError, Triggers, UploadScheduler, WindowConfig,
+    WGPU,
+};
 use winit::{
-    dpi::PhysicalSize,
     event::{Event, WindowEvent},
-    event_loop::{self, ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoop},
     window::Window,
 };
+
+// Most fixed updates run in one frame.
+const MAX_FIXED_STEPS: u32 = 5;
+
 pub struct Engine {
     pub gpu: WGPU,
+    // Declared after gpu so the surface is dropped before the window it draws into.
+    window: Window,
     pub sprites: SpriteRender,
+    // HUD and menu sprites, drawn over the finished frame with a camera pinned to the window.
+    pub ui: crate::UiLayer,
+    pub post: PostProcess,
+    // Which passes make up a frame, and in what order; add passes of your own here.
+    pub graph: RenderGraph,
+    debug: DebugDraw,
+    debug_overlay: bool,
+    #[cfg(feature = "text")]
+    console: Option<crate::Console>,
     pub input: input::Input,
+    pub uploads: UploadScheduler,
+    // Moves sprites by their velocities every fixed update.
+    pub kinematics: Kinematics,
+    // Zones that send Game::trigger events as tracked sprites come and go.
+    pub triggers: Triggers,
+    // Plays AnimationClips on sprites; their events go to Game::animation_event.
+    pub animator: Animator,
+    // Events of any type, for whoever wants them. Trigger, animation and ActionEvents go in here
+    // every frame too.
+    pub events: crate::Events,
+    // What Game::init or a level preloaded from an AssetManifest, by name.
+    pub assets: crate::Assets,
+    // Entities with a SpriteRef and a Transform draw as that sprite; the Engine moves them by
+    // their Velocity every fixed update and copies their Transforms into the sprites after the
+    // fixed updates and again after update.
+    #[cfg(feature = "ecs")]
+    pub world: bevy_ecs::world::World,
+    // Random numbers for the game, kept in EngineSnapshots so rewinding gets the same ones again.
+    pub rng: crate::Rng,
+    // Runs cutscene Sequences, advancing them every frame before Game::update. Not part of an
+    // EngineSnapshot: their steps can be closures, which can't be copied.
+    pub sequencer: crate::Sequencer,
+    // Rhai scripts, run every frame before Game::update.
+    #[cfg(feature = "scripting")]
+    pub scripts: crate::Scripts,
+    // Game time not yet covered by fixed updates.
+    fixed_time: f32,
+    #[cfg(feature = "audio")]
+    pub audio: crate::Audio,
+    #[cfg(feature = "gamepad")]
+    gamepads: crate::gamepad::GamepadBackend,
+    #[cfg(feature = "clipboard")]
+    clipboard: crate::clipboard::Clipboard,
+    clock: crate::time::Clock,
+    config: EngineConfig,
+    stats: crate::stats::StatsCollector,
+    // Only with GpuOptions::gpu_timing, on GPUs that can.
+    gpu_timer: Option<crate::gpu_timer::GpuTimer>,
+    focused: bool,
+    exit_requested: bool,
+    // For LoopMode::OnDemand: something other than the sprites wants a frame drawn.
+    redraw_requested: bool,
+    windows: Vec<SecondaryWindow>,
+    pending_windows: Vec<(SecondaryWindowId, WindowConfig)>,
+    next_window_id: u64,
+    // What the canvas follows the size of, with fill_browser_window.
+    #[cfg(target_arch = "wasm32")]
+    page: Option<crate::web::PageObserver>,
 }
 
 impl Engine {
-    pub fn start(event_loop: EventLoop<()>, window: Window, game: impl Game + 'static) {
+    // Creates the event loop and a window as described by `config.window`, then runs the game.
+    // Only returns if setup fails; once the game is running, exiting ends the process.
+    pub fn launch(config: EngineConfig, game: impl Game + 'static) -> Result<(), EngineError> {
+        Self::launch_on(EventLoop::new(), config, game)
+    }
+    // Like launch, for the `android_main` entry point of an app built with the android feature.
+    #[cfg(target_os = "android")]
+    pub fn launch_android(
+        app: winit::platform::android::activity::AndroidApp,
+        config: EngineConfig,
+        game: impl Game + 'static,
+    ) -> Result<(), EngineError> {
+        use winit::platform::android::EventLoopBuilderExtAndroid;
+        let event_loop = winit::event_loop::EventLoopBuilder::new()
+            .with_android_app(app)
+            .build();
+        Self::launch_on(event_loop, config, game)
+    }
+    fn launch_on(
+        event_loop: EventLoop<()>,
+        config: EngineConfig,
+        game: impl Game + 'static,
+    ) -> Result<(), EngineError> {
+        let window = config.window.builder().build(&event_loop)?;
+        window.set_fullscreen(fullscreen_for(&window, config.window.fullscreen));
+        Self::start_with_config(event_loop, window, config, game)
+    }
+    pub fn start(
+        event_loop: EventLoop<()>,
+        window: Window,
+        game: impl Game + 'static,
+    ) -> Result<(), EngineError> {
+        Self::start_with_config(event_loop, window, EngineConfig::default(), game)
+    }
+    // Runs the game in a window the caller already made; `config.window` is ignored.
+    pub fn start_with_config(
+        event_loop: EventLoop<()>,
+        window: Window,
+        config: EngineConfig,
+        game: impl Game + 'static,
+    ) -> Result<(), EngineError> {
         #[cfg(not(target_arch = "wasm32"))]
         {
+            #[cfg(feature = "text")]
+            crate::console::init_logger();
+            #[cfg(not(feature = "text"))]
             env_logger::init();
             // On native, we just want to wait for `run` to finish.
-            pollster::block_on(Self::run(event_loop, window, game));
+            pollster::block_on(Self::run(event_loop, window, config, game))
         }
         #[cfg(target_arch = "wasm32")]
         {
             // On web things are a little more complicated.
             std::panic::set_hook(Box::new(console_error_panic_hook::hook));
             console_log::init().expect("could not initialize logger");
-            use winit::platform::web::WindowExtWebSys;
-            // On wasm, append the canvas to the document body
-            web_sys::window()
-                .and_then(|win| win.document())
-                .and_then(|doc| doc.body())
-                .and_then(|body| {
-                    body.append_child(&web_sys::Element::from(window.canvas()))
-                        .ok()
-                })
-                .expect("couldn't append canvas to document body");
+            crate::web::attach_canvas(&window, config.fill_browser_window);
             // Now we use the browser's runtime to spawn our async run function.
-            wasm_bindgen_futures::spawn_local(run(event_loop, window));
+            // Setup errors can only be logged from there.
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) = Self::run(event_loop, window, config, game).await {
+                    log::error!("{e}");
+                }
+            });
+            Ok(())
         }
     }
-    async fn run(event_loop: EventLoop<()>, window: Window, mut game: impl Game + 'static) {
-        let mut gpu = WGPU::new(&window).await;
-        let mut sprites = SpriteRender::new(&gpu);
+    async fn run(
+        event_loop: EventLoop<()>,
+        window: Window,
+        config: EngineConfig,
+        mut game: impl Game + 'static,
+    ) -> Result<(), EngineError> {
+        // Android only hands us a native window to draw into once the activity has resumed.
+        #[cfg(target_os = "android")]
+        let mut event_loop = event_loop;
+        #[cfg(target_os = "android")]
+        {
+            use winit::platform::run_return::EventLoopExtRunReturn;
+            event_loop.run_return(|event, _, control_flow| {
+                *control_flow = match event {
+                    Event::Resumed => ControlFlow::Exit,
+                    _ => ControlFlow::Wait,
+                };
+            });
+        }
+        crate::assets::set_root(
+            config
+                .asset_root
+                .clone()
+                .unwrap_or_else(crate::assets::default_root),
+        );
+        let gpu = WGPU::new(&window, &config.gpu).await?;
+        let sprites = SpriteRender::new(&gpu);
+        let ui = crate::UiLayer::new(&gpu);
 
-        let input = input::Input::default();
+        #[allow(unused_mut)]
+        let mut input = input::Input::default();
+        #[cfg(feature = "gamepad")]
+        let gamepads = crate::gamepad::GamepadBackend::new(&mut input);
+        let post = PostProcess::new(&gpu);
+        let debug = DebugDraw::new(&gpu, gpu.config.format);
+        let gpu_timer = crate::gpu_timer::GpuTimer::new(&gpu);
         let mut engine = Engine {
             gpu,
+            window,
             sprites,
+            ui,
+            post,
+            graph: RenderGraph::default(),
+            debug,
+            debug_overlay: false,
+            #[cfg(feature = "text")]
+            console: None,
             input,
+            uploads: UploadScheduler::default(),
+            kinematics: Kinematics::default(),
+            triggers: Triggers::default(),
+            animator: Animator::default(),
+            events: crate::Events::new(),
+            assets: crate::Assets::new(),
+            rng: crate::Rng::from_entropy(),
+            sequencer: crate::Sequencer::default(),
+            #[cfg(feature = "scripting")]
+            scripts: crate::Scripts::new(),
+            #[cfg(feature = "ecs")]
+            world: bevy_ecs::world::World::new(),
+            fixed_time: 0.0,
+            #[cfg(feature = "audio")]
+            audio: crate::Audio::new(),
+            #[cfg(feature = "gamepad")]
+            gamepads,
+            #[cfg(feature = "clipboard")]
+            clipboard: crate::clipboard::Clipboard::new(),
+            clock: crate::time::Clock::new(),
+            config,
+            stats: Default::default(),
+            gpu_timer,
+            focused: true,
+            exit_requested: false,
+            redraw_requested: true,
+            windows: Vec::new(),
+            pending_windows: Vec::new(),
+            next_window_id: 0,
+            #[cfg(target_arch = "wasm32")]
+            page: None,
         };
+        #[cfg(target_arch = "wasm32")]
+        if engine.config.fill_browser_window {
+            engine.page = crate::web::PageObserver::new(&engine.window);
+            if engine.page.is_none() {
+                log::warn!("Couldn't watch the page for resizes; the canvas will keep its size");
+            }
+        }
 
         game.init(&mut engine).await;
-        event_loop.run(move |event, _, control_flow| {
-            // By default, tell the windowing system that there's no more work to do
-            // from the application's perspective.
-            *control_flow = ControlFlow::Wait;
+        // Don't count however long init took (loading textures, etc.) as the first frame's delta.
+        engine.clock = crate::time::Clock::new();
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut pacer = crate::time::FramePacer::new();
+        event_loop.run(move |event, target, control_flow| {
+            // Poll keeps the loop spinning for games; Wait tells the windowing system that there's
+            // no more work to do from the application's perspective until another event arrives.
+            // This is only reset as each loop iteration starts so that a WaitUntil set by the frame
+            // limiter below survives the rest of the iteration.
+            if let Event::NewEvents(_) = event {
+                *control_flow = match engine.config.loop_mode {
+                    LoopMode::Poll => ControlFlow::Poll,
+                    LoopMode::Wait | LoopMode::OnDemand => ControlFlow::Wait,
+                };
+            }
+            if let Event::WindowEvent { .. } = event {
+                engine.redraw_requested = true;
+            }
+            game.handle_event(&mut engine, &event);
             // Depending on the event, we'll need to do different things.
             // There is some pretty fancy pattern matching going on here,
             // so think back to CSCI054.
             match event {
+                // Events for any extra windows are dealt with here, so everything below only
+                // concerns the main window.
+                Event::WindowEvent { window_id, event } if window_id != engine.window.id() => {
+                    let Some(index) = engine
+                        .windows
+                        .iter()
+                        .position(|w| w.winit_id() == window_id)
+                    else {
+                        return;
+                    };
+                    match event {
+                        WindowEvent::Resized(size) => {
+                            engine.windows[index].resize(&engine.gpu, size);
+                        }
+                        WindowEvent::CloseRequested => {
+                            let id = engine.windows.remove(index).id();
+                            game.window_closed(&mut engine, id);
+                        }
+                        event => engine.windows[index].input.handle_window_event(&event),
+                    }
+                }
+                Event::RedrawRequested(window_id) if window_id != engine.window.id() => {
+                    let Some(id) = engine
+                        .windows
+                        .iter()
+                        .find(|w| w.winit_id() == window_id)
+                        .map(|w| w.id())
+                    else {
+                        return;
+                    };
+                    game.update_window(&mut engine, id);
+                    // The game might have closed the window in there.
+                    // It's drawn along with the main window's next frame.
+                    if let Some(window) = engine.windows.iter_mut().find(|w| w.id() == id) {
+                        window.input.next_frame();
+                        window.redraw = true;
+                    }
+                }
                 Event::WindowEvent {
                     // For example, "if it's a window event and the specific window event is that
                     // we have resized the window to a particular new size called `size`..."
@@ -67,153 +305,224 @@ impl Engine {
                 } => {
                     // Reconfigure the surface with the new size
                     engine.gpu.resize(size);
+                    let config = &engine.gpu.config;
+                    engine.ui.set_window_size((config.width, config.height));
+                    // Then let the game adapt its cameras and layout
+                    game.resized(&mut engine, size);
                     // On MacOS the window needs to be redrawn manually after resizing
-                    window.request_redraw();
+                    engine.window.request_redraw();
                 }
                 Event::WindowEvent {
                     // Note this deeply nested pattern match
                     event: WindowEvent::KeyboardInput { input: key_ev, .. },
                     ..
                 } => {
-                    engine.input.handle_key_event(key_ev);
-                }
-
-                Event::RedrawRequested(_) => {
-                    //This is all the code for moving the left side player
-                    if (engine.input.is_key_down(winit::event::VirtualKeyCode::W)) {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(2)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0],
-                            oldRegion[1] + 32.0,
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 2);
+                    #[cfg(feature = "audio")]
+                    engine.audio.unlock();
+                    #[cfg(feature = "text")]
+                    if let Some(console) = &mut engine.console {
+                        let was_open = console.is_open();
+                        if console.handle_key(key_ev) {
+                            // Keys held when it opened would otherwise never be let go of.
+                            if !was_open {
+                                engine.input.release_all();
+                            }
+                            return;
+                        }
                     }
-
-                    if (engine.input.is_key_down(winit::event::VirtualKeyCode::S)) {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(2)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0],
-                            oldRegion[1] - 32.0,
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 2);
+                    if key_ev.state == winit::event::ElementState::Pressed
+                        && key_ev.virtual_keycode.is_some()
+                        && key_ev.virtual_keycode == engine.config.debug_overlay_key
+                    {
+                        engine.debug_overlay = !engine.debug_overlay;
+                        return;
                     }
-                    if (engine.input.is_key_down(winit::event::VirtualKeyCode::D)) {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(2)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0] + 32.0,
-                            oldRegion[1],
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 2);
+                    engine.input.handle_key_event(key_ev);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } => {
+                    engine.input.handle_mouse_move(position);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::MouseInput { state, button, .. },
+                    ..
+                } => {
+                    #[cfg(feature = "audio")]
+                    engine.audio.unlock();
+                    engine.input.handle_mouse_button(state, button);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::MouseWheel { delta, .. },
+                    ..
+                } => {
+                    engine.input.handle_mouse_wheel(delta);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::Touch(touch),
+                    ..
+                } => {
+                    #[cfg(feature = "audio")]
+                    engine.audio.unlock();
+                    engine.input.handle_touch(touch);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::Focused(focused),
+                    ..
+                } => {
+                    engine.focused = focused;
+                    if !focused {
+                        engine.input.release_all();
                     }
-                    if (engine.input.is_key_down(winit::event::VirtualKeyCode::A)) {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(2)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0] - 32.0,
-                            oldRegion[1],
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 2);
+                    #[cfg(feature = "audio")]
+                    if engine.config.pause_when_unfocused {
+                        if focused {
+                            engine.audio.resume_all();
+                        } else {
+                            engine.audio.pause_all();
+                        }
                     }
-
-                    //This is all code for moving the Right side Player
-                    if (engine.input.is_key_down(winit::event::VirtualKeyCode::Up)) {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(3)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0],
-                            oldRegion[1] + 32.0,
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 3);
+                    game.focus_changed(&mut engine, focused);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::ModifiersChanged(modifiers),
+                    ..
+                } => {
+                    engine.input.handle_modifiers(modifiers);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::ReceivedCharacter(c),
+                    ..
+                } => {
+                    #[cfg(feature = "text")]
+                    if let Some(console) = &mut engine.console {
+                        if console.handle_char(c) {
+                            return;
+                        }
                     }
+                    engine.input.handle_char(c);
+                }
+                Event::WindowEvent {
+                    event: WindowEvent::Ime(ime),
+                    ..
+                } => {
+                    engine.input.handle_ime(ime);
+                }
 
-                    if (engine.input.is_key_down(winit::event::VirtualKeyCode::Down)) {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(3)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0],
-                            oldRegion[1] - 32.0,
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 3);
-                    }
-                    if (engine
-                        .input
-                        .is_key_down(winit::event::VirtualKeyCode::Right))
-                    {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(3)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0] + 32.0,
-                            oldRegion[1],
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 3);
+                Event::RedrawRequested(_) => {
+                    trace_span!("frame");
+                    engine.redraw_requested = false;
+                    if engine.gpu.is_device_lost() && !engine.recover_device(&mut game) {
+                        *control_flow = ControlFlow::Exit;
+                        return;
                     }
-                    if (engine.input.is_key_down(winit::event::VirtualKeyCode::Left)) {
-                        //Technically 0 Should always be the background
-                        //2 should always be the sprite until i change it
-                        let oldRegion = engine.sprites.get_sprites(3)[0].screen_region;
-                        let mut newRegion = [
-                            oldRegion[0] - 32.0,
-                            oldRegion[1],
-                            oldRegion[2],
-                            oldRegion[3],
-                        ];
-                        engine.sprites.update_position(newRegion, 3);
+                    engine.clock.tick();
+                    engine.stats.record_frame_time(engine.clock.delta());
+                    #[cfg(feature = "gamepad")]
+                    engine.gamepads.poll(&mut engine.input);
+                    // Replays run at the recorded frame times, so they do the same fixed
+                    // updates as the original run.
+                    if let Some(delta) = engine.input.begin_frame(engine.clock.delta()) {
+                        engine.clock.set_delta(delta);
                     }
+                    engine.input.buffer_presses();
 
-                    // engine.sprites.platform_move();
-
-                    engine.sprites.refresh_sprites(
+                    // Push out whatever queued textures fit in this frame's budget before the
+                    // game gets a chance to pick them up.
+                    engine.uploads.flush(&engine.gpu);
+                    engine.assets.update(
                         &engine.gpu,
-                        1,
-                        0..(engine.sprites.get_sprites(0).len()),
+                        #[cfg(feature = "audio")]
+                        &mut engine.audio,
                     );
-
-                    //This refreshes the sprite player group to update the position of both sprites
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        2,
-                        0..(engine.sprites.get_sprites(0).len()),
-                    );
-
-                    engine.sprites.refresh_sprites(
-                        &engine.gpu,
-                        3,
-                        0..(engine.sprites.get_sprites(0).len()),
+                    let evicted = engine.assets.take_evicted(
+                        #[cfg(feature = "audio")]
+                        &mut engine.audio,
                     );
+                    for texture in evicted {
+                        engine.unload_texture(texture);
+                    }
+                    if engine.config.hot_reload {
+                        engine.reload_changed_files();
+                    }
+                    engine.events.next_frame();
+                    engine.publish_action_events();
+                    #[cfg(feature = "clipboard")]
+                    if let Some(text) = engine.clipboard.take_requested() {
+                        engine.events.publish(crate::ClipboardText(text));
+                    }
 
-                    game.update(&mut engine);
+                    #[cfg(feature = "text")]
+                    if let Some(console) = &mut engine.console {
+                        for (command, args) in console.take_commands() {
+                            game.console_command(&mut engine, &command, &args);
+                        }
+                    }
+                    let update_start = crate::time::now();
+                    if engine.focused || !engine.config.pause_when_unfocused {
+                        engine.fixed_time += engine.clock.delta();
+                        let step = engine.config.fixed_timestep;
+                        let mut steps = 0;
+                        while engine.fixed_time >= step && steps < MAX_FIXED_STEPS {
+                            game.fixed_update(&mut engine);
+                            engine.kinematics.integrate(&mut engine.sprites, step);
+                            #[cfg(feature = "ecs")]
+                            crate::ecs::apply_velocities(&mut engine.world, step);
+                            engine.fixed_time -= step;
+                            steps += 1;
+                        }
+                        // After a long hitch, let the time go rather than spending the next
+                        // frames catching up (and falling further behind).
+                        engine.fixed_time = engine.fixed_time.min(step);
+                        #[cfg(feature = "ecs")]
+                        crate::ecs::sync_sprites(&mut engine.world, &mut engine.sprites);
+                        engine.triggers.update(&engine.sprites);
+                        for event in engine.triggers.take_events() {
+                            game.trigger(&mut engine, &event);
+                            engine.events.publish(event);
+                        }
+                        let delta = engine.clock.delta();
+                        engine.animator.update(&mut engine.sprites, delta);
+                        for event in engine.animator.take_events() {
+                            #[cfg(feature = "audio")]
+                            engine.audio.play_event_sound(&event.name);
+                            game.animation_event(&mut engine, &event);
+                            engine.events.publish(event);
+                        }
+                        crate::Sequencer::update(&mut engine, delta);
+                        #[cfg(feature = "scripting")]
+                        crate::Scripts::update(&mut engine, delta);
+                        trace_span!("update");
+                        game.update(&mut engine);
+                        #[cfg(feature = "ecs")]
+                        crate::ecs::sync_sprites(&mut engine.world, &mut engine.sprites);
+                    }
+                    // Fades hold still along with the game.
+                    #[cfg(feature = "audio")]
+                    if engine.focused || !engine.config.pause_when_unfocused {
+                        let group = engine.audio.listener_group();
+                        let listener = (group < engine.sprites.group_count())
+                            .then(|| engine.sprites.get_camera(group));
+                        engine.audio.update(listener, engine.clock.delta());
+                    }
                     engine.input.next_frame();
+                    let render_start = crate::time::now();
+                    engine
+                        .stats
+                        .record_update((render_start - update_start) as f32);
+
+                    if engine.debug_overlay {
+                        engine.draw_debug_overlay();
+                    }
 
                     // If the window system is telling us to redraw, let's get our next swapchain image
-                    let frame = engine
-                        .gpu
-                        .surface
-                        .get_current_texture()
-                        .expect("Failed to acquire next swap chain texture");
+                    // (the game still got its update even if there isn't one).
+                    let Some(frame) = engine.gpu.acquire_frame() else {
+                        engine.debug.clear();
+                        engine.render_windows_alone();
+                        return;
+                    };
                     // And set up a texture view onto it, since the GPU needs a way to interpret those
                     // image bytes for writing.
                     let view = frame
@@ -224,50 +533,660 @@ impl Engine {
                         .gpu
                         .device
                         .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-                    {
-                        // Now we begin a render pass.  The descriptor tells WGPU that
-                        // we want to draw onto our swapchain texture view (that's where the colors will go)
-                        // and that there's no depth buffer or stencil buffer.
-                        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                            label: None,
-                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                view: &view,
-                                resolve_target: None,
-                                ops: wgpu::Operations {
-                                    load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
-                                    store: true,
-                                },
-                            })],
-                            depth_stencil_attachment: None,
-                        });
-                        engine.sprites.render(&mut rpass);
+                    engine
+                        .sprites
+                        .set_target_size(engine.gpu.config.width, engine.gpu.config.height);
+                    engine.sprites.flush(&engine.gpu, &mut encoder);
+                    engine.ui.prepare(
+                        &engine.gpu,
+                        &mut encoder,
+                        (engine.gpu.config.width, engine.gpu.config.height),
+                    );
+                    #[cfg(feature = "text")]
+                    if let Some(console) = &mut engine.console {
+                        console.prepare(
+                            &engine.gpu,
+                            &mut encoder,
+                            (engine.gpu.config.width, engine.gpu.config.height),
+                        );
+                    }
+                    if let Some(timer) = &mut engine.gpu_timer {
+                        timer.begin(&mut encoder);
                     }
+                    // With post-processing the sprites go to an offscreen texture first.
+                    let post_process = engine.graph.runs(RenderGraph::POST_PROCESS);
+                    if post_process {
+                        engine.post.prepare_scene_target(&engine.gpu);
+                    }
+                    let scene = engine.post.scene_target().filter(|_| post_process);
+                    let mut graph = std::mem::take(&mut engine.graph);
+                    let mut ctx = PassContext {
+                        gpu: &engine.gpu,
+                        encoder: &mut encoder,
+                        scene: scene.unwrap_or(&view),
+                        frame: &view,
+                        size: (engine.gpu.config.width, engine.gpu.config.height),
+                        timer: engine.gpu_timer.as_mut(),
+                    };
+                    graph.execute(&mut ctx, |pass, ctx| match pass {
+                        BuiltinPass::Sprites => {
+                            trace_span!("sprite pass");
+                            // Now we begin a render pass.  The descriptor tells WGPU that
+                            // we want to draw onto our swapchain texture view (that's where the colors will go)
+                            // and where the depth buffer is, if GpuOptions asked for one.
+                            let mut rpass =
+                                ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                    label: None,
+                                    color_attachments: &[Some(ctx.gpu.frame_attachment(
+                                        ctx.scene,
+                                        match engine.config.clear_color {
+                                            // The overdraw heat map counts up from black.
+                                            _ if engine.sprites.debug_view()
+                                                == DebugView::Overdraw =>
+                                            {
+                                                wgpu::LoadOp::Clear(wgpu::Color::BLACK)
+                                            }
+                                            Some(color) => wgpu::LoadOp::Clear(
+                                                color.to_wgpu(ctx.gpu.config.format),
+                                            ),
+                                            None => wgpu::LoadOp::Load,
+                                        },
+                                    ))],
+                                    depth_stencil_attachment: ctx.gpu.frame_depth_attachment(),
+                                });
+                            engine.sprites.render(&mut rpass);
+                            #[cfg(feature = "text")]
+                            if let Some(console) = &engine.console {
+                                console.render(&mut rpass);
+                            }
+                        }
+                        BuiltinPass::PostProcess => engine.post.apply(
+                            ctx.gpu,
+                            ctx.encoder,
+                            ctx.frame,
+                            engine.clock.elapsed(),
+                            ctx.timer.as_deref_mut(),
+                        ),
+                        BuiltinPass::Game => game.render(ctx),
+                        BuiltinPass::Ui => engine.ui.render(ctx.encoder, ctx.frame),
+                        BuiltinPass::DebugDraw => {
+                            engine.debug.add_sprite_bounds(&engine.sprites);
+                            engine
+                                .debug
+                                .render(ctx.gpu, ctx.encoder, ctx.frame, ctx.size);
+                        }
+                    });
+                    if let Some(timer) = ctx.timer {
+                        timer.end(ctx.encoder);
+                    }
+                    engine.graph = graph;
+                    // In case the debug draw pass didn't run.
+                    engine.debug.clear();
 
+                    // The other windows go in the same submission, and are presented with this one.
+                    let window_frames: Vec<wgpu::SurfaceTexture> = engine
+                        .windows
+                        .iter_mut()
+                        .filter_map(|window| window.record(&engine.gpu, &mut encoder))
+                        .collect();
                     // Once the commands have been scheduled, we send them over to the GPU via the queue.
-                    engine.gpu.queue.submit(Some(encoder.finish()));
+                    {
+                        trace_span!("submit");
+                        engine.gpu.queue.submit(Some(encoder.finish()));
+                    }
+                    if let Some(timer) = &mut engine.gpu_timer {
+                        timer.submitted();
+                        engine.stats.record_gpu_passes(timer.collect(&engine.gpu));
+                    }
+                    engine.stats.record_render(
+                        (crate::time::now() - render_start) as f32,
+                        engine.sprites.draw_call_count(),
+                        engine.sprites.sprite_count(),
+                    );
                     // Then we wait for the commands to finish and tell the windowing system to
                     // present the swapchain image.
-                    frame.present();
-
-                    // (3)
-                    // And we have to tell the window to redraw!
-                    window.request_redraw(); // Creates a loop and procedds to redraw the window
+                    {
+                        trace_span!("present");
+                        frame.present();
+                        for frame in window_frames {
+                            frame.present();
+                        }
+                    }
+                }
+                // Mobile platforms take the window away while the app is in the background, so the
+                // surface has to go with it and be remade when we come back.
+                Event::Suspended => {
+                    engine.gpu.suspend();
+                    engine.input.release_all();
+                    game.suspended(&mut engine);
+                }
+                // Every platform also sends a Resumed at startup, when we already have a surface.
+                Event::Resumed if engine.gpu.surface.is_none() => {
+                    if let Err(e) = engine.gpu.resume(&engine.window) {
+                        log::error!("Couldn't recreate the surface: {e}");
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    game.resumed(&mut engine);
+                }
+                // All pending events have been handled, so this is where we decide whether to
+                // start the next frame. In Wait mode this only happens after some event woke us up.
+                Event::MainEventsCleared => {
+                    if engine.exit_requested {
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(fps_cap) = engine.config.fps_cap {
+                        if let Some(wake_at) = pacer.wait(fps_cap, engine.config.frame_limiter) {
+                            *control_flow = ControlFlow::WaitUntil(wake_at);
+                            return;
+                        }
+                    }
+                    // The browser doesn't tell winit when the page changes size; the PageObserver
+                    // does, and the device pixel ratio is checked here too.
+                    #[cfg(target_arch = "wasm32")]
+                    if let Some(size) = engine.fit_canvas() {
+                        game.resized(&mut engine, size);
+                    }
+                    // Windows can only be created from inside the loop, so open_window just queues
+                    // them up for here.
+                    for (id, config) in std::mem::take(&mut engine.pending_windows) {
+                        if let Some(window) = SecondaryWindow::new(&engine.gpu, id, &config, target)
+                        {
+                            engine.windows.push(window);
+                        }
+                    }
+                    if engine.config.loop_mode == LoopMode::OnDemand && !engine.needs_redraw() {
+                        return;
+                    }
+                    engine.window.request_redraw();
+                    for window in &engine.windows {
+                        window.window().request_redraw();
+                    }
+                }
+                // Something in this iteration (Game::update asking for another frame, an upload
+                // still queued, sprites changed by an event) wants a frame the OS won't wake us
+                // for, so go round again instead of waiting. Exiting and the fps cap's WaitUntil
+                // are left alone.
+                Event::RedrawEventsCleared
+                    if engine.config.loop_mode == LoopMode::OnDemand
+                        && *control_flow == ControlFlow::Wait
+                        && engine.needs_redraw() =>
+                {
+                    *control_flow = ControlFlow::Poll;
                 }
                 // If we're supposed to close the window, tell the event loop we're all done
                 Event::WindowEvent {
                     event: WindowEvent::CloseRequested,
                     ..
                 } => *control_flow = ControlFlow::Exit,
+                // The loop is going away (for whatever reason), so this is the game's last chance
+                // to do anything.
+                Event::LoopDestroyed => game.on_exit(&mut engine),
                 // Ignore every other event for now.
                 _ => {}
             }
         });
     }
+    // Loads from disk on native, or fetches `path` relative to the page on the web.
     pub async fn load_texture(
         &self,
         path: impl AsRef<std::path::Path>,
         label: Option<&str>,
-    ) -> Result<(wgpu::Texture, image::RgbaImage), image::ImageError> {
+    ) -> Result<(wgpu::Texture, image::RgbaImage), TextureError> {
         self.gpu.load_texture(path.as_ref(), label).await
     }
+    pub fn load_texture_from_bytes(
+        &self,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::RgbaImage), TextureError> {
+        self.gpu.load_texture_from_bytes(bytes, label)
+    }
+    // See WGPU::load_hdr_texture.
+    pub async fn load_hdr_texture(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::Rgba32FImage), TextureError> {
+        self.gpu.load_hdr_texture(path.as_ref(), label).await
+    }
+    pub fn load_hdr_texture_from_bytes(
+        &self,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::Rgba32FImage), TextureError> {
+        self.gpu.load_hdr_texture_from_bytes(bytes, label)
+    }
+    // See WGPU::load_animation.
+    pub async fn load_animation(
+        &self,
+        path: impl AsRef<std::path::Path>,
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::RgbaImage, crate::AnimationClip), TextureError> {
+        self.gpu.load_animation(path.as_ref(), label).await
+    }
+    pub fn load_animation_from_bytes(
+        &self,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::RgbaImage, crate::AnimationClip), TextureError> {
+        self.gpu.load_animation_from_bytes(bytes, label)
+    }
+    pub fn load_texture_from_image(
+        &self,
+        img: &image::RgbaImage,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        self.gpu.load_texture_from_image(img, label)
+    }
+    // Adds `sprite` to the end of `group` and an entity to Engine::world that draws as it, with a
+    // Transform from its screen_region. Add a Velocity or components of the game's own to it.
+    #[cfg(feature = "ecs")]
+    pub fn spawn_sprite(
+        &mut self,
+        group: usize,
+        sprite: crate::GPUSprite,
+    ) -> bevy_ecs::entity::Entity {
+        crate::ecs::spawn_sprite(&mut self.world, &mut self.sprites, group, sprite)
+    }
+    // Despawns the entity and removes its sprite from its group, fixing up the SpriteRefs of the
+    // sprites after it. False if there's no such entity.
+    #[cfg(feature = "ecs")]
+    pub fn despawn_sprite(&mut self, entity: bevy_ecs::entity::Entity) -> bool {
+        crate::ecs::despawn_sprite(&mut self.world, &mut self.sprites, entity)
+    }
+    // A copy of the Engine's game state (see EngineSnapshot) to go back to with restore.
+    pub fn snapshot(&self) -> crate::EngineSnapshot {
+        crate::EngineSnapshot {
+            sprites: crate::snapshot::capture(&self.sprites),
+            windows: self
+                .windows
+                .iter()
+                .map(|window| (window.id(), crate::snapshot::capture(&window.sprites)))
+                .collect(),
+            kinematics: self.kinematics.clone(),
+            triggers: self.triggers.clone(),
+            animator: self.animator.clone(),
+            rng: self.rng.clone(),
+            elapsed: self.clock.elapsed(),
+            fixed_time: self.fixed_time,
+        }
+    }
+    // Puts the Engine back the way it was when `snapshot` was taken, e.g. to rewind, or to roll
+    // back to the last confirmed frame and replay inputs. Windows closed since are skipped.
+    pub fn restore(&mut self, snapshot: &crate::EngineSnapshot) {
+        crate::snapshot::apply(&mut self.sprites, &self.gpu, &snapshot.sprites);
+        for (id, groups) in &snapshot.windows {
+            if let Some(window) = self.windows.iter_mut().find(|window| window.id() == *id) {
+                crate::snapshot::apply(&mut window.sprites, &self.gpu, groups);
+            }
+        }
+        self.kinematics = snapshot.kinematics.clone();
+        self.triggers = snapshot.triggers.clone();
+        self.animator = snapshot.animator.clone();
+        self.rng = snapshot.rng.clone();
+        self.clock.set_elapsed(snapshot.elapsed);
+        self.fixed_time = snapshot.fixed_time;
+    }
+    // Frees a texture now instead of whenever the last thing drawing it lets go: the groups of the
+    // Engine's SpriteRenders (its windows' and the UI's too) that draw it are left drawing
+    // nothing, like SpriteRender::unload_group, and it's dropped. Groups of SpriteRenders the game
+    // made itself have to be unloaded or given another texture for the memory to go.
+    pub fn unload_texture(&mut self, texture: wgpu::Texture) {
+        let id = texture.global_id();
+        self.sprites.release_texture(&self.gpu, id);
+        self.ui.release_texture(&self.gpu, id);
+        for window in &mut self.windows {
+            window.sprites.release_texture(&self.gpu, id);
+        }
+        self.gpu.forget_texture(id);
+    }
+    // Reads any other asset (levels, sounds, ...) the same way load_texture does.
+    pub async fn load_bytes(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<Vec<u8>> {
+        crate::assets::read_bytes(path.as_ref()).await
+    }
+    // Where relative asset paths are looked for, after the mounted folders; see
+    // EngineConfig::asset_root.
+    pub fn set_asset_root(&mut self, root: impl Into<std::path::PathBuf>) {
+        crate::assets::set_root(root.into());
+    }
+    pub fn asset_root(&self) -> std::path::PathBuf {
+        crate::assets::root()
+    }
+    // Looks for assets in `dir` before the asset root and the folders mounted before it, e.g. a
+    // mods folder whose files replace the game's own. A relative `dir` is relative to the
+    // working directory (on the web, the page).
+    pub fn mount_assets(&mut self, dir: impl Into<std::path::PathBuf>) {
+        crate::assets::mount(dir.into());
+    }
+    // Looks for assets in a pak archive, before the asset root and whatever was mounted before
+    // it. Mounting another archive with the same source replaces it.
+    pub fn mount_archive(&mut self, archive: crate::PakArchive) {
+        crate::assets::mount_archive(archive);
+    }
+    // Unmounts a folder, or an archive by its PakArchive::source.
+    pub fn unmount_assets(&mut self, path: impl AsRef<std::path::Path>) {
+        crate::assets::unmount(path.as_ref());
+    }
+    // The file a relative asset path currently stands for, going by the mounts and root.
+    pub fn resolve_asset(&self, path: impl AsRef<std::path::Path>) -> std::path::PathBuf {
+        crate::assets::resolve(path.as_ref())
+    }
+
+    // Seconds between the start of the previous frame and this one.
+    pub fn delta_time(&self) -> f32 {
+        self.clock.delta()
+    }
+    // Seconds since the first frame (Game::init isn't counted).
+    pub fn elapsed(&self) -> f64 {
+        self.clock.elapsed()
+    }
+    // FPS, frame time percentiles, and what the last frame cost, for diagnosing performance.
+    pub fn frame_stats(&self) -> crate::FrameStats {
+        self.stats.stats()
+    }
+    // Lines and shapes drawn over this frame, e.g. engine.debug_draw().rect(hitbox, [255, 0, 0, 255]).
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug
+    }
+    // An overlay over the top left of the main window with FPS, a graph of recent frame times,
+    // draw calls, every group's sprite count and camera position, and texture memory. Drawn
+    // through debug_draw(), so that has to be enabled too (it is in debug builds). Also toggled
+    // by EngineConfig::debug_overlay_key.
+    pub fn set_debug_overlay(&mut self, visible: bool) {
+        self.debug_overlay = visible;
+    }
+    pub fn debug_overlay(&self) -> bool {
+        self.debug_overlay
+    }
+    fn draw_debug_overlay(&mut self) {
+        let memory = self.memory_report();
+        crate::debug_overlay::draw(
+            &mut self.debug,
+            &self.stats.stats(),
+            self.stats.frame_times(),
+            &self.sprites,
+            &memory,
+            (self.gpu.config.width, self.gpu.config.height),
+        );
+    }
+    // Turns on the drop-down console (see Console), drawing its text in `font`, a TrueType or
+    // OpenType file. Register the game's commands through console().
+    #[cfg(feature = "text")]
+    pub fn enable_console(&mut self, font: Vec<u8>) -> Result<(), crate::FontError> {
+        self.console = Some(crate::Console::new(&self.gpu, font)?);
+        Ok(())
+    }
+    #[cfg(feature = "text")]
+    pub fn console(&mut self) -> Option<&mut crate::Console> {
+        self.console.as_mut()
+    }
+    pub fn set_clear_color(&mut self, color: impl Into<crate::Color>) {
+        self.config.clear_color = Some(color.into());
+    }
+    // Stops clearing the main window each frame; see EngineConfig::clear_color.
+    pub fn disable_clear(&mut self) {
+        self.config.clear_color = None;
+    }
+    pub fn clear_color(&self) -> Option<crate::Color> {
+        self.config.clear_color
+    }
+    // Resizes the canvas to the browser window if that changed, returning the new physical size.
+    // winit turns the logical size into canvas pixels using the device pixel ratio, so moving to
+    // a screen with a different one (which doesn't change the page's size) resizes it too.
+    #[cfg(target_arch = "wasm32")]
+    fn fit_canvas(&mut self) -> Option<winit::dpi::PhysicalSize<u32>> {
+        let page = self.page.as_ref()?.size();
+        let size: winit::dpi::PhysicalSize<u32> = page.to_physical(self.window.scale_factor());
+        if size == self.window.inner_size() {
+            return None;
+        }
+        self.window.set_inner_size(page);
+        let size = self.window.inner_size();
+        self.gpu.resize(size);
+        Some(size)
+    }
+    // Swaps in a new GPU device and rebuilds what the Engine owns on it. Returns false if there's
+    // no device to be had, in which case the game can't go on.
+    fn recover_device(&mut self, game: &mut impl Game) -> bool {
+        log::warn!("GPU device lost, trying to recover");
+        #[cfg(not(target_arch = "wasm32"))]
+        let recovered = pollster::block_on(self.gpu.recover());
+        // We can't block on the browser's event loop, and a lost WebGPU/WebGL context rarely
+        // comes back anyway.
+        #[cfg(target_arch = "wasm32")]
+        let recovered: Result<(), EngineError> = Err(EngineError::AdapterNotFound);
+        if let Err(e) = recovered {
+            log::error!("Couldn't recover from device loss: {e}");
+            return false;
+        }
+        self.sprites.recreate(&self.gpu);
+        self.ui.recreate(&self.gpu);
+        self.post.recreate(&self.gpu);
+        self.debug.recreate(&self.gpu);
+        self.gpu_timer = crate::gpu_timer::GpuTimer::new(&self.gpu);
+        #[cfg(feature = "text")]
+        if let Some(console) = &mut self.console {
+            console.recreate(&self.gpu);
+        }
+        for window in &mut self.windows {
+            window.recreate(&self.gpu);
+        }
+        game.device_recovered(self);
+        true
+    }
+    // Opens an extra window with its own SpriteRender and Input. It shows up at the start of the
+    // next frame; until then secondary_window(id) returns None.
+    pub fn open_window(&mut self, config: WindowConfig) -> SecondaryWindowId {
+        let id = SecondaryWindowId(self.next_window_id);
+        self.next_window_id += 1;
+        self.pending_windows.push((id, config));
+        id
+    }
+    pub fn close_window(&mut self, id: SecondaryWindowId) {
+        self.pending_windows.retain(|(pending, _)| *pending != id);
+        self.windows.retain(|w| w.id() != id);
+    }
+    pub fn secondary_window(&self, id: SecondaryWindowId) -> Option<&SecondaryWindow> {
+        self.windows.iter().find(|w| w.id() == id)
+    }
+    pub fn secondary_window_mut(&mut self, id: SecondaryWindowId) -> Option<&mut SecondaryWindow> {
+        self.windows.iter_mut().find(|w| w.id() == id)
+    }
+    pub fn secondary_windows(&self) -> impl Iterator<Item = &SecondaryWindow> {
+        self.windows.iter()
+    }
+    // Roughly how much GPU memory the sprites of every window (and the UI and console) use, to find
+    // what's blowing the VRAM budget. Post-processing targets and the like aren't counted.
+    pub fn memory_report(&self) -> crate::MemoryReport {
+        let mut textures = std::collections::HashMap::new();
+        let mut sprite_buffer_bytes = self.sprites.collect_memory(&mut textures);
+        sprite_buffer_bytes += self.ui.collect_memory(&mut textures);
+        for window in &self.windows {
+            sprite_buffer_bytes += window.sprites.collect_memory(&mut textures);
+        }
+        #[cfg(feature = "text")]
+        let mut atlases = Vec::new();
+        #[cfg(feature = "text")]
+        if let Some(console) = &self.console {
+            sprite_buffer_bytes += console.collect_memory(&mut textures);
+            atlases.push(console.atlas_usage());
+        }
+        crate::MemoryReport {
+            sprite_buffer_bytes,
+            textures: crate::memory::sorted(textures),
+            #[cfg(feature = "text")]
+            atlases,
+        }
+    }
+    // With LoopMode::OnDemand, runs another frame even if nothing changed.
+    pub fn request_redraw(&mut self) {
+        self.redraw_requested = true;
+    }
+    // Draws the secondary windows when the main window has nothing to draw into, e.g. while it's
+    // minimized.
+    fn render_windows_alone(&mut self) {
+        let mut encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let frames: Vec<wgpu::SurfaceTexture> = self
+            .windows
+            .iter_mut()
+            .filter_map(|window| window.record(&self.gpu, &mut encoder))
+            .collect();
+        if frames.is_empty() {
+            return;
+        }
+        self.gpu.queue.submit(Some(encoder.finish()));
+        for frame in frames {
+            frame.present();
+        }
+    }
+    fn publish_action_events(&mut self) {
+        let actions: Vec<String> = self
+            .input
+            .input_map()
+            .actions()
+            .map(str::to_owned)
+            .collect();
+        for action in actions {
+            let phase = if self.input.is_action_pressed(&action) {
+                crate::ActionPhase::Pressed
+            } else if self.input.is_action_released(&action) {
+                crate::ActionPhase::Released
+            } else {
+                continue;
+            };
+            self.events.publish(crate::ActionEvent { action, phase });
+        }
+    }
+    fn reload_changed_files(&mut self) {
+        #[cfg(feature = "scripting")]
+        self.scripts.reload_changed();
+        self.sprites.reload_changed_shaders(&self.gpu);
+        for window in &mut self.windows {
+            window.sprites.reload_changed_shaders(&self.gpu);
+        }
+        // The game's handle still points at the old texture; the groups drawing it are moved over.
+        for (old, texture) in self.gpu.reload_changed_textures() {
+            self.sprites.replace_texture(&self.gpu, old, &texture);
+            self.ui.replace_texture(&self.gpu, old, &texture);
+            for window in &mut self.windows {
+                window.sprites.replace_texture(&self.gpu, old, &texture);
+            }
+        }
+    }
+    fn needs_redraw(&self) -> bool {
+        self.redraw_requested
+            || self.sprites.has_changes()
+            || self.ui.has_changes()
+            || self.uploads.pending_count() > 0
+            || self.assets.is_loading()
+            || self
+                .windows
+                .iter()
+                .any(|window| window.sprites.has_changes())
+    }
+    // Asks the Engine to stop after the current frame. Game::on_exit will still be called.
+    pub fn request_exit(&mut self) {
+        self.exit_requested = true;
+    }
+    // Size of the drawable area in physical pixels.
+    pub fn window_size(&self) -> winit::dpi::PhysicalSize<u32> {
+        winit::dpi::PhysicalSize::new(self.gpu.config.width, self.gpu.config.height)
+    }
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+    // Loop mode and fps cap can be changed while running, e.g. to throttle a paused game.
+    pub fn config_mut(&mut self) -> &mut EngineConfig {
+        &mut self.config
+    }
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) {
+        self.window
+            .set_fullscreen(fullscreen_for(&self.window, mode));
+        // We'll also get a Resized event, but not necessarily before the next frame is drawn.
+        self.gpu.resize(self.window.inner_size());
+    }
+    pub fn fullscreen_mode(&self) -> FullscreenMode {
+        match self.window.fullscreen() {
+            None => FullscreenMode::Windowed,
+            Some(winit::window::Fullscreen::Borderless(_)) => FullscreenMode::Borderless,
+            Some(winit::window::Fullscreen::Exclusive(_)) => FullscreenMode::Exclusive,
+        }
+    }
+    // Flips between windowed and borderless fullscreen, e.g. for Alt+Enter.
+    pub fn toggle_fullscreen(&mut self) {
+        let mode = match self.fullscreen_mode() {
+            FullscreenMode::Windowed => FullscreenMode::Borderless,
+            _ => FullscreenMode::Windowed,
+        };
+        self.set_fullscreen(mode);
+    }
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+    // Turns on IME composition (needed for e.g. Japanese or Chinese text entry) while a text
+    // field has focus. Plain typed characters show up in Input::text either way.
+    pub fn set_text_input(&mut self, enabled: bool) {
+        self.window.set_ime_allowed(enabled);
+    }
+    // Returns the clipboard's text, or None if it's empty, not text, or unavailable. The browser
+    // won't hand over the clipboard right away, so on the web this is only what the game itself
+    // last copied; use clipboard_request there to paste from other pages.
+    #[cfg(feature = "clipboard")]
+    pub fn clipboard_get(&mut self) -> Option<String> {
+        self.clipboard.get()
+    }
+    // Reads the clipboard in the background, publishing a ClipboardText to Engine::events once
+    // it has the text. Browsers may ask the player's permission first, and only allow it from a
+    // key press or click.
+    #[cfg(feature = "clipboard")]
+    pub fn clipboard_request(&mut self) {
+        self.clipboard.request();
+    }
+    // Puts `text` on the clipboard, returning whether that worked.
+    #[cfg(feature = "clipboard")]
+    pub fn clipboard_set(&mut self, text: impl Into<String>) -> bool {
+        self.clipboard.set(text.into())
+    }
+    // Converts the cursor position (in window pixels, y pointing down) into the world space
+    // seen through `camera`, i.e. the same space as GPUSprite::screen_region.
+    pub fn mouse_world_position(&self, camera: &GPUCamera) -> crate::Vec2 {
+        let pos = self.input.mouse_pos();
+        let width = self.gpu.config.width.max(1) as f32;
+        let height = self.gpu.config.height.max(1) as f32;
+        // The shader maps the camera's screen_size onto the whole surface starting at screen_pos,
+        // with world y going up, so we just undo that.
+        camera.position()
+            + camera.size() * crate::Vec2::new(pos.x as f32 / width, 1.0 - pos.y as f32 / height)
+    }
+}
+
+fn fullscreen_for(window: &Window, mode: FullscreenMode) -> Option<winit::window::Fullscreen> {
+    use winit::window::Fullscreen;
+    match mode {
+        FullscreenMode::Windowed => None,
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
+        FullscreenMode::Exclusive => {
+            // Biggest resolution, then highest refresh rate. Some platforms (web, Wayland) have no
+            // exclusive modes at all, in which case borderless is the best we can do.
+            let best = window.current_monitor().and_then(|monitor| {
+                monitor.video_modes().max_by_key(|m| {
+                    let size = m.size();
+                    (size.width * size.height, m.refresh_rate_millihertz())
+                })
+            });
+            match best {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => Some(Fullscreen::Borderless(None)),
+            }
+        }
+    }
 }
@@ -0,0 +1,392 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Everything that can go wrong while setting up the Engine or loading its resources.
+#[derive(Debug)]
+pub enum EngineError {
+    WindowCreation(winit::error::OsError),
+    SurfaceCreation(wgpu::CreateSurfaceError),
+    // No GPU (or software renderer) can draw to the window.
+    AdapterNotFound,
+    DeviceRequest(wgpu::RequestDeviceError),
+    // GpuOptions::features asked for these, but the adapter doesn't have them.
+    MissingFeatures(wgpu::Features),
+    // The surface doesn't report any texture format we could render to.
+    NoSurfaceFormat,
+    Texture(TextureError),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::WindowCreation(e) => write!(f, "couldn't create the window: {e}"),
+            EngineError::SurfaceCreation(e) => write!(f, "couldn't create a surface: {e}"),
+            EngineError::AdapterNotFound => write!(f, "couldn't find a suitable graphics adapter"),
+            EngineError::DeviceRequest(e) => write!(f, "couldn't open the graphics device: {e}"),
+            EngineError::MissingFeatures(features) => {
+                write!(f, "the graphics adapter doesn't support {features:?}")
+            }
+            EngineError::NoSurfaceFormat => write!(f, "the surface has no usable texture format"),
+            EngineError::Texture(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EngineError::WindowCreation(e) => Some(e),
+            EngineError::SurfaceCreation(e) => Some(e),
+            EngineError::DeviceRequest(e) => Some(e),
+            EngineError::Texture(e) => Some(e),
+            EngineError::AdapterNotFound
+            | EngineError::MissingFeatures(_)
+            | EngineError::NoSurfaceFormat => None,
+        }
+    }
+}
+
+impl From<winit::error::OsError> for EngineError {
+    fn from(e: winit::error::OsError) -> Self {
+        EngineError::WindowCreation(e)
+    }
+}
+impl From<wgpu::CreateSurfaceError> for EngineError {
+    fn from(e: wgpu::CreateSurfaceError) -> Self {
+        EngineError::SurfaceCreation(e)
+    }
+}
+impl From<wgpu::RequestDeviceError> for EngineError {
+    fn from(e: wgpu::RequestDeviceError) -> Self {
+        EngineError::DeviceRequest(e)
+    }
+}
+impl From<TextureError> for EngineError {
+    fn from(e: TextureError) -> Self {
+        EngineError::Texture(e)
+    }
+}
+
+// Why a texture couldn't be loaded, always with the file it was loaded from. Textures loaded
+// from memory get a stand-in path made from their label, like "<player>".
+#[derive(Debug)]
+pub enum TextureError {
+    // The file couldn't be read at all (missing, no permission, ...).
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    // The file is in a format we can read but its contents are broken.
+    Decode {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    // The file's format (or a feature of it) isn't supported by the image crate.
+    UnsupportedFormat {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    // The file is fine but can't be used as it is, e.g. a compressed format this GPU lacks.
+    Unsupported {
+        path: PathBuf,
+        reason: String,
+    },
+    // Bigger than the GPU's max_texture_dimension_2d.
+    TooLarge {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        max: u32,
+    },
+}
+
+impl TextureError {
+    pub(crate) fn from_image(path: &Path, e: image::ImageError) -> Self {
+        let path = path.to_owned();
+        match e {
+            image::ImageError::IoError(source) => TextureError::Io { path, source },
+            image::ImageError::Unsupported(_) => {
+                TextureError::UnsupportedFormat { path, source: e }
+            }
+            _ => TextureError::Decode { path, source: e },
+        }
+    }
+    pub fn path(&self) -> &Path {
+        match self {
+            TextureError::Io { path, .. }
+            | TextureError::Decode { path, .. }
+            | TextureError::UnsupportedFormat { path, .. }
+            | TextureError::Unsupported { path, .. }
+            | TextureError::TooLarge { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            TextureError::Io { source, .. } => write!(f, "couldn't read {path}: {source}"),
+            TextureError::Decode { source, .. } => write!(f, "couldn't decode {path}: {source}"),
+            TextureError::UnsupportedFormat { source, .. } => {
+                write!(f, "{path} isn't in a supported format: {source}")
+            }
+            TextureError::Unsupported { reason, .. } => write!(f, "can't use {path}: {reason}"),
+            TextureError::TooLarge {
+                width, height, max, ..
+            } => write!(
+                f,
+                "{path} is {width}x{height}, but this GPU only supports textures up to {max}x{max}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Io { source, .. } => Some(source),
+            TextureError::Decode { source, .. }
+            | TextureError::UnsupportedFormat { source, .. } => Some(source),
+            TextureError::Unsupported { .. } | TextureError::TooLarge { .. } => None,
+        }
+    }
+}
+
+// A sprite group or sprite index that doesn't exist, from SpriteRender's try_* accessors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpriteError {
+    NoSuchGroup {
+        group: usize,
+        group_count: usize,
+    },
+    OutOfRange {
+        group: String,
+        range: std::ops::Range<usize>,
+        len: usize,
+    },
+}
+
+impl fmt::Display for SpriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpriteError::NoSuchGroup { group, group_count } => write!(
+                f,
+                "there's no sprite group {group} (only {group_count} groups exist)"
+            ),
+            SpriteError::OutOfRange { group, range, len } => write!(
+                f,
+                "sprites {range:?} are out of range for {group}, which has {len} sprites"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpriteError {}
+
+// A shader from SpriteRender::add_shader that wgpu wouldn't accept: a WGSL syntax error, or a
+// module that doesn't fit the sprite pipeline's bind groups and entry points.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderError {
+    pub label: String,
+    // wgpu's report, which points at the offending line for WGSL errors.
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "shader {:?} failed to build: {}",
+            self.label, self.message
+        )
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// A change to a RenderGraph that it turned down, leaving it as it was.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    DuplicatePass(String),
+    NoSuchPass(String),
+    // The passes that would each have had to wait for another of them.
+    Cycle(Vec<String>),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::DuplicatePass(name) => write!(f, "there's already a pass called {name:?}"),
+            GraphError::NoSuchPass(name) => write!(f, "there's no pass called {name:?}"),
+            GraphError::Cycle(names) => {
+                write!(f, "passes {names:?} depend on each other in a loop")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+// Bytes given to GlyphAtlas::add_font that aren't a TrueType or OpenType font.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FontError;
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a valid TrueType or OpenType font")
+    }
+}
+
+impl std::error::Error for FontError {}
+
+// Why a sound or piece of music couldn't be played, with the file it came from.
+#[cfg(feature = "audio")]
+#[derive(Debug)]
+pub enum AudioError {
+    // The file couldn't be read at all (missing, no permission, ...).
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    // The file isn't WAV, OGG, MP3 or FLAC, or its contents are broken.
+    Decode {
+        path: PathBuf,
+        source: rodio::decoder::DecoderError,
+    },
+}
+
+#[cfg(feature = "audio")]
+impl AudioError {
+    pub fn path(&self) -> &Path {
+        match self {
+            AudioError::Io { path, .. } | AudioError::Decode { path, .. } => path,
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            AudioError::Io { source, .. } => write!(f, "couldn't read {path}: {source}"),
+            AudioError::Decode { source, .. } => write!(f, "couldn't decode {path}: {source}"),
+        }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl std::error::Error for AudioError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AudioError::Io { source, .. } => Some(source),
+            AudioError::Decode { source, .. } => Some(source),
+        }
+    }
+}
+
+// Why AssetManifest::load couldn't read a manifest.
+#[cfg(feature = "manifest")]
+#[derive(Debug)]
+pub enum ManifestError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    // Not valid TOML, or not laid out like a manifest.
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+#[cfg(feature = "manifest")]
+impl ManifestError {
+    pub fn path(&self) -> &Path {
+        match self {
+            ManifestError::Io { path, .. }
+            | ManifestError::Toml { path, .. }
+            | ManifestError::Json { path, .. } => path,
+        }
+    }
+}
+
+#[cfg(feature = "manifest")]
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            ManifestError::Io { source, .. } => write!(f, "couldn't read {path}: {source}"),
+            ManifestError::Toml { source, .. } => write!(f, "couldn't parse {path}: {source}"),
+            ManifestError::Json { source, .. } => write!(f, "couldn't parse {path}: {source}"),
+        }
+    }
+}
+
+#[cfg(feature = "manifest")]
+impl std::error::Error for ManifestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ManifestError::Io { source, .. } => Some(source),
+            ManifestError::Toml { source, .. } => Some(source),
+            ManifestError::Json { source, .. } => Some(source),
+        }
+    }
+}
+
+#[cfg(feature = "scripting")]
+#[derive(Debug)]
+pub enum ScriptError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Compile {
+        path: PathBuf,
+        source: rhai::ParseError,
+    },
+    // The script's top-level statements failed, or a function Scripts::call called did.
+    Run {
+        path: PathBuf,
+        source: Box<rhai::EvalAltResult>,
+    },
+}
+
+#[cfg(feature = "scripting")]
+impl ScriptError {
+    pub fn path(&self) -> &Path {
+        match self {
+            ScriptError::Io { path, .. }
+            | ScriptError::Compile { path, .. }
+            | ScriptError::Run { path, .. } => path,
+        }
+    }
+}
+
+#[cfg(feature = "scripting")]
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            ScriptError::Io { source, .. } => write!(f, "couldn't read {path}: {source}"),
+            ScriptError::Compile { source, .. } => write!(f, "couldn't compile {path}: {source}"),
+            ScriptError::Run { source, .. } => write!(f, "{path}: {source}"),
+        }
+    }
+}
+
+#[cfg(feature = "scripting")]
+impl std::error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScriptError::Io { source, .. } => Some(source),
+            ScriptError::Compile { source, .. } => Some(source),
+            ScriptError::Run { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
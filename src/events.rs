@@ -0,0 +1,187 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+
+// Whether an InputMap action went down or came up this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ActionPhase {
+    Pressed,
+    Released,
+}
+
+// Published by the Engine for every bound action that was pressed or released this frame, the
+// same ones Input::is_action_pressed and is_action_released report.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionEvent {
+    pub action: String,
+    pub phase: ActionPhase,
+}
+
+struct Queue<T> {
+    // Each with the frame it was published in.
+    events: VecDeque<(u64, T)>,
+    // How many events have been dropped off the front, so readers can tell where they are.
+    dropped: u64,
+}
+
+impl<T> Queue<T> {
+    fn end(&self) -> u64 {
+        self.dropped + self.events.len() as u64
+    }
+}
+
+// A Queue of any type, for Events to keep together.
+trait AnyQueue {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    // Drops the events published before `frame`.
+    fn drop_before(&mut self, frame: u64);
+}
+
+impl<T: 'static> AnyQueue for Queue<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+    fn drop_before(&mut self, frame: u64) {
+        while self
+            .events
+            .front()
+            .is_some_and(|(published, _)| *published < frame)
+        {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+    }
+}
+
+// Where an EventReader has got to in the events of one type.
+pub struct EventReader<T> {
+    next: u64,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for EventReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            next: self.next,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    // Reads from the oldest event still kept.
+    fn default() -> Self {
+        Self {
+            next: 0,
+            _type: PhantomData,
+        }
+    }
+}
+
+// Queues of events by type, so subsystems and game code can tell each other things without
+// knowing about each other. The Engine keeps one (Engine::events) and publishes its
+// TriggerEvents, AnimationEvents and ActionEvents there every frame before Game::update, as well
+// as handing the first two to Game::trigger and Game::animation_event. Anything 'static can be an
+// event, including the game's own types and the Contacts from its collision checks:
+//
+//     struct CoinCollected { value: u32 }
+//
+//     engine.events.publish(CoinCollected { value: 10 });
+//     ...
+//     // self.coins: EventReader<CoinCollected>, from Default or Events::reader
+//     for coin in engine.events.read(&mut self.coins) {
+//         self.score += coin.value;
+//     }
+//
+// Each reader sees each event once, whenever it's published in the frame. Events are kept until
+// the end of the frame after the one they were published in, so a reader has to read at least
+// once a frame not to miss any.
+#[derive(Default)]
+pub struct Events {
+    queues: HashMap<TypeId, Box<dyn AnyQueue>>,
+    frame: u64,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn publish<T: 'static>(&mut self, event: T) {
+        let frame = self.frame;
+        self.queue_mut::<T>().events.push_back((frame, event));
+    }
+    // A reader that starts after the events already published, unlike EventReader::default.
+    pub fn reader<T: 'static>(&self) -> EventReader<T> {
+        EventReader {
+            next: self.queue::<T>().map_or(0, Queue::end),
+            _type: PhantomData,
+        }
+    }
+    // The events `reader` hasn't seen yet, oldest first, after which it has.
+    pub fn read<'a, T: 'static>(
+        &'a self,
+        reader: &mut EventReader<T>,
+    ) -> impl Iterator<Item = &'a T> + 'a {
+        let (events, skip) = match self.queue::<T>() {
+            Some(queue) => {
+                let skip = reader.next.saturating_sub(queue.dropped) as usize;
+                reader.next = queue.end();
+                (Some(&queue.events), skip)
+            }
+            None => (None, 0),
+        };
+        events
+            .into_iter()
+            .flat_map(move |events| events.iter().skip(skip).map(|(_, event)| event))
+    }
+    // Everything of type T published this frame so far, without a reader.
+    pub fn this_frame<T: 'static>(&self) -> impl Iterator<Item = &T> + '_ {
+        let frame = self.frame;
+        self.queue::<T>()
+            .into_iter()
+            .flat_map(move |queue| {
+                queue
+                    .events
+                    .iter()
+                    .filter(move |(published, _)| *published == frame)
+            })
+            .map(|(_, event)| event)
+    }
+    // Drops every event of type T, as though they had all been read.
+    pub fn clear<T: 'static>(&mut self) {
+        let queue = self.queue_mut::<T>();
+        queue.dropped += queue.events.len() as u64;
+        queue.events.clear();
+    }
+
+    // Starts a new frame, dropping the events published before the last one.
+    pub(crate) fn next_frame(&mut self) {
+        self.frame += 1;
+        for queue in self.queues.values_mut() {
+            queue.drop_before(self.frame - 1);
+        }
+    }
+
+    fn queue<T: 'static>(&self) -> Option<&Queue<T>> {
+        self.queues
+            .get(&TypeId::of::<T>())
+            .and_then(|queue| queue.as_any().downcast_ref())
+    }
+    fn queue_mut<T: 'static>(&mut self) -> &mut Queue<T> {
+        self.queues
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Box::new(Queue::<T> {
+                    events: VecDeque::new(),
+                    dropped: 0,
+                })
+            })
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+}
@@ -0,0 +1,180 @@
+use crate::Input;
+use std::collections::{HashMap, HashSet};
+
+pub use gilrs::{Axis as GamepadAxis, Button as GamepadButton, GamepadId};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+}
+
+#[derive(Default)]
+struct PadState {
+    now_buttons: HashSet<GamepadButton>,
+    prev_buttons: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+    prev_axes: HashMap<GamepadAxis, f32>,
+    // Analog values of pressure-sensitive buttons (mostly triggers), 0..1.
+    button_values: HashMap<GamepadButton, f32>,
+    connected: bool,
+}
+
+// Per-controller button and axis state, kept inside Input and fed by the Engine from gilrs.
+#[derive(Default)]
+pub(crate) struct Gamepads {
+    pads: HashMap<GamepadId, PadState>,
+    events: Vec<GamepadEvent>,
+}
+
+impl Gamepads {
+    pub(crate) fn next_frame(&mut self) {
+        for pad in self.pads.values_mut() {
+            pad.prev_buttons.clone_from(&pad.now_buttons);
+            pad.prev_axes.clone_from(&pad.axes);
+        }
+        self.events.clear();
+    }
+    // Whether any connected pad has `button` held, this frame or (with `prev`) last frame.
+    pub(crate) fn any_button(&self, button: GamepadButton, prev: bool) -> bool {
+        self.pads.values().any(|p| {
+            if prev {
+                p.prev_buttons.contains(&button)
+            } else {
+                p.now_buttons.contains(&button)
+            }
+        })
+    }
+    // Buttons that went down this frame on any pad.
+    pub(crate) fn pressed_buttons(&self) -> Vec<GamepadButton> {
+        self.pads
+            .values()
+            .flat_map(|p| p.now_buttons.difference(&p.prev_buttons).copied())
+            .collect()
+    }
+    // The value of `axis` on whichever pad is pushing it furthest.
+    pub(crate) fn any_axis(&self, axis: GamepadAxis, prev: bool) -> f32 {
+        self.pads
+            .values()
+            .filter_map(|p| if prev { &p.prev_axes } else { &p.axes }.get(&axis))
+            .fold(
+                0.0,
+                |best: f32, v| if v.abs() > best.abs() { *v } else { best },
+            )
+    }
+    // How far `button` is pushed in on whichever pad pushes it furthest. Digital-only
+    // buttons read as 0 or 1.
+    pub(crate) fn any_button_value(&self, button: GamepadButton) -> f32 {
+        self.pads
+            .values()
+            .map(|p| match p.button_values.get(&button) {
+                Some(v) => *v,
+                None if p.now_buttons.contains(&button) => 1.0,
+                None => 0.0,
+            })
+            .fold(0.0, f32::max)
+    }
+    fn handle_event(&mut self, ev: gilrs::Event) {
+        use gilrs::EventType;
+        let pad = self.pads.entry(ev.id).or_default();
+        match ev.event {
+            EventType::ButtonPressed(button, _) => {
+                pad.now_buttons.insert(button);
+            }
+            EventType::ButtonReleased(button, _) => {
+                pad.now_buttons.remove(&button);
+            }
+            EventType::ButtonChanged(button, value, _) => {
+                pad.button_values.insert(button, value);
+            }
+            EventType::AxisChanged(axis, value, _) => {
+                pad.axes.insert(axis, value);
+            }
+            EventType::Connected => {
+                pad.connected = true;
+                self.events.push(GamepadEvent::Connected(ev.id));
+            }
+            EventType::Disconnected => {
+                // Forget everything that was held so nothing stays stuck down.
+                *pad = PadState::default();
+                self.events.push(GamepadEvent::Disconnected(ev.id));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Owns the gilrs context. Lives on the Engine, which polls it once per frame.
+pub(crate) struct GamepadBackend {
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+impl GamepadBackend {
+    pub(crate) fn new(input: &mut Input) -> Self {
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepad support unavailable: {e}");
+                None
+            }
+        };
+        // Pads that were plugged in before we started don't send Connected events.
+        if let Some(gilrs) = &gilrs {
+            for (id, _) in gilrs.gamepads() {
+                input.gamepads.pads.entry(id).or_default().connected = true;
+            }
+        }
+        Self { gilrs }
+    }
+    pub(crate) fn poll(&mut self, input: &mut Input) {
+        if let Some(gilrs) = &mut self.gilrs {
+            while let Some(ev) = gilrs.next_event() {
+                input.gamepads.handle_event(ev);
+            }
+        }
+    }
+}
+
+impl Input {
+    pub fn connected_gamepads(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.gamepads
+            .pads
+            .iter()
+            .filter(|(_, pad)| pad.connected)
+            .map(|(id, _)| *id)
+    }
+    // Connection changes that happened since the last frame.
+    pub fn gamepad_events(&self) -> &[GamepadEvent] {
+        &self.gamepads.events
+    }
+    pub fn is_button_down(&self, pad: GamepadId, button: GamepadButton) -> bool {
+        self.gamepads
+            .pads
+            .get(&pad)
+            .is_some_and(|p| p.now_buttons.contains(&button))
+    }
+    pub fn is_button_up(&self, pad: GamepadId, button: GamepadButton) -> bool {
+        !self.is_button_down(pad, button)
+    }
+    pub fn is_button_pressed(&self, pad: GamepadId, button: GamepadButton) -> bool {
+        self.gamepads
+            .pads
+            .get(&pad)
+            .is_some_and(|p| p.now_buttons.contains(&button) && !p.prev_buttons.contains(&button))
+    }
+    pub fn is_button_released(&self, pad: GamepadId, button: GamepadButton) -> bool {
+        self.gamepads
+            .pads
+            .get(&pad)
+            .is_some_and(|p| !p.now_buttons.contains(&button) && p.prev_buttons.contains(&button))
+    }
+    // Raw axis value in -1..1 (0 for unknown pads or axes that haven't moved yet).
+    pub fn gamepad_axis(&self, pad: GamepadId, axis: GamepadAxis) -> f32 {
+        self.gamepads
+            .pads
+            .get(&pad)
+            .and_then(|p| p.axes.get(&axis))
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
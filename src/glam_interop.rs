@@ -0,0 +1,59 @@
+// Conversions to and from glam, for games that already do their math with it. Every engine
+// function taking `impl Into<Vec2>` takes a glam::Vec2 too.
+use crate::{GPUCamera, Transform2D, Vec2};
+
+impl From<glam::Vec2> for Vec2 {
+    fn from(v: glam::Vec2) -> Self {
+        Vec2::new(v.x, v.y)
+    }
+}
+
+impl From<Vec2> for glam::Vec2 {
+    fn from(v: Vec2) -> Self {
+        glam::Vec2::new(v.x, v.y)
+    }
+}
+
+impl From<Transform2D> for glam::Affine2 {
+    fn from(transform: Transform2D) -> Self {
+        glam::Affine2::from_scale_angle_translation(
+            transform.scale.into(),
+            transform.rotation,
+            transform.translation.into(),
+        )
+    }
+}
+
+impl From<Transform2D> for glam::Mat3 {
+    fn from(transform: Transform2D) -> Self {
+        glam::Affine2::from(transform).into()
+    }
+}
+
+// Any shear in `affine` is lost, as Transform2D can't hold one.
+impl From<glam::Affine2> for Transform2D {
+    fn from(affine: glam::Affine2) -> Self {
+        let (scale, rotation, translation) = affine.to_scale_angle_translation();
+        Transform2D {
+            translation: translation.into(),
+            rotation,
+            scale: scale.into(),
+        }
+    }
+}
+
+// Takes world space to clip space (-1 to 1 across the view), the same as the sprite shader does
+// with the camera, e.g. for drawing with the camera in a pass of your own.
+impl From<GPUCamera> for glam::Mat3 {
+    fn from(camera: GPUCamera) -> Self {
+        let scale = 2.0 / glam::Vec2::from(camera.size());
+        let translation = -glam::Vec2::from(camera.position()) * scale - glam::Vec2::ONE;
+        glam::Mat3::from_scale_angle_translation(scale, 0.0, translation)
+    }
+}
+
+impl From<&GPUCamera> for glam::Mat3 {
+    fn from(camera: &GPUCamera) -> Self {
+        (*camera).into()
+    }
+}
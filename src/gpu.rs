@@ -1,102 +1,467 @@
 // use gpu::{util::DeviceExt, RenderPass};
-use winit::{
-    dpi::PhysicalSize,
-    event::{Event, WindowEvent},
-    event_loop::{self, ControlFlow, EventLoop},
-    window::Window,
+use crate::hot_reload::FileWatcher;
+use crate::{EngineError, GraphError, TextureError};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
 };
+use winit::window::Window;
+
+// Knobs for how WGPU sets up the adapter, device, and surface.
+#[derive(Clone, Debug)]
+pub struct GpuOptions {
+    // AutoVsync/AutoNoVsync always work; Mailbox and Immediate fall back to Fifo (plain vsync) if
+    // the surface doesn't support them.
+    pub present_mode: wgpu::PresentMode,
+    // Which graphics APIs wgpu may use. The WGPU_BACKEND environment variable (e.g. "vulkan",
+    // "gl", "dx12,metal") overrides this, which is handy for chasing driver bugs without a
+    // rebuild.
+    pub backends: wgpu::Backends,
+    // Overridden by WGPU_POWER_PREF ("low" or "high").
+    pub power_preference: wgpu::PowerPreference,
+    pub adapter: AdapterSelection,
+    // Extra device features for pipelines of your own (e.g. TIMESTAMP_QUERY). WGPU::new fails
+    // with EngineError::MissingFeatures if the adapter doesn't have them all.
+    pub features: wgpu::Features,
+    // None asks for what the sprite renderer needs; set this to ask for more.
+    pub limits: Option<wgpu::Limits>,
+    // Give textures loaded from images a full mip chain, so sprites drawn smaller than their
+    // texture don't shimmer. Costs a third more memory per texture.
+    pub mipmaps: bool,
+    // Samples per pixel for antialiasing the edges of rotated and scaled sprites. 1 turns it off;
+    // 4 works on every GPU, and counts the surface format can't do are lowered until one fits.
+    pub msaa_samples: u32,
+    // Give frames a depth buffer, so GPUSprite::depth decides which sprites cover which no matter
+    // what order their groups draw in.
+    pub depth_buffer: bool,
+    // Measure how long the GPU spends on each pass (FrameStats::gpu_passes). Asks for
+    // TIMESTAMP_QUERY when the adapter has it, and does nothing when it doesn't.
+    pub gpu_timing: bool,
+}
+
+// Which GPU to use on machines with more than one, as listed by WGPU::enumerate_adapters.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum AdapterSelection {
+    // Let wgpu pick, going by power_preference.
+    #[default]
+    Auto,
+    Index(usize),
+    // The first adapter whose name contains this, ignoring case (e.g. "nvidia").
+    Name(String),
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        Self {
+            present_mode: wgpu::PresentMode::Fifo,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            adapter: AdapterSelection::Auto,
+            features: wgpu::Features::empty(),
+            limits: None,
+            mipmaps: false,
+            msaa_samples: 1,
+            depth_buffer: false,
+            gpu_timing: false,
+        }
+    }
+}
+
+impl GpuOptions {
+    fn backends(&self) -> wgpu::Backends {
+        wgpu::util::backend_bits_from_env().unwrap_or(self.backends)
+    }
+    fn power_preference(&self) -> wgpu::PowerPreference {
+        wgpu::util::power_preference_from_env().unwrap_or(self.power_preference)
+    }
+}
+
 pub struct WGPU {
     instance: wgpu::Instance,
-    pub(crate) surface: wgpu::Surface,
+    // None while the app is suspended (mobile platforms take the window away in the background).
+    pub(crate) surface: Option<wgpu::Surface>,
     adapter: wgpu::Adapter,
     pub(crate) device: wgpu::Device,
     pub(crate) queue: wgpu::Queue,
     pub(crate) config: wgpu::SurfaceConfiguration,
+    // Set from wgpu's error callback, which may run on another thread.
+    device_lost: Arc<AtomicBool>,
+    // Kept for opening a new device after device loss.
+    options: GpuOptions,
+    // Made the first time a texture needs mipmaps.
+    pub(crate) mip_blitter: std::sync::OnceLock<crate::mipmap::MipBlitter>,
+    pub(crate) cache: crate::pipeline_cache::PipelineCache,
+    // What textures made by create_texture_for were called, for memory reports.
+    texture_labels: std::sync::Mutex<std::collections::HashMap<wgpu::Id<wgpu::Texture>, String>>,
+    // Files textures were loaded from in debug builds, for reloading them when they change.
+    texture_files: std::sync::Mutex<FileWatcher<wgpu::Id<wgpu::Texture>>>,
+    // Where the main surface's frames are drawn before being resolved into the swapchain image,
+    // when multisampling. Follows the surface size.
+    msaa_view: Option<wgpu::TextureView>,
+    // Same size and sample count as the color target, if options.depth_buffer is set.
+    depth_view: Option<wgpu::TextureView>,
 }
 impl WGPU {
+    // PNG, JPEG, GIF (its first frame; see load_animation), BMP, TGA, WebP, QOI and the rest of
+    // what the image crate reads, picked by the file extension. DDS files with DXT1/3/5 data are
+    // decompressed to 8 bits a channel, and HDR and EXR images clamped to it (load_hdr_texture
+    // keeps their range).
     pub async fn load_texture(
         &self,
         path: &std::path::Path,
         label: Option<&str>,
-    ) -> Result<(wgpu::Texture, image::RgbaImage), image::ImageError> {
+    ) -> Result<(wgpu::Texture, image::RgbaImage), TextureError> {
         // This ? operator will return the error if there is one, unwrapping the result otherwise.
-        let img = image::open(path)?.to_rgba8();
+        let bytes = crate::assets::read_bytes(path)
+            .await
+            .map_err(|source| TextureError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        let img = decode_image(&bytes, path)
+            .map_err(|e| TextureError::from_image(path, e))?
+            .to_rgba8();
+        let texture = self.texture_for_image(&img, path, label)?;
+        if cfg!(debug_assertions) {
+            if let Ok(mut files) = self.texture_files.lock() {
+                files.watch(texture.global_id(), crate::assets::resolve(path));
+            }
+        }
+        Ok((texture, img))
+    }
+    // Loads the textures whose files changed since they were loaded (or last reloaded) over again,
+    // each as a new texture paired with the id of the one it replaces. One that fails to load is
+    // logged and left as it was. Only load_texture's files are watched, in debug builds.
+    pub(crate) fn reload_changed_textures(&self) -> Vec<(wgpu::Id<wgpu::Texture>, wgpu::Texture)> {
+        let Ok(mut files) = self.texture_files.lock() else {
+            return Vec::new();
+        };
+        let mut reloaded = Vec::new();
+        for (old, path) in files.changed() {
+            let img = match std::fs::read(&path) {
+                Ok(bytes) => decode_image(&bytes, &path)
+                    .map_err(|e| TextureError::from_image(&path, e))
+                    .map(|img| img.to_rgba8()),
+                Err(source) => Err(TextureError::Io {
+                    path: path.clone(),
+                    source,
+                }),
+            };
+            let label = self
+                .texture_labels
+                .lock()
+                .ok()
+                .and_then(|labels| labels.get(&old).cloned());
+            match img.and_then(|img| self.texture_for_image(&img, &path, label.as_deref())) {
+                Ok(texture) => {
+                    log::info!("reloaded {}", path.display());
+                    files.unwatch(&old);
+                    files.watch(texture.global_id(), path);
+                    reloaded.push((old, texture));
+                }
+                Err(e) => log::error!("couldn't reload {}: {e}", path.display()),
+            }
+        }
+        reloaded
+    }
+    // For images that didn't come from a file: downloaded, unpacked from an archive, etc. The
+    // format is guessed from the contents. Errors name the texture by `label`.
+    pub fn load_texture_from_bytes(
+        &self,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::RgbaImage), TextureError> {
+        let source = in_memory_source(label);
+        let img = image::load_from_memory(bytes)
+            .map_err(|e| TextureError::from_image(&source, e))?
+            .to_rgba8();
+        let texture = self.texture_for_image(&img, &source, label)?;
+        Ok((texture, img))
+    }
+    // Loads an animated GIF or PNG with all its frames on one texture, and a looping clip of them
+    // for Animator::play with their own timings. Still images come out as a one-frame clip.
+    pub async fn load_animation(
+        &self,
+        path: &std::path::Path,
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::RgbaImage, crate::AnimationClip), TextureError> {
+        let bytes = crate::assets::read_bytes(path)
+            .await
+            .map_err(|source| TextureError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        let (atlas, clip) = crate::animated_image::decode_animation(&bytes, path)?;
+        let texture = self.texture_for_image(&atlas, path, label)?;
+        Ok((texture, atlas, clip))
+    }
+    pub fn load_animation_from_bytes(
+        &self,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::RgbaImage, crate::AnimationClip), TextureError> {
+        let source = in_memory_source(label);
+        let (atlas, clip) = crate::animated_image::decode_animation(bytes, &source)?;
+        let texture = self.texture_for_image(&atlas, &source, label)?;
+        Ok((texture, atlas, clip))
+    }
+    // Loads an HDR or EXR image, e.g. a baked lightmap or a sky, into an Rgba16Float texture that
+    // keeps values above 1 for bloom and tone mapping to work with. Its values are taken as
+    // linear, as they are in those formats; other formats load too, with their (usually sRGB)
+    // values as they are. No mipmaps.
+    pub async fn load_hdr_texture(
+        &self,
+        path: &std::path::Path,
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::Rgba32FImage), TextureError> {
+        let bytes = crate::assets::read_bytes(path)
+            .await
+            .map_err(|source| TextureError::Io {
+                path: path.to_owned(),
+                source,
+            })?;
+        let img = decode_image(&bytes, path)
+            .map_err(|e| TextureError::from_image(path, e))?
+            .to_rgba32f();
+        let texture = self.hdr_texture_for_image(&img, path, label)?;
+        Ok((texture, img))
+    }
+    pub fn load_hdr_texture_from_bytes(
+        &self,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<(wgpu::Texture, image::Rgba32FImage), TextureError> {
+        let source = in_memory_source(label);
+        let img = image::load_from_memory(bytes)
+            .map_err(|e| TextureError::from_image(&source, e))?
+            .to_rgba32f();
+        let texture = self.hdr_texture_for_image(&img, &source, label)?;
+        Ok((texture, img))
+    }
+    fn hdr_texture_for_image(
+        &self,
+        img: &image::Rgba32FImage,
+        source: &std::path::Path,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
         let (width, height) = img.dimensions();
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(TextureError::TooLarge {
+                path: source.to_owned(),
+                width,
+                height,
+                max,
+            });
+        }
         let size = wgpu::Extent3d {
             width,
             height,
             depth_or_array_layers: 1,
         };
+        // Rgba32Float would keep more, but can't be filtered without a device feature.
         let texture = self.device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: wgpu::TextureFormat::Rgba16Float,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
+        self.remember_label(&texture, label.unwrap_or(&source.display().to_string()));
+        let halves: Vec<u16> = img
+            .as_raw()
+            .iter()
+            .map(|&value| half::f16::from_f32(value).to_bits())
+            .collect();
         self.queue.write_texture(
             texture.as_image_copy(),
-            &img,
+            bytemuck::cast_slice(&halves),
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * width),
+                bytes_per_row: Some(8 * width),
                 rows_per_image: Some(height),
             },
             size,
         );
-        Ok((texture, img))
+        Ok(texture)
+    }
+    // Uploads an image that's already decoded, e.g. one generated procedurally.
+    pub fn load_texture_from_image(
+        &self,
+        img: &image::RgbaImage,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        self.texture_for_image(img, &in_memory_source(label), label)
+    }
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip_all, fields(source = %source.display()))
+    )]
+    pub(crate) fn texture_for_image(
+        &self,
+        img: &image::RgbaImage,
+        source: &std::path::Path,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        let mip_level_count = self.mip_levels_for(img, source)?;
+        let texture = self.create_texture_for(img, label, mip_level_count);
+        if label.is_none() {
+            self.remember_label(&texture, &source.display().to_string());
+        }
+        self.write_texture_data(&texture, img);
+        if mip_level_count > 1 {
+            self.generate_mipmaps(&texture);
+        }
+        Ok(texture)
+    }
+
+    // Checks that `img` fits in a texture on this GPU, returning how many mip levels it gets.
+    pub(crate) fn mip_levels_for(
+        &self,
+        img: &image::RgbaImage,
+        source: &std::path::Path,
+    ) -> Result<u32, TextureError> {
+        let (width, height) = img.dimensions();
+        let max = self.device.limits().max_texture_dimension_2d;
+        if width > max || height > max {
+            return Err(TextureError::TooLarge {
+                path: source.to_owned(),
+                width,
+                height,
+                max,
+            });
+        }
+        if width == 0 || height == 0 {
+            return Err(TextureError::Unsupported {
+                path: source.to_owned(),
+                reason: "the image is empty".into(),
+            });
+        }
+        Ok(if self.options.mipmaps {
+            crate::mipmap::mip_level_count(width, height)
+        } else {
+            1
+        })
+    }
+    // Makes an empty texture with the right size and format for `img`, without uploading anything.
+    // With more than one mip level it can also be rendered to, for generate_mipmaps.
+    pub(crate) fn create_texture_for(
+        &self,
+        img: &image::RgbaImage,
+        label: Option<&str>,
+        mip_level_count: u32,
+    ) -> wgpu::Texture {
+        let (width, height) = img.dimensions();
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: if mip_level_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::empty()
+            } | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        if let Some(label) = label {
+            self.remember_label(&texture, label);
+        }
+        texture
+    }
+    pub(crate) fn remember_label(&self, texture: &wgpu::Texture, label: &str) {
+        if let Ok(mut labels) = self.texture_labels.lock() {
+            labels.insert(texture.global_id(), label.to_owned());
+        }
+    }
+    // Stops keeping track of a texture that's being unloaded.
+    pub(crate) fn forget_texture(&self, texture: wgpu::Id<wgpu::Texture>) {
+        if let Ok(mut labels) = self.texture_labels.lock() {
+            labels.remove(&texture);
+        }
+        if let Ok(mut files) = self.texture_files.lock() {
+            files.unwatch(&texture);
+        }
+    }
+    pub(crate) fn texture_memory(&self, texture: &wgpu::Texture) -> crate::TextureMemory {
+        let size = texture.size();
+        crate::TextureMemory {
+            label: self
+                .texture_labels
+                .lock()
+                .ok()
+                .and_then(|labels| labels.get(&texture.global_id()).cloned()),
+            size: [size.width, size.height],
+            format: texture.format(),
+            mip_level_count: texture.mip_level_count(),
+            bytes: crate::memory::texture_bytes(texture),
+            groups: 0,
+        }
     }
 
-    pub(crate) async fn new(window: &Window) -> Self {
+    // Copies the pixels of `img` into a texture made by `create_texture_for`.
+    pub(crate) fn write_texture_data(&self, texture: &wgpu::Texture, img: &image::RgbaImage) {
+        let (width, height) = img.dimensions();
+        self.queue.write_texture(
+            texture.as_image_copy(),
+            img,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    pub(crate) async fn new(window: &Window, options: &GpuOptions) -> Result<Self, EngineError> {
         // for example an &str.
 
         let size = window.inner_size();
 
         // An Instance is an instance of the graphics API.  It's the context in which other
         // WGPU values and operations take place, and there can be only one.
-        // Its implementation of the Default trait automatically selects a driver backend.
-        let instance = wgpu::Instance::default();
+        // It picks a driver backend from among the ones we allow.
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: options.backends(),
+            ..Default::default()
+        });
 
         // From the OS window (or web canvas) the graphics API can obtain a surface onto which
         // we can draw.  This operation is unsafe (it depends on the window not outliving the surface)
         // and it could fail (if the window can't provide a rendering destination).
-        // The unsafe {} block allows us to call unsafe functions, and the unwrap will abort the program
-        // if the operation fails.
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
+        // The unsafe {} block allows us to call unsafe functions, and the ? hands any failure back
+        // to the caller.
+        let surface = unsafe { instance.create_surface(&window) }?;
 
         // Next, we need to get a graphics adapter from the instance---this represents a physical
         // graphics card (GPU) or compute device.  Here we ask for a GPU that will be able to draw to the
         // surface we just obtained.
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                // Request an adapter which can render to our surface
-                compatible_surface: Some(&surface),
-            })
-            // This operation can take some time, so we await the result. We can only await like this
-            // in an async function.
-            .await
-            // And it can fail, so we panic with an error message if we can't get a GPU.
-            .expect("Failed to find an appropriate adapter");
+        // This operation can take some time, so we await the result. We can only await like this
+        // in an async function.
+        let adapter = match select_adapter(&instance, Some(&surface), options) {
+            Some(adapter) => adapter,
+            None => request_adapter(&instance, Some(&surface), options.power_preference()).await?,
+        };
 
         // Create the logical device and command queue.  A logical device is like a connection to a GPU, and
         // we'll be issuing instructions to the GPU over the command queue.
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    // Bump up the limits to require the availability of storage buffers.
-                    limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
-                },
-                None,
-            )
-            .await
-            .expect("Failed to create device");
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let (device, queue) = open_device(&adapter, options, &device_lost).await?;
 
         // The swapchain is how we obtain images from the surface we're drawing onto.
         // This is so we can draw onto one image while a different one is being presented
@@ -104,33 +469,930 @@ impl WGPU {
         let swapchain_capabilities = surface.get_capabilities(&adapter);
         // We'll just use the first supported format, we don't have any reason here to use
         // one format or another.
-        let swapchain_format = swapchain_capabilities.formats[0];
+        let swapchain_format = *swapchain_capabilities
+            .formats
+            .first()
+            .ok_or(EngineError::NoSurfaceFormat)?;
 
         // Our surface config lets us set up our surface for drawing with the device
         // we're actually using.  It's mutable in case the window's size changes later on.
-        let mut config = wgpu::SurfaceConfiguration {
+        let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: swapchain_format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: pick_present_mode(&swapchain_capabilities, options.present_mode),
             alpha_mode: swapchain_capabilities.alpha_modes[0],
             view_formats: vec![],
         };
         surface.configure(&device, &config);
 
-        Self {
+        let mut gpu = Self {
             instance,
-            surface,
+            surface: Some(surface),
             adapter,
             device,
             queue,
             config,
+            device_lost,
+            options: options.clone(),
+            mip_blitter: Default::default(),
+            cache: Default::default(),
+            texture_labels: Default::default(),
+            texture_files: Default::default(),
+            msaa_view: None,
+            depth_view: None,
+        };
+        gpu.init_msaa();
+        Ok(gpu)
+    }
+    // Every adapter wgpu can see with these options, in the order AdapterSelection::Index counts
+    // them. Always empty on the web, where the browser picks.
+    pub fn enumerate_adapters(options: &GpuOptions) -> Vec<wgpu::AdapterInfo> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: options.backends(),
+                ..Default::default()
+            });
+            instance
+                .enumerate_adapters(options.backends())
+                .map(|a| a.get_info())
+                .collect()
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = options;
+            Vec::new()
         }
     }
+    pub fn adapter_info(&self) -> wgpu::AdapterInfo {
+        self.adapter.get_info()
+    }
+    // A WGPU with no window or surface, for tests and tools (e.g. making thumbnails on a server).
+    // SpriteRender::new targets an Rgba8UnormSrgb image of `width`x`height`; draw it with
+    // render_to_image.
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        options: &GpuOptions,
+    ) -> Result<Self, EngineError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: options.backends(),
+            ..Default::default()
+        });
+        let adapter = match select_adapter(&instance, None, options) {
+            Some(adapter) => adapter,
+            None => request_adapter(&instance, None, options.power_preference()).await?,
+        };
+        let device_lost = Arc::new(AtomicBool::new(false));
+        let (device, queue) = open_device(&adapter, options, &device_lost).await?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let mut gpu = Self {
+            instance,
+            surface: None,
+            adapter,
+            device,
+            queue,
+            config,
+            device_lost,
+            options: options.clone(),
+            mip_blitter: Default::default(),
+            cache: Default::default(),
+            texture_labels: Default::default(),
+            texture_files: Default::default(),
+            msaa_view: None,
+            depth_view: None,
+        };
+        gpu.init_msaa();
+        Ok(gpu)
+    }
+    // Draws `sprites` into an offscreen texture the size of the surface and reads it back. Blocks
+    // until the GPU is done, so it's not for use every frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_to_image(
+        &self,
+        sprites: &mut crate::SpriteRender,
+        clear: impl Into<crate::Color>,
+    ) -> image::RgbaImage {
+        let (width, height) = (self.config.width, self.config.height);
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let target = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        // Texture-to-buffer copies need every row padded out to 256 bytes.
+        let row_bytes = 4 * width;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offscreen readback"),
+            size: (padded_row_bytes * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        sprites.flush(self, &mut encoder);
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(color_attachment(
+                    &view,
+                    self.msaa_view.as_ref(),
+                    wgpu::LoadOp::Clear(clear.into().to_wgpu(self.config.format)),
+                ))],
+                depth_stencil_attachment: depth_attachment(self.depth_view.as_ref()),
+            });
+            sprites.render(&mut rpass);
+        }
+        encoder.copy_texture_to_buffer(
+            target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            size,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let data = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        for row in data.chunks(padded_row_bytes as usize) {
+            pixels.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        drop(data);
+        readback.unmap();
+        // A window's surface format is often BGRA.
+        if matches!(
+            self.config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        image::RgbaImage::from_raw(width, height, pixels).expect("readback is exactly one image")
+    }
+    pub fn features(&self) -> wgpu::Features {
+        self.device.features()
+    }
+    pub fn limits(&self) -> wgpu::Limits {
+        self.device.limits()
+    }
+    // What this adapter can't do that WebGPU requires, e.g. WebGL2 has no compute shaders.
+    pub fn downlevel_capabilities(&self) -> wgpu::DownlevelCapabilities {
+        self.adapter.get_downlevel_capabilities()
+    }
+    // Whether the GPU device stopped working (driver reset, GPU unplugged, out of memory). The
+    // Engine checks this every frame and calls `recover`.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+    // Opens a fresh device and queue on the same surface. Everything made on the old device
+    // (textures, buffers, pipelines) is gone and has to be recreated by whoever owns it.
+    pub async fn recover(&mut self) -> Result<(), EngineError> {
+        let adapter = match select_adapter(&self.instance, self.surface.as_ref(), &self.options) {
+            Some(adapter) => adapter,
+            None => {
+                let power_preference = self.options.power_preference();
+                request_adapter(&self.instance, self.surface.as_ref(), power_preference).await?
+            }
+        };
+        let (device, queue) = open_device(&adapter, &self.options, &self.device_lost).await?;
+        self.adapter = adapter;
+        self.device = device;
+        self.queue = queue;
+        self.configure_surface();
+        self.recreate_render_targets();
+        self.mip_blitter = Default::default();
+        self.cache = Default::default();
+        self.device_lost.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+    // Switches present mode on the fly, with the same fallback as GpuOptions::present_mode.
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        let Some(surface) = &self.surface else {
+            // Picked again against the new surface when we resume.
+            self.config.present_mode = mode;
+            return;
+        };
+        let capabilities = surface.get_capabilities(&self.adapter);
+        self.config.present_mode = pick_present_mode(&capabilities, mode);
+        self.configure_surface();
+    }
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+    // Makes and configures a surface for an extra window, preferring the main surface's format so
+    // the same pipelines can draw into either.
+    pub(crate) fn create_window_surface(
+        &self,
+        window: &Window,
+    ) -> Option<(wgpu::Surface, wgpu::SurfaceConfiguration)> {
+        let surface = match unsafe { self.instance.create_surface(window) } {
+            Ok(surface) => surface,
+            Err(e) => {
+                log::error!("Couldn't create a surface for window: {e}");
+                return None;
+            }
+        };
+        let capabilities = surface.get_capabilities(&self.adapter);
+        let format = if capabilities.formats.contains(&self.config.format) {
+            self.config.format
+        } else {
+            *capabilities.formats.first()?
+        };
+        let size = window.inner_size();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: self.config.present_mode,
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: vec![],
+        };
+        surface.configure(&self.device, &config);
+        Some((surface, config))
+    }
     pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        // Minimized windows report a zero size, which the surface can't be configured with. We
+        // keep the old size and carry on once the window comes back.
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
         self.config.width = size.width;
         self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
+        self.configure_surface();
+        self.recreate_render_targets();
+    }
+    // How many samples per pixel pipelines drawing into `format` should use: options.msaa_samples,
+    // lowered to what the device supports.
+    pub(crate) fn sample_count_for(&self, format: wgpu::TextureFormat) -> u32 {
+        let features = if self
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        {
+            self.adapter.get_texture_format_features(format)
+        } else {
+            format.guaranteed_format_features(self.device.features())
+        };
+        let mut count = self.options.msaa_samples.max(1).next_power_of_two().min(16);
+        while count > 1 && !features.flags.sample_count_supported(count) {
+            count /= 2;
+        }
+        count
+    }
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count_for(self.config.format)
+    }
+    // A multisampled texture to draw into and resolve from, or None if `sample_count` is 1.
+    pub(crate) fn create_msaa_view(
+        &self,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count <= 1 {
+            return None;
+        }
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("msaa target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+    fn init_msaa(&mut self) {
+        let samples = self.sample_count();
+        if samples != self.options.msaa_samples.max(1) {
+            log::warn!(
+                "{}x MSAA isn't supported for {:?}, using {samples}x",
+                self.options.msaa_samples,
+                self.config.format
+            );
+        }
+        self.recreate_render_targets();
+    }
+    pub fn has_depth_buffer(&self) -> bool {
+        self.options.depth_buffer
+    }
+    // A depth texture for a color target of this size, or None without options.depth_buffer.
+    pub(crate) fn create_depth_view(
+        &self,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if !self.options.depth_buffer {
+            return None;
+        }
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth buffer"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+    fn recreate_render_targets(&mut self) {
+        let (width, height) = (self.config.width, self.config.height);
+        let samples = self.sample_count();
+        self.msaa_view = self.create_msaa_view(self.config.format, width, height, samples);
+        self.depth_view = self.create_depth_view(width, height, samples);
+    }
+    // The depth attachment to go with frame_attachment.
+    pub(crate) fn frame_depth_attachment(
+        &self,
+    ) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+        depth_attachment(self.depth_view.as_ref())
+    }
+    // The color attachment for drawing a frame into `view`: directly, or through the main
+    // surface's multisampled target when MSAA is on.
+    pub(crate) fn frame_attachment<'a>(
+        &'a self,
+        view: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        color_attachment(view, self.msaa_view.as_ref(), load)
+    }
+    fn configure_surface(&self) {
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+    // Drops the surface when the app goes to the background; on Android the window it was made
+    // from is about to be destroyed.
+    pub(crate) fn suspend(&mut self) {
+        self.surface = None;
+    }
+    // Makes a new surface for `window` after a suspend. Does nothing if we still have one.
+    pub(crate) fn resume(&mut self, window: &Window) -> Result<(), EngineError> {
+        if self.surface.is_some() {
+            return Ok(());
+        }
+        let surface = unsafe { self.instance.create_surface(window) }?;
+        let capabilities = surface.get_capabilities(&self.adapter);
+        if !capabilities.formats.contains(&self.config.format) {
+            // Pipelines were built for the old format, so there's nothing sensible to fall back to.
+            return Err(EngineError::NoSurfaceFormat);
+        }
+        let size = window.inner_size();
+        if size.width > 0 && size.height > 0 {
+            self.config.width = size.width;
+            self.config.height = size.height;
+        }
+        self.config.present_mode = pick_present_mode(&capabilities, self.config.present_mode);
+        self.surface = Some(surface);
+        self.configure_surface();
+        self.recreate_render_targets();
+        Ok(())
+    }
+    // Gets the next swapchain image, or None if this frame should be skipped. A lost or outdated
+    // surface (after a fast resize, or minimizing on Windows) is reconfigured so the next frame
+    // works again.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip_all))]
+    pub(crate) fn acquire_frame(&self) -> Option<wgpu::SurfaceTexture> {
+        match self.surface.as_ref()?.get_current_texture() {
+            Ok(frame) => Some(frame),
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.configure_surface();
+                None
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                log::error!("Out of memory while acquiring a frame, treating the device as lost");
+                self.device_lost.store(true, Ordering::Relaxed);
+                None
+            }
+            Err(e) => {
+                log::warn!("Skipping a frame: {e}");
+                None
+            }
+        }
+    }
+}
+
+// The adapter asked for by options.adapter, if there's a matching one that can draw to `surface`.
+// None means wgpu should choose.
+#[cfg(not(target_arch = "wasm32"))]
+fn select_adapter(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface>,
+    options: &GpuOptions,
+) -> Option<wgpu::Adapter> {
+    if options.adapter == AdapterSelection::Auto {
+        return None;
+    }
+    let found = instance
+        .enumerate_adapters(options.backends())
+        .enumerate()
+        .find(|(i, adapter)| match &options.adapter {
+            AdapterSelection::Auto => false,
+            AdapterSelection::Index(index) => i == index,
+            AdapterSelection::Name(name) => adapter
+                .get_info()
+                .name
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+        })
+        .map(|(_, adapter)| adapter);
+    match found {
+        Some(adapter) if surface.is_none_or(|s| adapter.is_surface_supported(s)) => Some(adapter),
+        Some(adapter) => {
+            log::warn!(
+                "{} can't draw to this window, picking another adapter",
+                adapter.get_info().name
+            );
+            None
+        }
+        None => {
+            log::warn!("No adapter matches {:?}, picking one", options.adapter);
+            None
+        }
+    }
+}
+#[cfg(target_arch = "wasm32")]
+fn select_adapter(
+    _instance: &wgpu::Instance,
+    _surface: Option<&wgpu::Surface>,
+    _options: &GpuOptions,
+) -> Option<wgpu::Adapter> {
+    None
+}
+
+// Asks for a real GPU first, then for a software adapter (headless CI, very old GPUs) before
+// giving up.
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface>,
+    power_preference: wgpu::PowerPreference,
+) -> Result<wgpu::Adapter, EngineError> {
+    for force_fallback_adapter in [false, true] {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference,
+                force_fallback_adapter,
+                // Request an adapter which can render to our surface
+                compatible_surface: surface,
+            })
+            .await;
+        if let Some(adapter) = adapter {
+            return Ok(adapter);
+        }
+        if !force_fallback_adapter {
+            log::warn!("No GPU adapter found, trying a software fallback adapter");
+        }
+    }
+    Err(EngineError::AdapterNotFound)
+}
+
+// Draws into `msaa` (when there is one) and resolves the result into `view`. The multisampled
+// texture is kept between frames, so LoadOp::Load still sees the last frame.
+pub(crate) fn color_attachment<'a>(
+    view: &'a wgpu::TextureView,
+    msaa: Option<&'a wgpu::TextureView>,
+    load: wgpu::LoadOp<wgpu::Color>,
+) -> wgpu::RenderPassColorAttachment<'a> {
+    let ops = wgpu::Operations { load, store: true };
+    match msaa {
+        Some(msaa) => wgpu::RenderPassColorAttachment {
+            view: msaa,
+            resolve_target: Some(view),
+            ops,
+        },
+        None => wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops,
+        },
+    }
+}
+
+pub(crate) const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+// Cleared to the far plane every frame, even when the color target isn't cleared.
+pub(crate) fn depth_attachment(
+    view: Option<&wgpu::TextureView>,
+) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+    Some(wgpu::RenderPassDepthStencilAttachment {
+        view: view?,
+        depth_ops: Some(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(1.0),
+            store: false,
+        }),
+        stencil_ops: None,
+    })
+}
+
+// What TextureError reports as the path of a texture that didn't come from a file.
+pub(crate) fn in_memory_source(label: Option<&str>) -> std::path::PathBuf {
+    format!("<{}>", label.unwrap_or("in-memory image")).into()
+}
+
+// Decodes by the file extension like image::open, falling back to sniffing the contents.
+pub(crate) fn decode_image(
+    bytes: &[u8],
+    path: &std::path::Path,
+) -> image::ImageResult<image::DynamicImage> {
+    match image::ImageFormat::from_path(path) {
+        Ok(format) => image::load_from_memory_with_format(bytes, format),
+        Err(_) => image::load_from_memory(bytes),
+    }
+}
+
+async fn open_device(
+    adapter: &wgpu::Adapter,
+    options: &GpuOptions,
+    device_lost: &Arc<AtomicBool>,
+) -> Result<(wgpu::Device, wgpu::Queue), EngineError> {
+    let missing = options.features - adapter.features();
+    if !missing.is_empty() {
+        return Err(EngineError::MissingFeatures(missing));
+    }
+    let limits = match &options.limits {
+        Some(limits) => limits.clone(),
+        None => {
+            // Bump up the limits to require the availability of storage buffers, unless the
+            // adapter can't manage even that, in which case we take what WebGL2 guarantees and
+            // hope for the best.
+            let mut limits = wgpu::Limits::downlevel_defaults();
+            if !limits.check_limits(&adapter.limits()) {
+                log::warn!(
+                    "{} doesn't meet the downlevel limits, using WebGL2 limits instead",
+                    adapter.get_info().name
+                );
+                limits = wgpu::Limits::downlevel_webgl2_defaults();
+            }
+            limits.using_resolution(adapter.limits())
+        }
+    };
+    let mut features = options.features;
+    if options.gpu_timing {
+        features |= adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+    }
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features,
+                limits,
+            },
+            None,
+        )
+        .await?;
+    // wgpu's default handler panics on any error. A lost device (or running out of memory) is
+    // flagged instead, so the Engine can start over with a new one. Validation errors are bugs,
+    // so debug builds still panic on them; release builds log them and carry on.
+    let lost = device_lost.clone();
+    device.on_uncaptured_error(Box::new(move |e| {
+        if matches!(e, wgpu::Error::OutOfMemory { .. }) || is_device_lost_error(&e) {
+            log::error!("wgpu error: {e}");
+            lost.store(true, Ordering::Relaxed);
+            return;
+        }
+        if cfg!(debug_assertions) {
+            panic!("wgpu error: {e}");
+        }
+        log::error!("wgpu error: {e}");
+    }));
+    Ok((device, queue))
+}
+
+// wgpu 0.17 has no error variant for a lost device; calls on one fail validation with
+// wgpu-core's DeviceError::Lost somewhere in the chain, which reads "Parent device is lost".
+// Check this still matches when updating wgpu.
+fn is_device_lost_error(e: &wgpu::Error) -> bool {
+    e.to_string().contains("Parent device is lost")
+}
+
+fn pick_present_mode(
+    capabilities: &wgpu::SurfaceCapabilities,
+    wanted: wgpu::PresentMode,
+) -> wgpu::PresentMode {
+    use wgpu::PresentMode;
+    match wanted {
+        // wgpu resolves these itself and they're always supported.
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => wanted,
+        _ if capabilities.present_modes.contains(&wanted) => wanted,
+        _ => {
+            log::warn!("Present mode {wanted:?} isn't supported here, using Fifo instead");
+            PresentMode::Fifo
+        }
+    }
+}
+
+// What the passes of a RenderGraph draw into and read from, for ordering them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GraphTarget {
+    // Where the sprites go: the window's image, or post-processing's offscreen texture while any
+    // post-processing pass is enabled.
+    Scene,
+    // The window's image, once post-processing is done with it.
+    Frame,
+    // Anything else passes hand each other, like a light map in a texture of your own.
+    Named(String),
+}
+
+impl GraphTarget {
+    pub fn named(name: impl Into<String>) -> Self {
+        GraphTarget::Named(name.into())
+    }
+}
+
+// What a pass records its work with. Everything goes into the frame's one command encoder.
+pub struct PassContext<'a> {
+    pub gpu: &'a WGPU,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    // GraphTarget::Scene and GraphTarget::Frame, the same view without post-processing.
+    pub scene: &'a wgpu::TextureView,
+    pub frame: &'a wgpu::TextureView,
+    // Of the window, in pixels.
+    pub size: (u32, u32),
+    pub(crate) timer: Option<&'a mut crate::gpu_timer::GpuTimer>,
+}
+
+// The passes the Engine always has; it knows how to run these itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BuiltinPass {
+    Sprites,
+    PostProcess,
+    Game,
+    Ui,
+    DebugDraw,
+}
+
+type PassFn = Box<dyn FnMut(&mut PassContext)>;
+
+enum PassKind {
+    Builtin(BuiltinPass),
+    Custom(PassFn),
+}
+
+struct GraphPass {
+    name: String,
+    reads: Vec<GraphTarget>,
+    writes: Vec<GraphTarget>,
+    kind: PassKind,
+    enabled: bool,
+}
+
+impl GraphPass {
+    // Where the pass goes among the passes touching `target`: first the ones that only write it
+    // (and so start it over), then the ones that draw over it, then the ones that only read it.
+    fn rank(&self, target: &GraphTarget) -> Option<u8> {
+        match (self.reads.contains(target), self.writes.contains(target)) {
+            (false, true) => Some(0),
+            (true, true) => Some(1),
+            (true, false) => Some(2),
+            (false, false) => None,
+        }
+    }
+}
+
+// The passes that make up a frame. Each one says which targets it reads and writes, and the
+// Engine runs them in an order where every target is written before it's read; passes writing
+// the same target keep the order they were added in. Starts out with the engine's own passes,
+// "sprites" (writes Scene), "post process" (reads Scene, writes Frame), then "game", "ui" and
+// "debug draw" (all reading and writing Frame), so e.g. an overlay pass reading and writing Frame
+// lands after post-processing.
+pub struct RenderGraph {
+    passes: Vec<GraphPass>,
+    // Indices into passes, in the order they run.
+    order: Vec<usize>,
+}
+
+impl Default for RenderGraph {
+    fn default() -> Self {
+        let builtin = |name: &str, reads: &[GraphTarget], writes: &[GraphTarget], pass| GraphPass {
+            name: name.to_owned(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            kind: PassKind::Builtin(pass),
+            enabled: true,
+        };
+        let passes = vec![
+            builtin(
+                Self::SPRITES,
+                &[],
+                &[GraphTarget::Scene],
+                BuiltinPass::Sprites,
+            ),
+            builtin(
+                Self::POST_PROCESS,
+                &[GraphTarget::Scene],
+                &[GraphTarget::Frame],
+                BuiltinPass::PostProcess,
+            ),
+            builtin(
+                Self::GAME,
+                &[GraphTarget::Frame],
+                &[GraphTarget::Frame],
+                BuiltinPass::Game,
+            ),
+            builtin(
+                Self::UI,
+                &[GraphTarget::Frame],
+                &[GraphTarget::Frame],
+                BuiltinPass::Ui,
+            ),
+            builtin(
+                Self::DEBUG_DRAW,
+                &[GraphTarget::Frame],
+                &[GraphTarget::Frame],
+                BuiltinPass::DebugDraw,
+            ),
+        ];
+        Self {
+            order: (0..passes.len()).collect(),
+            passes,
+        }
+    }
+}
+
+impl RenderGraph {
+    pub const SPRITES: &'static str = "sprites";
+    pub const POST_PROCESS: &'static str = "post process";
+    // Game::render.
+    pub const GAME: &'static str = "game";
+    // Engine::ui.
+    pub const UI: &'static str = "ui";
+    pub const DEBUG_DRAW: &'static str = "debug draw";
+
+    // Adds a pass of your own, which `run` records into the frame's encoder. Fails if the name
+    // is taken or the pass would make two passes each wait on the other.
+    pub fn add_pass(
+        &mut self,
+        name: impl Into<String>,
+        reads: &[GraphTarget],
+        writes: &[GraphTarget],
+        run: impl FnMut(&mut PassContext) + 'static,
+    ) -> Result<(), GraphError> {
+        let name = name.into();
+        if self.find(&name).is_ok() {
+            return Err(GraphError::DuplicatePass(name));
+        }
+        self.passes.push(GraphPass {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            kind: PassKind::Custom(Box::new(run)),
+            enabled: true,
+        });
+        self.reorder().inspect_err(|_| {
+            self.passes.pop();
+        })
+    }
+    // The engine's passes can go too, e.g. "debug draw" in a release build.
+    pub fn remove_pass(&mut self, name: &str) -> Result<(), GraphError> {
+        let index = self.find(name)?;
+        self.passes.remove(index);
+        self.reorder()
+    }
+    // Makes `name` wait for whatever writes `target`, e.g. the sprites for a light map pass.
+    pub fn add_read(&mut self, name: &str, target: GraphTarget) -> Result<(), GraphError> {
+        let index = self.find(name)?;
+        if self.passes[index].reads.contains(&target) {
+            return Ok(());
+        }
+        self.passes[index].reads.push(target);
+        self.reorder().inspect_err(|_| {
+            self.passes[index].reads.pop();
+        })
+    }
+    // Disabled passes keep their place but don't run.
+    pub fn set_pass_enabled(&mut self, name: &str, enabled: bool) -> Result<(), GraphError> {
+        let index = self.find(name)?;
+        self.passes[index].enabled = enabled;
+        Ok(())
+    }
+    // Whether `name` is there and enabled.
+    pub(crate) fn runs(&self, name: &str) -> bool {
+        self.find(name)
+            .is_ok_and(|index| self.passes[index].enabled)
+    }
+    // Every pass's name, in the order they run.
+    pub fn pass_order(&self) -> Vec<&str> {
+        self.order
+            .iter()
+            .map(|&index| self.passes[index].name.as_str())
+            .collect()
+    }
+    fn find(&self, name: &str) -> Result<usize, GraphError> {
+        self.passes
+            .iter()
+            .position(|pass| pass.name == name)
+            .ok_or_else(|| GraphError::NoSuchPass(name.to_owned()))
+    }
+    // Sorts the passes topologically, taking the earliest added of the ones that are ready.
+    fn reorder(&mut self) -> Result<(), GraphError> {
+        let count = self.passes.len();
+        let targets: Vec<&GraphTarget> = self
+            .passes
+            .iter()
+            .flat_map(|pass| pass.reads.iter().chain(&pass.writes))
+            .collect();
+        // before[j] lists the passes that have to run before j.
+        let mut before = vec![Vec::new(); count];
+        for (j, after) in self.passes.iter().enumerate() {
+            for (i, first) in self.passes.iter().enumerate().filter(|&(i, _)| i != j) {
+                let waits =
+                    targets
+                        .iter()
+                        .any(|target| match (first.rank(target), after.rank(target)) {
+                            (Some(a), Some(b)) => a < b || (a == b && a < 2 && i < j),
+                            _ => false,
+                        });
+                if waits {
+                    before[j].push(i);
+                }
+            }
+        }
+        let mut done = vec![false; count];
+        let mut order = Vec::with_capacity(count);
+        while order.len() < count {
+            let Some(next) = (0..count).find(|&j| !done[j] && before[j].iter().all(|&i| done[i]))
+            else {
+                let stuck = (0..count)
+                    .filter(|&j| !done[j])
+                    .map(|j| self.passes[j].name.clone())
+                    .collect();
+                return Err(GraphError::Cycle(stuck));
+            };
+            done[next] = true;
+            order.push(next);
+        }
+        self.order = order;
+        Ok(())
+    }
+    // Runs the enabled passes in order; `builtin` runs the engine's. Each pass is timed for
+    // GpuOptions::gpu_timing under its name, except post-processing, which times its own passes.
+    pub(crate) fn execute(
+        &mut self,
+        ctx: &mut PassContext,
+        mut builtin: impl FnMut(BuiltinPass, &mut PassContext),
+    ) {
+        for &index in &self.order {
+            let pass = &mut self.passes[index];
+            if !pass.enabled {
+                continue;
+            }
+            match &mut pass.kind {
+                PassKind::Builtin(BuiltinPass::PostProcess) => {
+                    builtin(BuiltinPass::PostProcess, ctx);
+                    continue;
+                }
+                PassKind::Builtin(kind) => builtin(*kind, ctx),
+                PassKind::Custom(run) => run(ctx),
+            }
+            if let Some(timer) = ctx.timer.as_deref_mut() {
+                timer.mark(ctx.encoder, &pass.name);
+            }
+        }
     }
 }
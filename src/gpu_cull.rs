@@ -0,0 +1,159 @@
+use crate::{GPUCamera, GPUSprite, WGPU};
+
+// Sprites per workgroup; matches @workgroup_size in shader_cull.wgsl.
+const WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub(crate) struct CullParams {
+    view: [f32; 4],
+    count: u32,
+    slot: u32,
+    // Uniform buffers come in multiples of 16 bytes.
+    _padding: [u32; 2],
+}
+
+impl CullParams {
+    pub(crate) fn new(camera: &GPUCamera, count: usize, slot: usize) -> Self {
+        let [x, y] = camera.screen_pos;
+        let [w, h] = camera.screen_size;
+        Self {
+            view: [x, y, w, h],
+            count: count as u32,
+            slot: slot as u32,
+            _padding: [0; 2],
+        }
+    }
+}
+
+// The compute pipeline, shared by every GPU-culled group of a SpriteRender.
+pub(crate) struct CullPipeline {
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+impl CullPipeline {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        let storage = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("sprite culling"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    storage(1, true),
+                    storage(2, false),
+                    storage(3, false),
+                ],
+            });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("sprite culling"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("sprite culling"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("shader_cull.wgsl").into()),
+            });
+        let pipeline = gpu
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("sprite culling"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "cull",
+            });
+        Self { pipeline, layout }
+    }
+    // One pass culling every group in `groups`: each one's bind group (see GpuCull) and sprite
+    // count.
+    pub(crate) fn dispatch<'a>(
+        &'a self,
+        encoder: &mut wgpu::CommandEncoder,
+        groups: impl Iterator<Item = (&'a wgpu::BindGroup, usize)>,
+    ) {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("sprite culling"),
+        });
+        pass.set_pipeline(&self.pipeline);
+        for (bind_group, count) in groups {
+            if count == 0 {
+                continue;
+            }
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.dispatch_workgroups((count as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+}
+
+// What a group culled on the GPU has on top of an ordinary group: every sprite in `source`
+// (its own sprite buffer only gets the visible ones), and the compute pass's parameters.
+pub(crate) struct GpuCull {
+    pub(crate) source: wgpu::Buffer,
+    pub(crate) params: wgpu::Buffer,
+    // Made on the first flush, and again whenever one of the buffers it points at is replaced.
+    pub(crate) bind_group: Option<wgpu::BindGroup>,
+}
+
+impl GpuCull {
+    pub(crate) fn new(gpu: &WGPU, capacity: usize) -> Self {
+        Self {
+            source: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("culled sprites"),
+                size: (capacity * std::mem::size_of::<GPUSprite>()) as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            params: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("sprite culling"),
+                size: std::mem::size_of::<CullParams>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            bind_group: None,
+        }
+    }
+    pub(crate) fn bind(
+        &mut self,
+        gpu: &WGPU,
+        pipeline: &CullPipeline,
+        visible: &wgpu::Buffer,
+        args: &wgpu::Buffer,
+    ) {
+        let entries = [&self.params, &self.source, visible, args]
+            .into_iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+        self.bind_group = Some(gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprite culling"),
+            layout: &pipeline.layout,
+            entries: &entries,
+        }));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{GpuPassTime, WGPU};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+// Timestamps a frame can take: one at the start, then one after each pass.
+const MAX_TIMESTAMPS: u32 = 32;
+// Frames whose timestamps can be on their way back at once. The results come a frame or two
+// late; if they're later than this, frames go untimed until a buffer frees up.
+const READBACKS: usize = 3;
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    // What each timestamp after the first marked the end of.
+    labels: Vec<String>,
+    in_use: bool,
+    // Set from map_async's callback.
+    mapped: Arc<AtomicBool>,
+}
+
+// Timestamp queries between the passes of a frame, for GpuOptions::gpu_timing.
+pub(crate) struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    // The readback this frame goes to and the labels so far, None when this frame isn't timed.
+    frame: Option<(usize, Vec<String>)>,
+    // Ended but not yet mapped, as buffers can't be mapped until the frame is submitted.
+    to_map: Option<usize>,
+    // Nanoseconds per tick.
+    period: f32,
+    latest: Vec<GpuPassTime>,
+}
+
+impl GpuTimer {
+    // None if the device wasn't opened with TIMESTAMP_QUERY.
+    pub(crate) fn new(gpu: &WGPU) -> Option<Self> {
+        if !gpu
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            return None;
+        }
+        let size = MAX_TIMESTAMPS as u64 * std::mem::size_of::<u64>() as u64;
+        let buffer = |usage| {
+            gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("gpu timing"),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        Some(Self {
+            query_set: gpu.device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("gpu timing"),
+                ty: wgpu::QueryType::Timestamp,
+                count: MAX_TIMESTAMPS,
+            }),
+            resolve: buffer(wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC),
+            readbacks: (0..READBACKS)
+                .map(|_| Readback {
+                    buffer: buffer(wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST),
+                    labels: Vec::new(),
+                    in_use: false,
+                    mapped: Arc::new(AtomicBool::new(false)),
+                })
+                .collect(),
+            frame: None,
+            to_map: None,
+            period: gpu.queue.get_timestamp_period(),
+            latest: Vec::new(),
+        })
+    }
+    pub(crate) fn begin(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.frame = self
+            .readbacks
+            .iter()
+            .position(|readback| !readback.in_use)
+            .map(|free| (free, Vec::new()));
+        if self.frame.is_some() {
+            encoder.write_timestamp(&self.query_set, 0);
+        }
+    }
+    // Ends the pass called `label`, which began where the last one ended.
+    pub(crate) fn mark(&mut self, encoder: &mut wgpu::CommandEncoder, label: &str) {
+        if let Some((_, labels)) = &mut self.frame {
+            if labels.len() + 1 < MAX_TIMESTAMPS as usize {
+                labels.push(label.to_owned());
+                encoder.write_timestamp(&self.query_set, labels.len() as u32);
+            }
+        }
+    }
+    pub(crate) fn end(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let Some((which, labels)) = self.frame.take() else {
+            return;
+        };
+        let count = labels.len() as u32 + 1;
+        let size = count as u64 * std::mem::size_of::<u64>() as u64;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve, 0);
+        let readback = &mut self.readbacks[which];
+        encoder.copy_buffer_to_buffer(&self.resolve, 0, &readback.buffer, 0, size);
+        readback.labels = labels;
+        readback.in_use = true;
+        self.to_map = Some(which);
+    }
+    // After the frame's commands were submitted.
+    pub(crate) fn submitted(&mut self) {
+        let Some(which) = self.to_map.take() else {
+            return;
+        };
+        let readback = &self.readbacks[which];
+        let mapped = readback.mapped.clone();
+        let size = (readback.labels.len() as u64 + 1) * std::mem::size_of::<u64>() as u64;
+        readback
+            .buffer
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                mapped.store(result.is_ok(), Ordering::Release);
+            });
+    }
+    // The passes of the latest frame whose timestamps have come back.
+    pub(crate) fn collect(&mut self, gpu: &WGPU) -> &[GpuPassTime] {
+        gpu.device.poll(wgpu::Maintain::Poll);
+        for readback in &mut self.readbacks {
+            if !readback.mapped.swap(false, Ordering::Acquire) {
+                continue;
+            }
+            let size = (readback.labels.len() as u64 + 1) * std::mem::size_of::<u64>() as u64;
+            {
+                let data = readback.buffer.slice(..size).get_mapped_range();
+                let ticks: &[u64] = bytemuck::cast_slice(&data);
+                self.latest = ticks
+                    .windows(2)
+                    .zip(&readback.labels)
+                    .map(|(pair, label)| GpuPassTime {
+                        label: label.clone(),
+                        seconds: pair[1].saturating_sub(pair[0]) as f32 * self.period * 1e-9,
+                    })
+                    .collect();
+            }
+            readback.buffer.unmap();
+            readback.in_use = false;
+        }
+        &self.latest
+    }
+}
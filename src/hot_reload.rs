@@ -0,0 +1,60 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Seconds between looks at the watched files' modification times.
+const POLL_INTERVAL: f64 = 0.25;
+
+// Notices files changing on disk by comparing modification times, for reloading shaders and the
+// like while the game runs. Polling keeps it working the same everywhere without a notify
+// dependency; with a handful of files it costs next to nothing. Files that can't be read (on wasm,
+// all of them) just never change.
+pub(crate) struct FileWatcher<K> {
+    files: Vec<(K, PathBuf, Option<SystemTime>)>,
+    last_poll: f64,
+}
+
+impl<K> Default for FileWatcher<K> {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            last_poll: 0.0,
+        }
+    }
+}
+
+impl<K: Clone + PartialEq> FileWatcher<K> {
+    // Starts watching `path` for `key`, instead of whatever file `key` had before. Changes that
+    // happened before this call don't count.
+    pub(crate) fn watch(&mut self, key: K, path: PathBuf) {
+        self.unwatch(&key);
+        let modified = modified(&path);
+        self.files.push((key, path, modified));
+    }
+    pub(crate) fn unwatch(&mut self, key: &K) {
+        self.files.retain(|(watched, _, _)| watched != key);
+    }
+    // The files modified since the last call, with their keys. Only looks every POLL_INTERVAL.
+    pub(crate) fn changed(&mut self) -> Vec<(K, PathBuf)> {
+        let now = crate::time::now();
+        if self.files.is_empty() || now - self.last_poll < POLL_INTERVAL {
+            return Vec::new();
+        }
+        self.last_poll = now;
+        let mut changed = Vec::new();
+        for (key, path, last_modified) in &mut self.files {
+            let modified = modified(path);
+            // Editors that save by replacing the file can leave it missing for a moment.
+            if modified.is_some() && modified != *last_modified {
+                *last_modified = modified;
+                changed.push((key.clone(), path.clone()));
+            }
+        }
+        changed
+    }
+}
+
+fn modified(path: &std::path::Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
@@ -1,27 +1,88 @@
+use std::collections::HashMap;
 pub use winit::dpi::PhysicalPosition as MousePos;
+pub use winit::event::ModifiersState as Modifiers;
+pub use winit::event::MouseButton;
 pub use winit::event::VirtualKeyCode as Key;
-use winit::event::{ElementState, MouseButton};
+use winit::event::{ElementState, Ime, MouseScrollDelta, Touch};
+
+pub(crate) const KEY_COUNT: usize = 255;
+pub(crate) const MOUSE_BUTTONS: usize = 16;
 
 pub struct Input {
-    now_keys: Box<[bool]>,
-    prev_keys: Box<[bool]>,
-    now_mouse: Box<[bool]>,
-    prev_mouse: Box<[bool]>,
-    now_mouse_pos: MousePos<f64>,
+    pub(crate) now_keys: Box<[bool]>,
+    pub(crate) prev_keys: Box<[bool]>,
+    pub(crate) now_mouse: Box<[bool]>,
+    pub(crate) prev_mouse: Box<[bool]>,
+    pub(crate) now_mouse_pos: MousePos<f64>,
     prev_mouse_pos: MousePos<f64>,
+    pub(crate) scroll: ScrollDelta,
+    pub(crate) now_modifiers: Modifiers,
+    prev_modifiers: Modifiers,
+    pub(crate) touches: HashMap<u64, TouchPoint>,
+    // Touches that ended in the frame they started, which end next frame instead so Started
+    // isn't missed.
+    ending: Vec<u64>,
+    pub(crate) text: String,
+    preedit: Option<(String, Option<(usize, usize)>)>,
+    pub(crate) actions: crate::InputMap,
+    pub(crate) record_mode: crate::recording::RecordMode,
+    pub(crate) frame: u64,
+    pub(crate) press_history: crate::input_buffer::PressHistory,
+    #[cfg(feature = "gamepad")]
+    pub(crate) gamepads: crate::gamepad::Gamepads,
+}
+
+// Scroll accumulated over one frame. Mouse wheels report whole lines, while
+// trackpads (and browsers) report pixels, so both are kept.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScrollDelta {
+    pub lines: [f32; 2],
+    pub pixels: [f64; 2],
 }
 impl Default for Input {
     fn default() -> Self {
         Self {
-            now_keys: vec![false; 255].into_boxed_slice(),
-            prev_keys: vec![false; 255].into_boxed_slice(),
-            now_mouse: vec![false; 16].into_boxed_slice(),
-            prev_mouse: vec![false; 16].into_boxed_slice(),
+            now_keys: vec![false; KEY_COUNT].into_boxed_slice(),
+            prev_keys: vec![false; KEY_COUNT].into_boxed_slice(),
+            now_mouse: vec![false; MOUSE_BUTTONS].into_boxed_slice(),
+            prev_mouse: vec![false; MOUSE_BUTTONS].into_boxed_slice(),
             now_mouse_pos: MousePos { x: 0.0, y: 0.0 },
             prev_mouse_pos: MousePos { x: 0.0, y: 0.0 },
+            scroll: ScrollDelta::default(),
+            now_modifiers: Modifiers::empty(),
+            prev_modifiers: Modifiers::empty(),
+            touches: HashMap::new(),
+            ending: Vec::new(),
+            text: String::new(),
+            preedit: None,
+            actions: crate::InputMap::default(),
+            record_mode: crate::recording::RecordMode::Idle,
+            frame: 0,
+            press_history: Default::default(),
+            #[cfg(feature = "gamepad")]
+            gamepads: Default::default(),
         }
     }
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchPhase {
+    // The finger went down this frame.
+    Started,
+    // The finger is still down (it may or may not have moved).
+    Held,
+    // The finger lifted (or the touch was cancelled) this frame; it'll be gone next frame. A tap
+    // shorter than a frame is Started for one frame and Ended the next.
+    Ended,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TouchPoint {
+    pub id: u64,
+    pub phase: TouchPhase,
+    pub position: MousePos<f64>,
+    pub start_position: MousePos<f64>,
+}
+
 #[allow(dead_code)]
 impl Input {
     pub fn is_key_down(&self, kc: Key) -> bool {
@@ -36,15 +97,76 @@ impl Input {
     pub fn is_key_released(&self, kc: Key) -> bool {
         !self.now_keys[kc as usize] && self.prev_keys[kc as usize]
     }
+    pub(crate) fn key_state(&self, kc: Key, prev: bool) -> bool {
+        if prev {
+            self.prev_keys[kc as usize]
+        } else {
+            self.now_keys[kc as usize]
+        }
+    }
+    pub(crate) fn mouse_state(&self, mb: MouseButton, prev: bool) -> bool {
+        let mb = Self::mouse_button_to_usize(mb);
+        if prev {
+            self.prev_mouse[mb]
+        } else {
+            self.now_mouse[mb]
+        }
+    }
+    pub fn modifiers(&self) -> Modifiers {
+        self.now_modifiers
+    }
+    pub fn shift(&self) -> bool {
+        self.now_modifiers.shift()
+    }
+    pub fn ctrl(&self) -> bool {
+        self.now_modifiers.ctrl()
+    }
+    pub fn alt(&self) -> bool {
+        self.now_modifiers.alt()
+    }
+    pub fn logo(&self) -> bool {
+        self.now_modifiers.logo()
+    }
+    // Modifier keys are answered from the modifier state rather than key events (which some
+    // platforms don't send for modifiers), and either side counts: LControl means "a ctrl key".
+    fn chord_key_state(&self, kc: Key, prev: bool) -> bool {
+        let mods = if prev {
+            self.prev_modifiers
+        } else {
+            self.now_modifiers
+        };
+        match kc {
+            Key::LShift | Key::RShift => mods.shift(),
+            Key::LControl | Key::RControl => mods.ctrl(),
+            Key::LAlt | Key::RAlt => mods.alt(),
+            Key::LWin | Key::RWin => mods.logo(),
+            _ => self.key_state(kc, prev),
+        }
+    }
+    // True while every key in the chord is held, e.g. `&[Key::LControl, Key::S]`.
+    pub fn is_chord_down(&self, chord: &[Key]) -> bool {
+        !chord.is_empty() && chord.iter().all(|kc| self.chord_key_state(*kc, false))
+    }
+    // True on the frame the chord becomes complete, whichever key went down last.
+    pub fn is_chord_pressed(&self, chord: &[Key]) -> bool {
+        self.is_chord_down(chord) && !chord.iter().all(|kc| self.chord_key_state(*kc, true))
+    }
+    pub fn is_chord_released(&self, chord: &[Key]) -> bool {
+        !chord.is_empty()
+            && !self.is_chord_down(chord)
+            && chord.iter().all(|kc| self.chord_key_state(*kc, true))
+    }
     pub fn is_mouse_down(&self, button: MouseButton) -> bool {
         self.now_mouse[Self::mouse_button_to_usize(button)]
     }
     fn mouse_button_to_usize(button: MouseButton) -> usize {
+        // Extra buttons past what we track all share the last slot rather than
+        // indexing off the end of the array.
         match button {
             MouseButton::Left => 0,
             MouseButton::Right => 1,
             MouseButton::Middle => 2,
-            MouseButton::Other(n) => n as usize,
+            MouseButton::Other(n) => (n as usize).min(MOUSE_BUTTONS - 1),
         }
     }
     pub fn is_mouse_up(&self, mb: MouseButton) -> bool {
@@ -67,6 +189,40 @@ impl Input {
             y: self.now_mouse_pos.y - self.prev_mouse_pos.y,
         }
     }
+    pub fn scroll_delta(&self) -> ScrollDelta {
+        self.scroll
+    }
+    // All touches that are down, or that ended this frame.
+    pub fn touches(&self) -> impl Iterator<Item = &TouchPoint> {
+        self.touches.values()
+    }
+    pub fn touch(&self, id: u64) -> Option<&TouchPoint> {
+        self.touches.get(&id)
+    }
+    pub fn is_touching(&self) -> bool {
+        self.touches.values().any(|t| t.phase != TouchPhase::Ended)
+    }
+    // Text typed since last frame, with control characters (backspace, enter...) left out;
+    // check those with is_key_pressed instead.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+    // Text the IME is still composing, along with the cursor's byte range within it, if any.
+    pub fn preedit(&self) -> Option<(&str, Option<(usize, usize)>)> {
+        self.preedit
+            .as_ref()
+            .map(|(s, cursor)| (s.as_str(), *cursor))
+    }
+    // Lets go of every key, button, and touch, e.g. when the window loses focus and we'd never
+    // see the matching release events.
+    pub fn release_all(&mut self) {
+        self.now_keys.fill(false);
+        self.now_mouse.fill(false);
+        self.now_modifiers = Modifiers::empty();
+        for touch in self.touches.values_mut() {
+            touch.phase = TouchPhase::Ended;
+        }
+    }
     pub fn key_axis(&self, down: Key, up: Key) -> f32 {
         (if self.is_key_down(down) { -1.0 } else { 0.0 })
             + (if self.is_key_down(up) { 1.0 } else { 0.0 })
@@ -75,6 +231,39 @@ impl Input {
         self.prev_keys.copy_from_slice(&self.now_keys);
         self.prev_mouse.copy_from_slice(&self.now_mouse);
         self.prev_mouse_pos = self.now_mouse_pos;
+        self.prev_modifiers = self.now_modifiers;
+        self.frame += 1;
+        self.scroll = ScrollDelta::default();
+        self.text.clear();
+        self.touches.retain(|_, t| t.phase != TouchPhase::Ended);
+        for touch in self.touches.values_mut() {
+            touch.phase = if self.ending.contains(&touch.id) {
+                TouchPhase::Ended
+            } else {
+                TouchPhase::Held
+            };
+        }
+        self.ending.clear();
+        #[cfg(feature = "gamepad")]
+        self.gamepads.next_frame();
+    }
+    // Routes any input-related window event to the matching handler below.
+    pub fn handle_window_event(&mut self, event: &winit::event::WindowEvent) {
+        use winit::event::WindowEvent;
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => self.handle_key_event(*input),
+            WindowEvent::CursorMoved { position, .. } => self.handle_mouse_move(*position),
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.handle_mouse_button(*state, *button)
+            }
+            WindowEvent::MouseWheel { delta, .. } => self.handle_mouse_wheel(*delta),
+            WindowEvent::Touch(touch) => self.handle_touch(*touch),
+            WindowEvent::ModifiersChanged(modifiers) => self.handle_modifiers(*modifiers),
+            WindowEvent::ReceivedCharacter(c) => self.handle_char(*c),
+            WindowEvent::Ime(ime) => self.handle_ime(ime.clone()),
+            WindowEvent::Focused(false) => self.release_all(),
+            _ => {}
+        }
     }
     pub fn handle_key_event(&mut self, ke: winit::event::KeyboardInput) {
         if let winit::event::KeyboardInput {
@@ -107,4 +296,67 @@ impl Input {
     pub fn handle_mouse_move(&mut self, position: MousePos<f64>) {
         self.now_mouse_pos = position;
     }
+    pub fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        match delta {
+            MouseScrollDelta::LineDelta(x, y) => {
+                self.scroll.lines[0] += x;
+                self.scroll.lines[1] += y;
+            }
+            MouseScrollDelta::PixelDelta(pos) => {
+                self.scroll.pixels[0] += pos.x;
+                self.scroll.pixels[1] += pos.y;
+            }
+        }
+    }
+    pub fn handle_touch(&mut self, touch: Touch) {
+        use winit::event::TouchPhase as Phase;
+        match touch.phase {
+            Phase::Started => {
+                self.ending.retain(|id| *id != touch.id);
+                self.touches.insert(
+                    touch.id,
+                    TouchPoint {
+                        id: touch.id,
+                        phase: TouchPhase::Started,
+                        position: touch.location,
+                        start_position: touch.location,
+                    },
+                );
+            }
+            Phase::Moved => {
+                if let Some(t) = self.touches.get_mut(&touch.id) {
+                    t.position = touch.location;
+                }
+            }
+            Phase::Ended | Phase::Cancelled => {
+                if let Some(t) = self.touches.get_mut(&touch.id) {
+                    t.position = touch.location;
+                    // A quick tap can start and end between two frames.
+                    if t.phase == TouchPhase::Started {
+                        self.ending.push(touch.id);
+                    } else {
+                        t.phase = TouchPhase::Ended;
+                    }
+                }
+            }
+        }
+    }
+    pub fn handle_char(&mut self, c: char) {
+        if !c.is_control() {
+            self.text.push(c);
+        }
+    }
+    pub fn handle_ime(&mut self, ime: Ime) {
+        match ime {
+            Ime::Preedit(text, cursor) if !text.is_empty() => self.preedit = Some((text, cursor)),
+            Ime::Preedit(..) | Ime::Enabled | Ime::Disabled => self.preedit = None,
+            Ime::Commit(text) => {
+                self.preedit = None;
+                self.text.push_str(&text);
+            }
+        }
+    }
+    pub fn handle_modifiers(&mut self, modifiers: Modifiers) {
+        self.now_modifiers = modifiers;
+    }
 }
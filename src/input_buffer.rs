@@ -0,0 +1,133 @@
+use crate::{Binding, Input, Key};
+use std::collections::VecDeque;
+
+// How many presses we remember. Plenty for jump buffering and command inputs, which only ever
+// look back a second or so.
+const HISTORY_LEN: usize = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BufferedPress {
+    binding: Binding,
+    frame: u64,
+    consumed: bool,
+}
+
+// Press events stamped with the frame they happened on, oldest first.
+#[derive(Default)]
+pub(crate) struct PressHistory {
+    presses: VecDeque<BufferedPress>,
+}
+
+impl PressHistory {
+    fn push(&mut self, binding: Binding, frame: u64) {
+        if self.presses.len() == HISTORY_LEN {
+            self.presses.pop_front();
+        }
+        self.presses.push_back(BufferedPress {
+            binding,
+            frame,
+            consumed: false,
+        });
+    }
+}
+
+impl Input {
+    // Frames elapsed since the Engine started; bumped once per frame after Game::update.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Logs every key/button that went down this frame. Runs after recording playback so replays
+    // build the same history.
+    pub(crate) fn buffer_presses(&mut self) {
+        let frame = self.frame;
+        for i in 0..self.now_keys.len() {
+            if self.now_keys[i] && !self.prev_keys[i] {
+                if let Some(kc) = key_from_index(i) {
+                    self.press_history.push(Binding::Key(kc), frame);
+                }
+            }
+        }
+        for (i, mb) in [
+            crate::MouseButton::Left,
+            crate::MouseButton::Right,
+            crate::MouseButton::Middle,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if self.now_mouse[i] && !self.prev_mouse[i] {
+                self.press_history.push(Binding::Mouse(mb), frame);
+            }
+        }
+        #[cfg(feature = "gamepad")]
+        for button in self.gamepads.pressed_buttons() {
+            self.press_history
+                .push(Binding::GamepadButton(button), frame);
+        }
+    }
+
+    fn buffered(&self, binding: Binding, frames: u64) -> Option<usize> {
+        self.press_history
+            .presses
+            .iter()
+            .rposition(|p| !p.consumed && p.binding == binding && self.frame - p.frame < frames)
+    }
+    // Whether `binding` went down within the last `frames` frames (1 means just this frame).
+    // Presses taken with `consume_press` don't count.
+    pub fn was_pressed_within(&self, binding: impl Into<Binding>, frames: u64) -> bool {
+        self.buffered(binding.into(), frames).is_some()
+    }
+    // Like was_pressed_within, but marks the press as used so it can't trigger twice, which is
+    // what jump buffering wants.
+    pub fn consume_press(&mut self, binding: impl Into<Binding>, frames: u64) -> bool {
+        match self.buffered(binding.into(), frames) {
+            Some(i) => {
+                self.press_history.presses[i].consumed = true;
+                true
+            }
+            None => false,
+        }
+    }
+    // Whether any binding of `action` went down within the last `frames` frames.
+    pub fn was_action_pressed_within(&self, action: &str, frames: u64) -> bool {
+        self.actions
+            .bindings(action)
+            .iter()
+            .any(|b| self.buffered(*b, frames).is_some())
+    }
+    // Whether `sequence` was pressed in order (other presses in between are allowed) with the
+    // whole thing fitting in the last `frames` frames and the final press being this frame,
+    // e.g. a quarter-circle of Down, Right, then J.
+    pub fn was_sequence_pressed(&self, sequence: &[Binding], frames: u64) -> bool {
+        let mut remaining = sequence.iter().rev().peekable();
+        for press in self.press_history.presses.iter().rev() {
+            if self.frame - press.frame >= frames {
+                break;
+            }
+            match remaining.peek() {
+                Some(want) if **want == press.binding => {
+                    // The last input of the sequence has to be what completed it this frame.
+                    if remaining.len() == sequence.len() && press.frame != self.frame {
+                        return false;
+                    }
+                    remaining.next();
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        !sequence.is_empty() && remaining.peek().is_none()
+    }
+}
+
+// Input stores keys by their VirtualKeyCode discriminant; this goes back the other way.
+pub(crate) fn key_from_index(i: usize) -> Option<Key> {
+    if i <= Key::Cut as usize {
+        // SAFETY: VirtualKeyCode is a fieldless #[repr(u32)] enum whose discriminants run
+        // contiguously from 0 to Cut, its last variant.
+        Some(unsafe { std::mem::transmute::<u32, Key>(i as u32) })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{Input, Key, MouseButton};
+use std::collections::HashMap;
+
+#[cfg(feature = "gamepad")]
+use crate::{GamepadAxis, GamepadButton};
+
+// One physical input that can trigger an action.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+    #[cfg(feature = "gamepad")]
+    GamepadButton(GamepadButton),
+    // Counts as held while the axis is pushed past `threshold` (use a negative threshold for the
+    // negative direction, e.g. stick left is `GamepadAxis(LeftStickX, -0.5)`).
+    #[cfg(feature = "gamepad")]
+    GamepadAxis(GamepadAxis, f32),
+}
+
+impl From<Key> for Binding {
+    fn from(kc: Key) -> Self {
+        Binding::Key(kc)
+    }
+}
+impl From<MouseButton> for Binding {
+    fn from(mb: MouseButton) -> Self {
+        Binding::Mouse(mb)
+    }
+}
+#[cfg(feature = "gamepad")]
+impl From<GamepadButton> for Binding {
+    fn from(button: GamepadButton) -> Self {
+        Binding::GamepadButton(button)
+    }
+}
+
+// Something that produces a value in -1..1 for a named axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AxisSource {
+    // -1 while `negative` is held, 1 while `positive` is held, 0 for both or neither.
+    Keys {
+        negative: Key,
+        positive: Key,
+    },
+    #[cfg(feature = "gamepad")]
+    Gamepad(GamepadAxis),
+    // An analog trigger (or any pressure-sensitive button), reading 0..1.
+    #[cfg(feature = "gamepad")]
+    Trigger(GamepadButton),
+    #[cfg(feature = "gamepad")]
+    Buttons {
+        negative: GamepadButton,
+        positive: GamepadButton,
+    },
+}
+
+// Shapes the axis value after the dead zone, for finer control near the center of a stick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseCurve {
+    Linear,
+    Quadratic,
+    Cubic,
+    Power(f32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisBinding {
+    pub sources: Vec<AxisSource>,
+    // Raw values with a magnitude below this read as 0; the rest is rescaled to still reach 1.
+    pub dead_zone: f32,
+    pub curve: ResponseCurve,
+    pub invert: bool,
+}
+
+impl Default for AxisBinding {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            dead_zone: 0.15,
+            curve: ResponseCurve::Linear,
+            invert: false,
+        }
+    }
+}
+
+impl AxisBinding {
+    fn shape(&self, raw: f32) -> f32 {
+        let magnitude = raw.abs().min(1.0);
+        if magnitude <= self.dead_zone {
+            return 0.0;
+        }
+        let magnitude = (magnitude - self.dead_zone) / (1.0 - self.dead_zone);
+        let magnitude = match self.curve {
+            ResponseCurve::Linear => magnitude,
+            ResponseCurve::Quadratic => magnitude * magnitude,
+            ResponseCurve::Cubic => magnitude * magnitude * magnitude,
+            ResponseCurve::Power(p) => magnitude.powf(p),
+        };
+        let value = magnitude.copysign(raw);
+        if self.invert {
+            -value
+        } else {
+            value
+        }
+    }
+}
+
+// Named actions ("jump", "left") bound to any number of keys/buttons, so game code never has to
+// mention a particular key and players can rebind controls at runtime. Named axes ("move_x") work
+// the same way for analog values.
+#[derive(Clone, Debug, Default)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+    axes: HashMap<String, AxisBinding>,
+}
+
+impl InputMap {
+    pub fn bind(&mut self, action: &str, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        let bindings = self.actions.entry(action.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+    pub fn unbind(&mut self, action: &str, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+    // Replaces every binding of `action`, e.g. from a controls menu.
+    pub fn rebind(&mut self, action: &str, bindings: impl IntoIterator<Item = Binding>) {
+        self.actions
+            .insert(action.to_owned(), bindings.into_iter().collect());
+    }
+    pub fn clear(&mut self, action: &str) {
+        self.actions.remove(action);
+    }
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(String::as_str)
+    }
+    pub fn axes(&self) -> impl Iterator<Item = &str> {
+        self.axes.keys().map(String::as_str)
+    }
+
+    // Adds a source to `axis`, creating it with the default dead zone and a linear curve.
+    pub fn bind_axis(&mut self, axis: &str, source: AxisSource) {
+        let binding = self.axes.entry(axis.to_owned()).or_default();
+        if !binding.sources.contains(&source) {
+            binding.sources.push(source);
+        }
+    }
+    pub fn set_axis(&mut self, axis: &str, binding: AxisBinding) {
+        self.axes.insert(axis.to_owned(), binding);
+    }
+    pub fn axis_binding(&self, axis: &str) -> Option<&AxisBinding> {
+        self.axes.get(axis)
+    }
+    pub fn axis_binding_mut(&mut self, axis: &str) -> Option<&mut AxisBinding> {
+        self.axes.get_mut(axis)
+    }
+    pub fn clear_axis(&mut self, axis: &str) {
+        self.axes.remove(axis);
+    }
+}
+
+impl Input {
+    pub fn input_map(&self) -> &InputMap {
+        &self.actions
+    }
+    pub fn input_map_mut(&mut self) -> &mut InputMap {
+        &mut self.actions
+    }
+    // Shorthand for `input_map_mut().bind(..)`.
+    pub fn bind(&mut self, action: &str, binding: impl Into<Binding>) {
+        self.actions.bind(action, binding);
+    }
+    fn binding_state(&self, binding: &Binding, prev: bool) -> bool {
+        match *binding {
+            Binding::Key(kc) => self.key_state(kc, prev),
+            Binding::Mouse(mb) => self.mouse_state(mb, prev),
+            #[cfg(feature = "gamepad")]
+            Binding::GamepadButton(button) => self.gamepads.any_button(button, prev),
+            #[cfg(feature = "gamepad")]
+            Binding::GamepadAxis(axis, threshold) => {
+                let value = self.gamepads.any_axis(axis, prev);
+                if threshold < 0.0 {
+                    value <= threshold
+                } else {
+                    value >= threshold
+                }
+            }
+        }
+    }
+    fn action_state(&self, action: &str, prev: bool) -> bool {
+        self.actions
+            .bindings(action)
+            .iter()
+            .any(|b| self.binding_state(b, prev))
+    }
+    fn axis_source_value(&self, source: &AxisSource) -> f32 {
+        match *source {
+            AxisSource::Keys { negative, positive } => self.key_axis(negative, positive),
+            #[cfg(feature = "gamepad")]
+            AxisSource::Gamepad(axis) => self.gamepads.any_axis(axis, false),
+            #[cfg(feature = "gamepad")]
+            AxisSource::Trigger(button) => self.gamepads.any_button_value(button),
+            #[cfg(feature = "gamepad")]
+            AxisSource::Buttons { negative, positive } => {
+                (if self.gamepads.any_button(negative, false) {
+                    -1.0
+                } else {
+                    0.0
+                }) + (if self.gamepads.any_button(positive, false) {
+                    1.0
+                } else {
+                    0.0
+                })
+            }
+        }
+    }
+    // The value of a named axis in -1..1. When several sources are bound, whichever is pushed
+    // furthest wins, so keyboard and stick can both drive "move_x". Unknown axes read 0.
+    pub fn axis(&self, axis: &str) -> f32 {
+        let Some(binding) = self.actions.axis_binding(axis) else {
+            return 0.0;
+        };
+        let raw = binding
+            .sources
+            .iter()
+            .map(|s| self.axis_source_value(s))
+            .fold(
+                0.0,
+                |best: f32, v| if v.abs() > best.abs() { v } else { best },
+            );
+        binding.shape(raw)
+    }
+    // Unknown actions are never down, so a typo'd name fails quietly rather than crashing.
+    pub fn is_action_down(&self, action: &str) -> bool {
+        self.action_state(action, false)
+    }
+    pub fn is_action_up(&self, action: &str) -> bool {
+        !self.action_state(action, false)
+    }
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        self.action_state(action, false) && !self.action_state(action, true)
+    }
+    pub fn is_action_released(&self, action: &str) -> bool {
+        !self.action_state(action, false) && self.action_state(action, true)
+    }
+}
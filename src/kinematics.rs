@@ -0,0 +1,92 @@
+use crate::{SpriteHandle, SpriteRender, Vec2};
+use std::collections::BTreeMap;
+
+// How one sprite moves on its own, in world units per second (and per second squared).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Motion {
+    pub velocity: Vec2,
+    pub acceleration: Vec2,
+    // How much of Kinematics::gravity pulls on it: 0 for things that float, 1 for everything else.
+    pub gravity_scale: f32,
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Self {
+            velocity: Vec2::ZERO,
+            acceleration: Vec2::ZERO,
+            gravity_scale: 1.0,
+        }
+    }
+}
+
+// Velocities, accelerations and gravity for sprites that only need to move, not to bounce off
+// each other like under a physics engine. The Engine keeps one (Engine::kinematics) and moves the
+// sprites in it by their screen_region every fixed update, after Game::fixed_update. Sprites
+// without a Motion are left alone.
+#[derive(Clone, Debug, Default)]
+pub struct Kinematics {
+    // Added to every sprite's acceleration (times its gravity_scale), e.g. Vec2::new(0.0, -980.0).
+    pub gravity: Vec2,
+    // Ordered, so sprites always move in the same order.
+    bodies: BTreeMap<SpriteHandle, Motion>,
+}
+
+impl Kinematics {
+    pub fn insert(&mut self, sprite: SpriteHandle, motion: Motion) {
+        self.bodies.insert(sprite, motion);
+    }
+    pub fn remove(&mut self, sprite: SpriteHandle) -> Option<Motion> {
+        self.bodies.remove(&sprite)
+    }
+    pub fn get(&self, sprite: SpriteHandle) -> Option<&Motion> {
+        self.bodies.get(&sprite)
+    }
+    // Adds a Motion::default() for sprites that don't have one yet.
+    pub fn get_mut(&mut self, sprite: SpriteHandle) -> &mut Motion {
+        self.bodies.entry(sprite).or_default()
+    }
+    pub fn set_velocity(&mut self, sprite: SpriteHandle, velocity: impl Into<Vec2>) {
+        self.get_mut(sprite).velocity = velocity.into();
+    }
+    // Zero for sprites without a Motion.
+    pub fn velocity(&self, sprite: SpriteHandle) -> Vec2 {
+        self.get(sprite)
+            .map_or(Vec2::ZERO, |motion| motion.velocity)
+    }
+    pub fn set_acceleration(&mut self, sprite: SpriteHandle, acceleration: impl Into<Vec2>) {
+        self.get_mut(sprite).acceleration = acceleration.into();
+    }
+    pub fn set_gravity_scale(&mut self, sprite: SpriteHandle, scale: f32) {
+        self.get_mut(sprite).gravity_scale = scale;
+    }
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.bodies.clear();
+    }
+    // Moves every sprite with a Motion on by `dt` seconds (semi-implicit Euler: velocity first,
+    // then position). Sprites that don't exist (any more) are skipped, not forgotten.
+    pub fn integrate(&mut self, sprites: &mut SpriteRender, dt: f32) {
+        for (sprite, motion) in &mut self.bodies {
+            if sprite.group >= sprites.group_count()
+                || sprite.index >= sprites.get_sprites(sprite.group).len()
+            {
+                continue;
+            }
+            motion.velocity += (motion.acceleration + self.gravity * motion.gravity_scale) * dt;
+            if motion.velocity == Vec2::ZERO {
+                continue;
+            }
+            let region = &mut sprites.get_sprites_mut(sprite.group, sprite.index..sprite.index + 1)
+                [0]
+            .screen_region;
+            region[0] += motion.velocity.x * dt;
+            region[1] += motion.velocity.y * dt;
+        }
+    }
+}
@@ -1,26 +1,216 @@
-use core::ops::Range;
-use image::{buffer, Pixel};
-use imageproc::drawing::{Blend, Canvas};
-use std::borrow::Cow;
+// Enters a tracing span for the rest of the enclosing block, with the tracing feature on.
+macro_rules! trace_span {
+    ($name:literal) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($name).entered();
+    };
+}
 
-use winit::{
-    dpi::PhysicalSize,
-    event::{Event, WindowEvent},
-    event_loop::{self, ControlFlow, EventLoop},
-    window::Window,
-};
+mod alpha_mask;
+mod animated_image;
+mod animation;
+mod asset_store;
+mod assets;
+#[cfg(feature = "audio")]
+mod audio;
+#[cfg(feature = "audio")]
+mod audio_clip;
+mod bloom;
+#[cfg(feature = "clipboard")]
+mod clipboard;
+mod collision;
+mod color;
+#[cfg(feature = "ktx2")]
+mod compressed;
+mod config;
+#[cfg(feature = "text")]
+mod console;
+mod debug_draw;
+mod debug_overlay;
+#[cfg(feature = "text")]
+mod dynamic_text;
+#[cfg(feature = "ecs")]
+mod ecs;
+mod embed;
+mod error;
+mod events;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+#[cfg(feature = "glam")]
+mod glam_interop;
 mod gpu;
+mod gpu_cull;
+mod gpu_timer;
+mod hot_reload;
 mod input;
+mod input_buffer;
+mod input_map;
+mod kinematics;
+mod loader;
+mod manifest;
+mod math;
+mod memory;
+mod mipmap;
+mod pak;
+mod pipeline_cache;
+mod postprocess;
+pub mod prelude;
+mod quadtree;
+mod raycast;
+mod recording;
+mod rng;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod sequence;
+#[cfg(feature = "shaping")]
+mod shaping;
+mod snapshot;
+mod spatial_hash;
 mod sprite;
-use sprite::SpriteRender;
-pub use sprite::{GPUCamera, GPUSprite};
+mod sprite_builder;
+#[cfg(feature = "serde")]
+mod sprite_state;
+mod sprite_tracking;
+mod stats;
+#[cfg(feature = "text")]
+mod text;
+mod time;
+mod triggers;
+mod ui;
+mod upload;
+#[cfg(target_arch = "wasm32")]
+mod web;
+mod windows;
+pub use alpha_mask::{pixels_overlap, AlphaMask};
+pub use animation::{AnimationClip, AnimationEvent, AnimationFrame, Animator, FrameEvent};
+pub use asset_store::Assets;
+#[cfg(feature = "audio")]
+pub use audio::{Audio, Bus, MusicOptions, SoundId, SoundOptions};
+#[cfg(feature = "audio")]
+pub use audio_clip::AudioClip;
+#[cfg(feature = "clipboard")]
+pub use clipboard::ClipboardText;
+pub use collision::{
+    collisions_between, collisions_within, sprites_overlapping, sweep, sweep_grid, sweep_rects,
+    Aabb, Contact,
+};
+pub use color::Color;
+#[cfg(feature = "text")]
+pub use console::Console;
+pub use debug_draw::DebugDraw;
+#[cfg(feature = "text")]
+pub use dynamic_text::DynamicText;
+#[cfg(feature = "ecs")]
+pub use ecs::{SpriteRef, Transform, Velocity};
+pub use embed::embed_assets_dir;
+pub use events::{ActionEvent, ActionPhase, EventReader, Events};
+#[cfg(feature = "gamepad")]
+pub use gamepad::{GamepadAxis, GamepadButton, GamepadEvent, GamepadId};
+pub use input::{
+    Input, Key, Modifiers, MouseButton, MousePos, ScrollDelta, TouchPhase, TouchPoint,
+};
+pub use input_map::{AxisBinding, AxisSource, Binding, InputMap, ResponseCurve};
+pub use kinematics::{Kinematics, Motion};
+pub use loader::{AssetId, AssetLoader};
+pub use manifest::{AssetKind, AssetManifest, ManifestEntry};
+pub use math::{Rect, Transform2D, Vec2};
+#[cfg(feature = "text")]
+pub use memory::AtlasUsage;
+pub use memory::{MemoryReport, TextureMemory};
+pub use pak::{pack_dir, PakArchive};
+pub use postprocess::{BloomOptions, CrtOptions, OverlayOptions, PassId, PostProcess};
+pub use quadtree::Quadtree;
+pub use raycast::{raycast_grid, raycast_rect, raycast_sprites, RayHit};
+pub use recording::InputRecording;
+pub use rng::Rng;
+#[cfg(feature = "scripting")]
+pub use scripting::{ScriptId, Scripts};
+pub use sequence::{Sequence, SequenceId, Sequencer};
+pub use snapshot::EngineSnapshot;
+pub use spatial_hash::SpatialHash;
+pub use sprite::{
+    BlendMode, DebugView, GPUCamera, GPUSprite, SamplerOptions, ShaderId, SpriteHandle,
+    SpriteRender, SpriteShader,
+};
+pub use sprite_builder::SpriteBuilder;
+#[cfg(feature = "serde")]
+pub use sprite_state::{GroupState, SpriteState};
+pub use sprite_tracking::{SpriteMut, TrackedSprites};
+pub use stats::{FrameStats, GpuPassTime};
+#[cfg(feature = "text")]
+pub use text::{
+    FontId, GlyphAtlas, HAlign, LineMetrics, RichText, TextEffect, TextLayout, TextMetrics,
+    TextSpan, VAlign,
+};
+pub use triggers::{TriggerArea, TriggerEvent, TriggerPhase, Triggers};
+pub use ui::{Anchor, Margins, UiLayer, UiLayout};
+pub use upload::{UploadId, UploadScheduler, DEFAULT_UPLOAD_BUDGET};
+pub use windows::{SecondaryWindow, SecondaryWindowId};
 
-pub use gpu::WGPU;
+pub use config::{EngineConfig, FrameLimiter, FullscreenMode, LoopMode, WindowConfig};
+#[cfg(feature = "audio")]
+pub use error::AudioError;
+#[cfg(feature = "manifest")]
+pub use error::ManifestError;
+#[cfg(feature = "scripting")]
+pub use error::ScriptError;
+pub use error::{EngineError, FontError, GraphError, ShaderError, SpriteError, TextureError};
+pub use gpu::{AdapterSelection, GpuOptions, GraphTarget, PassContext, RenderGraph, WGPU};
 mod engine;
 pub use engine::Engine;
 
+// Re-exported so games can use the exact wgpu/winit versions the engine is built on.
+pub use async_trait;
+#[cfg(feature = "ecs")]
+pub use bevy_ecs;
+#[cfg(feature = "glam")]
+pub use glam;
+#[cfg(feature = "scripting")]
+pub use rhai;
+pub use wgpu;
+pub use winit;
+
 #[async_trait::async_trait]
 pub trait Game {
     async fn init(&mut self, engine: &mut Engine);
     fn update(&mut self, engine: &mut Engine);
+    // Called every EngineConfig::fixed_timestep seconds of game time, as many times as fit in
+    // the last frame, before update. Engine::kinematics moves its sprites right after each call.
+    fn fixed_update(&mut self, _engine: &mut Engine) {}
+    // Called before update for each of the frame's events from Engine::triggers.
+    fn trigger(&mut self, _engine: &mut Engine, _event: &TriggerEvent) {}
+    // Called before update for each clip event Engine::animator reached this frame.
+    fn animation_event(&mut self, _engine: &mut Engine, _event: &AnimationEvent) {}
+    // Records passes of the game's own into the frame's command encoder, which goes to the GPU
+    // in the same submission as everything else. Runs as the RenderGraph's "game" pass: over the
+    // finished frame, after post-processing and before debug draw, unless the graph says
+    // otherwise.
+    fn render(&mut self, _ctx: &mut PassContext) {}
+    // Called when the window gains or loses keyboard focus (e.g. alt-tab).
+    fn focus_changed(&mut self, _engine: &mut Engine, _focused: bool) {}
+    // Called after the surface has been resized, with the new size in physical pixels.
+    fn resized(&mut self, _engine: &mut Engine, _new_size: winit::dpi::PhysicalSize<u32>) {}
+    // Called once as the Engine shuts down, whether from the window's close button or
+    // Engine::request_exit. A good place to save.
+    fn on_exit(&mut self, _engine: &mut Engine) {}
+    // Called after the GPU device was lost and replaced. Sprite groups keep their sprites and
+    // cameras but lose their textures, so reload them and hand them back with
+    // SpriteRender::set_group_texture.
+    fn device_recovered(&mut self, _engine: &mut Engine) {}
+    // Sees every winit event before the Engine handles it, for anything the Engine doesn't
+    // expose itself (dropped files, device events, ...).
+    fn handle_event(&mut self, _engine: &mut Engine, _event: &winit::event::Event<()>) {}
+    // Called when a mobile app goes to the background (and back). Nothing is drawn in between,
+    // but updates keep running unless you stop them, e.g. by pausing your game here.
+    fn suspended(&mut self, _engine: &mut Engine) {}
+    fn resumed(&mut self, _engine: &mut Engine) {}
+    // Called right before each extra window (see Engine::open_window) is drawn; update its
+    // sprites through engine.secondary_window_mut(window).
+    fn update_window(&mut self, _engine: &mut Engine, _window: SecondaryWindowId) {}
+    // Called after an extra window was closed by the user or Engine::close_window.
+    fn window_closed(&mut self, _engine: &mut Engine, _window: SecondaryWindowId) {}
+    // Called before update for each command typed into the console (see Engine::enable_console)
+    // that the game registered with Console::register.
+    #[cfg(feature = "text")]
+    fn console_command(&mut self, _engine: &mut Engine, _command: &str, _args: &[String]) {}
 }
@@ -0,0 +1,251 @@
+use crate::TextureError;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+// Most files loaded at once on native; each gets a thread while there's work.
+#[cfg(not(target_arch = "wasm32"))]
+const WORKERS: usize = 4;
+
+// One of the files queued on an AssetLoader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(usize);
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Bytes,
+    Image,
+    #[cfg(feature = "audio")]
+    Sound,
+}
+
+enum Outcome {
+    Bytes(io::Result<Vec<u8>>),
+    Image(Result<image::RgbaImage, TextureError>),
+    #[cfg(feature = "audio")]
+    Sound(Result<crate::AudioClip, crate::AudioError>),
+}
+
+type Job = (usize, PathBuf, Kind);
+
+// Loads a batch of files in the background, reading and decoding them on other threads (on the
+// web, as futures between frames), so the game can draw a loading screen while it waits instead
+// of holding up its first frame in Game::init. Queue everything, then call poll every frame until
+// it's done and take what was loaded:
+//
+//     let level = loader.queue_bytes("assets/level1.txt");
+//     let tiles = loader.queue_image("assets/tiles.png");
+//     ...
+//     let progress = loader.poll(); // in update: draw a bar this full
+//     if loader.is_done() {
+//         let (tiles, _) = loader.take_texture(tiles, &engine.gpu, Some("tiles")).unwrap()?;
+//     }
+//
+// Textures are made on the GPU when they're taken, since that has to happen on the main thread.
+pub struct AssetLoader {
+    paths: Vec<PathBuf>,
+    outcomes: Vec<Option<Outcome>>,
+    loaded: usize,
+    sender: mpsc::Sender<(usize, Outcome)>,
+    receiver: mpsc::Receiver<(usize, Outcome)>,
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: std::sync::Arc<std::sync::Mutex<Jobs>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct Jobs {
+    queue: std::collections::VecDeque<Job>,
+    // Threads still taking jobs off the queue.
+    workers: usize,
+}
+
+impl Default for AssetLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AssetLoader {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            paths: Vec::new(),
+            outcomes: Vec::new(),
+            loaded: 0,
+            sender,
+            receiver,
+            #[cfg(not(target_arch = "wasm32"))]
+            jobs: Default::default(),
+        }
+    }
+    // A file to read as it is, e.g. a level.
+    pub fn queue_bytes(&mut self, path: impl AsRef<Path>) -> AssetId {
+        self.queue(path.as_ref(), Kind::Bytes)
+    }
+    // An image to decode, for take_texture.
+    pub fn queue_image(&mut self, path: impl AsRef<Path>) -> AssetId {
+        self.queue(path.as_ref(), Kind::Image)
+    }
+    // A sound to decode, for take_sound.
+    #[cfg(feature = "audio")]
+    pub fn queue_sound(&mut self, path: impl AsRef<Path>) -> AssetId {
+        self.queue(path.as_ref(), Kind::Sound)
+    }
+    fn queue(&mut self, path: &Path, kind: Kind) -> AssetId {
+        let index = self.paths.len();
+        self.paths.push(path.to_owned());
+        self.outcomes.push(None);
+        let job = (index, path.to_owned(), kind);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.queue.push_back(job);
+            if jobs.workers < WORKERS {
+                jobs.workers += 1;
+                let (jobs, sender) = (self.jobs.clone(), self.sender.clone());
+                std::thread::spawn(move || work(&jobs, &sender));
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let sender = self.sender.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                let (index, path, kind) = job;
+                let _ = sender.send((index, load(&path, kind).await));
+            });
+        }
+        AssetId(index)
+    }
+
+    // Collects whatever has finished loading and returns how much of the queue has, from 0 to 1
+    // (1 when nothing is queued).
+    pub fn poll(&mut self) -> f32 {
+        while let Ok((index, outcome)) = self.receiver.try_recv() {
+            self.outcomes[index] = Some(outcome);
+            self.loaded += 1;
+        }
+        self.progress()
+    }
+    // As of the last poll.
+    pub fn progress(&self) -> f32 {
+        if self.paths.is_empty() {
+            1.0
+        } else {
+            self.loaded as f32 / self.paths.len() as f32
+        }
+    }
+    // Whether everything queued had finished loading (or failed to) as of the last poll.
+    pub fn is_done(&self) -> bool {
+        self.loaded == self.paths.len()
+    }
+    pub fn loaded_count(&self) -> usize {
+        self.loaded
+    }
+    // Everything ever queued, loaded or not.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+    pub fn path(&self, id: AssetId) -> &Path {
+        &self.paths[id.0]
+    }
+
+    // The file queued with queue_bytes, once it's loaded. Each asset can be taken once; None if
+    // it isn't loaded (yet), was queued as something else, or was already taken.
+    pub fn take_bytes(&mut self, id: AssetId) -> Option<io::Result<Vec<u8>>> {
+        match self.outcomes[id.0].take()? {
+            Outcome::Bytes(bytes) => Some(bytes),
+            other => {
+                self.outcomes[id.0] = Some(other);
+                None
+            }
+        }
+    }
+    // The image queued with queue_image, made into a texture like WGPU::load_texture would.
+    pub fn take_texture(
+        &mut self,
+        id: AssetId,
+        gpu: &crate::WGPU,
+        label: Option<&str>,
+    ) -> Option<Result<(wgpu::Texture, image::RgbaImage), TextureError>> {
+        match self.outcomes[id.0].take()? {
+            Outcome::Image(img) => Some(img.and_then(|img| {
+                let texture = gpu.texture_for_image(&img, &self.paths[id.0], label)?;
+                Ok((texture, img))
+            })),
+            other => {
+                self.outcomes[id.0] = Some(other);
+                None
+            }
+        }
+    }
+    // The sound queued with queue_sound, ready for Audio::play_clip.
+    #[cfg(feature = "audio")]
+    pub fn take_sound(
+        &mut self,
+        id: AssetId,
+    ) -> Option<Result<crate::AudioClip, crate::AudioError>> {
+        match self.outcomes[id.0].take()? {
+            Outcome::Sound(clip) => Some(clip),
+            other => {
+                self.outcomes[id.0] = Some(other);
+                None
+            }
+        }
+    }
+}
+
+// A worker thread: loads jobs until the queue runs dry.
+#[cfg(not(target_arch = "wasm32"))]
+fn work(jobs: &std::sync::Mutex<Jobs>, sender: &mpsc::Sender<(usize, Outcome)>) {
+    loop {
+        let (index, path, kind) = {
+            let mut jobs = jobs.lock().unwrap();
+            match jobs.queue.pop_front() {
+                Some(job) => job,
+                None => {
+                    // Checked under the same lock queue takes, so no job is left without a worker.
+                    jobs.workers -= 1;
+                    return;
+                }
+            }
+        };
+        let outcome = pollster::block_on(load(&path, kind));
+        if sender.send((index, outcome)).is_err() {
+            // The loader is gone.
+            jobs.lock().unwrap().workers -= 1;
+            return;
+        }
+    }
+}
+
+async fn load(path: &Path, kind: Kind) -> Outcome {
+    let bytes = crate::assets::read_bytes(path).await;
+    match kind {
+        Kind::Bytes => Outcome::Bytes(bytes),
+        Kind::Image => Outcome::Image(
+            bytes
+                .map_err(|source| TextureError::Io {
+                    path: path.to_owned(),
+                    source,
+                })
+                .and_then(|bytes| {
+                    crate::gpu::decode_image(&bytes, path)
+                        .map(|img| img.to_rgba8())
+                        .map_err(|e| TextureError::from_image(path, e))
+                }),
+        ),
+        #[cfg(feature = "audio")]
+        Kind::Sound => Outcome::Sound(
+            bytes
+                .map_err(|source| crate::AudioError::Io {
+                    path: path.to_owned(),
+                    source,
+                })
+                .and_then(|bytes| crate::AudioClip::decode(bytes.into(), path)),
+        ),
+    }
+}
@@ -0,0 +1,130 @@
+#[cfg(feature = "manifest")]
+use std::path::Path;
+use std::path::PathBuf;
+
+// What an asset in a manifest is loaded as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "manifest", derive(serde::Deserialize))]
+#[cfg_attr(feature = "manifest", serde(rename_all = "lowercase"))]
+pub enum AssetKind {
+    // A file kept as it is, e.g. a level.
+    Bytes,
+    Texture,
+    // Decoded into memory, like Audio::load_sound.
+    #[cfg(feature = "audio")]
+    Sound,
+    // Kept compressed, like Audio::load_music.
+    #[cfg(feature = "audio")]
+    Music,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "manifest", derive(serde::Deserialize))]
+pub struct ManifestEntry {
+    // What the game looks it up by once it's loaded.
+    pub name: String,
+    #[cfg_attr(feature = "manifest", serde(rename = "type"))]
+    pub kind: AssetKind,
+    // Found like any other asset path: mounts, then the asset root.
+    pub path: PathBuf,
+    // Which batch it's preloaded with, e.g. a level's name; None for the ones loaded at startup.
+    #[cfg_attr(feature = "manifest", serde(default))]
+    pub bundle: Option<String>,
+}
+
+// A list of a game's assets by name, for Assets::preload to load as a batch. Written by hand
+// with add, or (with the manifest feature) read from a file like:
+//
+//     [[assets]]
+//     name = "player"
+//     type = "texture"
+//     path = "sprites/player.png"
+//
+//     [[assets]]
+//     name = "boss"
+//     type = "texture"
+//     path = "sprites/boss.png"
+//     bundle = "castle"
+//
+// or the same in JSON: {"assets": [{"name": "player", "type": "texture", ...}]}.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "manifest", derive(serde::Deserialize))]
+pub struct AssetManifest {
+    #[cfg_attr(feature = "manifest", serde(default))]
+    pub assets: Vec<ManifestEntry>,
+}
+
+impl AssetManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add(&mut self, name: impl Into<String>, kind: AssetKind, path: impl Into<PathBuf>) {
+        self.assets.push(ManifestEntry {
+            name: name.into(),
+            kind,
+            path: path.into(),
+            bundle: None,
+        });
+    }
+    // Adds to a bundle instead of the startup set.
+    pub fn add_to_bundle(
+        &mut self,
+        bundle: impl Into<String>,
+        name: impl Into<String>,
+        kind: AssetKind,
+        path: impl Into<PathBuf>,
+    ) {
+        self.add(name, kind, path);
+        self.assets.last_mut().unwrap().bundle = Some(bundle.into());
+    }
+    pub fn get(&self, name: &str) -> Option<&ManifestEntry> {
+        self.assets.iter().find(|entry| entry.name == name)
+    }
+    // The entries in `bundle`, or the startup set for None.
+    pub fn bundle<'a>(
+        &'a self,
+        bundle: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a ManifestEntry> {
+        self.assets
+            .iter()
+            .filter(move |entry| entry.bundle.as_deref() == bundle)
+    }
+
+    // Reads a manifest the way Engine::load_bytes reads any asset. It's JSON if the file name
+    // ends in .json and TOML otherwise.
+    #[cfg(feature = "manifest")]
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, crate::ManifestError> {
+        let path = path.as_ref();
+        let bytes =
+            crate::assets::read_bytes(path)
+                .await
+                .map_err(|source| crate::ManifestError::Io {
+                    path: path.to_owned(),
+                    source,
+                })?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            Self::from_json(&bytes, path)
+        } else {
+            let text = std::str::from_utf8(&bytes).map_err(|e| crate::ManifestError::Io {
+                path: path.to_owned(),
+                source: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            })?;
+            Self::from_toml(text, path)
+        }
+    }
+    // Errors name the manifest by `path`.
+    #[cfg(feature = "manifest")]
+    pub fn from_toml(text: &str, path: impl AsRef<Path>) -> Result<Self, crate::ManifestError> {
+        toml::from_str(text).map_err(|source| crate::ManifestError::Toml {
+            path: path.as_ref().to_owned(),
+            source,
+        })
+    }
+    #[cfg(feature = "manifest")]
+    pub fn from_json(bytes: &[u8], path: impl AsRef<Path>) -> Result<Self, crate::ManifestError> {
+        serde_json::from_slice(bytes).map_err(|source| crate::ManifestError::Json {
+            path: path.as_ref().to_owned(),
+            source,
+        })
+    }
+}
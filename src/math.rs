@@ -0,0 +1,422 @@
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
+
+// A point or a direction in world space. Anything taking one also takes an [x, y] array (or an
+// (x, y) pair), and [0]/[1] index it like one, so code written against arrays keeps working.
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Self = Self::new(0.0, 0.0);
+    pub const ONE: Self = Self::new(1.0, 1.0);
+    pub const X: Self = Self::new(1.0, 0.0);
+    pub const Y: Self = Self::new(0.0, 1.0);
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+    pub const fn splat(v: f32) -> Self {
+        Self::new(v, v)
+    }
+    pub fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+    // The z of the 3D cross product: positive when `other` is counter-clockwise from this.
+    pub fn cross(self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+    pub fn length(self) -> f32 {
+        self.length_squared().sqrt()
+    }
+    pub fn length_squared(self) -> f32 {
+        self.dot(self)
+    }
+    pub fn distance(self, other: Vec2) -> f32 {
+        (other - self).length()
+    }
+    // The same direction at length 1, or zero for a zero vector rather than NaNs.
+    pub fn normalize_or_zero(self) -> Vec2 {
+        let length = self.length();
+        if length > 0.0 {
+            self / length
+        } else {
+            Vec2::ZERO
+        }
+    }
+    // Turned a quarter turn counter-clockwise.
+    pub fn perp(self) -> Vec2 {
+        Vec2::new(-self.y, self.x)
+    }
+    // Turned `angle` radians counter-clockwise.
+    pub fn rotate(self, angle: f32) -> Vec2 {
+        let (sin, cos) = angle.sin_cos();
+        Vec2::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+    // `t` of the way from this to `other`.
+    pub fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        self + (other - self) * t
+    }
+    pub fn min(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x.min(other.x), self.y.min(other.y))
+    }
+    pub fn max(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x.max(other.x), self.y.max(other.y))
+    }
+    pub fn abs(self) -> Vec2 {
+        Vec2::new(self.x.abs(), self.y.abs())
+    }
+    pub fn to_array(self) -> [f32; 2] {
+        [self.x, self.y]
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<(f32, f32)> for Vec2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        v.to_array()
+    }
+}
+
+impl Index<usize> for Vec2 {
+    type Output = f32;
+    fn index(&self, axis: usize) -> &f32 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("Vec2 has no axis {axis}"),
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec2 {
+    fn index_mut(&mut self, axis: usize) -> &mut f32 {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("Vec2 has no axis {axis}"),
+        }
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+    fn add(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+    fn sub(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+// Component by component, for scaling by a size.
+impl Mul for Vec2 {
+    type Output = Vec2;
+    fn mul(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x * other.x, self.y * other.y)
+    }
+}
+
+impl Div for Vec2 {
+    type Output = Vec2;
+    fn div(self, other: Vec2) -> Vec2 {
+        Vec2::new(self.x / other.x, self.y / other.y)
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+    fn mul(self, s: f32) -> Vec2 {
+        Vec2::new(self.x * s, self.y * s)
+    }
+}
+
+impl Mul<Vec2> for f32 {
+    type Output = Vec2;
+    fn mul(self, v: Vec2) -> Vec2 {
+        v * self
+    }
+}
+
+impl Div<f32> for Vec2 {
+    type Output = Vec2;
+    fn div(self, s: f32) -> Vec2 {
+        Vec2::new(self.x / s, self.y / s)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+    fn neg(self) -> Vec2 {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Vec2) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Vec2) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign<f32> for Vec2 {
+    fn mul_assign(&mut self, s: f32) {
+        *self = *self * s;
+    }
+}
+
+impl DivAssign<f32> for Vec2 {
+    fn div_assign(&mut self, s: f32) {
+        *self = *self / s;
+    }
+}
+
+// An axis-aligned rectangle in world space: (x, y) is the bottom-left corner, like
+// GPUSprite::screen_region, and width and height are never negative. Anything taking one also
+// takes an [x, y, width, height] array.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self::from_region([x, y, width, height])
+    }
+    // From its bottom-left corner and size.
+    pub fn from_position_size(position: impl Into<Vec2>, size: impl Into<Vec2>) -> Self {
+        let (position, size) = (position.into(), size.into());
+        Self::new(position.x, position.y, size.x, size.y)
+    }
+    // The smallest rectangle around both points, whichever corners they are.
+    pub fn from_corners(a: impl Into<Vec2>, b: impl Into<Vec2>) -> Self {
+        let (a, b) = (a.into(), b.into());
+        Self::from_position_size(a.min(b), (b - a).abs())
+    }
+    // From an [x, y, width, height] such as screen_region. Negative sizes (flipped sprites) run
+    // the other way from x and y, and are turned around.
+    pub fn from_region(region: [f32; 4]) -> Self {
+        let [x, y, w, h] = region;
+        Self {
+            x: x.min(x + w),
+            y: y.min(y + h),
+            width: w.abs(),
+            height: h.abs(),
+        }
+    }
+    pub fn region(&self) -> [f32; 4] {
+        [self.x, self.y, self.width, self.height]
+    }
+    // The bottom-left corner.
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+    pub fn top(&self) -> f32 {
+        self.y + self.height
+    }
+    pub fn center(&self) -> Vec2 {
+        self.position() + self.size() / 2.0
+    }
+    // Moved by `offset`, the same size.
+    pub fn translate(&self, offset: impl Into<Vec2>) -> Rect {
+        let offset = offset.into();
+        Rect {
+            x: self.x + offset.x,
+            y: self.y + offset.y,
+            ..*self
+        }
+    }
+    // Grown by `amount` on every side (shrunk, for a negative amount, down to nothing).
+    pub fn inflate(&self, amount: f32) -> Rect {
+        let width = (self.width + amount * 2.0).max(0.0);
+        let height = (self.height + amount * 2.0).max(0.0);
+        let center = self.center();
+        Rect {
+            x: center.x - width / 2.0,
+            y: center.y - height / 2.0,
+            width,
+            height,
+        }
+    }
+    // Points on the left and bottom edges are inside; ones on the right and top edges aren't.
+    pub fn contains(&self, point: impl Into<Vec2>) -> bool {
+        let Vec2 { x: px, y: py } = point.into();
+        px >= self.x && px < self.right() && py >= self.y && py < self.top()
+    }
+    // Whether `other` is entirely inside this one, edges included.
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.right() <= self.right()
+            && other.top() <= self.top()
+    }
+    // Rectangles that only touch along an edge don't overlap.
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.top()
+            && other.y < self.top()
+    }
+    // The part both cover, if they overlap.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.overlaps(other) {
+            return None;
+        }
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        Some(Rect {
+            x,
+            y,
+            width: self.right().min(other.right()) - x,
+            height: self.top().min(other.top()) - y,
+        })
+    }
+    // The smallest rectangle covering both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        Rect {
+            x,
+            y,
+            width: self.right().max(other.right()) - x,
+            height: self.top().max(other.top()) - y,
+        }
+    }
+}
+
+impl From<[f32; 4]> for Rect {
+    fn from(region: [f32; 4]) -> Self {
+        Self::from_region(region)
+    }
+}
+
+impl From<Rect> for [f32; 4] {
+    fn from(rect: Rect) -> Self {
+        rect.region()
+    }
+}
+
+// Moves, turns and scales points: scaled first, then rotated (counter-clockwise, in radians)
+// about the origin, then moved by translation. For placing things relative to a parent (a gun on
+// a turret, particles around an emitter) and working out where they end up:
+//
+//     let turret = Transform2D::from_translation([200.0, 80.0]).with_rotation(aim);
+//     let muzzle = turret.transform_point([24.0, 0.0]);
+//     let gun = turret * Transform2D::from_translation([24.0, 0.0]);
+//
+// Sprites are drawn upright, so the place to put a sprite moved by one is its bounds:
+// sprite.set_bounds(transform.bounds(local)).
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform2D {
+    pub translation: Vec2,
+    pub rotation: f32,
+    pub scale: Vec2,
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform2D {
+    pub const IDENTITY: Self = Self {
+        translation: Vec2::ZERO,
+        rotation: 0.0,
+        scale: Vec2::ONE,
+    };
+
+    pub fn from_translation(translation: impl Into<Vec2>) -> Self {
+        Self {
+            translation: translation.into(),
+            ..Self::IDENTITY
+        }
+    }
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+    pub fn with_scale(mut self, scale: impl Into<Vec2>) -> Self {
+        self.scale = scale.into();
+        self
+    }
+    pub fn transform_point(&self, point: impl Into<Vec2>) -> Vec2 {
+        self.transform_vector(point) + self.translation
+    }
+    // Like transform_point without the translation, for directions and velocities.
+    pub fn transform_vector(&self, vector: impl Into<Vec2>) -> Vec2 {
+        (vector.into() * self.scale).rotate(self.rotation)
+    }
+    // The transform that undoes this one. Only exact without rotation or with even scaling, as
+    // scaling unevenly after a rotation makes a shear this type can't hold.
+    pub fn inverse(&self) -> Transform2D {
+        let scale = Vec2::ONE / self.scale;
+        Transform2D {
+            translation: (-self.translation).rotate(-self.rotation) * scale,
+            rotation: -self.rotation,
+            scale,
+        }
+    }
+    // The upright rectangle around `rect` once it's transformed.
+    pub fn bounds(&self, rect: &Rect) -> Rect {
+        let corners = [
+            self.transform_point(rect.position()),
+            self.transform_point([rect.right(), rect.y]),
+            self.transform_point([rect.x, rect.top()]),
+            self.transform_point([rect.right(), rect.top()]),
+        ];
+        let (min, max) = corners[1..]
+            .iter()
+            .fold((corners[0], corners[0]), |(min, max), &corner| {
+                (min.min(corner), max.max(corner))
+            });
+        Rect::from_corners(min, max)
+    }
+}
+
+// `parent * child` does child first, then parent, like placing the child in the parent's space.
+// The same caveat as inverse applies to uneven scaling of a rotated child.
+impl Mul for Transform2D {
+    type Output = Transform2D;
+    fn mul(self, child: Transform2D) -> Transform2D {
+        Transform2D {
+            translation: self.transform_point(child.translation),
+            rotation: self.rotation + child.rotation,
+            scale: self.scale * child.scale,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Roughly how much GPU memory the Engine's sprites are using, from Engine::memory_report. Drivers
+// add their own padding and alignment, so real usage runs somewhat higher.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryReport {
+    // Sprite, camera and draw argument buffers of every SpriteRender, spares and GPU-culling
+    // copies included.
+    pub sprite_buffer_bytes: u64,
+    // The textures sprite groups draw, each once however many groups share it, biggest first.
+    // Textures the game holds on to without drawing them aren't counted.
+    pub textures: Vec<TextureMemory>,
+    // Glyph atlases the Engine knows about (the console's); add your own with GlyphAtlas::usage.
+    #[cfg(feature = "text")]
+    pub atlases: Vec<AtlasUsage>,
+}
+
+impl MemoryReport {
+    pub fn texture_bytes(&self) -> u64 {
+        self.textures.iter().map(|texture| texture.bytes).sum()
+    }
+    pub fn total_bytes(&self) -> u64 {
+        self.sprite_buffer_bytes + self.texture_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureMemory {
+    // What it was loaded with; for files, that's usually the path.
+    pub label: Option<String>,
+    pub size: [u32; 2],
+    pub format: wgpu::TextureFormat,
+    pub mip_level_count: u32,
+    // All mip levels.
+    pub bytes: u64,
+    // How many sprite groups draw it.
+    pub groups: usize,
+}
+
+// How full a GlyphAtlas is. Once `used` nears 1 the next new glyph doubles its size.
+#[cfg(feature = "text")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasUsage {
+    pub size: [u32; 2],
+    pub bytes: u64,
+    // Fraction of the area glyphs have taken up, counting the unused ends of full rows.
+    pub used: f32,
+}
+
+// The textures SpriteRender::collect_memory found, biggest first.
+pub(crate) fn sorted(
+    textures: std::collections::HashMap<wgpu::Id<wgpu::Texture>, TextureMemory>,
+) -> Vec<TextureMemory> {
+    let mut textures: Vec<TextureMemory> = textures.into_values().collect();
+    textures.sort_by_key(|texture| std::cmp::Reverse(texture.bytes));
+    textures
+}
+
+// Size of `texture` with all its mip levels, going by its format's block size.
+pub(crate) fn texture_bytes(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_size(None).unwrap_or(4) as u64;
+    let size = texture.size();
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let width = (size.width >> level).max(1).div_ceil(block_width) as u64;
+            let height = (size.height >> level).max(1).div_ceil(block_height) as u64;
+            width * height * size.depth_or_array_layers as u64 * block_size
+        })
+        .sum::<u64>()
+        * texture.sample_count() as u64
+}
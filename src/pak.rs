@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"SPAK";
+const VERSION: u32 = 1;
+
+// A single data file holding many assets, so a shipped game can be one executable and one pak
+// instead of a folder of loose files. Mount it with Engine::mount_archive and everything that
+// loads assets finds the files inside by their paths, as if they were in the asset root.
+//
+// The format is simple on purpose: "SPAK", a version, the number of files, then for each its
+// path (UTF-8, with / between folders) and where its bytes are, then the bytes, all
+// little-endian and uncompressed (PNG, OGG and the like are compressed already). Make one with
+// pack_dir, e.g. from a build script.
+pub struct PakArchive {
+    // The file it's read from, or a label for one in memory.
+    source: PathBuf,
+    data: Option<Arc<[u8]>>,
+    // Offset and length of each file's bytes.
+    entries: HashMap<String, (u64, u64)>,
+}
+
+impl PakArchive {
+    // Reads the index; the files themselves are read from disk when they're asked for. Not on the
+    // web, which has to fetch the whole thing and use from_bytes.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)?;
+        let end = file.metadata()?.len();
+        let entries = read_index(&mut io::BufReader::new(file))?;
+        check_bounds(&entries, end)?;
+        Ok(Self {
+            source: path.to_owned(),
+            data: None,
+            entries,
+        })
+    }
+    // A pak already in memory, e.g. fetched with Engine::load_bytes or compiled in with
+    // include_bytes!. Errors name it by `label`.
+    pub fn from_bytes(bytes: impl Into<Arc<[u8]>>, label: &str) -> io::Result<Self> {
+        let data = bytes.into();
+        let entries = read_index(&mut io::Cursor::new(&data[..]))?;
+        check_bounds(&entries, data.len() as u64)?;
+        Ok(Self {
+            source: format!("<{label}>").into(),
+            data: Some(data),
+            entries,
+        })
+    }
+    // Where it was opened from, or its label in angle brackets.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+    // `name` is the file's path inside the archive, with / between folders.
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        let &(offset, len) = self.entries.get(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{name} isn't in {}", self.source.display()),
+            )
+        })?;
+        if let Some(data) = &self.data {
+            return Ok(data[offset as usize..(offset + len) as usize].to_vec());
+        }
+        let mut file = std::fs::File::open(&self.source)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = vec![0; len as usize];
+        file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+// Packs every file under `dir` (recursively) into a pak at `out`, named by their paths relative
+// to `dir`. `out` shouldn't be inside `dir`.
+pub fn pack_dir(dir: impl AsRef<Path>, out: impl AsRef<Path>) -> io::Result<()> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.sort();
+    let names: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let relative = file.strip_prefix(dir).unwrap_or(file);
+            let parts: Vec<_> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect();
+            parts.join("/")
+        })
+        .collect();
+    let mut lens = Vec::with_capacity(files.len());
+    for file in &files {
+        lens.push(std::fs::metadata(file)?.len());
+    }
+
+    let mut writer = io::BufWriter::new(std::fs::File::create(out)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(files.len() as u32).to_le_bytes())?;
+    let index_len: u64 = names.iter().map(|name| 2 + name.len() as u64 + 16).sum();
+    let mut offset = 12 + index_len;
+    for (name, &len) in names.iter().zip(&lens) {
+        let name_len = u16::try_from(name.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path too long"))?;
+        writer.write_all(&name_len.to_le_bytes())?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(&offset.to_le_bytes())?;
+        writer.write_all(&len.to_le_bytes())?;
+        offset += len;
+    }
+    for (file, &len) in files.iter().zip(&lens) {
+        let copied = io::copy(&mut std::fs::File::open(file)?, &mut writer)?;
+        if copied != len {
+            return Err(io::Error::other(format!(
+                "{} changed while being packed",
+                file.display()
+            )));
+        }
+    }
+    writer.flush()
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn read_index(reader: &mut impl Read) -> io::Result<HashMap<String, (u64, u64)>> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(corrupt("not a pak archive"));
+    }
+    let version = read_u32(reader)?;
+    if version != VERSION {
+        return Err(corrupt(&format!("unsupported pak version {version}")));
+    }
+    let count = read_u32(reader)?;
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let mut name_len = [0; 2];
+        reader.read_exact(&mut name_len)?;
+        let mut name = vec![0; u16::from_le_bytes(name_len) as usize];
+        reader.read_exact(&mut name)?;
+        let name = String::from_utf8(name).map_err(|_| corrupt("a path isn't UTF-8"))?;
+        let offset = read_u64(reader)?;
+        let len = read_u64(reader)?;
+        entries.insert(name, (offset, len));
+    }
+    Ok(entries)
+}
+
+// Makes sure every file's bytes are inside an archive `end` bytes long, so a corrupt index can't
+// make read slice out of bounds or allocate more than the archive holds.
+fn check_bounds(entries: &HashMap<String, (u64, u64)>, end: u64) -> io::Result<()> {
+    let past_end = |&(offset, len): &(u64, u64)| offset.checked_add(len).is_none_or(|e| e > end);
+    if entries.values().any(past_end) {
+        return Err(corrupt("a file runs past the end of the archive"));
+    }
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn corrupt(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
@@ -0,0 +1,87 @@
+use crate::sprite::{SamplerOptions, SpriteLayouts};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Everything that makes one sprite pipeline different from another. The source is the whole
+// WGSL module, so two SpriteRenders adding the same shader share its pipelines.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct PipelineKey {
+    pub(crate) source: String,
+    pub(crate) entry_point: &'static str,
+    pub(crate) blend: Option<wgpu::BlendState>,
+    pub(crate) format: wgpu::TextureFormat,
+    pub(crate) sample_count: u32,
+    pub(crate) depth: bool,
+}
+
+// What every SpriteRender on a device would otherwise make for itself: the bind group and
+// pipeline layouts, compiled shaders, pipelines for each blend mode and target, and samplers.
+// Lives in WGPU and starts over with a new device.
+#[derive(Default)]
+pub(crate) struct PipelineCache {
+    sprite_layouts: OnceLock<Arc<SpriteLayouts>>,
+    modules: Mutex<HashMap<String, Arc<wgpu::ShaderModule>>>,
+    pipelines: Mutex<HashMap<PipelineKey, Arc<wgpu::RenderPipeline>>>,
+    samplers: Mutex<HashMap<SamplerOptions, Arc<wgpu::Sampler>>>,
+}
+
+impl PipelineCache {
+    pub(crate) fn sprite_layouts(
+        &self,
+        create: impl FnOnce() -> SpriteLayouts,
+    ) -> Arc<SpriteLayouts> {
+        self.sprite_layouts
+            .get_or_init(|| Arc::new(create()))
+            .clone()
+    }
+    // The pipeline for `key`, which `create` makes out of the compiled key.source if there's none
+    // yet.
+    pub(crate) fn render_pipeline(
+        &self,
+        device: &wgpu::Device,
+        key: PipelineKey,
+        label: &str,
+        create: impl FnOnce(&wgpu::ShaderModule) -> wgpu::RenderPipeline,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let mut pipelines = self.pipelines.lock().unwrap();
+        if let Some(pipeline) = pipelines.get(&key) {
+            return pipeline.clone();
+        }
+        let module = self
+            .modules
+            .lock()
+            .unwrap()
+            .entry(key.source.clone())
+            .or_insert_with(|| {
+                Arc::new(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(label),
+                    source: wgpu::ShaderSource::Wgsl(key.source.clone().into()),
+                }))
+            })
+            .clone();
+        let pipeline = Arc::new(create(&module));
+        pipelines.insert(key, pipeline.clone());
+        pipeline
+    }
+    // Drops whatever was made from `source`, e.g. a shader that failed to compile, so that trying
+    // it again reports the error again.
+    pub(crate) fn forget_source(&self, source: &str) {
+        self.modules.lock().unwrap().remove(source);
+        self.pipelines
+            .lock()
+            .unwrap()
+            .retain(|key, _| key.source != source);
+    }
+    pub(crate) fn sampler(
+        &self,
+        device: &wgpu::Device,
+        options: SamplerOptions,
+    ) -> Arc<wgpu::Sampler> {
+        self.samplers
+            .lock()
+            .unwrap()
+            .entry(options)
+            .or_insert_with(|| Arc::new(device.create_sampler(&options.descriptor())))
+            .clone()
+    }
+}
@@ -0,0 +1,573 @@
+use crate::bloom::Bloom;
+use crate::gpu_timer::GpuTimer;
+use crate::{Color, ShaderError, TextureError, WGPU};
+use std::borrow::Cow;
+
+// Identifies a pass added with PostProcess::add_pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PassId(u64);
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+pub(crate) struct PostParams {
+    pub(crate) resolution: [f32; 2],
+    pub(crate) time: f32,
+    pub(crate) _padding: f32,
+    pub(crate) values: [f32; 4],
+    pub(crate) color: [f32; 4],
+}
+
+// Settings for the built-in CRT filter, each from 0 (off) to about 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrtOptions {
+    // How much darker the gaps between scanlines are.
+    pub scanlines: f32,
+    // How far the picture bulges, like the glass of a tube TV.
+    pub curvature: f32,
+    pub vignette: f32,
+}
+
+impl Default for CrtOptions {
+    fn default() -> Self {
+        Self {
+            scanlines: 0.3,
+            curvature: 0.4,
+            vignette: 0.5,
+        }
+    }
+}
+
+impl CrtOptions {
+    fn values(&self) -> [f32; 4] {
+        [self.scanlines, self.curvature, self.vignette, 0.0]
+    }
+}
+
+// Settings for the built-in bloom.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomOptions {
+    // How bright (0 to 1, by the brightest channel) a pixel has to be to glow.
+    pub threshold: f32,
+    // How strongly the glow is added back; above 1 overexposes.
+    pub intensity: f32,
+}
+
+impl Default for BloomOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            intensity: 0.6,
+        }
+    }
+}
+
+// Settings for the built-in overlay: a vignette and a full-screen tint, e.g. a red flash when
+// the player is hit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OverlayOptions {
+    // How dark the corners get, from 0 (not at all) to 1 (black).
+    pub vignette: f32,
+    // Color to mix the frame toward; alpha is how far, so 0 turns the tint off.
+    pub tint: Color,
+}
+
+impl Default for OverlayOptions {
+    fn default() -> Self {
+        Self {
+            vignette: 0.4,
+            tint: Color::TRANSPARENT,
+        }
+    }
+}
+
+struct PostPass {
+    id: PassId,
+    label: String,
+    // Just the fs_main; postprocess.wgsl supplies the rest.
+    fragment: Cow<'static, str>,
+    pipeline: wgpu::RenderPipeline,
+    params: wgpu::Buffer,
+    values: [f32; 4],
+    color: [f32; 4],
+    // Bound as t_extra.
+    texture: Option<wgpu::TextureView>,
+    // For the built-in bloom, whose blur chain runs before `pipeline` adds it to the frame.
+    bloom: Option<Box<Bloom>>,
+    enabled: bool,
+}
+
+// Full-screen shader passes run over the finished frame, in the order they were added. While any
+// pass is enabled the sprites are drawn into an offscreen texture first, and each pass reads the
+// previous one's output; the last one draws to the window.
+pub struct PostProcess {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+    // t_extra for passes without a texture.
+    no_texture: wgpu::TextureView,
+    format: wgpu::TextureFormat,
+    passes: Vec<PostPass>,
+    next_id: u64,
+    // Two frame-sized textures to ping-pong between, made when first needed and remade when the
+    // frame size changes.
+    targets: Option<[wgpu::TextureView; 2]>,
+    target_size: (u32, u32),
+}
+
+impl PostProcess {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("post process"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("post process"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let sampler = gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let white = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+        let no_texture = gpu.create_texture_for(&white, Some("no texture"), 1);
+        gpu.write_texture_data(&no_texture, &white);
+        Self {
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            no_texture: no_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            format: gpu.config.format,
+            passes: Vec::new(),
+            next_id: 0,
+            targets: None,
+            target_size: (0, 0),
+        }
+    }
+    // `entry_point` is the fragment shader to use; passes of their own always use fs_main.
+    pub(crate) fn create_pipeline(
+        &self,
+        gpu: &WGPU,
+        label: &str,
+        fragment: &str,
+        entry_point: &str,
+        blend: Option<wgpu::BlendState>,
+    ) -> wgpu::RenderPipeline {
+        let source = format!("{}\n{fragment}", include_str!("postprocess.wgsl"));
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+            });
+        gpu.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: self.format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+    }
+    // Adds a pass to the end of the chain. `fragment` is WGSL with an
+    // `fs_main(in: FullscreenOutput) -> @location(0) vec4<f32>` that reads the frame so far with
+    // `textureSample(t_source, s_source, in.uv)`; `params` has the resolution, the time, and the
+    // pass's own values. Like SpriteRender::add_shader, this waits to find out whether it compiled.
+    pub async fn add_pass(
+        &mut self,
+        gpu: &WGPU,
+        label: &str,
+        fragment: impl Into<Cow<'static, str>>,
+    ) -> Result<PassId, ShaderError> {
+        gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let id = self.push_pass(gpu, label, fragment.into());
+        if let Some(e) = gpu.device.pop_error_scope().await {
+            self.remove_pass(id);
+            return Err(ShaderError {
+                label: label.to_owned(),
+                message: e.to_string(),
+            });
+        }
+        Ok(id)
+    }
+    // Adds the built-in CRT filter (scanlines, curvature, vignette) to the end of the chain.
+    // Change its look later with set_crt.
+    pub fn add_crt(&mut self, gpu: &WGPU, options: CrtOptions) -> PassId {
+        let id = self.push_pass(
+            gpu,
+            "crt",
+            Cow::Borrowed(include_str!("postprocess_crt.wgsl")),
+        );
+        self.set_crt(id, options);
+        id
+    }
+    pub fn set_crt(&mut self, id: PassId, options: CrtOptions) {
+        self.set_params(id, options.values());
+    }
+    // Adds the built-in vignette and tint overlay to the end of the chain. It's meant to be left in
+    // place and animated with set_overlay, e.g. fading a damage flash out over a few frames.
+    pub fn add_overlay(&mut self, gpu: &WGPU, options: OverlayOptions) -> PassId {
+        let id = self.push_pass(
+            gpu,
+            "overlay",
+            Cow::Borrowed(include_str!("postprocess_overlay.wgsl")),
+        );
+        self.set_overlay(id, options);
+        id
+    }
+    pub fn set_overlay(&mut self, id: PassId, options: OverlayOptions) {
+        self.set_params(id, [options.vignette, 0.0, 0.0, 0.0]);
+        // The overlay shader takes the color as sRGB and linearizes it itself.
+        self.set_pass_color(id, options.tint.to_array());
+    }
+    // Adds the built-in bloom to the end of the chain, so bright sprites (e.g. BlendMode::Additive
+    // ones) glow.
+    pub fn add_bloom(&mut self, gpu: &WGPU, options: BloomOptions) -> PassId {
+        let id = self.push_pass(
+            gpu,
+            "bloom",
+            Cow::Borrowed(include_str!("postprocess_bloom.wgsl")),
+        );
+        let bloom = Bloom::new(self, gpu);
+        if let Some(pass) = self.pass_mut(id) {
+            pass.bloom = Some(Box::new(bloom));
+        }
+        self.set_bloom(id, options);
+        id
+    }
+    pub fn set_bloom(&mut self, id: PassId, options: BloomOptions) {
+        self.set_params(id, [options.threshold, options.intensity, 0.0, 0.0]);
+    }
+    // Adds a color grading pass using `lut`, a strip of N slices of N by N texels (e.g. 256x16),
+    // as made by most image editors' LUT export. Swap looks at runtime with set_pass_texture, and
+    // fade the effect with set_params (x is the strength).
+    pub fn add_color_grade(
+        &mut self,
+        gpu: &WGPU,
+        lut: &wgpu::Texture,
+    ) -> Result<PassId, TextureError> {
+        check_lut(lut)?;
+        let id = self.push_pass(
+            gpu,
+            "color grade",
+            Cow::Borrowed(include_str!("postprocess_lut.wgsl")),
+        );
+        self.set_params(id, [1.0, 0.0, 0.0, 0.0]);
+        self.set_pass_texture(id, Some(lut));
+        Ok(id)
+    }
+    // Checks that `lut` is shaped like a LUT before handing it to a color grading pass.
+    pub fn set_color_grade_lut(
+        &mut self,
+        id: PassId,
+        lut: &wgpu::Texture,
+    ) -> Result<(), TextureError> {
+        check_lut(lut)?;
+        self.set_pass_texture(id, Some(lut));
+        Ok(())
+    }
+    // What the pass's shader sees as t_extra, e.g. a noise or lookup texture.
+    pub fn set_pass_texture(&mut self, id: PassId, texture: Option<&wgpu::Texture>) {
+        if let Some(pass) = self.pass_mut(id) {
+            pass.texture = texture.map(|t| t.create_view(&wgpu::TextureViewDescriptor::default()));
+        }
+    }
+    fn push_pass(&mut self, gpu: &WGPU, label: &str, fragment: Cow<'static, str>) -> PassId {
+        let pipeline = self.create_pipeline(gpu, label, &fragment, "fs_main", None);
+        let id = PassId(self.next_id);
+        self.next_id += 1;
+        self.passes.push(PostPass {
+            id,
+            label: label.to_owned(),
+            fragment,
+            pipeline,
+            params: create_params_buffer(gpu, label),
+            values: [0.0; 4],
+            color: [0.0; 4],
+            texture: None,
+            bloom: None,
+            enabled: true,
+        });
+        id
+    }
+    pub fn remove_pass(&mut self, id: PassId) {
+        self.passes.retain(|pass| pass.id != id);
+    }
+    pub fn clear(&mut self) {
+        self.passes.clear();
+    }
+    // Disabled passes are skipped as if they weren't there.
+    pub fn set_enabled(&mut self, id: PassId, enabled: bool) {
+        if let Some(pass) = self.pass_mut(id) {
+            pass.enabled = enabled;
+        }
+    }
+    pub fn is_enabled(&self, id: PassId) -> bool {
+        self.passes.iter().any(|pass| pass.id == id && pass.enabled)
+    }
+    // What the pass's shader sees as `params.values`, e.g. an effect's strength.
+    pub fn set_params(&mut self, id: PassId, values: [f32; 4]) {
+        if let Some(pass) = self.pass_mut(id) {
+            pass.values = values;
+        }
+    }
+    // What the pass's shader sees as `params.color`.
+    pub fn set_pass_color(&mut self, id: PassId, rgba: [f32; 4]) {
+        if let Some(pass) = self.pass_mut(id) {
+            pass.color = rgba;
+        }
+    }
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+    fn pass_mut(&mut self, id: PassId) -> Option<&mut PostPass> {
+        self.passes.iter_mut().find(|pass| pass.id == id)
+    }
+    pub(crate) fn no_texture(&self) -> &wgpu::TextureView {
+        &self.no_texture
+    }
+    pub(crate) fn is_active(&self) -> bool {
+        self.passes.iter().any(|pass| pass.enabled)
+    }
+    // Makes sure scene_target fits the window, if there is to be one this frame.
+    pub(crate) fn prepare_scene_target(&mut self, gpu: &WGPU) {
+        if !self.is_active() {
+            return;
+        }
+        let size = (gpu.config.width, gpu.config.height);
+        if self.targets.is_none() || self.target_size != size {
+            let target = |label| {
+                gpu.device
+                    .create_texture(&wgpu::TextureDescriptor {
+                        label: Some(label),
+                        size: wgpu::Extent3d {
+                            width: size.0,
+                            height: size.1,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: self.format,
+                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    })
+                    .create_view(&wgpu::TextureViewDescriptor::default())
+            };
+            self.targets = Some([target("post process a"), target("post process b")]);
+            self.target_size = size;
+        }
+        let format = self.format;
+        for bloom in self
+            .passes
+            .iter_mut()
+            .filter_map(|pass| pass.bloom.as_mut())
+        {
+            bloom.resize(gpu, size, format);
+        }
+    }
+    // Where the sprites should be drawn this frame: None to draw straight to the window.
+    pub(crate) fn scene_target(&self) -> Option<&wgpu::TextureView> {
+        if !self.is_active() {
+            return None;
+        }
+        self.targets.as_ref().map(|[scene, _]| scene)
+    }
+    // Runs the enabled passes over what was drawn into scene_target, ending up in `output`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "post process", skip_all)
+    )]
+    pub(crate) fn apply(
+        &self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        time: f64,
+        mut timer: Option<&mut GpuTimer>,
+    ) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        let enabled: Vec<&PostPass> = self.passes.iter().filter(|pass| pass.enabled).collect();
+        for (i, pass) in enabled.iter().enumerate() {
+            let params = PostParams {
+                resolution: [self.target_size.0 as f32, self.target_size.1 as f32],
+                time: time as f32,
+                _padding: 0.0,
+                values: pass.values,
+                color: pass.color,
+            };
+            gpu.queue
+                .write_buffer(&pass.params, 0, bytemuck::bytes_of(&params));
+            let source = &targets[i % 2];
+            let destination = if i + 1 == enabled.len() {
+                output
+            } else {
+                &targets[(i + 1) % 2]
+            };
+            let extra = match &pass.bloom {
+                Some(bloom) => Some(bloom.run(self, gpu, encoder, source, pass.values)),
+                None => pass.texture.as_ref(),
+            };
+            self.draw_fullscreen(
+                gpu,
+                encoder,
+                &pass.label,
+                &pass.pipeline,
+                [source, extra.unwrap_or(&self.no_texture)],
+                &pass.params,
+                destination,
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            );
+            if let Some(timer) = timer.as_deref_mut() {
+                timer.mark(encoder, &pass.label);
+            }
+        }
+    }
+    // One full-screen triangle with `pipeline`, reading `textures` as t_source and t_extra.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw_fullscreen(
+        &self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::RenderPipeline,
+        [source, extra]: [&wgpu::TextureView; 2],
+        params: &wgpu::Buffer,
+        destination: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(extra),
+                },
+            ],
+        });
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: destination,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+    // Rebuilds every pass on the new device after WGPU::recover, keeping order and settings.
+    pub(crate) fn recreate(&mut self, gpu: &WGPU) {
+        let passes = std::mem::take(&mut self.passes);
+        let next_id = self.next_id;
+        *self = Self::new(gpu);
+        self.next_id = next_id;
+        for pass in passes {
+            let pipeline = self.create_pipeline(gpu, &pass.label, &pass.fragment, "fs_main", None);
+            let bloom = pass.bloom.as_ref().map(|_| Box::new(Bloom::new(self, gpu)));
+            self.passes.push(PostPass {
+                pipeline,
+                bloom,
+                params: create_params_buffer(gpu, &pass.label),
+                // Textures belonged to the old device; the game has to set them again.
+                texture: None,
+                ..pass
+            });
+        }
+    }
+}
+
+pub(crate) fn create_params_buffer(gpu: &WGPU, label: &str) -> wgpu::Buffer {
+    gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: std::mem::size_of::<PostParams>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn check_lut(lut: &wgpu::Texture) -> Result<(), TextureError> {
+    let (width, height) = (lut.width(), lut.height());
+    if height < 2 || width != height * height {
+        return Err(TextureError::Unsupported {
+            path: crate::gpu::in_memory_source(Some("color grading LUT")),
+            reason: format!("a {width}x{height} image isn't a LUT strip (N*N wide, N tall)"),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+// The part of every post-processing shader that the engine provides: a triangle covering the
+// screen, the previous pass's output, and the pass's parameters. The pass's own fs_main is
+// appended after this.
+
+struct PostParams {
+    // Size of the frame in pixels.
+    resolution: vec2<f32>,
+    // Seconds since the game started.
+    time: f32,
+    // Set per pass with PostProcess::set_params.
+    values: vec4<f32>,
+    // Set per pass with PostProcess::set_pass_color.
+    color: vec4<f32>,
+}
+
+struct FullscreenOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0,0 is the top left of the frame.
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_source: texture_2d<f32>;
+@group(0) @binding(1)
+var s_source: sampler;
+@group(0) @binding(2)
+var<uniform> params: PostParams;
+// Whatever texture the pass was given with PostProcess::set_pass_texture (1x1 white if none).
+@group(0) @binding(3)
+var t_extra: texture_2d<f32>;
+
+// One oversized triangle instead of a quad; the corners outside the screen get clipped.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return FullscreenOutput(
+        vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0),
+        uv
+    );
+}
@@ -0,0 +1,51 @@
+// The built-in bloom: bright parts of the frame are cut out and shrunk down a chain of
+// half-size textures (blurring as they go), added back up the chain, and finally added to the
+// frame. params.values: x = threshold, y = intensity. Apart from fs_main, params.resolution is
+// the size of t_source rather than of the frame.
+
+// Four bilinear taps one texel out on the diagonals, i.e. a blur over 4x4 texels.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let t = 1.0 / params.resolution;
+    return (textureSample(t_source, s_source, uv + vec2(-t.x, -t.y)).rgb
+        + textureSample(t_source, s_source, uv + vec2(t.x, -t.y)).rgb
+        + textureSample(t_source, s_source, uv + vec2(-t.x, t.y)).rgb
+        + textureSample(t_source, s_source, uv + vec2(t.x, t.y)).rgb) * 0.25;
+}
+
+// The first step down keeps only what's brighter than the threshold.
+@fragment
+fn fs_threshold(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = downsample(in.uv);
+    let brightness = max(color.r, max(color.g, color.b));
+    let keep = max(brightness - params.values.x, 0.0) / max(brightness, 0.0001);
+    return vec4(color * keep, 1.0);
+}
+
+@fragment
+fn fs_down(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4(downsample(in.uv), 1.0);
+}
+
+// A 3x3 tent filter over the smaller texture, added onto the bigger one.
+@fragment
+fn fs_up(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let t = 1.0 / params.resolution;
+    var color = textureSample(t_source, s_source, in.uv).rgb * 4.0;
+    color += (textureSample(t_source, s_source, in.uv + vec2(-t.x, 0.0)).rgb
+        + textureSample(t_source, s_source, in.uv + vec2(t.x, 0.0)).rgb
+        + textureSample(t_source, s_source, in.uv + vec2(0.0, -t.y)).rgb
+        + textureSample(t_source, s_source, in.uv + vec2(0.0, t.y)).rgb) * 2.0;
+    color += textureSample(t_source, s_source, in.uv + vec2(-t.x, -t.y)).rgb
+        + textureSample(t_source, s_source, in.uv + vec2(t.x, -t.y)).rgb
+        + textureSample(t_source, s_source, in.uv + vec2(-t.x, t.y)).rgb
+        + textureSample(t_source, s_source, in.uv + vec2(t.x, t.y)).rgb;
+    return vec4(color / 16.0, 1.0);
+}
+
+// Adds the blurred highlights (t_extra) onto the frame.
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv);
+    let glow = textureSample(t_extra, s_source, in.uv).rgb;
+    return vec4(color.rgb + glow * params.values.y, color.a);
+}
@@ -0,0 +1,21 @@
+// The built-in CRT filter. params.values: x = scanline darkness, y = screen curvature,
+// z = vignette strength.
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    // Bulge the picture out from the center, like the glass of an old tube.
+    let centered = in.uv * 2.0 - 1.0;
+    let bent = centered * (1.0 + params.values.y * dot(centered, centered) * 0.25);
+    let uv = bent * 0.5 + 0.5;
+    var color = textureSample(t_source, s_source, uv).rgb;
+    // Past the edge of the bent screen is the black of the bezel.
+    if any(uv < vec2(0.0)) || any(uv > vec2(1.0)) {
+        color = vec3(0.0);
+    }
+    // Every other row of physical pixels is darker.
+    let row = uv.y * params.resolution.y;
+    color *= 1.0 - params.values.x * (0.5 + 0.5 * cos(row * 3.14159265));
+    // Darken toward the corners.
+    color *= 1.0 - params.values.z * dot(centered, centered) * 0.5;
+    return vec4(color, 1.0);
+}
@@ -0,0 +1,31 @@
+// The built-in color grading pass. t_extra is a 3D lookup table unwrapped into a strip of
+// N slices, each N by N: red goes across a slice, green down it, and blue picks the slice.
+// params.values.x is the strength, from 0 (ungraded) to 1.
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+fn lut_sample(slice: f32, rg: vec2<f32>, size: f32, dims: vec2<f32>) -> vec3<f32> {
+    // Texel centers only, so the linear filter never reaches into the next slice over.
+    let texel = rg * (size - 1.0) + 0.5;
+    let uv = vec2((slice * size + texel.x) / dims.x, texel.y / dims.y);
+    return textureSampleLevel(t_extra, s_source, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv);
+    let dims = vec2<f32>(textureDimensions(t_extra));
+    let size = dims.y;
+    // LUTs are authored against sRGB values.
+    let srgb = clamp(linear_to_srgb(color.rgb), vec3(0.0), vec3(1.0));
+    let blue = srgb.b * (size - 1.0);
+    let slice = floor(blue);
+    let graded = mix(
+        lut_sample(slice, srgb.rg, size, dims),
+        lut_sample(min(slice + 1.0, size - 1.0), srgb.rg, size, dims),
+        blue - slice
+    );
+    return vec4(mix(color.rgb, graded, params.values.x), color.a);
+}
@@ -0,0 +1,15 @@
+// The built-in overlay: darkens the edges of the screen by params.values.x and mixes the whole
+// frame toward params.color (an sRGB color whose alpha is how much of it to mix in).
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv);
+    let centered = in.uv * 2.0 - 1.0;
+    let vignette = 1.0 - params.values.x * smoothstep(0.4, 1.4, length(centered));
+    let tinted = mix(color.rgb, srgb_to_linear(params.color.rgb), params.color.a);
+    return vec4(tinted * vignette, color.a);
+}
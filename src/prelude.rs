@@ -0,0 +1,42 @@
+// Everything a typical game needs, so `use engine::prelude::*;` is enough to get started.
+// The winit/wgpu items come from the versions this crate was built against, which saves
+// games from having to pin matching versions in their own Cargo.toml.
+#[cfg(feature = "clipboard")]
+pub use crate::ClipboardText;
+#[cfg(feature = "manifest")]
+pub use crate::ManifestError;
+#[cfg(feature = "serde")]
+pub use crate::SpriteState;
+pub use crate::{
+    Aabb, ActionEvent, ActionPhase, AlphaMask, Anchor, AnimationClip, AnimationEvent,
+    AnimationFrame, Animator, AssetId, AssetKind, AssetLoader, AssetManifest, Assets, AxisBinding,
+    AxisSource, Binding, BlendMode, BloomOptions, Color, Contact, CrtOptions, DebugDraw, DebugView,
+    Engine, EngineConfig, EngineError, EngineSnapshot, EventReader, Events, FrameStats,
+    FullscreenMode, GPUCamera, GPUSprite, Game, GpuOptions, GraphError, GraphTarget, Input,
+    InputMap, Key, Kinematics, Margins, Modifiers, Motion, MouseButton, MousePos, OverlayOptions,
+    PakArchive, PassContext, PassId, PostProcess, Quadtree, RayHit, Rect, RenderGraph, Rng,
+    SamplerOptions, ScrollDelta, SecondaryWindow, SecondaryWindowId, Sequence, SequenceId,
+    Sequencer, ShaderError, ShaderId, SpatialHash, SpriteBuilder, SpriteError, SpriteHandle,
+    SpriteMut, SpriteRender, SpriteShader, TextureError, TouchPhase, TouchPoint, TrackedSprites,
+    Transform2D, TriggerArea, TriggerEvent, TriggerPhase, Triggers, UiLayer, UiLayout, UploadId,
+    UploadScheduler, Vec2, WindowConfig, WGPU,
+};
+#[cfg(feature = "audio")]
+pub use crate::{Audio, AudioClip, AudioError, Bus, MusicOptions, SoundId, SoundOptions};
+#[cfg(feature = "text")]
+pub use crate::{
+    Console, DynamicText, FontId, GlyphAtlas, HAlign, LineMetrics, RichText, TextEffect,
+    TextLayout, TextMetrics, TextSpan, VAlign,
+};
+#[cfg(feature = "gamepad")]
+pub use crate::{GamepadAxis, GamepadButton, GamepadEvent, GamepadId};
+#[cfg(feature = "scripting")]
+pub use crate::{ScriptError, ScriptId, Scripts};
+#[cfg(feature = "ecs")]
+pub use crate::{SpriteRef, Transform, Velocity};
+pub use async_trait::async_trait;
+pub use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    event_loop::EventLoop,
+    window::{Window, WindowBuilder},
+};
@@ -0,0 +1,173 @@
+use crate::{GPUSprite, Rect};
+use std::collections::HashMap;
+
+// Items a node holds before it splits into quarters, and how many times a quarter can be split
+// again.
+const MAX_ITEMS: usize = 8;
+const MAX_DEPTH: u32 = 8;
+
+// A quadtree of rectangles, for big worlds of mostly static geometry: walls, platforms, the solid
+// tiles of a level. Where a SpatialHash spends a cell on every grid square anything touches, this
+// only divides up the parts of the world that are crowded. Items are told apart by a number of the
+// game's choosing, e.g. their sprite index. Removing items doesn't merge quarters back together,
+// so for things that move every frame a SpatialHash is the better fit.
+#[derive(Clone, Debug)]
+pub struct Quadtree {
+    // The root first. Items too big for any one quarter of a node stay in the node itself, and
+    // items outside the root's bounds in the root.
+    nodes: Vec<Node>,
+    // Which node each item is in.
+    located: HashMap<usize, usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    bounds: Rect,
+    depth: u32,
+    items: Vec<(usize, Rect)>,
+    // Indices into nodes of the four quarters, once it's split.
+    children: Option<[usize; 4]>,
+}
+
+impl Quadtree {
+    // An empty tree dividing up `bounds`, which should cover the whole world. Items outside it can
+    // still be added but aren't sorted any further.
+    pub fn new(bounds: Rect) -> Self {
+        Self {
+            nodes: vec![Node {
+                bounds,
+                depth: 0,
+                items: Vec::new(),
+                children: None,
+            }],
+            located: HashMap::new(),
+        }
+    }
+    // A tree of `sprites`' screen_regions, numbered by their index, e.g. a level's wall group.
+    pub fn from_sprites(sprites: &[GPUSprite]) -> Self {
+        Self::from_rects(sprites.iter().map(GPUSprite::bounds).enumerate())
+    }
+    // A tree of these items, with bounds just big enough for them all.
+    pub fn from_rects(items: impl IntoIterator<Item = (usize, Rect)>) -> Self {
+        let items: Vec<(usize, Rect)> = items.into_iter().collect();
+        let bounds = items
+            .iter()
+            .map(|(_, rect)| *rect)
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or_default();
+        let mut tree = Self::new(bounds);
+        for (item, rect) in items {
+            tree.insert(item, rect);
+        }
+        tree
+    }
+    pub fn bounds(&self) -> Rect {
+        self.nodes[0].bounds
+    }
+    pub fn len(&self) -> usize {
+        self.located.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.located.is_empty()
+    }
+    // Adds `item` covering `rect`, or moves it there if it's already in.
+    pub fn insert(&mut self, item: usize, rect: Rect) {
+        self.remove(item);
+        let mut node = 0;
+        while let Some(children) = self.nodes[node].children {
+            match children
+                .into_iter()
+                .find(|&child| self.nodes[child].bounds.contains_rect(&rect))
+            {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        self.nodes[node].items.push((item, rect));
+        self.located.insert(item, node);
+        self.split_if_full(node);
+    }
+    // Whether `item` was in.
+    pub fn remove(&mut self, item: usize) -> bool {
+        let Some(node) = self.located.remove(&item) else {
+            return false;
+        };
+        self.nodes[node].items.retain(|(other, _)| *other != item);
+        true
+    }
+    pub fn get(&self, item: usize) -> Option<Rect> {
+        let node = self.located.get(&item)?;
+        self.nodes[*node]
+            .items
+            .iter()
+            .find(|(other, _)| *other == item)
+            .map(|(_, rect)| *rect)
+    }
+    pub fn clear(&mut self) {
+        let bounds = self.bounds();
+        *self = Self::new(bounds);
+    }
+    // The items whose rectangles overlap `rect`, in order.
+    pub fn query(&self, rect: &Rect) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            found.extend(
+                node.items
+                    .iter()
+                    .filter(|(_, other)| other.overlaps(rect))
+                    .map(|(item, _)| *item),
+            );
+            if let Some(children) = node.children {
+                stack.extend(
+                    children
+                        .into_iter()
+                        .filter(|&child| self.nodes[child].bounds.overlaps(rect)),
+                );
+            }
+        }
+        found.sort_unstable();
+        found
+    }
+    // Splits `node` into quarters if it's holding too much, and moves down whatever fits in one.
+    fn split_if_full(&mut self, node: usize) {
+        let Node {
+            bounds,
+            depth,
+            ref items,
+            children,
+        } = self.nodes[node];
+        if children.is_some() || items.len() <= MAX_ITEMS || depth >= MAX_DEPTH {
+            return;
+        }
+        let (half_width, half_height) = (bounds.width / 2.0, bounds.height / 2.0);
+        let first = self.nodes.len();
+        for (dx, dy) in [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)] {
+            self.nodes.push(Node {
+                bounds: Rect {
+                    x: bounds.x + dx * half_width,
+                    y: bounds.y + dy * half_height,
+                    width: half_width,
+                    height: half_height,
+                },
+                depth: depth + 1,
+                items: Vec::new(),
+                children: None,
+            });
+        }
+        let children = [first, first + 1, first + 2, first + 3];
+        self.nodes[node].children = Some(children);
+        for (item, rect) in std::mem::take(&mut self.nodes[node].items) {
+            let home = children
+                .into_iter()
+                .find(|&child| self.nodes[child].bounds.contains_rect(&rect))
+                .unwrap_or(node);
+            self.nodes[home].items.push((item, rect));
+            self.located.insert(item, home);
+        }
+        for child in children {
+            self.split_if_full(child);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{GPUSprite, Rect, Vec2};
+
+// Where a ray first hits something.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub point: Vec2,
+    // From the ray's origin, in world units.
+    pub distance: f32,
+    // Out of the face that was hit, toward the origin. Zero for rays starting inside.
+    pub normal: Vec2,
+}
+
+fn normalize(direction: [f32; 2]) -> Option<[f32; 2]> {
+    let length = direction[0].hypot(direction[1]);
+    (length > 0.0).then(|| [direction[0] / length, direction[1] / length])
+}
+
+fn hit(origin: [f32; 2], direction: [f32; 2], distance: f32, normal: [f32; 2]) -> RayHit {
+    RayHit {
+        point: Vec2::from(origin) + Vec2::from(direction) * distance,
+        distance,
+        normal: normal.into(),
+    }
+}
+
+// Casts a ray from `origin` along `direction` (any length but zero) for up to `max_distance`,
+// and finds where it enters `rect`. Rays starting inside hit right away.
+pub fn raycast_rect(
+    origin: impl Into<Vec2>,
+    direction: impl Into<Vec2>,
+    max_distance: f32,
+    rect: &Rect,
+) -> Option<RayHit> {
+    let origin = origin.into().to_array();
+    let direction = normalize(direction.into().to_array())?;
+    let (mut near, mut far) = (0.0f32, max_distance);
+    let mut normal = [0.0; 2];
+    let slabs = [(rect.x, rect.right()), (rect.y, rect.top())];
+    for (axis, (low, high)) in slabs.into_iter().enumerate() {
+        if direction[axis] == 0.0 {
+            if origin[axis] < low || origin[axis] >= high {
+                return None;
+            }
+            continue;
+        }
+        let to_low = (low - origin[axis]) / direction[axis];
+        let to_high = (high - origin[axis]) / direction[axis];
+        // Heading up the axis, the ray comes in through the low side, which faces down it.
+        let (enter, exit, facing) = if to_low < to_high {
+            (to_low, to_high, -1.0)
+        } else {
+            (to_high, to_low, 1.0)
+        };
+        if enter > near {
+            near = enter;
+            normal = [0.0; 2];
+            normal[axis] = facing;
+        }
+        far = far.min(exit);
+        if near > far {
+            return None;
+        }
+    }
+    Some(hit(origin, direction, near, normal))
+}
+
+// Walks a grid of `cell_size` squares (cell [0, 0] has its bottom-left corner at the origin) cell
+// by cell along the ray, and returns the first one `solid` says is a wall, for line of sight over
+// a tile map. Only the cells the ray passes through are asked about, nearest first.
+// `max_distance` has to be finite, since open space would otherwise be walked forever; None if
+// it isn't.
+pub fn raycast_grid(
+    origin: impl Into<Vec2>,
+    direction: impl Into<Vec2>,
+    max_distance: f32,
+    cell_size: f32,
+    mut solid: impl FnMut([i32; 2]) -> bool,
+) -> Option<([i32; 2], RayHit)> {
+    if !max_distance.is_finite() {
+        return None;
+    }
+    let origin = origin.into().to_array();
+    let direction = normalize(direction.into().to_array())?;
+    let mut cell = origin.map(|at| (at / cell_size).floor() as i32);
+    if solid(cell) {
+        return Some((cell, hit(origin, direction, 0.0, [0.0; 2])));
+    }
+    let step = direction.map(|d| if d > 0.0 { 1 } else { -1 });
+    // How far along the ray one cell is on each axis, and how far to the next cell boundary.
+    let across = direction.map(|d| (cell_size / d).abs());
+    let mut next = [0, 1].map(|axis| {
+        let d = direction[axis];
+        if d == 0.0 {
+            return f32::INFINITY;
+        }
+        let boundary = (cell[axis] + (d > 0.0) as i32) as f32 * cell_size;
+        (boundary - origin[axis]) / d
+    });
+    loop {
+        let axis = if next[0] < next[1] { 0 } else { 1 };
+        let distance = next[axis];
+        // Not finite for a NaN or infinite ray, which never gets anywhere.
+        if !distance.is_finite() || distance > max_distance {
+            return None;
+        }
+        cell[axis] = cell[axis].checked_add(step[axis])?;
+        next[axis] += across[axis];
+        if solid(cell) {
+            let mut normal = [0.0; 2];
+            normal[axis] = -step[axis] as f32;
+            return Some((cell, hit(origin, direction, distance, normal)));
+        }
+    }
+}
+
+// The nearest of `sprites` the ray hits, by screen_region, with its index.
+pub fn raycast_sprites(
+    origin: impl Into<Vec2>,
+    direction: impl Into<Vec2>,
+    max_distance: f32,
+    sprites: &[GPUSprite],
+) -> Option<(usize, RayHit)> {
+    let (origin, direction) = (origin.into(), direction.into());
+    sprites
+        .iter()
+        .enumerate()
+        .filter_map(|(index, sprite)| {
+            Some((
+                index,
+                raycast_rect(origin, direction, max_distance, &sprite.bounds())?,
+            ))
+        })
+        .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))
+}
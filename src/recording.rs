@@ -0,0 +1,283 @@
+use crate::input::{Input, Modifiers, MousePos, ScrollDelta, TouchPhase, TouchPoint};
+use std::io::{self, Read, Write};
+
+// Everything the game can observe through Input on a single frame, and how long the frame was.
+#[derive(Clone, Debug, PartialEq)]
+struct InputFrame {
+    delta: f32,
+    keys: Box<[bool]>,
+    modifiers: Modifiers,
+    mouse: Box<[bool]>,
+    mouse_pos: MousePos<f64>,
+    scroll: ScrollDelta,
+    text: String,
+    touches: Vec<TouchPoint>,
+}
+
+// A frame-by-frame log of input state. Gamepads aren't captured, so bind gameplay you
+// want to replay to keys, mouse, or touch.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputRecording {
+    frames: Vec<InputFrame>,
+}
+
+pub(crate) enum RecordMode {
+    Idle,
+    Recording(InputRecording),
+    Playing(InputRecording, usize),
+}
+
+const MAGIC: &[u8; 4] = b"INPR";
+// 2 added frame times.
+const VERSION: u32 = 2;
+
+impl InputRecording {
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+    pub fn load(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Self::read_from(&mut io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    // A small little-endian binary format: a header, then each frame's time, pressed key and
+    // mouse indices, cursor, scroll, typed text, and touches.
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        write_u32(out, VERSION)?;
+        write_u32(out, self.frames.len() as u32)?;
+        for frame in &self.frames {
+            out.write_all(&frame.delta.to_le_bytes())?;
+            write_flags(out, &frame.keys)?;
+            write_u32(out, frame.modifiers.bits())?;
+            write_flags(out, &frame.mouse)?;
+            write_f64(out, frame.mouse_pos.x)?;
+            write_f64(out, frame.mouse_pos.y)?;
+            out.write_all(&frame.scroll.lines[0].to_le_bytes())?;
+            out.write_all(&frame.scroll.lines[1].to_le_bytes())?;
+            write_f64(out, frame.scroll.pixels[0])?;
+            write_f64(out, frame.scroll.pixels[1])?;
+            write_u32(out, frame.text.len() as u32)?;
+            out.write_all(frame.text.as_bytes())?;
+            write_u32(out, frame.touches.len() as u32)?;
+            for touch in &frame.touches {
+                out.write_all(&touch.id.to_le_bytes())?;
+                out.write_all(&[match touch.phase {
+                    TouchPhase::Started => 0,
+                    TouchPhase::Held => 1,
+                    TouchPhase::Ended => 2,
+                }])?;
+                write_f64(out, touch.position.x)?;
+                write_f64(out, touch.position.y)?;
+                write_f64(out, touch.start_position.x)?;
+                write_f64(out, touch.start_position.y)?;
+            }
+        }
+        Ok(())
+    }
+    pub fn read_from(inp: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        inp.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an input recording",
+            ));
+        }
+        let version = read_u32(inp)?;
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("input recording version {version}, expected {VERSION}"),
+            ));
+        }
+        let count = read_u32(inp)?;
+        // Counts come from the file, so they're only trusted as far as reading them succeeds.
+        let mut frames = Vec::with_capacity(count.min(1024) as usize);
+        for _ in 0..count {
+            let delta = read_f32(inp)?;
+            let keys = read_flags(inp, crate::input::KEY_COUNT)?;
+            let modifiers = Modifiers::from_bits_truncate(read_u32(inp)?);
+            let mouse = read_flags(inp, crate::input::MOUSE_BUTTONS)?;
+            let mouse_pos = MousePos {
+                x: read_f64(inp)?,
+                y: read_f64(inp)?,
+            };
+            let scroll = ScrollDelta {
+                lines: [read_f32(inp)?, read_f32(inp)?],
+                pixels: [read_f64(inp)?, read_f64(inp)?],
+            };
+            let text_len = read_u32(inp)? as u64;
+            let mut text = Vec::new();
+            inp.take(text_len).read_to_end(&mut text)?;
+            if text.len() as u64 != text_len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let text = String::from_utf8(text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let touch_count = read_u32(inp)?;
+            let mut touches = Vec::with_capacity(touch_count.min(16) as usize);
+            for _ in 0..touch_count {
+                let mut id = [0; 8];
+                inp.read_exact(&mut id)?;
+                let mut phase = [0; 1];
+                inp.read_exact(&mut phase)?;
+                touches.push(TouchPoint {
+                    id: u64::from_le_bytes(id),
+                    phase: match phase[0] {
+                        0 => TouchPhase::Started,
+                        1 => TouchPhase::Held,
+                        _ => TouchPhase::Ended,
+                    },
+                    position: MousePos {
+                        x: read_f64(inp)?,
+                        y: read_f64(inp)?,
+                    },
+                    start_position: MousePos {
+                        x: read_f64(inp)?,
+                        y: read_f64(inp)?,
+                    },
+                });
+            }
+            frames.push(InputFrame {
+                delta,
+                keys,
+                modifiers,
+                mouse,
+                mouse_pos,
+                scroll,
+                text,
+                touches,
+            });
+        }
+        Ok(Self { frames })
+    }
+}
+
+fn write_u32(out: &mut impl Write, v: u32) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+fn write_f64(out: &mut impl Write, v: f64) -> io::Result<()> {
+    out.write_all(&v.to_le_bytes())
+}
+// Only the indices of the set flags are stored, since almost everything is up on any frame.
+fn write_flags(out: &mut impl Write, flags: &[bool]) -> io::Result<()> {
+    let set: Vec<u8> = (0..flags.len())
+        .filter(|i| flags[*i])
+        .map(|i| i as u8)
+        .collect();
+    out.write_all(&[set.len() as u8])?;
+    out.write_all(&set)
+}
+fn read_u32(inp: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    inp.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+fn read_f32(inp: &mut impl Read) -> io::Result<f32> {
+    let mut buf = [0; 4];
+    inp.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+fn read_f64(inp: &mut impl Read) -> io::Result<f64> {
+    let mut buf = [0; 8];
+    inp.read_exact(&mut buf)?;
+    Ok(f64::from_le_bytes(buf))
+}
+fn read_flags(inp: &mut impl Read, len: usize) -> io::Result<Box<[bool]>> {
+    let mut count = [0; 1];
+    inp.read_exact(&mut count)?;
+    let mut set = vec![0; count[0] as usize];
+    inp.read_exact(&mut set)?;
+    let mut flags = vec![false; len].into_boxed_slice();
+    for i in set {
+        if let Some(flag) = flags.get_mut(i as usize) {
+            *flag = true;
+        }
+    }
+    Ok(flags)
+}
+
+impl Input {
+    // Starts logging every frame's input. Any recording or playback in progress is dropped.
+    pub fn start_recording(&mut self) {
+        self.record_mode = RecordMode::Recording(InputRecording::default());
+    }
+    pub fn stop_recording(&mut self) -> Option<InputRecording> {
+        match std::mem::replace(&mut self.record_mode, RecordMode::Idle) {
+            RecordMode::Recording(rec) => Some(rec),
+            other => {
+                self.record_mode = other;
+                None
+            }
+        }
+    }
+    // Replays `recording` from its first frame. While playing, real keyboard/mouse/touch events
+    // are ignored so the game sees exactly what was recorded.
+    pub fn play(&mut self, recording: InputRecording) {
+        self.record_mode = RecordMode::Playing(recording, 0);
+    }
+    pub fn stop_playback(&mut self) {
+        if self.is_playing() {
+            self.record_mode = RecordMode::Idle;
+        }
+    }
+    pub fn is_recording(&self) -> bool {
+        matches!(self.record_mode, RecordMode::Recording(_))
+    }
+    pub fn is_playing(&self) -> bool {
+        matches!(self.record_mode, RecordMode::Playing(..))
+    }
+
+    // Called by the Engine at the start of every frame, `delta` seconds after the last: either
+    // logs the frame's input or replaces it with the next recorded frame, returning that frame's
+    // time for the Engine to use instead.
+    pub(crate) fn begin_frame(&mut self, delta: f32) -> Option<f32> {
+        match &mut self.record_mode {
+            RecordMode::Idle => None,
+            RecordMode::Recording(rec) => {
+                let frame = InputFrame {
+                    delta,
+                    keys: self.now_keys.clone(),
+                    modifiers: self.now_modifiers,
+                    mouse: self.now_mouse.clone(),
+                    mouse_pos: self.now_mouse_pos,
+                    scroll: self.scroll,
+                    text: self.text.clone(),
+                    touches: self.touches.values().copied().collect(),
+                };
+                rec.frames.push(frame);
+                None
+            }
+            RecordMode::Playing(rec, index) => match rec.frames.get(*index) {
+                Some(frame) => {
+                    self.now_keys.copy_from_slice(&frame.keys);
+                    self.now_modifiers = frame.modifiers;
+                    self.now_mouse.copy_from_slice(&frame.mouse);
+                    self.now_mouse_pos = frame.mouse_pos;
+                    self.scroll = frame.scroll;
+                    self.text.clone_from(&frame.text);
+                    self.touches = frame.touches.iter().map(|t| (t.id, *t)).collect();
+                    *index += 1;
+                    Some(frame.delta)
+                }
+                None => {
+                    // Let go of anything the recording left held down.
+                    self.now_keys.fill(false);
+                    self.now_modifiers = Modifiers::empty();
+                    self.now_mouse.fill(false);
+                    self.touches.clear();
+                    self.record_mode = RecordMode::Idle;
+                    None
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::ops::Range;
+
+// A small, fast pseudo-random number generator (SplitMix64). Not for anything security-related,
+// but its whole state is one number, so it's cheap to copy into an EngineSnapshot and the same
+// seed always gives the same numbers, for replays and rollback. Engine::rng is one seeded
+// differently every run; replace it with Rng::new for repeatable runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    // Seeded from the clock and the standard library's per-process hash keys.
+    pub fn from_entropy() -> Self {
+        use std::hash::BuildHasher;
+        let keys = std::collections::hash_map::RandomState::new();
+        Self::new(keys.hash_one(crate::time::now().to_bits()))
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+    // From 0 up to (not including) 1.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+    pub fn range(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+    // range.start if the range is empty.
+    pub fn range_usize(&mut self, range: Range<usize>) -> usize {
+        let len = range.end.saturating_sub(range.start) as u64;
+        if len == 0 {
+            return range.start;
+        }
+        range.start + (self.next_u64() % len) as usize
+    }
+    // True with probability `p`, from 0 to 1.
+    pub fn chance(&mut self, p: f32) -> bool {
+        self.next_f32() < p
+    }
+}
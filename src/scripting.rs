@@ -0,0 +1,583 @@
+use crate::hot_reload::FileWatcher;
+use crate::{AnimationClip, Engine, GPUSprite, Input, Key, ScriptError, SpriteHandle};
+use rhai::{CallFnOptions, Dynamic, EvalAltResult, Scope, AST};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// One of the scripts Scripts::load loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ScriptId(usize);
+
+struct Script {
+    path: PathBuf,
+    ast: AST,
+    scope: Scope<'static>,
+    // The script's own state, `this` in its functions; kept through reloads.
+    this: Dynamic,
+    started: bool,
+}
+
+struct Timer {
+    id: i64,
+    script: usize,
+    function: String,
+    remaining: f64,
+    // For every: how long between calls.
+    interval: Option<f64>,
+}
+
+enum AnimationCommand {
+    Play(SpriteHandle, String),
+    Stop(SpriteHandle),
+}
+
+// What the scripts see of the game this frame, and what they asked for. The Engine fills it in
+// before the scripts run and carries out what they did after, so a script never holds on to the
+// Engine itself.
+#[derive(Default)]
+struct State {
+    dt: f64,
+    time: f64,
+    keys: Vec<(bool, bool, bool)>,
+    // Down, pressed and released, by bound action.
+    actions: HashMap<String, (bool, bool, bool)>,
+    axes: HashMap<String, f32>,
+    mouse: [f64; 2],
+    // Every group's screen_regions, which scripts move sprites by changing. Sprites they spawn
+    // are added to the end, where push_sprite will put them.
+    regions: Vec<Vec<[f32; 4]>>,
+    spawned: Vec<(usize, GPUSprite)>,
+    animations: Vec<AnimationCommand>,
+    timers: Vec<Timer>,
+    next_timer: i64,
+    // Which script is running, for its timers.
+    current: usize,
+}
+
+impl State {
+    fn region(&mut self, sprite: SpriteHandle) -> ScriptResult<&mut [f32; 4]> {
+        self.regions
+            .get_mut(sprite.group)
+            .and_then(|regions| regions.get_mut(sprite.index))
+            .ok_or_else(|| format!("no sprite {} in group {}", sprite.index, sprite.group).into())
+    }
+    fn add_timer(&mut self, seconds: f64, function: String, interval: Option<f64>) -> i64 {
+        let id = self.next_timer;
+        self.next_timer += 1;
+        self.timers.push(Timer {
+            id,
+            script: self.current,
+            function,
+            remaining: seconds,
+            interval,
+        });
+        id
+    }
+}
+
+// Rhai scripts that drive sprites, so behaviour can change without recompiling the game. The
+// Engine keeps one (Engine::scripts) and runs its scripts every frame before Game::update; with
+// EngineConfig::hot_reload, a script saved while the game runs is reloaded and carries on.
+//
+//     // scripts/bat.rhai
+//     fn init() {
+//         this.bat = spawn_sprite(0, 100.0, 100.0, 16.0, 16.0);
+//         this.speed = 40.0;
+//         play_animation(this.bat, "flap");
+//         every(2.0, "turn");
+//     }
+//     fn update(dt) {
+//         this.bat.x += this.speed * dt;
+//         if action_pressed("jump") { this.bat.y += 8.0; }
+//     }
+//     fn turn() { this.speed = -this.speed; }
+//     fn reload() { print("bat reloaded"); }
+//
+// Scripts keep their state in `this`, which outlives reloads, since Rhai functions can't see the
+// script's top-level variables. They get:
+//
+//     spawn_sprite(group, x, y, w, h), spawn_sprite(group, x, y, w, h, sx, sy, sw, sh)
+//         a new sprite in `group` (the whole texture, or that part of it)
+//     sprite(group, index)       one of the game's own sprites
+//     sprite.x, .y, .w, .h       its screen_region, to read or change
+//     sprite.move_by(dx, dy)
+//     play_animation(sprite, name), stop_animation(sprite)
+//                                clips the game gave Scripts::add_clip
+//     key_down(name), key_pressed(name), key_released(name)
+//                                keys by their Key names: "Space", "A", "Left"
+//     action_down(name), action_pressed(name), action_released(name), axis(name)
+//     mouse_x(), mouse_y(), time(), delta_time()
+//     after(seconds, "function"), every(seconds, "function"), cancel_timer(id)
+//                                call one of the script's functions later, or repeatedly
+//
+// Numbers can be written either way (1 or 1.0). print goes to the log. Errors are logged with the
+// script's path and don't stop the others.
+pub struct Scripts {
+    engine: rhai::Engine,
+    state: Rc<RefCell<State>>,
+    scripts: Vec<Option<Script>>,
+    clips: HashMap<String, Arc<AnimationClip>>,
+    files: FileWatcher<usize>,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scripts {
+    pub fn new() -> Self {
+        let state = Rc::new(RefCell::new(State::default()));
+        let mut engine = rhai::Engine::new();
+        engine.on_print(|text| log::info!("{text}"));
+        register_api(&mut engine, &state);
+        Self {
+            engine,
+            state,
+            scripts: Vec::new(),
+            clips: HashMap::new(),
+            files: FileWatcher::default(),
+        }
+    }
+    // Reads and compiles a script the way Engine::load_bytes reads any asset, then runs its
+    // top-level statements. Its init function runs before its first update.
+    pub async fn load(&mut self, path: impl AsRef<Path>) -> Result<ScriptId, ScriptError> {
+        let path = path.as_ref();
+        let io_error = |source| ScriptError::Io {
+            path: path.to_owned(),
+            source,
+        };
+        let bytes = crate::assets::read_bytes(path).await.map_err(io_error)?;
+        let source = String::from_utf8(bytes)
+            .map_err(|e| io_error(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?;
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|source| ScriptError::Compile {
+                path: path.to_owned(),
+                source,
+            })?;
+        let mut scope = Scope::new();
+        self.engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|source| ScriptError::Run {
+                path: path.to_owned(),
+                source,
+            })?;
+        let id = self.scripts.len();
+        self.files.watch(id, crate::assets::resolve(path));
+        self.scripts.push(Some(Script {
+            path: path.to_owned(),
+            ast,
+            scope,
+            this: Dynamic::from_map(Default::default()),
+            started: false,
+        }));
+        Ok(ScriptId(id))
+    }
+    // Stops running the script, along with its timers.
+    pub fn unload(&mut self, script: ScriptId) {
+        if let Some(slot) = self.scripts.get_mut(script.0) {
+            *slot = None;
+        }
+        self.files.unwatch(&script.0);
+        self.state
+            .borrow_mut()
+            .timers
+            .retain(|timer| timer.script != script.0);
+    }
+    // The clip play_animation(sprite, name) plays.
+    pub fn add_clip(&mut self, name: impl Into<String>, clip: Arc<AnimationClip>) {
+        self.clips.insert(name.into(), clip);
+    }
+    // Calls a function of the script's with `this` bound, e.g. from the game when something
+    // happens that the script handles. Missing functions are an error, like any other. Sprites
+    // and input are only there while the scripts run each frame, so from here a function should
+    // just change `this` and leave the rest to its update.
+    pub fn call(
+        &mut self,
+        script: ScriptId,
+        function: &str,
+        args: impl rhai::FuncArgs,
+    ) -> Result<Dynamic, ScriptError> {
+        let Some(Some(loaded)) = self.scripts.get_mut(script.0) else {
+            return Err(ScriptError::Run {
+                path: PathBuf::new(),
+                source: format!("script {} was unloaded", script.0).into(),
+            });
+        };
+        self.state.borrow_mut().current = script.0;
+        call(&self.engine, loaded, function, args).map_err(|source| ScriptError::Run {
+            path: loaded.path.clone(),
+            source,
+        })
+    }
+    // The state a script keeps in `this`, for the game to read or change.
+    pub fn this(&mut self, script: ScriptId) -> Option<&mut Dynamic> {
+        Some(&mut self.scripts.get_mut(script.0)?.as_mut()?.this)
+    }
+
+    // Runs init (the first time), the timers that are due and update for every script, then
+    // applies whatever they did to the Engine's sprites and animator.
+    pub(crate) fn update(engine: &mut Engine, dt: f32) {
+        if engine.scripts.scripts.iter().all(Option::is_none) {
+            return;
+        }
+        let time = engine.elapsed();
+        engine
+            .scripts
+            .state
+            .borrow_mut()
+            .begin(&engine.input, &engine.sprites, dt, time);
+        engine.scripts.run(dt);
+        let mut state = engine.scripts.state.borrow_mut();
+        // Only the sprites that moved, so the rest aren't uploaded again.
+        for (group, regions) in state.regions.iter().enumerate() {
+            let sprites = engine.sprites.get_sprites(group);
+            let changed: Vec<usize> = (0..sprites.len())
+                .filter(|&i| sprites[i].screen_region != regions[i])
+                .collect();
+            for i in changed {
+                engine.sprites.get_sprites_mut(group, i..i + 1)[0].screen_region = regions[i];
+            }
+        }
+        for (group, mut sprite) in std::mem::take(&mut state.spawned) {
+            let index = engine.sprites.get_sprites(group).len();
+            sprite.screen_region = state.regions[group][index];
+            engine.sprites.push_sprite(group, sprite);
+        }
+        for command in std::mem::take(&mut state.animations) {
+            match command {
+                AnimationCommand::Play(sprite, name) => match engine.scripts.clips.get(&name) {
+                    Some(clip) => engine.animator.play(sprite, clip.clone()),
+                    None => log::warn!("scripts: no clip named {name}; see Scripts::add_clip"),
+                },
+                AnimationCommand::Stop(sprite) => engine.animator.stop(sprite),
+            }
+        }
+        state.regions.clear();
+    }
+    // Reloads the scripts whose files changed and calls their reload functions. One that no
+    // longer compiles keeps running as it was.
+    pub(crate) fn reload_changed(&mut self) {
+        for (id, path) in self.files.changed() {
+            let Some(Some(script)) = self.scripts.get_mut(id) else {
+                continue;
+            };
+            let ast = match std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| self.engine.compile(source).map_err(|e| e.to_string()))
+            {
+                Ok(ast) => ast,
+                Err(e) => {
+                    log::error!("couldn't reload {}: {e}", path.display());
+                    continue;
+                }
+            };
+            script.ast = ast;
+            log::info!("reloaded {}", path.display());
+            if has_function(&script.ast, "reload") {
+                if let Err(e) = call(&self.engine, script, "reload", ()) {
+                    log::error!("{}: {e}", script.path.display());
+                }
+            }
+        }
+    }
+
+    fn run(&mut self, dt: f32) {
+        let dt = dt as f64;
+        // Timers first, so a script's update sees what its timers did.
+        let due: Vec<(usize, String)> = {
+            let mut state = self.state.borrow_mut();
+            let mut due = Vec::new();
+            state.timers.retain_mut(|timer| {
+                timer.remaining -= dt;
+                if timer.remaining > 0.0 {
+                    return true;
+                }
+                due.push((timer.script, timer.function.clone()));
+                match timer.interval {
+                    Some(interval) => {
+                        // Never more than once a frame, however short the interval.
+                        timer.remaining = (timer.remaining + interval).max(0.0);
+                        true
+                    }
+                    None => false,
+                }
+            });
+            due
+        };
+        for (index, function) in due {
+            self.run_function(index, &function, (), true);
+        }
+        for index in 0..self.scripts.len() {
+            self.run_function(index, "update", (dt,), false);
+        }
+    }
+    // Calls `function` on script `index` if it has it (or always, if `required`), starting the
+    // script first if it hasn't been. Errors go to the log.
+    fn run_function(
+        &mut self,
+        index: usize,
+        function: &str,
+        args: impl rhai::FuncArgs,
+        required: bool,
+    ) {
+        let Some(Some(script)) = self.scripts.get_mut(index) else {
+            return;
+        };
+        self.state.borrow_mut().current = index;
+        if !script.started {
+            script.started = true;
+            if has_function(&script.ast, "init") {
+                if let Err(e) = call(&self.engine, script, "init", ()) {
+                    log::error!("{}: {e}", script.path.display());
+                }
+            }
+        }
+        if required || has_function(&script.ast, function) {
+            if let Err(e) = call(&self.engine, script, function, args) {
+                log::error!("{}: {e}", script.path.display());
+            }
+        }
+    }
+}
+
+impl State {
+    fn begin(&mut self, input: &Input, sprites: &crate::SpriteRender, dt: f32, time: f64) {
+        self.dt = dt as f64;
+        self.time = time;
+        self.keys = (0..crate::input::KEY_COUNT)
+            .map(|i| match crate::input_buffer::key_from_index(i) {
+                Some(key) => (
+                    input.is_key_down(key),
+                    input.is_key_pressed(key),
+                    input.is_key_released(key),
+                ),
+                None => (false, false, false),
+            })
+            .collect();
+        let map = input.input_map();
+        self.actions = map
+            .actions()
+            .map(|action| {
+                let state = (
+                    input.is_action_down(action),
+                    input.is_action_pressed(action),
+                    input.is_action_released(action),
+                );
+                (action.to_owned(), state)
+            })
+            .collect();
+        self.axes = map
+            .axes()
+            .map(|axis| (axis.to_owned(), input.axis(axis)))
+            .collect();
+        let mouse = input.mouse_pos();
+        self.mouse = [mouse.x, mouse.y];
+        self.regions = (0..sprites.group_count())
+            .map(|group| {
+                sprites
+                    .get_sprites(group)
+                    .iter()
+                    .map(|sprite| sprite.screen_region)
+                    .collect()
+            })
+            .collect();
+    }
+}
+
+fn has_function(ast: &AST, name: &str) -> bool {
+    ast.iter_functions().any(|function| function.name == name)
+}
+
+fn call(
+    engine: &rhai::Engine,
+    script: &mut Script,
+    function: &str,
+    args: impl rhai::FuncArgs,
+) -> ScriptResult<Dynamic> {
+    let options = CallFnOptions::new()
+        .eval_ast(false)
+        .bind_this_ptr(&mut script.this);
+    engine.call_fn_with_options(options, &mut script.scope, &script.ast, function, args)
+}
+
+// A number a script passed, whether it wrote 1 or 1.0.
+fn number(value: Dynamic) -> ScriptResult<f32> {
+    if let Ok(float) = value.as_float() {
+        Ok(float as f32)
+    } else if let Ok(int) = value.as_int() {
+        Ok(int as f32)
+    } else {
+        Err(format!("expected a number, got {}", value.type_name()).into())
+    }
+}
+
+// Every Key's index in Input, by its name in lower case.
+fn key_names() -> HashMap<String, usize> {
+    (0..crate::input::KEY_COUNT)
+        .filter_map(|i| {
+            let key: Key = crate::input_buffer::key_from_index(i)?;
+            Some((format!("{key:?}").to_lowercase(), i))
+        })
+        .collect()
+}
+
+fn register_api(engine: &mut rhai::Engine, state: &Rc<RefCell<State>>) {
+    engine.register_type_with_name::<SpriteHandle>("Sprite");
+
+    let s = state.clone();
+    engine.register_fn(
+        "spawn_sprite",
+        move |group: i64, x: Dynamic, y: Dynamic, w: Dynamic, h: Dynamic| {
+            let region = [number(x)?, number(y)?, number(w)?, number(h)?];
+            spawn(&s, group, region, [0.0, 0.0, 1.0, 1.0])
+        },
+    );
+    let s = state.clone();
+    engine.register_fn(
+        "spawn_sprite",
+        move |group: i64,
+              x: Dynamic,
+              y: Dynamic,
+              w: Dynamic,
+              h: Dynamic,
+              sx: Dynamic,
+              sy: Dynamic,
+              sw: Dynamic,
+              sh: Dynamic| {
+            let region = [number(x)?, number(y)?, number(w)?, number(h)?];
+            let sheet = [number(sx)?, number(sy)?, number(sw)?, number(sh)?];
+            spawn(&s, group, region, sheet)
+        },
+    );
+    engine.register_fn("sprite", |group: i64, index: i64| {
+        SpriteHandle::new(group.max(0) as usize, index.max(0) as usize)
+    });
+    engine.register_get("group", |sprite: &mut SpriteHandle| sprite.group as i64);
+    engine.register_get("index", |sprite: &mut SpriteHandle| sprite.index as i64);
+    for (name, i) in [("x", 0), ("y", 1), ("w", 2), ("h", 3)] {
+        let get = state.clone();
+        let set = state.clone();
+        engine.register_get(
+            name,
+            move |sprite: &mut SpriteHandle| -> ScriptResult<f64> {
+                Ok(get.borrow_mut().region(*sprite)?[i] as f64)
+            },
+        );
+        engine.register_set(
+            name,
+            move |sprite: &mut SpriteHandle, value: Dynamic| -> ScriptResult<()> {
+                set.borrow_mut().region(*sprite)?[i] = number(value)?;
+                Ok(())
+            },
+        );
+    }
+    let s = state.clone();
+    engine.register_fn(
+        "move_by",
+        move |sprite: &mut SpriteHandle, dx: Dynamic, dy: Dynamic| -> ScriptResult<()> {
+            let (dx, dy) = (number(dx)?, number(dy)?);
+            let mut state = s.borrow_mut();
+            let region = state.region(*sprite)?;
+            region[0] += dx;
+            region[1] += dy;
+            Ok(())
+        },
+    );
+
+    let s = state.clone();
+    engine.register_fn("play_animation", move |sprite: SpriteHandle, name: &str| {
+        s.borrow_mut()
+            .animations
+            .push(AnimationCommand::Play(sprite, name.to_owned()));
+    });
+    let s = state.clone();
+    engine.register_fn("stop_animation", move |sprite: SpriteHandle| {
+        s.borrow_mut()
+            .animations
+            .push(AnimationCommand::Stop(sprite));
+    });
+
+    type KeyState = fn(&(bool, bool, bool)) -> bool;
+    let phases: [(&str, &str, KeyState); 3] = [
+        ("key_down", "action_down", |state| state.0),
+        ("key_pressed", "action_pressed", |state| state.1),
+        ("key_released", "action_released", |state| state.2),
+    ];
+    let keys = Rc::new(key_names());
+    for (key_fn, action_fn, phase) in phases {
+        let s = state.clone();
+        let keys = keys.clone();
+        engine.register_fn(key_fn, move |name: &str| -> ScriptResult<bool> {
+            let Some(&index) = keys.get(&name.to_lowercase()) else {
+                return Err(format!("no key named {name}").into());
+            };
+            Ok(s.borrow().keys.get(index).is_some_and(phase))
+        });
+        let s = state.clone();
+        engine.register_fn(action_fn, move |name: &str| {
+            s.borrow().actions.get(name).is_some_and(phase)
+        });
+    }
+    let s = state.clone();
+    engine.register_fn("axis", move |name: &str| {
+        s.borrow().axes.get(name).copied().unwrap_or(0.0) as f64
+    });
+    let s = state.clone();
+    engine.register_fn("mouse_x", move || s.borrow().mouse[0]);
+    let s = state.clone();
+    engine.register_fn("mouse_y", move || s.borrow().mouse[1]);
+    let s = state.clone();
+    engine.register_fn("time", move || s.borrow().time);
+    let s = state.clone();
+    engine.register_fn("delta_time", move || s.borrow().dt);
+
+    let s = state.clone();
+    engine.register_fn(
+        "after",
+        move |seconds: Dynamic, function: &str| -> ScriptResult<i64> {
+            let seconds = number(seconds)? as f64;
+            Ok(s.borrow_mut().add_timer(seconds, function.to_owned(), None))
+        },
+    );
+    let s = state.clone();
+    engine.register_fn(
+        "every",
+        move |seconds: Dynamic, function: &str| -> ScriptResult<i64> {
+            let seconds = number(seconds)? as f64;
+            let mut state = s.borrow_mut();
+            Ok(state.add_timer(seconds, function.to_owned(), Some(seconds)))
+        },
+    );
+    let s = state.clone();
+    engine.register_fn("cancel_timer", move |id: i64| {
+        s.borrow_mut().timers.retain(|timer| timer.id != id);
+    });
+}
+
+fn spawn(
+    state: &Rc<RefCell<State>>,
+    group: i64,
+    screen_region: [f32; 4],
+    sheet_region: [f32; 4],
+) -> ScriptResult<SpriteHandle> {
+    let mut state = state.borrow_mut();
+    let group = usize::try_from(group).map_err(|_| format!("no group {group}"))?;
+    let Some(regions) = state.regions.get_mut(group) else {
+        return Err(format!("no group {group}").into());
+    };
+    regions.push(screen_region);
+    let index = regions.len() - 1;
+    state
+        .spawned
+        .push((group, GPUSprite::new(screen_region, sheet_region)));
+    Ok(SpriteHandle::new(group, index))
+}
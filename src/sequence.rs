@@ -0,0 +1,225 @@
+use crate::{AnimationClip, Engine, SpriteHandle, Vec2};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+type Action = Box<dyn FnMut(&mut Engine)>;
+type Condition = Box<dyn FnMut(&Engine) -> bool>;
+
+enum Step {
+    Wait(f32),
+    MoveTo {
+        sprite: SpriteHandle,
+        target: Vec2,
+        duration: f32,
+    },
+    Play(SpriteHandle, Arc<AnimationClip>),
+    WaitForAnimation(SpriteHandle),
+    Call(Action),
+    WaitUntil(Condition),
+}
+
+// A script of steps run one after another across frames, for cutscenes and scripted events:
+//
+//     let intro = Sequence::new()
+//         .move_to(king, [200.0, 64.0], 1.5)
+//         .wait(2.0)
+//         .play(king, bow.clone())
+//         .wait_for_animation(king)
+//         .call(|engine| {
+//             let _ = engine.audio.play_sound("fanfare.ogg", SoundOptions::default());
+//         })
+//         .wait_until(|engine| engine.input.is_key_pressed(Key::Space));
+//     engine.sequencer.start(intro);
+//
+// Anything without a step of its own (showing text, changing the game's state) goes in a call.
+#[derive(Default)]
+pub struct Sequence {
+    steps: VecDeque<Step>,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Does nothing for `seconds` of game time.
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.steps.push_back(Step::Wait(seconds));
+        self
+    }
+    // Slides the sprite's bottom-left corner from wherever it is when the step starts to
+    // `target`, evenly over `seconds`.
+    pub fn move_to(mut self, sprite: SpriteHandle, target: impl Into<Vec2>, seconds: f32) -> Self {
+        self.steps.push_back(Step::MoveTo {
+            sprite,
+            target: target.into(),
+            duration: seconds,
+        });
+        self
+    }
+    // Starts `clip` on the sprite through Engine::animator and goes straight on.
+    pub fn play(mut self, sprite: SpriteHandle, clip: Arc<AnimationClip>) -> Self {
+        self.steps.push_back(Step::Play(sprite, clip));
+        self
+    }
+    // Waits until the sprite's animation has finished (or been stopped). Looping clips never
+    // finish.
+    pub fn wait_for_animation(mut self, sprite: SpriteHandle) -> Self {
+        self.steps.push_back(Step::WaitForAnimation(sprite));
+        self
+    }
+    // Runs `action` once and goes straight on.
+    pub fn call(mut self, action: impl FnMut(&mut Engine) + 'static) -> Self {
+        self.steps.push_back(Step::Call(Box::new(action)));
+        self
+    }
+    // Waits until `condition` is true, checking once a frame.
+    pub fn wait_until(mut self, condition: impl FnMut(&Engine) -> bool + 'static) -> Self {
+        self.steps.push_back(Step::WaitUntil(Box::new(condition)));
+        self
+    }
+    // Adds `other`'s steps after these.
+    pub fn then(mut self, other: Sequence) -> Self {
+        self.steps.extend(other.steps);
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+// One of the Sequencer's running sequences.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SequenceId(u64);
+
+struct Running {
+    id: SequenceId,
+    sequence: Sequence,
+    // Seconds into the current step.
+    time: f32,
+    // Where a MoveTo step started from, once it has.
+    from: Option<Vec2>,
+}
+
+// Runs Sequences, any number at once. The Engine keeps one (Engine::sequencer) and advances it
+// every frame before Game::update, while the game isn't paused.
+#[derive(Default)]
+pub struct Sequencer {
+    running: Vec<Running>,
+    next_id: u64,
+    // Stopped while the sequences were being advanced.
+    stopped: Vec<SequenceId>,
+}
+
+impl Sequencer {
+    pub fn start(&mut self, sequence: Sequence) -> SequenceId {
+        let id = SequenceId(self.next_id);
+        self.next_id += 1;
+        self.running.push(Running {
+            id,
+            sequence,
+            time: 0.0,
+            from: None,
+        });
+        id
+    }
+    // Stops the sequence where it is; a sprite halfway through a move_to stays halfway.
+    pub fn stop(&mut self, id: SequenceId) {
+        self.running.retain(|running| running.id != id);
+        self.stopped.push(id);
+    }
+    pub fn stop_all(&mut self) {
+        let ids = self.running.iter().map(|running| running.id);
+        self.stopped.extend(ids);
+        self.running.clear();
+    }
+    // Whether the sequence still has steps to go.
+    pub fn is_running(&self, id: SequenceId) -> bool {
+        self.running.iter().any(|running| running.id == id)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    // Advances every sequence by `dt` seconds. Steps that finish let the next one start in the
+    // same frame, so a run of calls all happen at once.
+    pub(crate) fn update(engine: &mut Engine, dt: f32) {
+        let mut running = std::mem::take(&mut engine.sequencer.running);
+        running.retain_mut(|running| {
+            let mut dt = dt;
+            loop {
+                // Calls can stop their own sequence.
+                if engine.sequencer.stopped.contains(&running.id) {
+                    return false;
+                }
+                let Some(step) = running.sequence.steps.front_mut() else {
+                    return false;
+                };
+                running.time += dt;
+                let done = advance(step, engine, running.time, &mut running.from);
+                if !done {
+                    return true;
+                }
+                // Time past the end of a timed step carries into the next.
+                dt = match step {
+                    Step::Wait(seconds) => running.time - *seconds,
+                    Step::MoveTo { duration, .. } => running.time - *duration,
+                    _ => 0.0,
+                }
+                .max(0.0);
+                running.sequence.steps.pop_front();
+                running.time = 0.0;
+                running.from = None;
+            }
+        });
+        // Sequences started by calls go after the ones that were already running.
+        running.append(&mut engine.sequencer.running);
+        engine.sequencer.running = running;
+        engine.sequencer.stopped.clear();
+    }
+}
+
+// Does a frame of `step`, `time` seconds into it, returning whether it's finished.
+fn advance(step: &mut Step, engine: &mut Engine, time: f32, from: &mut Option<Vec2>) -> bool {
+    match step {
+        Step::Wait(seconds) => time >= *seconds,
+        Step::MoveTo {
+            sprite,
+            target,
+            duration,
+        } => {
+            let Some(current) = engine
+                .sprites
+                .try_get_sprites(sprite.group)
+                .and_then(|sprites| sprites.get(sprite.index))
+            else {
+                return true;
+            };
+            let start = *from.get_or_insert(current.position());
+            let t = if *duration > 0.0 {
+                (time / *duration).min(1.0)
+            } else {
+                1.0
+            };
+            engine
+                .sprites
+                .get_sprites_mut(sprite.group, sprite.index..sprite.index + 1)[0]
+                .set_position(start.lerp(*target, t));
+            t >= 1.0
+        }
+        Step::Play(sprite, clip) => {
+            engine.animator.play(*sprite, clip.clone());
+            true
+        }
+        Step::WaitForAnimation(sprite) => {
+            engine.animator.clip(*sprite).is_none() || engine.animator.is_finished(*sprite)
+        }
+        Step::Call(action) => {
+            action(engine);
+            true
+        }
+        Step::WaitUntil(condition) => condition(engine),
+    }
+}
@@ -19,7 +19,14 @@ struct Camera {
 // GPUSprite, from before
 struct GPUSprite {
     to_rect:vec4<f32>,
-    from_rect:vec4<f32>
+    from_rect:vec4<f32>,
+    depth:f32,
+    flags:u32,
+    // Four 8-bit sRGB channels.
+    outline_color:u32,
+    outline_width:f32,
+    // Packed like outline_color, one per corner.
+    tint:vec4<u32>
 }
 
 // One binding for the camera...
@@ -33,6 +40,31 @@ var<storage, read> sprites: array<GPUSprite>;
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    // The rest is the same for the whole sprite.
+    @location(1) @interpolate(flat) sheet_region: vec4<f32>,
+    @location(2) @interpolate(flat) flags: u32,
+    @location(3) @interpolate(flat) outline_color: vec4<f32>,
+    @location(4) @interpolate(flat) outline_width: f32,
+    // Blended between the corners.
+    @location(5) tint: vec4<f32>,
+}
+
+// Matches GPUSprite::TINT.
+const FLAG_TINT: u32 = 2u;
+
+// Tints and outlines are sent as sRGB, like colors are written; everything here is linear.
+fn unpack_srgb(color: u32) -> vec4<f32> {
+    let rgba = unpack4x8unorm(color);
+    let rgb = select(pow((rgba.rgb + 0.055) / 1.055, vec3(2.4)), rgba.rgb / 12.92, rgba.rgb <= vec3(0.04045));
+    return vec4(rgb, rgba.a);
+}
+
+// Corners are numbered like GPUSprite::tint: x + 2y.
+fn corner_tint(flags: u32, tint: vec4<u32>, which_vtx: vec2<f32>) -> vec4<f32> {
+    if (flags & FLAG_TINT) == 0u {
+        return vec4(1.0);
+    }
+    return unpack_srgb(tint[u32(which_vtx.x + 2.0 * which_vtx.y)]);
 }
 
 @vertex
@@ -41,7 +73,7 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32,
            @builtin(instance_index) sprite_index:u32) -> VertexOutput {
     // The corner and size of the sprite in world space.
     // Which sprite? sprites[sprite_index]
-    let corner:vec4<f32> = vec4(sprites[sprite_index].to_rect.xy,0.,1.);
+    let corner:vec4<f32> = vec4(sprites[sprite_index].to_rect.xy,sprites[sprite_index].depth,1.);
     let size:vec2<f32> = sprites[sprite_index].to_rect.zw;
     // The corner and size of the texture area in UVs
     let tex_corner:vec2<f32> = sprites[sprite_index].from_rect.xy;
@@ -50,11 +82,18 @@ fn vs_main(@builtin(vertex_index) in_vertex_index: u32,
     let which_vtx:vec2<f32> = VERTICES[in_vertex_index];
     // Which corner of the UV square we need to draw (UV coordinates are flipped in Y)
     let which_uv: vec2<f32> = vec2(VERTICES[in_vertex_index].x, 1.0 - VERTICES[in_vertex_index].y);
+    // This corner's tint color, or white (no change) for untinted sprites
+    let tint:vec4<f32> = corner_tint(sprites[sprite_index].flags, sprites[sprite_index].tint, which_vtx);
     return VertexOutput(
         // Offset corner by size * which_vtx to get the right corner, then do camera stuff. Dividing screen size by 2 and the last subtraction are to deal with the NDC coordinate space, which goes from -1 to 1 in WGPU.
         ((corner + vec4(which_vtx*size,0.,0.) - vec4(camera.screen_pos,0.,0.)) / vec4(camera.screen_size/2., 1.0, 1.0)) - vec4(1.0, 1.0, 0.0, 0.0),
         // Offset texture corner by tex_size * which_uv to get the right corner
-        tex_corner + which_uv*tex_size
+        tex_corner + which_uv*tex_size,
+        sprites[sprite_index].from_rect,
+        sprites[sprite_index].flags,
+        unpack_srgb(sprites[sprite_index].outline_color),
+        sprites[sprite_index].outline_width,
+        tint
     );
 }
 
@@ -66,14 +105,4 @@ var t_diffuse: texture_2d<f32>;
 @group(1) @binding(1)
 var s_diffuse: sampler;
 // Both are in the same binding group here since they go together naturally.
-
-// Our fragment shader takes an interpolated `VertexOutput` as input now
-@fragment
-fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
-    // And we use the tex coords from the vertex output to sample from the texture.
-    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
-    // This is new: if the alpha value of the color is very low, don't draw any fragment here.
-    // This is like "cutout" transparency.
-    if color.w < 0.2 { discard; }
-    return color;
-}
\ No newline at end of file
+// fs_main comes from shader_fragment.wgsl, or from the game (SpriteShader::Fragment).
@@ -0,0 +1,48 @@
+// SpriteRender::set_group_gpu_culling: copies the sprites that overlap the camera from `source`
+// into the buffer the group draws from, and counts them into the group's draw arguments.
+
+// Same layout as GPUSprite.
+struct Sprite {
+    screen_region: vec4<f32>,
+    sheet_region: vec4<f32>,
+    depth: f32,
+    flags: u32,
+    outline_color: u32,
+    outline_width: f32,
+    tint: vec4<u32>,
+}
+
+struct Params {
+    // The camera's screen_pos and screen_size.
+    view: vec4<f32>,
+    count: u32,
+    // Which of `args` is this group's.
+    slot: u32,
+}
+
+struct DrawArgs {
+    vertex_count: u32,
+    instance_count: atomic<u32>,
+    first_vertex: u32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> source: array<Sprite>;
+@group(0) @binding(2) var<storage, read_write> visible: array<Sprite>;
+@group(0) @binding(3) var<storage, read_write> args: array<DrawArgs>;
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.count {
+        return;
+    }
+    let sprite = source[id.x];
+    let region = sprite.screen_region;
+    // Negative sizes flip the sprite, so its region runs the other way.
+    let low = min(region.xy, region.xy + region.zw);
+    let high = max(region.xy, region.xy + region.zw);
+    if all(low < params.view.xy + params.view.zw) && all(high > params.view.xy) {
+        visible[atomicAdd(&args[params.slot].instance_count, 1u)] = sprite;
+    }
+}
@@ -0,0 +1,16 @@
+// Fragment shaders for SpriteRender::set_debug_view. Both output white; the pipelines' blend
+// states turn that into the group's color (fs_main) or a step up the heat map (fs_overdraw).
+
+@fragment
+fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
+    if textureSample(t_diffuse, s_diffuse, in.tex_coords).w < 0.2 {
+        discard;
+    }
+    return vec4(1.0);
+}
+
+// Transparent texels cost as much to shade as opaque ones, so they count here.
+@fragment
+fn fs_overdraw(in:VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(1.0);
+}
@@ -0,0 +1,46 @@
+// The built-in sprite fragment shader, appended to shader.wgsl or shader_instanced.wgsl.
+
+// Matches GPUSprite::OUTLINE.
+const FLAG_OUTLINE: u32 = 1u;
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+// Whether any opaque pixel of the sprite is within `width` texels of `uv`. Looks in eight
+// directions, which is plenty for the few-texel outlines this is meant for.
+fn near_opaque(uv: vec2<f32>, region: vec4<f32>, width: f32) -> bool {
+    let texel = width / vec2<f32>(textureDimensions(t_diffuse));
+    let lo = region.xy;
+    let hi = region.xy + region.zw;
+    for (var i = 0; i < 8; i++) {
+        let angle = f32(i) * 0.7853982;
+        let p = uv + vec2(cos(angle), sin(angle)) * texel;
+        // Neighboring frames on the sheet don't count.
+        if any(p < lo) || any(p > hi) {
+            continue;
+        }
+        if textureSampleLevel(t_diffuse, s_diffuse, p, 0.0).w >= 0.2 {
+            return true;
+        }
+    }
+    return false;
+}
+
+// Our fragment shader takes an interpolated `VertexOutput` as input now
+@fragment
+fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
+    // And we use the tex coords from the vertex output to sample from the texture.
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    // This is new: if the alpha value of the color is very low, don't draw any fragment here.
+    // This is like "cutout" transparency.
+    if color.w < 0.2 {
+        // Outlined sprites fill in the transparent texels around their edges instead.
+        if (in.flags & FLAG_OUTLINE) != 0u && near_opaque(in.tex_coords, in.sheet_region, in.outline_width) {
+            return vec4(srgb_to_linear(in.outline_color.rgb), in.outline_color.a);
+        }
+        discard;
+    }
+    // Untinted sprites have a white tint, which changes nothing.
+    return color * vec4(srgb_to_linear(in.tint.rgb), in.tint.a);
+}
@@ -0,0 +1,81 @@
+// The same sprite shader as shader.wgsl, for devices without storage buffers (WebGL2).
+// Each sprite arrives as per-instance vertex attributes instead of being looked up by index.
+var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    vec2<f32>(0., 0.),
+    vec2<f32>(1., 0.),
+    vec2<f32>(0., 1.),
+    vec2<f32>(0., 1.),
+    vec2<f32>(1., 0.),
+    vec2<f32>(1., 1.)
+);
+
+struct Camera {
+    screen_pos: vec2<f32>,
+    screen_size: vec2<f32>
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// One GPUSprite, read from the instance buffer.
+struct SpriteInput {
+    @location(0) to_rect: vec4<f32>,
+    @location(1) from_rect: vec4<f32>,
+    @location(2) depth: f32,
+    @location(3) flags: u32,
+    @location(4) outline_color: u32,
+    @location(5) outline_width: f32,
+    @location(6) tint: vec4<u32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    // The rest is the same for the whole sprite.
+    @location(1) @interpolate(flat) sheet_region: vec4<f32>,
+    @location(2) @interpolate(flat) flags: u32,
+    @location(3) @interpolate(flat) outline_color: vec4<f32>,
+    @location(4) @interpolate(flat) outline_width: f32,
+    // Blended between the corners.
+    @location(5) tint: vec4<f32>,
+}
+
+const FLAG_TINT: u32 = 2u;
+
+fn unpack_srgb(color: u32) -> vec4<f32> {
+    let rgba = unpack4x8unorm(color);
+    let rgb = select(pow((rgba.rgb + 0.055) / 1.055, vec3(2.4)), rgba.rgb / 12.92, rgba.rgb <= vec3(0.04045));
+    return vec4(rgb, rgba.a);
+}
+
+fn corner_tint(flags: u32, tint: vec4<u32>, which_vtx: vec2<f32>) -> vec4<f32> {
+    if (flags & FLAG_TINT) == 0u {
+        return vec4(1.0);
+    }
+    return unpack_srgb(tint[u32(which_vtx.x + 2.0 * which_vtx.y)]);
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32, sprite: SpriteInput) -> VertexOutput {
+    let corner:vec4<f32> = vec4(sprite.to_rect.xy,sprite.depth,1.);
+    let size:vec2<f32> = sprite.to_rect.zw;
+    let tex_corner:vec2<f32> = sprite.from_rect.xy;
+    let tex_size:vec2<f32> = sprite.from_rect.zw;
+    let which_vtx:vec2<f32> = VERTICES[in_vertex_index];
+    let which_uv: vec2<f32> = vec2(VERTICES[in_vertex_index].x, 1.0 - VERTICES[in_vertex_index].y);
+    let tint:vec4<f32> = corner_tint(sprite.flags, sprite.tint, which_vtx);
+    return VertexOutput(
+        ((corner + vec4(which_vtx*size,0.,0.) - vec4(camera.screen_pos,0.,0.)) / vec4(camera.screen_size/2., 1.0, 1.0)) - vec4(1.0, 1.0, 0.0, 0.0),
+        tex_corner + which_uv*tex_size,
+        sprite.from_rect,
+        sprite.flags,
+        unpack_srgb(sprite.outline_color),
+        sprite.outline_width,
+        tint
+    );
+}
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1) @binding(1)
+var s_diffuse: sampler;
@@ -0,0 +1,25 @@
+// Palette swapping: the group's texture holds color indices in its red channel, and each index
+// picks a pixel from the first row of the palette texture.
+
+@group(1) @binding(2)
+var t_palette: texture_2d<f32>;
+
+// Sprite textures are sRGB, so the sampled index comes back linearized and has to be re-encoded
+// before it can be turned back into an integer.
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        return c * 12.92;
+    }
+    return 1.055 * pow(c, 1.0 / 2.4) - 0.055;
+}
+
+@fragment
+fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
+    let texel:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    if texel.w < 0.2 { discard; }
+    let palette_size = textureDimensions(t_palette);
+    let index = min(u32(round(linear_to_srgb(texel.r) * 255.0)), palette_size.x - 1u);
+    let color = textureLoad(t_palette, vec2<u32>(index, 0u), 0);
+    if color.w < 0.2 { discard; }
+    return color;
+}
@@ -0,0 +1,34 @@
+// Signed distance field text (ShaderId::SDF_TEXT), for glyphs from GlyphAtlas::new_sdf. Alpha
+// holds the distance to the glyph's edge: 0.5 on the edge, more inside, less outside.
+
+// Match GPUSprite::OUTLINE and GPUSprite::SOFT.
+const FLAG_OUTLINE: u32 = 1u;
+const FLAG_SOFT: u32 = 4u;
+// How much the field changes per atlas texel (0.5 over the glyph atlas's SDF_SPREAD).
+const SDF_TEXEL: f32 = 0.125;
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+@fragment
+fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
+    let distance = textureSample(t_diffuse, s_diffuse, in.tex_coords).w;
+    // Half a screen pixel's worth of field, so edges stay about a pixel wide at any zoom.
+    var width = max(fwidth(distance) * 0.5, 0.001);
+    // Soft sprites fade out over the whole field instead, for blurry drop shadows.
+    if (in.flags & FLAG_SOFT) != 0u {
+        width = 0.5;
+    }
+    let fill = smoothstep(0.5 - width, 0.5 + width, distance);
+    var color = vec4(srgb_to_linear(in.tint.rgb), in.tint.a * fill);
+    if (in.flags & FLAG_OUTLINE) != 0u {
+        let outer = 0.5 - in.outline_width * SDF_TEXEL;
+        let outline = smoothstep(outer - width, outer + width, distance) * in.outline_color.a;
+        color = vec4(mix(srgb_to_linear(in.outline_color.rgb), color.rgb, fill), max(outline, color.a));
+    }
+    if color.a <= 0.0 {
+        discard;
+    }
+    return color;
+}
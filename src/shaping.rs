@@ -0,0 +1,71 @@
+use crate::text::{ShapedGlyph, StyledChar};
+use ab_glyph::{Font as _, ScaleFont as _};
+
+// Lays out one line the way TextLayout::shaping asks for: split into runs by direction (the
+// Unicode bidi algorithm) and then by font, each run shaped by rustybuzz, the runs placed left to
+// right in visual order.
+pub(crate) fn shape(
+    fonts: &[ab_glyph::FontArc],
+    font_data: &[Vec<u8>],
+    size: f32,
+    line: &[StyledChar],
+) -> (Vec<ShapedGlyph>, f32) {
+    let text: String = line.iter().map(|styled| styled.c).collect();
+    // Which character of `line` each byte of `text` belongs to.
+    let mut char_at = Vec::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        char_at.extend(std::iter::repeat_n(i, c.len_utf8()));
+    }
+    let mut glyphs = Vec::with_capacity(line.len());
+    let mut pen = 0.0;
+    let bidi = unicode_bidi::BidiInfo::new(&text, None);
+    for paragraph in &bidi.paragraphs {
+        let (levels, runs) = bidi.visual_runs(paragraph, paragraph.range.clone());
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+            let font_of = |byte: usize| line[char_at[byte]].font;
+            let mut pieces = Vec::new();
+            let mut start = run.start;
+            for (offset, _) in text[run.clone()].char_indices().skip(1) {
+                let at = run.start + offset;
+                if font_of(at) != font_of(start) {
+                    pieces.push(start..at);
+                    start = at;
+                }
+            }
+            pieces.push(start..run.end);
+            // The run's pieces are in reading order, which is backwards on screen.
+            if rtl {
+                pieces.reverse();
+            }
+            for piece in pieces {
+                let font = font_of(piece.start);
+                let Some(face) = rustybuzz::Face::from_slice(&font_data[font.0], 0) else {
+                    continue;
+                };
+                let mut buffer = rustybuzz::UnicodeBuffer::new();
+                buffer.push_str(&text[piece.clone()]);
+                buffer.set_direction(if rtl {
+                    rustybuzz::Direction::RightToLeft
+                } else {
+                    rustybuzz::Direction::LeftToRight
+                });
+                let shaped = rustybuzz::shape(&face, &[], buffer);
+                // rustybuzz works in font units; this is how ab_glyph scales them to `size`.
+                let scaled = fonts[font.0].as_scaled(size);
+                let (h_scale, v_scale) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+                for (info, position) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                    glyphs.push(ShapedGlyph {
+                        font,
+                        glyph: ab_glyph::GlyphId(info.glyph_id as u16),
+                        x: pen + position.x_offset as f32 * h_scale,
+                        y: position.y_offset as f32 * v_scale,
+                        span: line[char_at[piece.start + info.cluster as usize]].span,
+                    });
+                    pen += position.x_advance as f32 * h_scale;
+                }
+            }
+        }
+    }
+    (glyphs, pen)
+}
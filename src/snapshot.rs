@@ -0,0 +1,67 @@
+use crate::{
+    Animator, GPUCamera, GPUSprite, Kinematics, Rng, SecondaryWindowId, SpriteRender, Triggers,
+    WGPU,
+};
+
+#[derive(Clone)]
+pub(crate) struct GroupSnapshot {
+    sprites: Vec<GPUSprite>,
+    camera: GPUCamera,
+}
+
+// Everything Engine::snapshot copies: the sprites and cameras of every group (the secondary
+// windows' too), the motions, trigger and animation state, Engine::rng, and the game clock. It's
+// all on the CPU, so taking one every frame for rewinding or rollback netcode is cheap; the game's
+// own state goes in its own snapshot alongside. Textures, shaders and the groups' other settings
+// aren't in it, nor is Engine::world with the ecs feature.
+#[derive(Clone)]
+pub struct EngineSnapshot {
+    pub(crate) sprites: Vec<GroupSnapshot>,
+    pub(crate) windows: Vec<(SecondaryWindowId, Vec<GroupSnapshot>)>,
+    pub(crate) kinematics: Kinematics,
+    pub(crate) triggers: Triggers,
+    pub(crate) animator: Animator,
+    pub(crate) rng: Rng,
+    pub(crate) elapsed: f64,
+    pub(crate) fixed_time: f32,
+}
+
+impl EngineSnapshot {
+    // Engine::elapsed when it was taken.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+    // Roughly how many bytes of sprites it holds.
+    pub fn sprite_bytes(&self) -> usize {
+        let groups = self
+            .sprites
+            .iter()
+            .chain(self.windows.iter().flat_map(|(_, groups)| groups));
+        groups
+            .map(|group| std::mem::size_of_val(&group.sprites[..]))
+            .sum()
+    }
+}
+
+pub(crate) fn capture(sprites: &SpriteRender) -> Vec<GroupSnapshot> {
+    (0..sprites.group_count())
+        .map(|which| GroupSnapshot {
+            sprites: sprites.get_sprites(which).to_vec(),
+            camera: sprites.get_camera(which),
+        })
+        .collect()
+}
+
+// Puts the groups back the way they were, only touching those that changed so the rest aren't
+// uploaded again. Groups added since are left alone, and ones removed since aren't brought back.
+pub(crate) fn apply(sprites: &mut SpriteRender, gpu: &WGPU, groups: &[GroupSnapshot]) {
+    for (which, group) in groups.iter().enumerate().take(sprites.group_count()) {
+        let current: &[u8] = bytemuck::cast_slice(sprites.get_sprites(which));
+        if current != bytemuck::cast_slice::<GPUSprite, u8>(&group.sprites) {
+            sprites.set_sprites(which, group.sprites.clone());
+        }
+        if bytemuck::bytes_of(&sprites.get_camera(which)) != bytemuck::bytes_of(&group.camera) {
+            sprites.set_camera(gpu, which, group.camera);
+        }
+    }
+}
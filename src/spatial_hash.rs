@@ -0,0 +1,130 @@
+use crate::Rect;
+use std::collections::HashMap;
+
+// A broad phase for finding what's near a spot without looking at everything: items are filed
+// under every square cell of a grid their rectangle touches. Cells work best around the size of a
+// typical item; much smaller and big items sit in lots of cells, much bigger and each cell holds
+// too many items to be much of a shortcut. SpriteRender::set_group_spatial_hash keeps one up to
+// date for a group's sprites.
+#[derive(Clone, Debug)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // Indexed by item, for finding their cells again when they move or go.
+    items: Vec<Option<Rect>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell size has to be positive");
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            items: Vec::new(),
+        }
+    }
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+    // Adds `item` covering `rect`, or moves it there if it's already in.
+    pub fn insert(&mut self, item: usize, rect: Rect) {
+        self.remove(item);
+        if item >= self.items.len() {
+            self.items.resize(item + 1, None);
+        }
+        self.items[item] = Some(rect);
+        let (xs, ys) = self.cell_range(&rect);
+        for cx in xs {
+            for cy in ys.clone() {
+                self.cells.entry((cx, cy)).or_default().push(item);
+            }
+        }
+    }
+    pub fn remove(&mut self, item: usize) {
+        let Some(rect) = self.items.get_mut(item).and_then(Option::take) else {
+            return;
+        };
+        let (xs, ys) = self.cell_range(&rect);
+        for cx in xs {
+            for cy in ys.clone() {
+                if let Some(cell) = self.cells.get_mut(&(cx, cy)) {
+                    cell.retain(|&other| other != item);
+                    if cell.is_empty() {
+                        self.cells.remove(&(cx, cy));
+                    }
+                }
+            }
+        }
+    }
+    // Removes every item from `len` on.
+    pub fn truncate(&mut self, len: usize) {
+        for item in len..self.items.len() {
+            self.remove(item);
+        }
+        self.items.truncate(len);
+    }
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.items.clear();
+    }
+    pub fn get(&self, item: usize) -> Option<Rect> {
+        self.items.get(item).copied().flatten()
+    }
+    // The items whose rectangles overlap `rect`, in order.
+    pub fn query_region(&self, rect: &Rect) -> Vec<usize> {
+        let (xs, ys) = self.cell_range(rect);
+        let mut found = Vec::new();
+        let span = |range: &std::ops::RangeInclusive<i32>| {
+            (*range.end() as i64 - *range.start() as i64 + 1).max(0)
+        };
+        if span(&xs) * span(&ys) > self.cells.len() as i64 {
+            // Covers more of the grid than is filled in; cheaper to go through what's there.
+            for (&(cx, cy), cell) in &self.cells {
+                if xs.contains(&cx) && ys.contains(&cy) {
+                    found.extend_from_slice(cell);
+                }
+            }
+        } else {
+            for cx in xs {
+                for cy in ys.clone() {
+                    if let Some(cell) = self.cells.get(&(cx, cy)) {
+                        found.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+        // Items in more than one cell turn up once per cell.
+        found.sort_unstable();
+        found.dedup();
+        found.retain(|&item| self.items[item].is_some_and(|other| other.overlaps(rect)));
+        found
+    }
+    // The other items within `radius` of `item`'s rectangle (overlapping it once it's grown by
+    // `radius` on every side), in order. Empty if `item` isn't in.
+    pub fn neighbors(&self, item: usize, radius: f32) -> Vec<usize> {
+        let Some(rect) = self.get(item) else {
+            return Vec::new();
+        };
+        let grown = Rect {
+            x: rect.x - radius,
+            y: rect.y - radius,
+            width: rect.width + 2.0 * radius,
+            height: rect.height + 2.0 * radius,
+        };
+        let mut found = self.query_region(&grown);
+        found.retain(|&other| other != item);
+        found
+    }
+    fn cell(&self, at: f32) -> i32 {
+        (at / self.cell_size).floor() as i32
+    }
+    fn cell_range(
+        &self,
+        rect: &Rect,
+    ) -> (std::ops::RangeInclusive<i32>, std::ops::RangeInclusive<i32>) {
+        (
+            self.cell(rect.x)..=self.cell(rect.right()),
+            self.cell(rect.y)..=self.cell(rect.top()),
+        )
+    }
+}
@@ -1,42 +1,511 @@
-use crate::{gpu, WGPU};
-use bytemuck::bytes_of;
+use crate::hot_reload::FileWatcher;
+use crate::memory::TextureMemory;
+use crate::pipeline_cache::PipelineKey;
+use crate::{
+    AlphaMask, Color, MemoryReport, Rect, ShaderError, SpatialHash, SpriteError, Vec2, WGPU,
+};
 use core::ops::Range;
 use std::borrow::Cow;
-use wgpu;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GPUSprite {
     pub screen_region: [f32; 4], // This is the area of the screen the sprite should take up, like a collision box
     // Textures with a bunch of sprites are often called "sprite sheets"
     pub sheet_region: [f32; 4], // Which part of the sheet to look at for the sprite ??
+    // 0 (front) to 1 (back); only matters with GpuOptions::depth_buffer, and then a sprite covers
+    // the ones behind it whichever group draws first. Ties go to the one drawn last. Pixels are
+    // either drawn or discarded, so there's no blending to get out of order (unless the group has a
+    // BlendMode other than Replace; those groups are best drawn last, back to front).
+    pub depth: f32,
+    // GPUSprite::OUTLINE and friends, or'd together.
+    pub flags: u32,
+    // Packed sRGB, see with_outline.
+    pub outline_color: u32,
+    // In texels of the sprite's texture.
+    pub outline_width: f32,
+    // Packed sRGB colors multiplied into the bottom-left, bottom-right, top-left and top-right
+    // corners, blended across the sprite. Only used with GPUSprite::TINT.
+    pub tint: [u32; 4],
 }
 
+impl GPUSprite {
+    // Fills the transparent texels around the sprite's edges with outline_color, e.g. for
+    // selection highlights. Only transparent texels inside the sprite's sheet_region can be
+    // filled, so frames need a margin as wide as the outline.
+    pub const OUTLINE: u32 = 1 << 0;
+    // Multiplies the sprite by its tint colors.
+    pub const TINT: u32 = 1 << 1;
+    // For ShaderId::SDF_TEXT: blurs the glyph's edge, for soft drop shadows.
+    pub const SOFT: u32 = 1 << 2;
+    // Only collides where its group's AlphaMask is solid (see SpriteRender::set_group_alpha_mask),
+    // in group_collisions. The shaders don't look at it.
+    pub const PIXEL_COLLISION: u32 = 1 << 3;
+
+    pub fn new(screen_region: [f32; 4], sheet_region: [f32; 4]) -> Self {
+        Self {
+            screen_region,
+            sheet_region,
+            depth: 0.0,
+            flags: 0,
+            outline_color: 0,
+            outline_width: 0.0,
+            tint: [0; 4],
+        }
+    }
+    // screen_region as a Rect, turned the right way round if the sprite is flipped.
+    pub fn bounds(&self) -> Rect {
+        Rect::from_region(self.screen_region)
+    }
+    // Covers `bounds` exactly, staying flipped if it was.
+    pub fn set_bounds(&mut self, bounds: impl Into<Rect>) {
+        let bounds = bounds.into();
+        let [_, _, w, h] = self.screen_region;
+        self.screen_region = [
+            if w < 0.0 { bounds.right() } else { bounds.x },
+            if h < 0.0 { bounds.top() } else { bounds.y },
+            bounds.width.copysign(w),
+            bounds.height.copysign(h),
+        ];
+    }
+    // The corner screen_region starts at: the bottom left, unless the sprite is flipped.
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.screen_region[0], self.screen_region[1])
+    }
+    pub fn set_position(&mut self, position: impl Into<Vec2>) {
+        let position = position.into();
+        self.screen_region[0] = position.x;
+        self.screen_region[1] = position.y;
+    }
+    // Negative along the axes the sprite is flipped on.
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.screen_region[2], self.screen_region[3])
+    }
+    // A flat-colored rectangle, for groups made with SpriteRender::add_rect_group.
+    pub fn solid(screen_region: [f32; 4], color: impl Into<Color>) -> Self {
+        let color = color.into();
+        Self::gradient(screen_region, [color; 4])
+    }
+    // Corners in the same order as `tint`.
+    pub fn gradient(screen_region: [f32; 4], corners: [impl Into<Color>; 4]) -> Self {
+        Self {
+            flags: Self::TINT,
+            tint: corners.map(|corner| corner.into().packed()),
+            ..Self::new(screen_region, [0.0, 0.0, 1.0, 1.0])
+        }
+    }
+    // Multiplies the whole sprite by one color, e.g. to flash it or fade it out (with a blended
+    // group).
+    pub fn with_tint(self, color: impl Into<Color>) -> Self {
+        Self {
+            flags: self.flags | Self::TINT,
+            tint: [color.into().packed(); 4],
+            ..self
+        }
+    }
+    pub fn with_depth(self, depth: f32) -> Self {
+        Self { depth, ..self }
+    }
+    // Turns the outline on, `width` texels wide.
+    pub fn with_outline(self, color: impl Into<Color>, width: f32) -> Self {
+        Self {
+            flags: self.flags | Self::OUTLINE,
+            outline_color: color.into().packed(),
+            outline_width: width,
+            ..self
+        }
+    }
+    // Shows or hides the outline, keeping its color and width.
+    pub fn set_outline(&mut self, visible: bool) {
+        if visible {
+            self.flags |= Self::OUTLINE;
+        } else {
+            self.flags &= !Self::OUTLINE;
+        }
+    }
+}
+
+// Which part of the world a group is drawn from. The rectangle screen_pos..screen_pos+screen_size
+// of world space is stretched over the whole target, so screen_size is in world units, not pixels:
+// a camera the size of the window in pixels (fit_window) draws one world unit per pixel, and a
+// fixed one (say 320 by 180) keeps showing the same amount of the world however big the window
+// gets.
 #[repr(C)]
-#[derive(Clone, Copy, bytemuck::Zeroable, bytemuck::Pod)]
+#[derive(Clone, Copy, Debug, PartialEq, bytemuck::Zeroable, bytemuck::Pod)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GPUCamera {
-    pub screen_pos: [f32; 2],  // Position of the camera
-    pub screen_size: [f32; 2], // The size of our screen???
+    // The world position at the bottom-left corner of the view.
+    pub screen_pos: [f32; 2],
+    // How much of the world the view spans, across and up.
+    pub screen_size: [f32; 2],
+}
+
+// One world unit per pixel of a window made with the default WindowConfig, from the origin.
+impl Default for GPUCamera {
+    fn default() -> Self {
+        let [width, height] = crate::WindowConfig::default().size;
+        Self::fixed(width as f32, height as f32)
+    }
+}
+
+impl GPUCamera {
+    // One world unit per pixel of a window (or other target) of `size`, with the origin at the
+    // bottom left; pass Engine::window_size, and again after a resize to keep it that way.
+    pub fn fit_window(size: winit::dpi::PhysicalSize<u32>) -> Self {
+        Self::fixed(size.width as f32, size.height as f32)
+    }
+    // A view `width` by `height` world units from the origin, whatever size the window is, for
+    // games made at a virtual resolution. Windows of another shape stretch it.
+    pub fn fixed(width: f32, height: f32) -> Self {
+        Self::new(Vec2::ZERO, [width, height])
+    }
+    pub fn new(position: impl Into<Vec2>, size: impl Into<Vec2>) -> Self {
+        Self {
+            screen_pos: position.into().to_array(),
+            screen_size: size.into().to_array(),
+        }
+    }
+    // The bottom-left corner of the view, in world space.
+    pub fn position(&self) -> Vec2 {
+        self.screen_pos.into()
+    }
+    pub fn set_position(&mut self, position: impl Into<Vec2>) {
+        self.screen_pos = position.into().to_array();
+    }
+    // How much of the world the view takes in.
+    pub fn size(&self) -> Vec2 {
+        self.screen_size.into()
+    }
+    // The part of the world in view.
+    pub fn view(&self) -> Rect {
+        Rect::from_position_size(self.screen_pos, self.screen_size)
+    }
+}
+
+// How a group's texture is filtered and what happens past its edges.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerOptions {
+    // Used when the texture is drawn bigger than it is.
+    pub mag_filter: wgpu::FilterMode,
+    // Used when it's drawn smaller.
+    pub min_filter: wgpu::FilterMode,
+    // Between mip levels; only matters for textures that have them (GpuOptions::mipmaps).
+    pub mipmap_filter: wgpu::FilterMode,
+    // Repeat lets sheet regions outside 0..1 tile the texture, e.g. for scrolling backgrounds.
+    pub address_mode: wgpu::AddressMode,
+}
+
+impl SamplerOptions {
+    // Crisp texels at any zoom, for pixel art.
+    pub const PIXEL_ART: Self = Self {
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        address_mode: wgpu::AddressMode::ClampToEdge,
+    };
+    pub const SMOOTH: Self = Self {
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        address_mode: wgpu::AddressMode::ClampToEdge,
+    };
+    pub fn repeat(self) -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::Repeat,
+            ..self
+        }
+    }
+    // What add_sprite_group uses: pixel art, except that mipmapped textures blend between levels
+    // (trilinear minification) so they don't shimmer when shrunk.
+    pub fn for_texture(tex: &wgpu::Texture) -> Self {
+        if tex.mip_level_count() > 1 {
+            Self {
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Self::PIXEL_ART
+            }
+        } else {
+            Self::PIXEL_ART
+        }
+    }
+    pub(crate) fn descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        wgpu::SamplerDescriptor {
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            ..Default::default()
+        }
+    }
 }
 
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self::PIXEL_ART
+    }
+}
+
+// One sprite: its group and where it is in the group. Removing a sprite moves the ones after it
+// down an index, so handles to those need fixing up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpriteHandle {
+    pub group: usize,
+    pub index: usize,
+}
+
+impl SpriteHandle {
+    pub fn new(group: usize, index: usize) -> Self {
+        Self { group, index }
+    }
+}
+
+// A sprite shader of the game's own, for special effects.
+#[derive(Clone, Debug)]
+pub enum SpriteShader {
+    // A whole module with a vs_main and an fs_main, using the same bind groups as shader.wgsl (or
+    // shader_instanced.wgsl on devices without storage buffers, see SpriteRender::is_instanced).
+    Module(Cow<'static, str>),
+    // Only an fs_main, added to the built-in vertex shader. It takes `in: VertexOutput` and can
+    // sample the group's texture as `textureSample(t_diffuse, s_diffuse, in.tex_coords)`.
+    Fragment(Cow<'static, str>),
+}
+
+// A shader added with SpriteRender::add_shader. Only means something to the SpriteRender that
+// handed it out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShaderId(usize);
+
+impl ShaderId {
+    // The built-in shader every group starts with.
+    pub const DEFAULT: Self = Self(0);
+    // What set_group_palette switches a group to.
+    pub const PALETTE: Self = Self(1);
+    // For glyphs from GlyphAtlas::new_sdf, which stay sharp however far they're scaled. Colored
+    // by the sprites' tint, and outlined with GPUSprite::with_outline (up to 4 atlas texels).
+    // Needs a smooth sampler and BlendMode::Alpha.
+    pub const SDF_TEXT: Self = Self(2);
+}
+
+// How a group's pixels combine with what's already been drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    // Pixels overwrite what's under them; the shader discards the mostly-transparent ones.
+    #[default]
+    Replace,
+    // Ordinary transparency, for textures with straight (non-premultiplied) alpha.
+    Alpha,
+    // Transparency for textures whose colors are already multiplied by their alpha.
+    Premultiplied,
+    // Adds to what's underneath (scaled by alpha), so overlaps get brighter: glows, lights, sparks.
+    Additive,
+    // Multiplies what's underneath by the sprite's color, for shadows and tinting. Ignores alpha.
+    Multiply,
+}
+
+impl BlendMode {
+    fn blend_state(self) -> Option<wgpu::BlendState> {
+        // Blending modes other than Alpha leave the target's alpha alone.
+        let keep_alpha = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Zero,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        match self {
+            BlendMode::Replace => None,
+            BlendMode::Alpha => Some(wgpu::BlendState::ALPHA_BLENDING),
+            BlendMode::Premultiplied => Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            BlendMode::Additive => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: keep_alpha,
+            }),
+            BlendMode::Multiply => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::Zero,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: keep_alpha,
+            }),
+        }
+    }
+}
+
+// Diagnostic ways of drawing the sprites, for seeing where a frame's time goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    #[default]
+    Normal,
+    // Every group (i.e. draw call) in a flat color of its own, so it's easy to see which sprites
+    // get drawn together and how many separate draws a scene takes.
+    DrawCalls,
+    // A heat map of how many sprites cover each pixel, from dark red through orange to white.
+    // Whole quads count, transparent parts included, since those cost as much to draw.
+    Overdraw,
+}
+
+impl DebugView {
+    // Each overdraw layer adds this much (in linear color).
+    const OVERDRAW_STEP: wgpu::Color = wgpu::Color {
+        r: 0.12,
+        g: 0.05,
+        b: 0.02,
+        a: 0.0,
+    };
+    fn blend_state(self) -> Option<wgpu::BlendState> {
+        // Both debug fragment shaders output white, so the blend constant is the color.
+        let constant = |dst_factor| wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor,
+            operation: wgpu::BlendOperation::Add,
+        };
+        match self {
+            DebugView::Normal => None,
+            DebugView::DrawCalls => Some(wgpu::BlendState {
+                color: constant(wgpu::BlendFactor::Zero),
+                alpha: wgpu::BlendComponent::REPLACE,
+            }),
+            DebugView::Overdraw => Some(wgpu::BlendState {
+                color: constant(wgpu::BlendFactor::One),
+                alpha: wgpu::BlendComponent::REPLACE,
+            }),
+        }
+    }
+}
+
+// A bright color for draw call `index`, with hues spread out by the golden ratio so that
+// neighbors look different.
+fn draw_call_color(index: usize) -> wgpu::Color {
+    let hue = (index as f64 * 0.618_034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    wgpu::Color { r, g, b, a: 1.0 }
+}
+
+// What a named group's GPU objects are called, e.g. "platforms sprites".
+fn gpu_label(name: Option<&str>, what: &str) -> Option<String> {
+    name.map(|name| format!("{name} {what}"))
+}
+
+// Whether any of `sprite` is in the part of the world `camera` shows.
+fn overlaps_camera(sprite: &GPUSprite, camera: &GPUCamera) -> bool {
+    let [cx, cy] = camera.screen_pos;
+    let [cw, ch] = camera.screen_size;
+    sprite.bounds().overlaps(&Rect::new(cx, cy, cw, ch))
+}
+
+// Sorts ranges of sprites by group and joins the ones that overlap or touch.
+fn merge_ranges(mut ranges: Vec<(usize, Range<usize>)>) -> Vec<(usize, Range<usize>)> {
+    ranges.sort_by_key(|(which, range)| (*which, range.start));
+    let mut merged: Vec<(usize, Range<usize>)> = Vec::with_capacity(ranges.len());
+    for (which, range) in ranges {
+        match merged.last_mut() {
+            Some((last, last_range)) if *last == which && range.start <= last_range.end => {
+                last_range.end = last_range.end.max(range.end);
+            }
+            _ => merged.push((which, range)),
+        }
+    }
+    merged
+}
+
+// Size of the staging buffers refreshed sprites go through. Bigger refreshes get a buffer of their
+// own.
+const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 256 * 1024;
+
 pub struct SpriteRender {
-    pipeline: wgpu::RenderPipeline,
+    // Shared with the device's other SpriteRenders, like the pipelines and samplers.
+    layouts: Arc<SpriteLayouts>,
+    // Indexed by ShaderId, with the built-in shader first. Sources are kept for rebuilding the
+    // pipelines after device loss.
+    shaders: Vec<(String, SpriteShader)>,
+    // Made as groups ask for them; every shader has at least its BlendMode::Replace pipeline.
+    pipelines: HashMap<(ShaderId, BlendMode), Arc<wgpu::RenderPipeline>>,
     groups: Vec<SpriteGroup>,
-    sprite_bind_group_layout: wgpu::BindGroupLayout,
-    texture_bind_group_layout: wgpu::BindGroupLayout,
+    // 1x1 white: the texture of rect groups, and the palette of groups that don't have one.
+    white: wgpu::Texture,
+    white_view: wgpu::TextureView,
+    format: wgpu::TextureFormat,
+    // Sprites go through an instance vertex buffer instead of a storage buffer, for devices that
+    // don't have storage buffers (WebGL2).
+    instanced: bool,
+    sample_count: u32,
+    // Made with SpriteRender::overlay: no depth buffer either.
+    overlay: bool,
+    // Size in pixels of what render() draws into, which group clip rects are kept inside.
+    target_size: (u32, u32),
+    debug_view: DebugView,
+    // Used for every group while debug_view isn't Normal.
+    debug_pipeline: Option<Arc<wgpu::RenderPipeline>>,
+    // Sprites refreshed since the last flush, by group, and the reusable buffers they're copied
+    // to the GPU through.
+    pending: Vec<(usize, Range<usize>)>,
+    staging: wgpu::util::StagingBelt,
+    // Set while drawing with draw_indirect; see set_indirect.
+    indirect: Option<IndirectArgs>,
+    // Made for the first group culled on the GPU.
+    cull_pipeline: Option<crate::gpu_cull::CullPipeline>,
+    frames_in_flight: usize,
+    // What the last frames_in_flight - 1 flushes wrote, merged.
+    recent: VecDeque<Vec<(usize, Range<usize>)>>,
+    // Something that shows was changed since the last flush; see has_changes.
+    changed: bool,
+    // Files to reload shaders from when they change; see watch_shader.
+    shader_files: FileWatcher<ShaderFile>,
+    // Stands in for the built-in vertex stage once watch_vertex_shader has reloaded it.
+    vertex_source: Option<String>,
 }
-impl SpriteRender {
-    pub fn new(wgpu: &WGPU) -> Self {
-        let shader = wgpu
-            .device
-            .create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: None,
-                // Cow is a "copy on write" wrapper that abstracts over owned or borrowed memory.
-                // Here we just need to use it since wgpu wants "some text" to compile a shader from.
-                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shader.wgsl"))),
-            });
+
+// What a watched shader file holds.
+#[derive(Clone, Copy, PartialEq)]
+enum ShaderFile {
+    Vertex,
+    Shader(ShaderId),
+}
+
+// One DrawIndirect per group, in group order, and what was last written into the buffer.
+#[derive(Default)]
+struct IndirectArgs {
+    buffer: Option<wgpu::Buffer>,
+    args: Vec<[u32; 4]>,
+}
+
+// How the instanced shader reads a GPUSprite out of the vertex buffer.
+const SPRITE_INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+    0 => Float32x4, 1 => Float32x4, 2 => Float32, 3 => Uint32, 4 => Uint32, 5 => Float32,
+    6 => Uint32x4
+];
+// The bind group and pipeline layouts every SpriteRender on a device shares, through WGPU's
+// PipelineCache.
+pub(crate) struct SpriteLayouts {
+    sprite: wgpu::BindGroupLayout,
+    texture: wgpu::BindGroupLayout,
+    pipeline: wgpu::PipelineLayout,
+}
+
+impl SpriteLayouts {
+    fn new(gpu: &WGPU, instanced: bool) -> Self {
         let texture_bind_group_layout =
-            wgpu.device
+            gpu.device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: None,
                     // This bind group's first entry is for the texture and the second is for the sampler.
@@ -70,6 +539,17 @@ impl SpriteRender {
                             // No count
                             count: None,
                         },
+                        // The palette, read with textureLoad by shader_palette.wgsl
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
@@ -79,228 +559,1801 @@ impl SpriteRender {
         // whether to draw both the fronts and backs of triangles, and how many times to run the pipeline for
         // things like multisampling antialiasing.
 
+        let sprite_layout_entries = [
+            // The camera binding
+            wgpu::BindGroupLayoutEntry {
+                // This matches the binding in the shader
+                binding: 0,
+                // Available in vertex shader
+                visibility: wgpu::ShaderStages::VERTEX,
+                // It's a buffer
+                ty: wgpu::BindingType::Buffer {
+                    // Specifically, a uniform buffer
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                // No count, not a buffer array binding
+                count: None,
+            },
+            // The sprite buffer binding
+            wgpu::BindGroupLayoutEntry {
+                // This matches the binding in the shader
+                binding: 1,
+                // Available in vertex shader
+                visibility: wgpu::ShaderStages::VERTEX,
+                // It's a buffer
+                ty: wgpu::BindingType::Buffer {
+                    // Specifically, a storage buffer
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                // No count, not a buffer array binding
+                count: None,
+            },
+        ];
+        // The instanced path only needs the camera; sprites come in as vertices.
+        let sprite_binding_count = if instanced { 1 } else { 2 };
         let sprite_bind_group_layout =
-            wgpu.device
+            gpu.device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                     label: None,
-                    entries: &[
-                        // The camera binding
-                        wgpu::BindGroupLayoutEntry {
-                            // This matches the binding in the shader
-                            binding: 0,
-                            // Available in vertex shader
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            // It's a buffer
-                            ty: wgpu::BindingType::Buffer {
-                                // Specifically, a uniform buffer
-                                ty: wgpu::BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            // No count, not a buffer array binding
-                            count: None,
-                        },
-                        // The sprite buffer binding
-                        wgpu::BindGroupLayoutEntry {
-                            // This matches the binding in the shader
-                            binding: 1,
-                            // Available in vertex shader
-                            visibility: wgpu::ShaderStages::VERTEX,
-                            // It's a buffer
-                            ty: wgpu::BindingType::Buffer {
-                                // Specifically, a storage buffer
-                                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            // No count, not a buffer array binding
-                            count: None,
-                        },
-                    ],
+                    entries: &sprite_layout_entries[..sprite_binding_count],
                 });
 
         // A graphics pipeline is sort of like the conventions for a function call: it defines
         // the shapes of arguments (bind groups and push constants) that will be used for
         // draw calls.
         // Now we'll create our pipeline layout, specifying the shape of the execution environment (the bind group)
-        let pipeline_layout = wgpu
+        let pipeline_layout = gpu
             .device
             .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
                 bind_group_layouts: &[&sprite_bind_group_layout, &texture_bind_group_layout],
                 push_constant_ranges: &[],
             });
+        Self {
+            sprite: sprite_bind_group_layout,
+            texture: texture_bind_group_layout,
+            pipeline: pipeline_layout,
+        }
+    }
+}
 
-        let pipeline = wgpu
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu.config.format.into())],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
-        //Converting that CPU stuff to GPU stuff
+impl SpriteRender {
+    pub fn new(wgpu: &WGPU) -> Self {
+        Self::with_format(wgpu, wgpu.config.format)
+    }
+    // For drawing into something other than the main surface, e.g. another window.
+    pub fn with_format(wgpu: &WGPU, format: wgpu::TextureFormat) -> Self {
+        Self::build(wgpu, format, false)
+    }
+    // For drawing over a finished frame in a pass of its own, like Engine::ui: straight into the
+    // window's image, without multisampling or the depth buffer, so it can come after
+    // post-processing.
+    pub fn overlay(wgpu: &WGPU) -> Self {
+        Self::build(wgpu, wgpu.config.format, true)
+    }
+    fn build(wgpu: &WGPU, format: wgpu::TextureFormat, overlay: bool) -> Self {
+        let instanced = wgpu.device.limits().max_storage_buffers_per_shader_stage == 0;
+        let sample_count = if overlay {
+            1
+        } else {
+            wgpu.sample_count_for(format)
+        };
+        let layouts = wgpu
+            .cache
+            .sprite_layouts(|| SpriteLayouts::new(wgpu, instanced));
 
-        Self {
-            pipeline,
+        let pixel = image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4]));
+        let white = wgpu.create_texture_for(&pixel, Some("white"), 1);
+        wgpu.write_texture_data(&white, &pixel);
+        let white_view = white.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut render = Self {
+            layouts,
+            shaders: Vec::new(),
+            pipelines: HashMap::new(),
             groups: Vec::default(),
-            sprite_bind_group_layout,
-            texture_bind_group_layout,
+            white,
+            white_view,
+            format,
+            instanced,
+            sample_count,
+            overlay,
+            target_size: (wgpu.config.width, wgpu.config.height),
+            debug_view: DebugView::Normal,
+            debug_pipeline: None,
+            pending: Vec::new(),
+            staging: wgpu::util::StagingBelt::new(STAGING_CHUNK_SIZE),
+            indirect: None,
+            cull_pipeline: None,
+            frames_in_flight: 1,
+            recent: VecDeque::new(),
+            changed: true,
+            shader_files: FileWatcher::default(),
+            vertex_source: None,
+        };
+        render.push_shader(
+            wgpu,
+            "sprite".to_owned(),
+            SpriteShader::Fragment(Cow::Borrowed(include_str!("shader_fragment.wgsl"))),
+        );
+        render.push_shader(
+            wgpu,
+            "palette".to_owned(),
+            SpriteShader::Fragment(Cow::Borrowed(include_str!("shader_palette.wgsl"))),
+        );
+        render.push_shader(
+            wgpu,
+            "sdf text".to_owned(),
+            SpriteShader::Fragment(Cow::Borrowed(include_str!("shader_sdf.wgsl"))),
+        );
+        render
+    }
+    fn has_depth(&self, gpu: &WGPU) -> bool {
+        gpu.has_depth_buffer() && !self.overlay
+    }
+    // The whole WGSL module for `shader`, vertex stage included.
+    fn full_source<'a>(&self, shader: &'a SpriteShader) -> Cow<'a, str> {
+        match shader {
+            SpriteShader::Module(source) => Cow::Borrowed(source),
+            SpriteShader::Fragment(fragment) => {
+                let vertex = match &self.vertex_source {
+                    Some(vertex) => vertex.as_str(),
+                    None if self.instanced => include_str!("shader_instanced.wgsl"),
+                    None => include_str!("shader.wgsl"),
+                };
+                Cow::Owned(format!("{vertex}\n{fragment}"))
+            }
         }
     }
-    pub fn add_sprite_group(
+    // Built once per device, and shared with every other SpriteRender asking for the same one.
+    fn create_pipeline(
+        &self,
+        gpu: &WGPU,
+        label: &str,
+        shader: &SpriteShader,
+        entry_point: &'static str,
+        blend: Option<wgpu::BlendState>,
+    ) -> Arc<wgpu::RenderPipeline> {
+        let key = PipelineKey {
+            source: self.full_source(shader).into_owned(),
+            entry_point,
+            blend,
+            format: self.format,
+            sample_count: self.sample_count,
+            depth: self.has_depth(gpu),
+        };
+        gpu.cache
+            .render_pipeline(&gpu.device, key, label, |shader| {
+                gpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some(label),
+                        layout: Some(&self.layouts.pipeline),
+                        vertex: wgpu::VertexState {
+                            module: shader,
+                            entry_point: "vs_main",
+                            buffers: if self.instanced {
+                                &[wgpu::VertexBufferLayout {
+                                    array_stride: std::mem::size_of::<GPUSprite>() as u64,
+                                    step_mode: wgpu::VertexStepMode::Instance,
+                                    attributes: &SPRITE_INSTANCE_ATTRIBUTES,
+                                }]
+                            } else {
+                                &[]
+                            },
+                        },
+                        fragment: Some(wgpu::FragmentState {
+                            module: shader,
+                            entry_point,
+                            targets: &[Some(wgpu::ColorTargetState {
+                                format: self.format,
+                                blend,
+                                write_mask: wgpu::ColorWrites::ALL,
+                            })],
+                        }),
+                        primitive: wgpu::PrimitiveState::default(),
+                        depth_stencil: self.has_depth(gpu).then(|| wgpu::DepthStencilState {
+                            format: crate::gpu::DEPTH_FORMAT,
+                            depth_write_enabled: true,
+                            // Equal depths pass, so without any depths set draw order decides as usual.
+                            depth_compare: wgpu::CompareFunction::LessEqual,
+                            stencil: wgpu::StencilState::default(),
+                            bias: wgpu::DepthBiasState::default(),
+                        }),
+                        multisample: wgpu::MultisampleState {
+                            count: self.sample_count,
+                            ..Default::default()
+                        },
+                        multiview: None,
+                    })
+            })
+    }
+    fn ensure_pipeline(&mut self, gpu: &WGPU, shader: ShaderId, blend: BlendMode) {
+        if !self.pipelines.contains_key(&(shader, blend)) {
+            let (label, source) = &self.shaders[shader.0];
+            let pipeline = self.create_pipeline(gpu, label, source, "fs_main", blend.blend_state());
+            self.pipelines.insert((shader, blend), pipeline);
+        }
+    }
+    fn push_shader(&mut self, gpu: &WGPU, label: String, shader: SpriteShader) -> ShaderId {
+        self.shaders.push((label, shader));
+        let id = ShaderId(self.shaders.len() - 1);
+        self.ensure_pipeline(gpu, id, BlendMode::default());
+        id
+    }
+    // Builds a pipeline for `shader`, for groups to use with set_group_shader. Waits to hear back
+    // from the GPU whether the shader compiled, so mistakes show up here rather than as a stream
+    // of errors once a frame.
+    pub async fn add_shader(
         &mut self,
         gpu: &WGPU,
-        tex: &wgpu::Texture,
-        sprites: Vec<GPUSprite>,
-        camera: GPUCamera,
+        label: &str,
+        shader: SpriteShader,
+    ) -> Result<ShaderId, ShaderError> {
+        gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let id = self.push_shader(gpu, label.to_owned(), shader);
+        if let Some(e) = gpu.device.pop_error_scope().await {
+            self.pipelines.remove(&(id, BlendMode::default()));
+            let (_, shader) = self.shaders.pop().unwrap();
+            gpu.cache.forget_source(&self.full_source(&shader));
+            return Err(ShaderError {
+                label: label.to_owned(),
+                message: e.to_string(),
+            });
+        }
+        Ok(id)
+    }
+    // Reloads `shader` from `path` whenever the file changes; see reload_changed_shaders. The file
+    // holds the same kind of source the shader was added with, a whole Module or only a Fragment.
+    // The built-in shaders can be pointed at the crate's own src/shader_fragment.wgsl and friends.
+    pub fn watch_shader(&mut self, shader: ShaderId, path: impl Into<PathBuf>) {
+        assert!(
+            shader.0 < self.shaders.len(),
+            "{shader:?} wasn't made by this SpriteRender"
+        );
+        self.shader_files
+            .watch(ShaderFile::Shader(shader), path.into());
+    }
+    // Same for the vertex stage every SpriteShader::Fragment is added to: the crate's
+    // src/shader.wgsl, or src/shader_instanced.wgsl when is_instanced.
+    pub fn watch_vertex_shader(&mut self, path: impl Into<PathBuf>) {
+        self.shader_files.watch(ShaderFile::Vertex, path.into());
+    }
+    pub fn unwatch_shader(&mut self, shader: ShaderId) {
+        self.shader_files.unwatch(&ShaderFile::Shader(shader));
+    }
+    // Recompiles the watched shaders whose files changed and swaps their pipelines in. A shader
+    // that doesn't compile is logged and the old one kept, so a typo mid-edit doesn't take the
+    // game down. The Engine calls this every frame while EngineConfig::hot_reload is on.
+    pub fn reload_changed_shaders(&mut self, gpu: &WGPU) {
+        for (file, path) in self.shader_files.changed() {
+            let source = match std::fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    log::error!("couldn't reload {}: {e}", path.display());
+                    continue;
+                }
+            };
+            let old_shaders = self.shaders.clone();
+            let old_vertex = self.vertex_source.clone();
+            let old_sources = self.full_sources();
+            match file {
+                ShaderFile::Vertex => self.vertex_source = Some(source),
+                ShaderFile::Shader(id) => {
+                    let shader = &mut self.shaders[id.0].1;
+                    *shader = match shader {
+                        SpriteShader::Module(_) => SpriteShader::Module(source.into()),
+                        SpriteShader::Fragment(_) => SpriteShader::Fragment(source.into()),
+                    };
+                }
+            }
+            let new_sources = self.full_sources();
+            // Whichever side lost, its pipelines aren't needed in the device's cache any more.
+            let unused = match self.rebuild_pipelines(gpu) {
+                Ok(()) => {
+                    log::info!("reloaded {}", path.display());
+                    old_sources.difference(&new_sources)
+                }
+                Err(message) => {
+                    log::error!(
+                        "{} didn't compile, keeping the old shader: {message}",
+                        path.display()
+                    );
+                    self.shaders = old_shaders;
+                    self.vertex_source = old_vertex;
+                    // Straight from the cache, since these compiled before.
+                    let _ = self.rebuild_pipelines(gpu);
+                    new_sources.difference(&old_sources)
+                }
+            };
+            for source in unused {
+                gpu.cache.forget_source(source);
+            }
+        }
+    }
+    fn full_sources(&self) -> std::collections::HashSet<String> {
+        self.shaders
+            .iter()
+            .map(|(_, shader)| self.full_source(shader).into_owned())
+            .collect()
+    }
+    // Makes every pipeline in use again from the current sources, and returns the error if any of
+    // them didn't compile.
+    fn rebuild_pipelines(&mut self, gpu: &WGPU) -> Result<(), String> {
+        let variants: Vec<_> = self.pipelines.keys().copied().collect();
+        self.pipelines.clear();
+        gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        for (shader, blend) in variants {
+            self.ensure_pipeline(gpu, shader, blend);
+        }
+        self.set_debug_view(gpu, self.debug_view);
+        match pollster::block_on(gpu.device.pop_error_scope()) {
+            Some(e) => Err(e.to_string()),
+            None => Ok(()),
+        }
+    }
+    pub fn set_group_shader(&mut self, gpu: &WGPU, which: usize, shader: ShaderId) {
+        assert!(
+            shader.0 < self.shaders.len(),
+            "{shader:?} wasn't made by this SpriteRender"
+        );
+        self.ensure_pipeline(gpu, shader, self.groups[which].blend);
+        self.groups[which].shader = shader;
+        self.changed = true;
+    }
+    #[cfg(feature = "serde")]
+    pub(crate) fn has_shader(&self, shader: ShaderId) -> bool {
+        shader.0 < self.shaders.len()
+    }
+    pub fn group_shader(&self, which: usize) -> ShaderId {
+        self.groups[which].shader
+    }
+    pub fn set_group_blend(&mut self, gpu: &WGPU, which: usize, blend: BlendMode) {
+        self.ensure_pipeline(gpu, self.groups[which].shader, blend);
+        self.groups[which].blend = blend;
+        self.changed = true;
+    }
+    pub fn group_blend(&self, which: usize) -> BlendMode {
+        self.groups[which].blend
+    }
+    // Clips the group's sprites to `clip` ([x, y, width, height] in pixels from the top left of
+    // the target), e.g. for a scrolling panel or a minimap. None draws the whole group.
+    pub fn set_group_clip(&mut self, which: usize, clip: Option<[u32; 4]>) {
+        self.changed = true;
+        self.groups[which].clip = clip;
+    }
+    pub fn group_clip(&self, which: usize) -> Option<[u32; 4]> {
+        self.groups[which].clip
+    }
+    // Only draws the group's sprites that overlap its camera's view, picked out again on flush
+    // whenever the camera or the sprites change, for groups spread over a world much bigger than
+    // the screen. Sprites keep their order. Worth it when most of the group is off screen;
+    // custom shaders that draw outside a sprite's screen_region may get cut off.
+    pub fn set_group_culling(&mut self, which: usize, culling: bool) {
+        if culling {
+            self.set_group_gpu_culling_off(which);
+        }
+        let group = &mut self.groups[which];
+        if group.culled != culling {
+            group.culled = culling;
+            group.needs_cull = culling;
+            if !culling {
+                // The buffer holds only the visible sprites; put them all back.
+                let len = group.sprites.len();
+                self.mark_dirty(which, 0..len);
+            }
+        }
+    }
+    pub fn group_culling(&self, which: usize) -> bool {
+        self.groups[which].culled
+    }
+    // Culls the group like set_group_culling, but in a compute pass at flush, so the sprites
+    // never go through the CPU again after they change. Visible sprites come out in whatever
+    // order the GPU finds them, so it's for groups where draw order doesn't matter, or where
+    // depths (GpuOptions::depth_buffer) settle it. Turns on set_indirect, which draws the count
+    // the GPU came up with. Returns false, changing nothing, where there are no compute shaders
+    // or indirect draws (WebGL2).
+    pub fn set_group_gpu_culling(&mut self, gpu: &WGPU, which: usize, culling: bool) -> bool {
+        if !culling {
+            self.set_group_gpu_culling_off(which);
+            return true;
+        }
+        let supported = !self.instanced
+            && gpu
+                .downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+        if !supported || !self.set_indirect(gpu, true) {
+            return false;
+        }
+        if self.groups[which].gpu_cull.is_none() {
+            self.set_group_culling(which, false);
+            self.cull_pipeline
+                .get_or_insert_with(|| crate::gpu_cull::CullPipeline::new(gpu));
+            let group = &mut self.groups[which];
+            group.gpu_cull = Some(crate::gpu_cull::GpuCull::new(gpu, group.capacity));
+            let len = group.sprites.len();
+            self.mark_dirty(which, 0..len);
+        }
+        true
+    }
+    fn set_group_gpu_culling_off(&mut self, which: usize) {
+        if self.groups[which].gpu_cull.take().is_some() {
+            // Like set_group_culling, the buffer only has the visible sprites in it.
+            let len = self.groups[which].sprites.len();
+            self.mark_dirty(which, 0..len);
+        }
+    }
+    pub fn group_gpu_culling(&self, which: usize) -> bool {
+        self.groups[which].gpu_cull.is_some()
+    }
+    // Switches every group to a diagnostic view (or back to Normal), ignoring their own shaders
+    // and blend modes meanwhile.
+    pub fn set_debug_view(&mut self, gpu: &WGPU, view: DebugView) {
+        self.debug_view = view;
+        self.changed = true;
+        self.debug_pipeline = match view {
+            DebugView::Normal => None,
+            DebugView::DrawCalls | DebugView::Overdraw => Some(self.create_pipeline(
+                gpu,
+                "debug view",
+                &SpriteShader::Fragment(Cow::Borrowed(include_str!("shader_debug.wgsl"))),
+                if view == DebugView::Overdraw {
+                    "fs_overdraw"
+                } else {
+                    "fs_main"
+                },
+                view.blend_state(),
+            )),
+        };
+    }
+    // Roughly how much GPU memory the groups' buffers and textures take up. Engine::memory_report
+    // adds up all of the Engine's SpriteRenders.
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut textures = HashMap::new();
+        let sprite_buffer_bytes = self.collect_memory(&mut textures);
+        MemoryReport {
+            sprite_buffer_bytes,
+            textures: crate::memory::sorted(textures),
+            #[cfg(feature = "text")]
+            atlases: Vec::new(),
+        }
+    }
+    // Adds the groups' textures to `textures` (counting the groups drawing each) and returns the
+    // size of the buffers.
+    pub(crate) fn collect_memory(
+        &self,
+        textures: &mut HashMap<wgpu::Id<wgpu::Texture>, TextureMemory>,
+    ) -> u64 {
+        let mut bytes = self
+            .indirect
+            .as_ref()
+            .and_then(|indirect| indirect.buffer.as_ref())
+            .map_or(0, wgpu::Buffer::size);
+        for group in &self.groups {
+            bytes += group.sprite_buffer.size() + group.buffer_camera.size();
+            bytes += group
+                .spare_buffers
+                .iter()
+                .map(|(buffer, _)| buffer.size())
+                .sum::<u64>();
+            if let Some(cull) = &group.gpu_cull {
+                bytes += cull.source.size() + cull.params.size();
+            }
+            if let Some((id, memory)) = &group.texture {
+                textures
+                    .entry(*id)
+                    .or_insert_with(|| TextureMemory {
+                        groups: 0,
+                        ..memory.clone()
+                    })
+                    .groups += 1;
+            }
+        }
+        bytes
+    }
+    // Whether the next frame would look different from the last one: sprites, cameras, groups
+    // or their settings changed since the last flush. LoopMode::OnDemand goes by this.
+    pub fn has_changes(&self) -> bool {
+        self.changed
+    }
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+    // The Engine keeps this up to date for its windows; set it when calling render() on a target
+    // of your own, or clipped groups may be clipped against the wrong size.
+    pub fn set_target_size(&mut self, width: u32, height: u32) {
+        self.target_size = (width, height);
+    }
+    // Whether sprites reach the vertex shader as instance attributes instead of through a storage
+    // buffer, which a SpriteShader::Module has to match.
+    pub fn is_instanced(&self) -> bool {
+        self.instanced
+    }
+    // Samples per pixel the pipeline was built for; the render pass's color target has to match.
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+    // Rebuilds everything on the (new) device after WGPU::recover. Sprites and cameras come back
+    // from their CPU-side copies, but the textures were the game's, so every group shows a plain
+    // white placeholder until set_group_texture gives it its texture again. Rect groups were white
+    // to begin with and need nothing.
+    pub fn recreate(&mut self, gpu: &WGPU) {
+        let groups = std::mem::take(&mut self.groups);
+        let shaders = std::mem::take(&mut self.shaders);
+        let variants: Vec<_> = self.pipelines.keys().copied().collect();
+        let debug_view = self.debug_view;
+        let indirect = self.indirect.is_some();
+        let frames_in_flight = self.frames_in_flight;
+        let shader_files = std::mem::take(&mut self.shader_files);
+        let vertex_source = self.vertex_source.take();
+        let gpu_culled: Vec<usize> = (0..groups.len())
+            .filter(|&which| groups[which].gpu_cull.is_some())
+            .collect();
+        *self = Self::build(gpu, self.format, self.overlay);
+        // Reloaded built-in shaders come back as they were reloaded.
+        self.shaders = shaders;
+        self.vertex_source = vertex_source;
+        self.shader_files = shader_files;
+        self.pipelines.clear();
+        self.set_debug_view(gpu, debug_view);
+        self.set_indirect(gpu, indirect);
+        self.set_frames_in_flight(gpu, frames_in_flight);
+        for (shader, blend) in variants {
+            self.ensure_pipeline(gpu, shader, blend);
+        }
+        for group in groups {
+            let placeholder = self
+                .white
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            self.push_group(
+                gpu,
+                group.name,
+                placeholder,
+                group.sprites,
+                group.camera,
+                group.sampler,
+            );
+            let recreated = self.groups.last_mut().unwrap();
+            recreated.shader = group.shader;
+            recreated.blend = group.blend;
+            recreated.clip = group.clip;
+            recreated.culled = group.culled;
+            recreated.needs_cull = group.culled;
+            recreated.spatial = group.spatial;
+            recreated.spatial_stale = group.spatial_stale;
+            recreated.alpha_mask = group.alpha_mask;
+            recreated.tags = group.tags;
+            // The palette texture went with the old device too; the game has to set it again.
+        }
+        for which in gpu_culled {
+            self.set_group_gpu_culling(gpu, which, true);
+        }
+    }
+    pub fn set_group_texture(&mut self, gpu: &WGPU, which: usize, tex: &wgpu::Texture) {
+        self.groups[which].tex_view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+        self.groups[which].texture = Some((tex.global_id(), gpu.texture_memory(tex)));
+        self.refresh_texture_bind_group(gpu, which);
+    }
+    // Points every group drawing the texture `old` at `new` instead, for textures reloaded from
+    // disk.
+    pub(crate) fn replace_texture(
+        &mut self,
+        gpu: &WGPU,
+        old: wgpu::Id<wgpu::Texture>,
+        new: &wgpu::Texture,
     ) {
-        let view_kingtex_king = tex.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler_kingtex_king = gpu
-            .device
-            .create_sampler(&wgpu::SamplerDescriptor::default());
-        let tex_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &self.texture_bind_group_layout,
+        for which in 0..self.groups.len() {
+            if self.groups[which].texture.as_ref().map(|(id, _)| *id) == Some(old) {
+                self.set_group_texture(gpu, which, new);
+            }
+        }
+    }
+    pub fn set_group_sampler(&mut self, gpu: &WGPU, which: usize, sampler: SamplerOptions) {
+        self.groups[which].sampler = sampler;
+        self.refresh_texture_bind_group(gpu, which);
+    }
+    pub fn group_sampler(&self, which: usize) -> SamplerOptions {
+        self.groups[which].sampler
+    }
+    // Draws the group palette-swapped: its texture's red channel holds color indices (0-255) into
+    // the first row of `palette`, so one sheet can be recolored any number of ways. Index textures
+    // should keep nearest filtering, since blended indices are meaningless. None goes back to the
+    // default shader.
+    pub fn set_group_palette(&mut self, gpu: &WGPU, which: usize, palette: Option<&wgpu::Texture>) {
+        let view = palette.map(|p| p.create_view(&wgpu::TextureViewDescriptor::default()));
+        let shader = if view.is_some() {
+            ShaderId::PALETTE
+        } else {
+            ShaderId::DEFAULT
+        };
+        self.groups[which].palette = view;
+        self.refresh_texture_bind_group(gpu, which);
+        self.set_group_shader(gpu, which, shader);
+    }
+    fn refresh_texture_bind_group(&mut self, gpu: &WGPU, which: usize) {
+        let group = &self.groups[which];
+        let tex_bind_group = self.texture_bind_group(
+            gpu,
+            group.name.as_deref(),
+            &group.tex_view,
+            &group.sampler,
+            group.palette.as_ref(),
+        );
+        self.groups[which].tex_bind_group = tex_bind_group;
+        self.changed = true;
+    }
+    fn texture_bind_group(
+        &self,
+        gpu: &WGPU,
+        name: Option<&str>,
+        view: &wgpu::TextureView,
+        sampler: &SamplerOptions,
+        palette: Option<&wgpu::TextureView>,
+    ) -> wgpu::BindGroup {
+        let sampler = gpu.cache.sampler(&gpu.device, *sampler);
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: gpu_label(name, "texture bind group").as_deref(),
+            layout: &self.layouts.texture,
             entries: &[
-                // One for the texture, one for the sampler
+                // One for the texture, one for the sampler, and one for the palette
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view_kingtex_king),
+                    resource: wgpu::BindingResource::TextureView(view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler_kingtex_king),
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        palette.unwrap_or(&self.white_view),
+                    ),
                 },
             ],
-        });
-
-        let buffer_sprite = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: bytemuck::cast_slice::<_, u8>(&sprites).len() as u64,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        })
+    }
+    pub fn add_sprite_group(
+        &mut self,
+        gpu: &WGPU,
+        tex: &wgpu::Texture,
+        sprites: Vec<GPUSprite>,
+        camera: GPUCamera,
+    ) {
+        let sampler = SamplerOptions::for_texture(tex);
+        self.add_sprite_group_with_sampler(gpu, tex, sprites, camera, sampler);
+    }
+    // add_sprite_group with a name to find the group by (group_by_name) that also shows up in
+    // error messages and on its buffers and bind groups in GPU debuggers like RenderDoc.
+    pub fn add_named_sprite_group(
+        &mut self,
+        gpu: &WGPU,
+        name: &str,
+        tex: &wgpu::Texture,
+        sprites: Vec<GPUSprite>,
+        camera: GPUCamera,
+    ) {
+        let sampler = SamplerOptions::for_texture(tex);
+        self.add_group_with_texture(gpu, Some(name.to_owned()), tex, sprites, camera, sampler);
+    }
+    pub fn add_sprite_group_with_sampler(
+        &mut self,
+        gpu: &WGPU,
+        tex: &wgpu::Texture,
+        sprites: Vec<GPUSprite>,
+        camera: GPUCamera,
+        sampler: SamplerOptions,
+    ) {
+        self.add_group_with_texture(gpu, None, tex, sprites, camera, sampler);
+    }
+    fn add_group_with_texture(
+        &mut self,
+        gpu: &WGPU,
+        name: Option<String>,
+        tex: &wgpu::Texture,
+        sprites: Vec<GPUSprite>,
+        camera: GPUCamera,
+        sampler: SamplerOptions,
+    ) {
+        let tex_view = tex.create_view(&wgpu::TextureViewDescriptor::default());
+        self.push_group(gpu, name, tex_view, sprites, camera, sampler);
+        self.groups.last_mut().unwrap().texture = Some((tex.global_id(), gpu.texture_memory(tex)));
+    }
+    // A group of plain colored rectangles (GPUSprite::solid and gradient) with no texture of its
+    // own, for prototyping, fades and UI backgrounds. Give it BlendMode::Alpha for translucency.
+    pub fn add_rect_group(&mut self, gpu: &WGPU, rects: Vec<GPUSprite>, camera: GPUCamera) {
+        let tex_view = self
+            .white
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.push_group(
+            gpu,
+            None,
+            tex_view,
+            rects,
+            camera,
+            SamplerOptions::PIXEL_ART,
+        );
+    }
+    fn push_group(
+        &mut self,
+        gpu: &WGPU,
+        name: Option<String>,
+        tex_view: wgpu::TextureView,
+        sprites: Vec<GPUSprite>,
+        camera: GPUCamera,
+        sampler: SamplerOptions,
+    ) {
+        let label = name.as_deref();
+        let tex_bind_group = self.texture_bind_group(gpu, label, &tex_view, &sampler, None);
+        self.changed = true;
 
         let buffer_camera = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
+            label: gpu_label(label, "camera").as_deref(),
             size: std::mem::size_of::<GPUCamera>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
-
-        let sprite_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &self.sprite_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: buffer_camera.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: buffer_sprite.as_entire_binding(),
-                },
-            ],
-        });
+        let capacity = sprites.len().max(1);
+        let (buffer_sprite, sprite_bind_group) =
+            self.create_sprite_buffer(gpu, label, capacity, &buffer_camera);
         gpu.queue
             .write_buffer(&buffer_sprite, 0, bytemuck::cast_slice(&sprites));
 
         gpu.queue
             .write_buffer(&buffer_camera, 0, bytemuck::bytes_of(&camera));
+        let spare_buffers = self.create_spare_buffers(gpu, label, capacity, &buffer_camera);
         self.groups.push(SpriteGroup {
+            name,
             sprite_buffer: buffer_sprite,
             sprites,
             tex_bind_group,
+            tex_view,
+            sampler,
+            palette: None,
+            shader: ShaderId::DEFAULT,
+            blend: BlendMode::Replace,
+            clip: None,
             sprite_bind_group,
             camera,
             buffer_camera,
+            capacity,
+            culled: false,
+            visible: 0,
+            needs_cull: false,
+            gpu_cull: None,
+            spare_buffers,
+            stale_buffers: 0,
+            spatial: None,
+            spatial_stale: Vec::new(),
+            alpha_mask: None,
+            tags: HashMap::new(),
+            texture: None,
         });
+        // The spares start out empty.
+        if self.frames_in_flight > 1 {
+            let which = self.groups.len() - 1;
+            let len = self.groups[which].sprites.len();
+            self.mark_dirty(which, 0..len);
+        }
 
         // self.groups.len() - 1
     }
+    // The buffers a group takes turns with besides its own, see set_frames_in_flight.
+    fn create_spare_buffers(
+        &self,
+        gpu: &WGPU,
+        name: Option<&str>,
+        capacity: usize,
+        buffer_camera: &wgpu::Buffer,
+    ) -> VecDeque<(wgpu::Buffer, wgpu::BindGroup)> {
+        (1..self.frames_in_flight)
+            .map(|_| self.create_sprite_buffer(gpu, name, capacity, buffer_camera))
+            .collect()
+    }
+    // Gives every group a buffer per frame in flight (up to 3), which they take turns drawing
+    // from. With one, the copies flush makes each frame have to wait for the GPU to finish
+    // drawing the last frame from the same buffer; with more, they go into one it's done with.
+    // Costs the memory of the extra buffers, and changed sprites are copied once per buffer.
+    // Groups culled on the GPU stay with one, since the CPU never writes what they draw from.
+    pub fn set_frames_in_flight(&mut self, gpu: &WGPU, frames: usize) {
+        let frames = frames.clamp(1, 3);
+        if frames == self.frames_in_flight {
+            return;
+        }
+        self.frames_in_flight = frames;
+        self.recent.clear();
+        for which in 0..self.groups.len() {
+            let group = &self.groups[which];
+            let spares = self.create_spare_buffers(
+                gpu,
+                group.name.as_deref(),
+                group.capacity,
+                &group.buffer_camera,
+            );
+            let group = &mut self.groups[which];
+            group.spare_buffers = spares;
+            group.needs_cull |= group.culled;
+            let len = group.sprites.len();
+            self.mark_dirty(which, 0..len);
+        }
+    }
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames_in_flight
+    }
+    // Room for `capacity` sprites, and the bind group that points the shader at it (and the
+    // camera).
+    fn create_sprite_buffer(
+        &self,
+        gpu: &WGPU,
+        name: Option<&str>,
+        capacity: usize,
+        buffer_camera: &wgpu::Buffer,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer_sprite = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: gpu_label(name, "sprites").as_deref(),
+            size: (capacity * std::mem::size_of::<GPUSprite>()) as u64,
+            usage: if self.instanced {
+                wgpu::BufferUsages::VERTEX
+            } else {
+                wgpu::BufferUsages::STORAGE
+            } | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let sprite_entries = [
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer_camera.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: buffer_sprite.as_entire_binding(),
+            },
+        ];
+        let sprite_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: gpu_label(name, "sprite bind group").as_deref(),
+            layout: &self.layouts.sprite,
+            entries: &sprite_entries[..if self.instanced { 1 } else { 2 }],
+        });
+        (buffer_sprite, sprite_bind_group)
+    }
+    // Gives the group a buffer for `capacity` sprites and marks them all to be written into it.
+    fn resize_sprite_buffer(&mut self, gpu: &WGPU, which: usize, capacity: usize) {
+        let group = &self.groups[which];
+        let name = group.name.as_deref();
+        let (buffer, bind_group) =
+            self.create_sprite_buffer(gpu, name, capacity, &group.buffer_camera);
+        let spares = self.create_spare_buffers(gpu, name, capacity, &group.buffer_camera);
+        let group = &mut self.groups[which];
+        group.sprite_buffer = buffer;
+        group.sprite_bind_group = bind_group;
+        group.spare_buffers = spares;
+        group.capacity = capacity;
+        if group.gpu_cull.is_some() {
+            group.gpu_cull = Some(crate::gpu_cull::GpuCull::new(gpu, capacity));
+        }
+        let len = group.sprites.len();
+        self.mark_dirty(which, 0..len);
+    }
+    // Adds a sprite to the end of the group (drawn last) and returns its index. The group's
+    // buffer grows on flush when it runs out of room, doubling so that adding one sprite at a
+    // time doesn't mean a new buffer every time.
+    pub fn push_sprite(&mut self, which: usize, sprite: GPUSprite) -> usize {
+        self.debug_check_group(which);
+        let sprites = &mut self.groups[which].sprites;
+        sprites.push(sprite);
+        let index = sprites.len() - 1;
+        self.mark_dirty(which, index..index + 1);
+        index
+    }
+    // Takes a sprite out of the group; the ones after it move down one index. The buffer keeps
+    // its size (see shrink_to_fit).
+    pub fn remove_sprite(&mut self, which: usize, index: usize) -> GPUSprite {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_range(which, &(index..index + 1)) {
+                panic!("remove_sprite: {e}");
+            }
+        }
+        let group = &mut self.groups[which];
+        let removed = group.sprites.remove(index);
+        // Its tags go with it, and the ones after it follow their sprites down.
+        group.tags.retain(|_, tagged| *tagged != index);
+        for tagged in group.tags.values_mut() {
+            if *tagged > index {
+                *tagged -= 1;
+            }
+        }
+        let len = group.sprites.len();
+        self.mark_dirty(which, index..len);
+        removed
+    }
+    // Replaces all of the group's sprites, keeping its buffer if they fit. Tags stay on the same
+    // indices, as long as there are still that many sprites.
+    pub fn set_sprites(&mut self, which: usize, sprites: Vec<GPUSprite>) {
+        self.debug_check_group(which);
+        let len = sprites.len();
+        self.groups[which].tags.retain(|_, tagged| *tagged < len);
+        self.groups[which].sprites = sprites;
+        self.mark_dirty(which, 0..len);
+    }
+    // Sprites the group's buffer has room for.
+    pub fn group_capacity(&self, which: usize) -> usize {
+        self.groups[which].capacity
+    }
+    // Gives back the buffer space of removed sprites.
+    pub fn shrink_to_fit(&mut self, gpu: &WGPU, which: usize) {
+        let len = self.groups[which].sprites.len().max(1);
+        if len < self.groups[which].capacity {
+            self.resize_sprite_buffer(gpu, which, len);
+        }
+    }
+    // Empties the group and gives back what it held on the GPU: its buffers shrink to one sprite
+    // and it lets go of its texture and palette, drawing nothing until set_group_texture gives it
+    // another. The group stays (with its camera, shader and other settings) so that the groups
+    // after it keep their indices; truncate_groups removes groups for good.
+    pub fn unload_group(&mut self, gpu: &WGPU, which: usize) {
+        self.debug_check_group(which);
+        let group = &mut self.groups[which];
+        group.sprites = Vec::new();
+        group.tags.clear();
+        group.visible = 0;
+        if let Some(spatial) = &mut group.spatial {
+            spatial.clear();
+        }
+        group.spatial_stale.clear();
+        group.alpha_mask = None;
+        group.palette = None;
+        group.texture = None;
+        group.tex_view = self
+            .white
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        if group.shader == ShaderId::PALETTE {
+            group.shader = ShaderId::DEFAULT;
+        }
+        self.pending.retain(|(group, _)| *group != which);
+        self.refresh_texture_bind_group(gpu, which);
+        self.resize_sprite_buffer(gpu, which, 1);
+    }
+    // Removes every group from `len` on, freeing their buffers and whatever textures only they
+    // were drawing, e.g. the groups a level added on top of the ones the game always has.
+    pub fn truncate_groups(&mut self, len: usize) {
+        if len >= self.groups.len() {
+            return;
+        }
+        self.groups.truncate(len);
+        self.pending.retain(|(group, _)| *group < len);
+        for changed in &mut self.recent {
+            changed.retain(|(group, _)| *group < len);
+        }
+        self.changed = true;
+    }
+    // Points every group drawing `texture` at nothing (see unload_group), so that dropping the
+    // game's handle to it frees it.
+    pub(crate) fn release_texture(&mut self, gpu: &WGPU, texture: wgpu::Id<wgpu::Texture>) {
+        for which in 0..self.groups.len() {
+            if self.groups[which].texture.as_ref().map(|(id, _)| *id) == Some(texture) {
+                self.groups[which].texture = None;
+                self.groups[which].tex_view = self
+                    .white
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                self.refresh_texture_bind_group(gpu, which);
+            }
+        }
+    }
 
-    pub fn print_group(&self, sprite: usize) {}
+    pub fn print_group(&self, _sprite: usize) {}
     pub fn set_camera(&mut self, gpu: &WGPU, index: usize, camera: GPUCamera) {
+        self.debug_check_group(index);
+        self.changed = true;
         let sg = &mut self.groups[index];
         sg.camera = camera;
+        sg.needs_cull |= sg.culled;
 
         gpu.queue
             .write_buffer(&sg.buffer_camera, 0, bytemuck::bytes_of(&sg.camera));
     }
+    pub fn get_camera(&self, index: usize) -> GPUCamera {
+        self.groups[index].camera
+    }
+    pub fn try_set_camera(
+        &mut self,
+        gpu: &WGPU,
+        index: usize,
+        camera: GPUCamera,
+    ) -> Result<(), SpriteError> {
+        self.check_group(index)?;
+        self.set_camera(gpu, index, camera);
+        Ok(())
+    }
     pub fn set_camera_all(&mut self, gpu: &WGPU, camera: GPUCamera) {
         for sg_index in 0..self.groups.len() {
             self.set_camera(gpu, sg_index, camera);
         }
     }
 
-    pub fn refresh_sprites(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
-        gpu.queue.write_buffer(
-            &self.groups[which].sprite_buffer,
-            range.start as u64,
-            bytemuck::cast_slice(&self.groups[which].sprites[range]),
-        )
+    // Marks sprites to be sent to the GPU by the next flush. Sprites changed through
+    // get_sprite_mut and get_all_sprites_mut (and their try_ versions) are marked already, so this
+    // is only needed for changes made some other way.
+    pub fn refresh_sprites(&mut self, _gpu: &WGPU, which: usize, range: Range<usize>) {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_range(which, &range) {
+                panic!("refresh_sprites: {e}");
+            }
+        }
+        self.mark_dirty(which, range);
+    }
+    // Overlapping and neighboring ranges end up as one copy. Most changes come in order (a loop
+    // over a group), so they're merged as they arrive and the rest at flush time.
+    pub(crate) fn mark_dirty(&mut self, which: usize, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.changed = true;
+        let group = &mut self.groups[which];
+        if group.spatial.is_some() {
+            match group.spatial_stale.last_mut() {
+                Some(last) if range.start <= last.end && last.start <= range.end => {
+                    last.start = last.start.min(range.start);
+                    last.end = last.end.max(range.end);
+                }
+                _ => group.spatial_stale.push(range.clone()),
+            }
+        }
+        // Culled groups are written out whole, so there's nothing finer to keep track of.
+        if self.groups[which].culled {
+            self.groups[which].needs_cull = true;
+            return;
+        }
+        if let Some((last, last_range)) = self.pending.last_mut() {
+            if *last == which && range.start <= last_range.end && last_range.start <= range.end {
+                last_range.start = last_range.start.min(range.start);
+                last_range.end = last_range.end.max(range.end);
+                return;
+            }
+        }
+        self.pending.push((which, range));
+    }
+    // Copies the refreshed sprites into their groups' buffers as part of `encoder`, which has to
+    // be submitted before render's pass is. The Engine does this for its own SpriteRenders; call
+    // it yourself when rendering one into a target of your own.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "sprite upload", skip_all)
+    )]
+    pub fn flush(&mut self, gpu: &WGPU, encoder: &mut wgpu::CommandEncoder) {
+        // Anything from the last flush has been submitted by now, so its buffers can come back.
+        self.staging.recall();
+        self.changed = false;
+        for which in 0..self.groups.len() {
+            let (len, capacity) = (
+                self.groups[which].sprites.len(),
+                self.groups[which].capacity,
+            );
+            if len > capacity {
+                self.resize_sprite_buffer(gpu, which, len.max(capacity * 2));
+            }
+        }
+        for group in &mut self.groups {
+            if group.gpu_cull.is_none() {
+                if let Some((buffer, bind_group)) = group.spare_buffers.pop_front() {
+                    let buffer = std::mem::replace(&mut group.sprite_buffer, buffer);
+                    let bind_group = std::mem::replace(&mut group.sprite_bind_group, bind_group);
+                    group.spare_buffers.push_back((buffer, bind_group));
+                }
+            }
+        }
+        for group in &mut self.groups {
+            // Every buffer the group takes turns with needs the newly visible sprites.
+            if std::mem::take(&mut group.needs_cull) {
+                group.stale_buffers = self.frames_in_flight;
+            }
+            if group.stale_buffers == 0 {
+                continue;
+            }
+            group.stale_buffers -= 1;
+            let visible: Vec<GPUSprite> = group
+                .sprites
+                .iter()
+                .filter(|sprite| overlaps_camera(sprite, &group.camera))
+                .copied()
+                .collect();
+            group.visible = visible.len();
+            let bytes: &[u8] = bytemuck::cast_slice(&visible);
+            if let Some(size) = wgpu::BufferSize::new(bytes.len() as u64) {
+                self.staging
+                    .write_buffer(encoder, &group.sprite_buffer, 0, size, &gpu.device)
+                    .copy_from_slice(bytes);
+            }
+        }
+        let changed = merge_ranges(std::mem::take(&mut self.pending));
+        // This frame's buffers missed what changed while the other buffers were current.
+        let merged = merge_ranges(
+            self.recent
+                .iter()
+                .flatten()
+                .chain(&changed)
+                .cloned()
+                .collect(),
+        );
+        self.recent.push_back(changed);
+        while self.recent.len() >= self.frames_in_flight {
+            self.recent.pop_front();
+        }
+        for (which, range) in merged {
+            let group = &self.groups[which];
+            // Marked before culling was turned on; the cull above wrote the group already.
+            if group.culled {
+                continue;
+            }
+            // Sprites may have been removed since.
+            let len = group.sprites.len();
+            let range = range.start.min(len)..range.end.min(len);
+            let bytes: &[u8] = bytemuck::cast_slice(&group.sprites[range.clone()]);
+            let Some(size) = wgpu::BufferSize::new(bytes.len() as u64) else {
+                continue;
+            };
+            // GPU-culled groups keep all of their sprites in the buffer the culling reads.
+            let target = group
+                .gpu_cull
+                .as_ref()
+                .map_or(&group.sprite_buffer, |cull| &cull.source);
+            self.staging
+                .write_buffer(
+                    encoder,
+                    target,
+                    // The offset is in bytes, not sprites.
+                    (range.start * std::mem::size_of::<GPUSprite>()) as u64,
+                    size,
+                    &gpu.device,
+                )
+                .copy_from_slice(bytes);
+        }
+        self.write_indirect_args(gpu, encoder);
+        self.cull_on_gpu(gpu, encoder);
+        self.staging.finish();
+    }
+    // Draws every group with draw_indirect, its instance count read from a buffer on the GPU
+    // that flush keeps up to date, rather than from the draw call. That's the groundwork for
+    // GPU-driven drawing, where a compute pass decides how many sprites each group draws.
+    // (Every group has its own bind groups, so there's nothing for multi_draw_indirect to merge.)
+    // Returns false, leaving it off, where indirect draws aren't supported (WebGL2).
+    pub fn set_indirect(&mut self, gpu: &WGPU, indirect: bool) -> bool {
+        if !indirect {
+            // GPU culling can't do without it.
+            for which in 0..self.groups.len() {
+                self.set_group_gpu_culling_off(which);
+            }
+            self.indirect = None;
+            return true;
+        }
+        let supported = gpu
+            .downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION);
+        if supported && self.indirect.is_none() {
+            self.indirect = Some(IndirectArgs::default());
+        }
+        supported
+    }
+    pub fn is_indirect(&self) -> bool {
+        self.indirect.is_some()
+    }
+    // Writes the draw arguments that changed since the last flush. GPU-culled groups start from
+    // zero sprites every time, for the compute pass to count up.
+    fn write_indirect_args(&mut self, gpu: &WGPU, encoder: &mut wgpu::CommandEncoder) {
+        let Some(indirect) = &mut self.indirect else {
+            return;
+        };
+        let args: Vec<[u32; 4]> = self
+            .groups
+            .iter()
+            .map(|group| match group.gpu_cull {
+                Some(_) => [6, 0, 0, 0],
+                None => [6, group.drawn() as u32, 0, 0],
+            })
+            .collect();
+        let gpu_culling = self.groups.iter().any(|group| group.gpu_cull.is_some());
+        if indirect.args == args && !gpu_culling {
+            return;
+        }
+        let needed = std::mem::size_of_val(args.as_slice()) as u64;
+        let buffer = match &indirect.buffer {
+            Some(buffer) if buffer.size() >= needed => buffer,
+            _ => {
+                // The culling bind groups point at the old one.
+                for group in &mut self.groups {
+                    if let Some(cull) = &mut group.gpu_cull {
+                        cull.bind_group = None;
+                    }
+                }
+                indirect
+                    .buffer
+                    .insert(gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some("sprite draw args"),
+                        size: needed.next_power_of_two(),
+                        usage: wgpu::BufferUsages::INDIRECT
+                            | wgpu::BufferUsages::STORAGE
+                            | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }))
+            }
+        };
+        if let Some(size) = wgpu::BufferSize::new(needed) {
+            self.staging
+                .write_buffer(encoder, buffer, 0, size, &gpu.device)
+                .copy_from_slice(bytemuck::cast_slice(&args));
+        }
+        indirect.args = args;
+    }
+    // Runs the compute pass that fills in the GPU-culled groups' buffers and sprite counts.
+    fn cull_on_gpu(&mut self, gpu: &WGPU, encoder: &mut wgpu::CommandEncoder) {
+        let (
+            Some(pipeline),
+            Some(IndirectArgs {
+                buffer: Some(args), ..
+            }),
+        ) = (&self.cull_pipeline, &self.indirect)
+        else {
+            return;
+        };
+        let mut any = false;
+        for (slot, group) in self.groups.iter_mut().enumerate() {
+            let Some(cull) = &mut group.gpu_cull else {
+                continue;
+            };
+            any = true;
+            let params = crate::gpu_cull::CullParams::new(&group.camera, group.sprites.len(), slot);
+            let bytes = bytemuck::bytes_of(&params);
+            if let Some(size) = wgpu::BufferSize::new(bytes.len() as u64) {
+                self.staging
+                    .write_buffer(encoder, &cull.params, 0, size, &gpu.device)
+                    .copy_from_slice(bytes);
+            }
+            if cull.bind_group.is_none() {
+                cull.bind(gpu, pipeline, &group.sprite_buffer, args);
+            }
+        }
+        if any {
+            pipeline.dispatch(
+                encoder,
+                self.groups.iter().filter_map(|group| {
+                    let bind_group = group.gpu_cull.as_ref()?.bind_group.as_ref()?;
+                    Some((bind_group, group.sprites.len().min(group.capacity)))
+                }),
+            );
+        }
+    }
+    pub fn try_refresh_sprites(
+        &mut self,
+        gpu: &WGPU,
+        which: usize,
+        range: Range<usize>,
+    ) -> Result<(), SpriteError> {
+        self.check_range(which, &range)?;
+        self.refresh_sprites(gpu, which, range);
+        Ok(())
     }
 
     pub fn get_sprite_mut(&mut self, which: usize, range: usize) -> &mut GPUSprite {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_range(which, &(range..range + 1)) {
+                panic!("get_sprite_mut: {e}");
+            }
+        }
+        self.mark_dirty(which, range..range + 1);
         &mut self.groups[which].sprites[range]
     }
+    pub fn try_get_sprite_mut(&mut self, which: usize, index: usize) -> Option<&mut GPUSprite> {
+        self.check_range(which, &(index..index + 1)).ok()?;
+        self.mark_dirty(which, index..index + 1);
+        self.groups[which].sprites.get_mut(index)
+    }
     pub fn get_sprites(&self, which: usize) -> &[GPUSprite] {
+        self.debug_check_group(which);
         &self.groups[which].sprites
     }
+    pub fn try_get_sprites(&self, which: usize) -> Option<&[GPUSprite]> {
+        self.groups.get(which).map(|g| g.sprites.as_slice())
+    }
+    // The group's sprites without marking any of them, for code that marks what it changed
+    // itself (TrackedSprites).
+    pub(crate) fn sprites_mut_unmarked(&mut self, which: usize) -> &mut [GPUSprite] {
+        &mut self.groups[which].sprites
+    }
+    // Marks the whole group for the next flush; to send less, change sprites with get_sprite_mut
+    // or call refresh_sprites with the range that changed.
     pub fn get_all_sprites_mut(&mut self, which: usize) -> &mut [GPUSprite] {
+        self.debug_check_group(which);
+        self.mark_dirty(which, 0..self.groups[which].sprites.len());
         &mut self.groups[which].sprites
     }
+    pub fn try_get_all_sprites_mut(&mut self, which: usize) -> Option<&mut [GPUSprite]> {
+        self.check_group(which).ok()?;
+        Some(self.get_all_sprites_mut(which))
+    }
+    // Gives a sprite a name to look it up by within its group, so game code doesn't depend on the
+    // order sprites were added in. Anything that's AsRef<str> will do, including enums that
+    // implement it (by hand or with strum's AsRefStr). A tag names one sprite per group, so
+    // tagging another with it moves it. Tags follow their sprites when remove_sprite moves them
+    // down, and go when the sprite does.
+    pub fn tag_sprite(&mut self, which: usize, index: usize, tag: impl AsRef<str>) {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_range(which, &(index..index + 1)) {
+                panic!("tag_sprite: {e}");
+            }
+        }
+        self.groups[which]
+            .tags
+            .insert(tag.as_ref().to_owned(), index);
+    }
+    // Takes the tag off whichever sprite had it, returning that sprite's index.
+    pub fn untag_sprite(&mut self, which: usize, tag: impl AsRef<str>) -> Option<usize> {
+        self.debug_check_group(which);
+        self.groups[which].tags.remove(tag.as_ref())
+    }
+    pub fn sprite_index_by_tag(&self, which: usize, tag: impl AsRef<str>) -> Option<usize> {
+        self.groups.get(which)?.tags.get(tag.as_ref()).copied()
+    }
+    pub fn get_sprite_by_tag(&self, which: usize, tag: impl AsRef<str>) -> Option<&GPUSprite> {
+        let index = self.sprite_index_by_tag(which, tag)?;
+        self.groups[which].sprites.get(index)
+    }
+    // Marks just that sprite for the next flush, like get_sprite_mut.
+    pub fn get_sprite_by_tag_mut(
+        &mut self,
+        which: usize,
+        tag: impl AsRef<str>,
+    ) -> Option<&mut GPUSprite> {
+        let index = self.sprite_index_by_tag(which, tag)?;
+        self.try_get_sprite_mut(which, index)
+    }
+    // The first sprite with this tag in any group, for when the group isn't known either.
+    pub fn find_sprite_by_tag(&self, tag: impl AsRef<str>) -> Option<SpriteHandle> {
+        let tag = tag.as_ref();
+        self.groups.iter().enumerate().find_map(|(which, group)| {
+            let index = *group.tags.get(tag)?;
+            Some(SpriteHandle::new(which, index))
+        })
+    }
+    // Like get_all_sprites_mut, marking only `range`.
+    pub fn get_sprites_mut(&mut self, which: usize, range: Range<usize>) -> &mut [GPUSprite] {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_range(which, &range) {
+                panic!("get_sprites_mut: {e}");
+            }
+        }
+        self.mark_dirty(which, range.clone());
+        &mut self.groups[which].sprites[range]
+    }
+    // Hands `update` the sprites in `range` (any range: 2..5, ..10, ..) and marks exactly that
+    // range afterwards, so the next flush copies it into the buffer in one write, whatever the
+    // closure did. Returns what `update` does.
+    //
+    //     let moved = engine.sprites.update_sprites(bullets, .., |bullets| {
+    //         bullets.iter_mut().for_each(|b| b.screen_region[1] += 4.0);
+    //         bullets.len()
+    //     });
+    pub fn update_sprites<T>(
+        &mut self,
+        which: usize,
+        range: impl std::ops::RangeBounds<usize>,
+        update: impl FnOnce(&mut [GPUSprite]) -> T,
+    ) -> T {
+        use std::ops::Bound;
+        self.debug_check_group(which);
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.groups[which].sprites.len(),
+        };
+        let range = start..end;
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_range(which, &range) {
+                panic!("update_sprites: {e}");
+            }
+        }
+        let result = update(&mut self.groups[which].sprites[range.clone()]);
+        self.mark_dirty(which, range);
+        result
+    }
+    // Calls `update` with the index and a mutable reference of every sprite in the group, spread
+    // across rayon's thread pool, and marks the group for the next flush. Only worth it for big
+    // groups whose sprites don't depend on each other, e.g. particles.
+    #[cfg(feature = "parallel")]
+    pub fn par_update_sprites(
+        &mut self,
+        which: usize,
+        update: impl Fn(usize, &mut GPUSprite) + Sync + Send,
+    ) {
+        use rayon::prelude::*;
+        // Small enough pieces to balance, big enough to not be all overhead.
+        const MIN_PER_TASK: usize = 1024;
+        self.get_all_sprites_mut(which)
+            .par_iter_mut()
+            .with_min_len(MIN_PER_TASK)
+            .enumerate()
+            .for_each(|(i, sprite)| update(i, sprite));
+    }
     pub fn group_size(&self, which: usize) -> &[GPUSprite] {
         &self.groups[which].sprites
     }
+    // Keeps a SpatialHash of the group's sprites alongside them, with cells `cell_size` wide, so
+    // that query_region and sprite_neighbors don't have to look at every sprite. Worth it for
+    // groups of thousands of sprites. None drops it.
+    pub fn set_group_spatial_hash(&mut self, which: usize, cell_size: Option<f32>) {
+        let group = &mut self.groups[which];
+        group.spatial = cell_size.map(SpatialHash::new);
+        group.spatial_stale.clear();
+        if group.spatial.is_some() {
+            group.spatial_stale.push(0..group.sprites.len());
+        }
+    }
+    pub fn group_spatial_hash(&self, which: usize) -> Option<&SpatialHash> {
+        self.groups[which].spatial.as_ref()
+    }
+    // The indices of the group's sprites overlapping `rect`, in order. Goes through the group's
+    // spatial hash if it has one, and every sprite otherwise.
+    pub fn query_region(&mut self, which: usize, rect: &Rect) -> Vec<usize> {
+        self.update_spatial_hash(which);
+        let group = &self.groups[which];
+        match &group.spatial {
+            Some(spatial) => spatial.query_region(rect),
+            None => crate::sprites_overlapping(&group.sprites, rect),
+        }
+    }
+    // query_region as handles, for box selection in an editor, everything in a blast and the
+    // like. Takes a Rect or an [x, y, width, height]; Rect::from_corners makes one from a drag.
+    pub fn sprites_in_rect(&mut self, which: usize, rect: impl Into<Rect>) -> Vec<SpriteHandle> {
+        let rect = rect.into();
+        self.query_region(which, &rect)
+            .into_iter()
+            .map(|index| SpriteHandle::new(which, index))
+            .collect()
+    }
+    // sprites_in_rect over several groups, group by group in the order given.
+    pub fn sprites_in_rect_of(
+        &mut self,
+        groups: impl IntoIterator<Item = usize>,
+        rect: impl Into<Rect>,
+    ) -> Vec<SpriteHandle> {
+        let rect = rect.into();
+        groups
+            .into_iter()
+            .flat_map(|which| self.sprites_in_rect(which, rect))
+            .collect()
+    }
+    // The group's other sprites within `radius` of sprite `index`; see SpatialHash::neighbors.
+    pub fn sprite_neighbors(&mut self, which: usize, index: usize, radius: f32) -> Vec<usize> {
+        self.update_spatial_hash(which);
+        let group = &self.groups[which];
+        match &group.spatial {
+            Some(spatial) => spatial.neighbors(index, radius),
+            None => {
+                let rect = group.sprites[index].bounds();
+                let grown = Rect::new(
+                    rect.x - radius,
+                    rect.y - radius,
+                    rect.width + 2.0 * radius,
+                    rect.height + 2.0 * radius,
+                );
+                let mut found = crate::sprites_overlapping(&group.sprites, &grown);
+                found.retain(|&other| other != index);
+                found
+            }
+        }
+    }
+    // The nearest of the group's sprites a ray hits; see raycast_sprites. With a spatial hash,
+    // only the sprites near the ray are tried.
+    pub fn raycast_group(
+        &mut self,
+        which: usize,
+        origin: impl Into<Vec2>,
+        direction: impl Into<Vec2>,
+        max_distance: f32,
+    ) -> Option<(usize, crate::RayHit)> {
+        let (origin, direction) = (origin.into(), direction.into());
+        if self.groups[which].spatial.is_none() {
+            return crate::raycast_sprites(
+                origin,
+                direction,
+                max_distance,
+                &self.groups[which].sprites,
+            );
+        }
+        let length = direction[0].hypot(direction[1]);
+        if length == 0.0 {
+            return None;
+        }
+        let end = [
+            origin[0] + direction[0] / length * max_distance,
+            origin[1] + direction[1] / length * max_distance,
+        ];
+        let reach =
+            Rect::new(origin[0], origin[1], 0.0, 0.0).union(&Rect::new(end[0], end[1], 0.0, 0.0));
+        // Grown a little, so sprites the ray only grazes along an edge are still tried.
+        let reach = Rect::new(
+            reach.x - 1.0,
+            reach.y - 1.0,
+            reach.width + 2.0,
+            reach.height + 2.0,
+        );
+        let candidates = self.query_region(which, &reach);
+        let sprites = &self.groups[which].sprites;
+        candidates
+            .into_iter()
+            .filter_map(|index| {
+                Some((
+                    index,
+                    crate::raycast_rect(origin, direction, max_distance, &sprites[index].bounds())?,
+                ))
+            })
+            .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))
+    }
+    fn update_spatial_hash(&mut self, which: usize) {
+        let group = &mut self.groups[which];
+        let Some(spatial) = &mut group.spatial else {
+            return;
+        };
+        let len = group.sprites.len();
+        spatial.truncate(len);
+        for range in group.spatial_stale.drain(..) {
+            for index in range.start..range.end.min(len) {
+                spatial.insert(index, group.sprites[index].bounds());
+            }
+        }
+    }
+    // Pairs of overlapping sprites between groups `a` and `b` (or within `a`, if they're the same
+    // group); see collisions_between and collisions_within. Both groups' sprites are taken to be
+    // in the same world space. Sprites with GPUSprite::PIXEL_COLLISION in a group with an alpha
+    // mask only count where they're solid.
+    pub fn group_collisions(&self, a: usize, b: usize) -> Vec<(usize, usize)> {
+        let (group_a, group_b) = (&self.groups[a], &self.groups[b]);
+        let pairs = if a == b {
+            crate::collisions_within(&group_a.sprites)
+        } else {
+            crate::collisions_between(&group_a.sprites, &group_b.sprites)
+        };
+        if group_a.alpha_mask.is_none() && group_b.alpha_mask.is_none() {
+            return pairs;
+        }
+        pairs
+            .into_iter()
+            .filter(|&(i, j)| {
+                let (sprite_a, sprite_b) = (&group_a.sprites[i], &group_b.sprites[j]);
+                crate::pixels_overlap(
+                    sprite_a,
+                    group_a.mask_for(sprite_a),
+                    sprite_b,
+                    group_b.mask_for(sprite_b),
+                )
+            })
+            .collect()
+    }
+    // Which texels of the group's texture are solid, for its sprites with
+    // GPUSprite::PIXEL_COLLISION. Share one mask between the groups drawing the same texture.
+    pub fn set_group_alpha_mask(&mut self, which: usize, mask: Option<Arc<AlphaMask>>) {
+        self.groups[which].alpha_mask = mask;
+    }
+    pub fn group_alpha_mask(&self, which: usize) -> Option<&Arc<AlphaMask>> {
+        self.groups[which].alpha_mask.as_ref()
+    }
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    // The name the group was made with (add_named_sprite_group) or given by set_group_name.
+    pub fn group_name(&self, which: usize) -> Option<&str> {
+        self.groups[which].name.as_deref()
+    }
+    // Names (or renames) a group for group_by_name and error messages. Buffers and bind groups the
+    // group already has keep the labels they were made with.
+    pub fn set_group_name(&mut self, which: usize, name: impl Into<String>) {
+        self.debug_check_group(which);
+        self.groups[which].name = Some(name.into());
+    }
+    // The first group with this name, so code doesn't have to hard-code the order groups were
+    // added in.
+    pub fn group_by_name(&self, name: &str) -> Option<usize> {
+        self.groups
+            .iter()
+            .position(|group| group.name.as_deref() == Some(name))
+    }
+
+    // How errors and assertions refer to a group.
+    fn describe_group(&self, which: usize) -> String {
+        match self
+            .groups
+            .get(which)
+            .and_then(|group| group.name.as_deref())
+        {
+            Some(name) => format!("group {which} ({name:?})"),
+            None => format!("group {which}"),
+        }
+    }
+    fn check_group(&self, which: usize) -> Result<(), SpriteError> {
+        if which < self.groups.len() {
+            Ok(())
+        } else {
+            Err(SpriteError::NoSuchGroup {
+                group: which,
+                group_count: self.groups.len(),
+            })
+        }
+    }
+    fn check_range(&self, which: usize, range: &Range<usize>) -> Result<(), SpriteError> {
+        self.check_group(which)?;
+        let len = self.groups[which].sprites.len();
+        if range.start <= range.end && range.end <= len {
+            Ok(())
+        } else {
+            Err(SpriteError::OutOfRange {
+                group: self.describe_group(which),
+                range: range.clone(),
+                len,
+            })
+        }
+    }
+    // Gives a readable panic in debug builds instead of a bare index-out-of-bounds.
+    fn debug_check_group(&self, which: usize) {
+        if cfg!(debug_assertions) {
+            if let Err(e) = self.check_group(which) {
+                panic!("{e}");
+            }
+        }
+    }
+
+    // Draw calls and sprites the next `render` will submit.
+    pub fn draw_call_count(&self) -> usize {
+        self.groups.len()
+    }
+    pub fn sprite_count(&self) -> usize {
+        self.groups.iter().map(SpriteGroup::drawn).sum()
+    }
 
     pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
     where
         's: 'pass,
     {
-        rpass.set_pipeline(&self.pipeline);
-        for group in self.groups.iter() {
+        let (target_width, target_height) = self.target_size;
+        let mut current_pipeline = None;
+        // The scissor rect stays set for the rest of the pass, so it's reset after a clipped group.
+        let mut scissored = false;
+        if let Some(pipeline) = &self.debug_pipeline {
+            rpass.set_pipeline(pipeline);
+            rpass.set_blend_constant(DebugView::OVERDRAW_STEP);
+        }
+        for (index, group) in self.groups.iter().enumerate() {
+            if self.debug_view == DebugView::DrawCalls {
+                rpass.set_blend_constant(draw_call_color(index));
+            } else if self.debug_view == DebugView::Normal {
+                let pipeline = (group.shader, group.blend);
+                if current_pipeline != Some(pipeline) {
+                    rpass.set_pipeline(&self.pipelines[&pipeline]);
+                    current_pipeline = Some(pipeline);
+                }
+            }
             // rpass.set_vertex_buffer(0, group.sprite_buffer.slice(0..10));
             //maybe take out of loop idk
 
+            if let Some([x, y, width, height]) = group.clip {
+                // wgpu rejects scissor rects that poke outside the target.
+                let x = x.min(target_width);
+                let y = y.min(target_height);
+                let width = width.min(target_width - x);
+                let height = height.min(target_height - y);
+                if width == 0 || height == 0 {
+                    continue;
+                }
+                rpass.set_scissor_rect(x, y, width, height);
+                scissored = true;
+            } else if scissored {
+                rpass.set_scissor_rect(0, 0, target_width, target_height);
+                scissored = false;
+            }
             rpass.set_bind_group(0, &group.sprite_bind_group, &[]);
             rpass.set_bind_group(1, &group.tex_bind_group, &[]);
-            rpass.draw(0..6, 0..(group.sprites.len() as u32));
+            if self.instanced {
+                // Slicing an empty buffer panics.
+                if group.drawn() == 0 {
+                    continue;
+                }
+                rpass.set_vertex_buffer(0, group.sprite_buffer.slice(..));
+            }
+            // Groups added since the last flush don't have arguments yet.
+            match &self.indirect {
+                Some(IndirectArgs {
+                    buffer: Some(buffer),
+                    args,
+                }) if index < args.len() => {
+                    rpass.draw_indirect(buffer, (index * std::mem::size_of::<[u32; 4]>()) as u64)
+                }
+                _ => rpass.draw(0..6, 0..(group.drawn() as u32)),
+            }
         }
     }
 
-    pub fn update_position(&mut self, newRegion: [f32; 4], sprite: usize) {
-        let theSprite = self.get_sprite_mut(sprite, 0);
-        theSprite.screen_region = newRegion;
+    pub fn update_position(&mut self, new_region: [f32; 4], sprite: usize) {
+        let the_sprite = self.get_sprite_mut(sprite, 0);
+        the_sprite.screen_region = new_region;
     }
 
     //Trying to make moving platforms that move back and foth
     pub fn platform_move(&mut self) {
-        let allPlatforms = self.get_all_sprites_mut(2);
-        for platform in allPlatforms.iter_mut() {
-            platform.sheet_region[0] = platform.sheet_region[0] + 32.0;
+        let all_platforms = self.get_all_sprites_mut(2);
+        for platform in all_platforms.iter_mut() {
+            platform.sheet_region[0] += 32.0;
         }
     }
 }
 
 pub struct SpriteGroup {
+    // See SpriteRender::set_group_name.
+    name: Option<String>,
     sprite_buffer: wgpu::Buffer,
     sprites: Vec<GPUSprite>,
     tex_bind_group: wgpu::BindGroup,
+    // Kept so the sampler can be swapped without the game handing the texture back.
+    tex_view: wgpu::TextureView,
+    sampler: SamplerOptions,
+    palette: Option<wgpu::TextureView>,
+    shader: ShaderId,
+    blend: BlendMode,
+    clip: Option<[u32; 4]>,
     sprite_bind_group: wgpu::BindGroup,
     camera: GPUCamera,
     buffer_camera: wgpu::Buffer,
+    // How many sprites sprite_buffer has room for; sprites.len() can be more until the next
+    // flush grows it.
+    capacity: usize,
+    // Only the sprites overlapping the camera go in the buffer (see set_group_culling), the
+    // first `visible` of it.
+    culled: bool,
+    visible: usize,
+    // The camera or the sprites changed since the visible ones were last picked out.
+    needs_cull: bool,
+    // Set for set_group_gpu_culling instead.
+    gpu_cull: Option<crate::gpu_cull::GpuCull>,
+    // Buffers and bind groups that take turns with sprite_buffer and sprite_bind_group, the
+    // next one first; see SpriteRender::set_frames_in_flight.
+    spare_buffers: VecDeque<(wgpu::Buffer, wgpu::BindGroup)>,
+    // How many more flushes have to write out the visible sprites of a culled group.
+    stale_buffers: usize,
+    // What tex_view is a view of, for memory_report; None for the white placeholder.
+    texture: Option<(wgpu::Id<wgpu::Texture>, TextureMemory)>,
+    // See SpriteRender::set_group_spatial_hash. The sprites changed since it was last brought up
+    // to date are filed again before the next query.
+    spatial: Option<SpatialHash>,
+    spatial_stale: Vec<Range<usize>>,
+    alpha_mask: Option<Arc<AlphaMask>>,
+    // See SpriteRender::tag_sprite.
+    tags: HashMap<String, usize>,
+}
+
+impl SpriteGroup {
+    // The mask `sprite` collides by, if it asked for one with GPUSprite::PIXEL_COLLISION.
+    fn mask_for(&self, sprite: &GPUSprite) -> Option<&AlphaMask> {
+        if sprite.flags & GPUSprite::PIXEL_COLLISION == 0 {
+            return None;
+        }
+        self.alpha_mask.as_deref()
+    }
+}
+
+impl SpriteGroup {
+    // How many sprites from the start of the buffer render draws.
+    fn drawn(&self) -> usize {
+        if self.culled {
+            self.visible
+        } else {
+            self.sprites.len().min(self.capacity)
+        }
+    }
 }
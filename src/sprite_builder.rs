@@ -0,0 +1,158 @@
+use crate::{Color, GPUSprite, Rect, Vec2};
+
+// Part of a texture, in texture coordinates or in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sheet {
+    Uv([f32; 4]),
+    Px(Rect),
+}
+
+// Makes a GPUSprite from named parts rather than the arrays it's stored as:
+//
+//     let player = GPUSprite::builder()
+//         .position(32.0, 48.0)
+//         .sheet_size(256, 128)
+//         .sheet_px([64.0, 0.0, 16.0, 24.0])
+//         .flip_x(facing_left)
+//         .build();
+//
+// sheet_px takes pixels from the top left of the texture, the way image editors show them, and
+// needs the texture's size (sheet_size or texture) to turn them into texture coordinates. Without
+// size, the sprite is as many world units across as the sheet_px rect is pixels (or 1 by 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpriteBuilder {
+    position: Vec2,
+    size: Option<Vec2>,
+    sheet: Sheet,
+    sheet_size: Option<Vec2>,
+    flip_x: bool,
+    flip_y: bool,
+    depth: f32,
+    tint: Option<Color>,
+    outline: Option<(Color, f32)>,
+}
+
+impl Default for SpriteBuilder {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            size: None,
+            sheet: Sheet::Uv([0.0, 0.0, 1.0, 1.0]),
+            sheet_size: None,
+            flip_x: false,
+            flip_y: false,
+            depth: 0.0,
+            tint: None,
+            outline: None,
+        }
+    }
+}
+
+impl SpriteBuilder {
+    // The bottom-left corner, in world space (the camera's units).
+    pub fn position(mut self, x: f32, y: f32) -> Self {
+        self.position = Vec2::new(x, y);
+        self
+    }
+    pub fn size(mut self, width: f32, height: f32) -> Self {
+        self.size = Some(Vec2::new(width, height));
+        self
+    }
+    // Position and size at once.
+    pub fn bounds(mut self, bounds: impl Into<Rect>) -> Self {
+        let bounds = bounds.into();
+        self.position = bounds.position();
+        self.size = Some(bounds.size());
+        self
+    }
+    // Part of the texture in texture coordinates, 0 to 1 from the top left, like sheet_region.
+    // The whole texture unless set.
+    pub fn sheet(mut self, region: [f32; 4]) -> Self {
+        self.sheet = Sheet::Uv(region);
+        self
+    }
+    // Part of the texture in pixels from its top left.
+    pub fn sheet_px(mut self, rect: impl Into<Rect>) -> Self {
+        self.sheet = Sheet::Px(rect.into());
+        self
+    }
+    // The texture's size in pixels, for sheet_px.
+    pub fn sheet_size(mut self, width: u32, height: u32) -> Self {
+        self.sheet_size = Some(Vec2::new(width as f32, height as f32));
+        self
+    }
+    // sheet_size from the texture itself.
+    pub fn texture(self, texture: &wgpu::Texture) -> Self {
+        self.sheet_size(texture.width(), texture.height())
+    }
+    // Mirrors the sprite left to right within the same bounds, e.g. to face the other way.
+    pub fn flip_x(mut self, flip: bool) -> Self {
+        self.flip_x = flip;
+        self
+    }
+    pub fn flip_y(mut self, flip: bool) -> Self {
+        self.flip_y = flip;
+        self
+    }
+    pub fn depth(mut self, depth: f32) -> Self {
+        self.depth = depth;
+        self
+    }
+    pub fn tint(mut self, color: impl Into<Color>) -> Self {
+        self.tint = Some(color.into());
+        self
+    }
+    pub fn outline(mut self, color: impl Into<Color>, width: f32) -> Self {
+        self.outline = Some((color.into(), width));
+        self
+    }
+
+    pub fn build(self) -> GPUSprite {
+        let sheet_region = match self.sheet {
+            Sheet::Uv(region) => region,
+            Sheet::Px(rect) => match self.sheet_size {
+                Some(sheet) => {
+                    let [x, y, w, h] = rect.region();
+                    [x / sheet.x, y / sheet.y, w / sheet.x, h / sheet.y]
+                }
+                None => {
+                    log::warn!(
+                        "SpriteBuilder::sheet_px without a sheet_size; using the whole texture"
+                    );
+                    [0.0, 0.0, 1.0, 1.0]
+                }
+            },
+        };
+        let size = self.size.unwrap_or(match self.sheet {
+            Sheet::Px(rect) => rect.size(),
+            Sheet::Uv(_) => Vec2::ONE,
+        });
+        let mut sprite = GPUSprite::new(
+            [self.position.x, self.position.y, size.x, size.y],
+            sheet_region,
+        )
+        .with_depth(self.depth);
+        // A negative size draws the sprite mirrored, starting from the opposite edge.
+        if self.flip_x {
+            sprite.screen_region[0] += size.x;
+            sprite.screen_region[2] = -size.x;
+        }
+        if self.flip_y {
+            sprite.screen_region[1] += size.y;
+            sprite.screen_region[3] = -size.y;
+        }
+        if let Some(color) = self.tint {
+            sprite = sprite.with_tint(color);
+        }
+        if let Some((color, width)) = self.outline {
+            sprite = sprite.with_outline(color, width);
+        }
+        sprite
+    }
+}
+
+impl GPUSprite {
+    pub fn builder() -> SpriteBuilder {
+        SpriteBuilder::default()
+    }
+}
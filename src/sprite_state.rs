@@ -0,0 +1,64 @@
+use crate::{BlendMode, GPUCamera, GPUSprite, ShaderId, SpriteError, SpriteRender, WGPU};
+use serde::{Deserialize, Serialize};
+
+// What SpriteRender::save_state keeps of a group: everything but its texture, sampler and
+// palette, which the game sets up when it makes the group.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GroupState {
+    pub sprites: Vec<GPUSprite>,
+    pub camera: GPUCamera,
+    pub shader: ShaderId,
+    pub blend: BlendMode,
+    pub clip: Option<[u32; 4]>,
+    pub culling: bool,
+}
+
+// The groups of a SpriteRender, first to last, for saving a game or writing a level file with
+// whichever serde format the game likes.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SpriteState {
+    pub groups: Vec<GroupState>,
+}
+
+impl SpriteRender {
+    pub fn save_state(&self) -> SpriteState {
+        let groups = (0..self.group_count())
+            .map(|which| GroupState {
+                sprites: self.get_sprites(which).to_vec(),
+                camera: self.get_camera(which),
+                shader: self.group_shader(which),
+                blend: self.group_blend(which),
+                clip: self.group_clip(which),
+                culling: self.group_culling(which),
+            })
+            .collect();
+        SpriteState { groups }
+    }
+    // Puts the saved groups' sprites and settings back into the first groups of this
+    // SpriteRender, which should be made the same way (with the same textures and shaders) as
+    // the one they were saved from. Groups after those are left as they are. A shader this
+    // SpriteRender doesn't have goes back to the default one.
+    pub fn load_state(&mut self, gpu: &WGPU, state: &SpriteState) -> Result<(), SpriteError> {
+        if state.groups.len() > self.group_count() {
+            return Err(SpriteError::NoSuchGroup {
+                group: self.group_count(),
+                group_count: self.group_count(),
+            });
+        }
+        for (which, group) in state.groups.iter().enumerate() {
+            self.set_sprites(which, group.sprites.clone());
+            self.set_camera(gpu, which, group.camera);
+            let shader = if self.has_shader(group.shader) {
+                group.shader
+            } else {
+                log::warn!("group {which} was saved with a shader that isn't here any more");
+                ShaderId::DEFAULT
+            };
+            self.set_group_shader(gpu, which, shader);
+            self.set_group_blend(gpu, which, group.blend);
+            self.set_group_clip(which, group.clip);
+            self.set_group_culling(which, group.culling);
+        }
+        Ok(())
+    }
+}
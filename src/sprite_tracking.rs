@@ -0,0 +1,119 @@
+use crate::{GPUSprite, SpriteRender};
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+
+// A group's sprites, for going over them and changing some: only the ones actually written to
+// through a SpriteMut are sent to the GPU on the next flush, where get_all_sprites_mut sends the
+// whole group. The changes are marked when this is dropped.
+//
+//     let mut sprites = engine.sprites.track_sprites(enemies);
+//     for mut enemy in sprites.iter_mut() {
+//         if enemy.position().y < 0.0 {
+//             enemy.set_position([enemy.position().x, 480.0]);
+//         }
+//     }
+pub struct TrackedSprites<'a> {
+    render: &'a mut SpriteRender,
+    which: usize,
+    // One per sprite, set once it's been written to.
+    changed: Vec<Cell<bool>>,
+}
+
+// One sprite of a TrackedSprites. Reading it through Deref doesn't count as a change; anything
+// that needs DerefMut does, even if it writes back the same value.
+pub struct SpriteMut<'a> {
+    index: usize,
+    sprite: &'a mut GPUSprite,
+    changed: &'a Cell<bool>,
+}
+
+impl SpriteMut<'_> {
+    // Where the sprite is in its group.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    pub fn is_changed(&self) -> bool {
+        self.changed.get()
+    }
+}
+
+impl Deref for SpriteMut<'_> {
+    type Target = GPUSprite;
+    fn deref(&self) -> &GPUSprite {
+        self.sprite
+    }
+}
+
+impl DerefMut for SpriteMut<'_> {
+    fn deref_mut(&mut self) -> &mut GPUSprite {
+        self.changed.set(true);
+        self.sprite
+    }
+}
+
+impl TrackedSprites<'_> {
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = SpriteMut<'_>> {
+        let changed = &self.changed;
+        self.render
+            .sprites_mut_unmarked(self.which)
+            .iter_mut()
+            .zip(changed)
+            .enumerate()
+            .map(|(index, (sprite, changed))| SpriteMut {
+                index,
+                sprite,
+                changed,
+            })
+    }
+    pub fn get_mut(&mut self, index: usize) -> Option<SpriteMut<'_>> {
+        let sprite = self
+            .render
+            .sprites_mut_unmarked(self.which)
+            .get_mut(index)?;
+        Some(SpriteMut {
+            index,
+            sprite,
+            changed: &self.changed[index],
+        })
+    }
+    pub fn len(&self) -> usize {
+        self.changed.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty()
+    }
+    // How many sprites have been written to so far.
+    pub fn changed_count(&self) -> usize {
+        self.changed.iter().filter(|changed| changed.get()).count()
+    }
+}
+
+impl Drop for TrackedSprites<'_> {
+    fn drop(&mut self) {
+        // Runs of changed sprites each go in as one range.
+        let mut start = None;
+        for index in 0..=self.changed.len() {
+            let changed = self.changed.get(index).is_some_and(Cell::get);
+            match (changed, start) {
+                (true, None) => start = Some(index),
+                (false, Some(from)) => {
+                    self.render.mark_dirty(self.which, from..index);
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl SpriteRender {
+    // The group's sprites, marking only the ones that get changed; see TrackedSprites.
+    pub fn track_sprites(&mut self, which: usize) -> TrackedSprites<'_> {
+        let len = self.get_sprites(which).len();
+        TrackedSprites {
+            render: self,
+            which,
+            changed: (0..len).map(|_| Cell::new(false)).collect(),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+
+// How many frames the percentiles and averages are computed over (about two seconds at 60fps).
+pub(crate) const WINDOW: usize = 120;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub fps: f32,
+    // Frame times in seconds over the recent window.
+    pub frame_time_avg: f32,
+    pub frame_time_p50: f32,
+    pub frame_time_p95: f32,
+    pub frame_time_p99: f32,
+    pub frame_time_max: f32,
+    // Time spent in Game::update and in recording/submitting GPU work on the last frame, in seconds.
+    pub update_time: f32,
+    pub render_time: f32,
+    // What the sprite renderer submitted on the last frame.
+    pub draw_calls: usize,
+    pub sprites: usize,
+    // How long the GPU took for each pass of the RenderGraph, in order, as of a frame or two ago.
+    // Empty unless GpuOptions::gpu_timing is set and the GPU can do it.
+    pub gpu_passes: Vec<GpuPassTime>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GpuPassTime {
+    // The RenderGraph pass's name, or for post-processing, the label its pass was added with.
+    pub label: String,
+    pub seconds: f32,
+}
+
+#[derive(Default)]
+pub(crate) struct StatsCollector {
+    frame_times: VecDeque<f32>,
+    current: FrameStats,
+}
+
+impl StatsCollector {
+    pub(crate) fn record_frame_time(&mut self, dt: f32) {
+        // The very first frame has no meaningful delta.
+        if dt <= 0.0 {
+            return;
+        }
+        if self.frame_times.len() == WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt);
+        let mut sorted: Vec<f32> = self.frame_times.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        let percentile = |p: f32| sorted[((sorted.len() - 1) as f32 * p).round() as usize];
+        let avg = sorted.iter().sum::<f32>() / sorted.len() as f32;
+        self.current.frame_time_avg = avg;
+        self.current.fps = 1.0 / avg;
+        self.current.frame_time_p50 = percentile(0.5);
+        self.current.frame_time_p95 = percentile(0.95);
+        self.current.frame_time_p99 = percentile(0.99);
+        self.current.frame_time_max = sorted[sorted.len() - 1];
+    }
+    pub(crate) fn record_update(&mut self, seconds: f32) {
+        self.current.update_time = seconds;
+    }
+    pub(crate) fn record_render(&mut self, seconds: f32, draw_calls: usize, sprites: usize) {
+        self.current.render_time = seconds;
+        self.current.draw_calls = draw_calls;
+        self.current.sprites = sprites;
+    }
+    pub(crate) fn record_gpu_passes(&mut self, passes: &[GpuPassTime]) {
+        self.current.gpu_passes.clear();
+        self.current.gpu_passes.extend_from_slice(passes);
+    }
+    // The recent window of frame times, oldest first.
+    pub(crate) fn frame_times(&self) -> &VecDeque<f32> {
+        &self.frame_times
+    }
+    pub(crate) fn stats(&self) -> FrameStats {
+        self.current.clone()
+    }
+}
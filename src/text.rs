@@ -0,0 +1,922 @@
+use crate::{Color, FontError, GPUSprite, Rect, Vec2, WGPU};
+use ab_glyph::{Font as _, ScaleFont as _};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// A font added to a GlyphAtlas. Only means something to the atlas that handed it out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(pub(crate) usize);
+
+// Empty texels left around each glyph so that filtering doesn't pick up its neighbors.
+const GLYPH_PADDING: u32 = 1;
+// Distance field glyphs are all rasterized at this size and scaled from there.
+const SDF_SIZE: f32 = 32.0;
+// How many texels the distance field reaches out from (and in from) each glyph's edge. The SDF
+// shader's SDF_TEXEL depends on it.
+const SDF_SPREAD: u32 = 4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+// Where and how GlyphAtlas::layout places a block of text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextLayout {
+    pub font: FontId,
+    // In pixels, i.e. world units.
+    pub size: f32,
+    // In world space, like GPUSprite::screen_region. Lines wrap at the width; text that doesn't
+    // fit the height runs out of the bottom (or top, or both, by v_align).
+    pub bounds: Rect,
+    pub h_align: HAlign,
+    pub v_align: VAlign,
+    // Times the font's own line height.
+    pub line_spacing: f32,
+    // Shape the text with HarfBuzz's rules (through rustybuzz) and lay right-to-left runs out
+    // right to left, for Arabic, Hebrew, Indic scripts and combining marks. Slower, so it's off
+    // unless asked for.
+    #[cfg(feature = "shaping")]
+    pub shaping: bool,
+}
+
+impl TextLayout {
+    // Top left aligned, with the font's line spacing. An infinite width never wraps (and then
+    // only left alignment makes sense).
+    pub fn new(font: FontId, size: f32, bounds: impl Into<Rect>) -> Self {
+        Self {
+            font,
+            size,
+            bounds: bounds.into(),
+            h_align: HAlign::Left,
+            v_align: VAlign::Top,
+            line_spacing: 1.0,
+            #[cfg(feature = "shaping")]
+            shaping: false,
+        }
+    }
+    pub fn with_align(self, h_align: HAlign, v_align: VAlign) -> Self {
+        Self {
+            h_align,
+            v_align,
+            ..self
+        }
+    }
+    pub fn with_line_spacing(self, line_spacing: f32) -> Self {
+        Self {
+            line_spacing,
+            ..self
+        }
+    }
+    #[cfg(feature = "shaping")]
+    pub fn with_shaping(self, shaping: bool) -> Self {
+        Self { shaping, ..self }
+    }
+    fn is_shaped(&self) -> bool {
+        #[cfg(feature = "shaping")]
+        return self.shaping;
+        #[cfg(not(feature = "shaping"))]
+        false
+    }
+}
+
+// Something a span of rich text does over time; see RichText::animate.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TextEffect {
+    #[default]
+    None,
+    // Letters bob up and down one after another, `amplitude` pixels each way, `speed` radians a
+    // second.
+    Wave {
+        amplitude: f32,
+        speed: f32,
+    },
+    // Letters jitter randomly up to `amplitude` pixels, e.g. for shouting or fear.
+    Shake {
+        amplitude: f32,
+    },
+}
+
+// A run of text with its own look, for GlyphAtlas::layout_spans.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    // Tints the glyphs (see GPUSprite::with_tint); None leaves them white.
+    pub color: Option<Color>,
+    // Another font for this span, e.g. a bold face of the layout's. None uses the layout's font.
+    pub font: Option<FontId>,
+    pub effect: TextEffect,
+}
+
+impl TextSpan {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            color: None,
+            font: None,
+            effect: TextEffect::None,
+        }
+    }
+    pub fn with_color(self, color: impl Into<Color>) -> Self {
+        Self {
+            color: Some(color.into()),
+            ..self
+        }
+    }
+    pub fn with_font(self, font: FontId) -> Self {
+        Self {
+            font: Some(font),
+            ..self
+        }
+    }
+    pub fn with_effect(self, effect: TextEffect) -> Self {
+        Self { effect, ..self }
+    }
+    // Reads BBCode-style markup into spans, for dialogue scripts and the like:
+    // [color=#rrggbb] (or #rrggbbaa) ... [/color], [b] ... [/b] (in the `bold` font),
+    // [wave] ... [/wave] and [shake] ... [/shake]. Tags nest; "[[" is a literal '['. Anything
+    // that isn't one of these tags is left in the text as it is.
+    pub fn parse(markup: &str, bold: Option<FontId>) -> Vec<TextSpan> {
+        let mut spans = Vec::new();
+        let mut colors: Vec<Color> = Vec::new();
+        let mut bolds = 0;
+        let mut effects: Vec<TextEffect> = Vec::new();
+        let mut text = String::new();
+        let mut rest = markup;
+        while let Some(c) = rest.chars().next() {
+            if rest.starts_with("[[") {
+                text.push('[');
+                rest = &rest[2..];
+                continue;
+            }
+            let tag = rest
+                .strip_prefix('[')
+                .and_then(|after| after.split_once(']'))
+                .filter(|(tag, _)| is_tag(tag));
+            let Some((tag, after)) = tag else {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+                continue;
+            };
+            if !text.is_empty() {
+                spans.push(TextSpan {
+                    text: std::mem::take(&mut text),
+                    color: colors.last().copied(),
+                    font: if bolds > 0 { bold } else { None },
+                    effect: effects.last().copied().unwrap_or_default(),
+                });
+            }
+            match tag {
+                "b" => bolds += 1,
+                "/b" => bolds = (bolds - 1).max(0),
+                "wave" => effects.push(TextEffect::Wave {
+                    amplitude: 2.0,
+                    speed: 6.0,
+                }),
+                "shake" => effects.push(TextEffect::Shake { amplitude: 1.5 }),
+                "/wave" | "/shake" => {
+                    effects.pop();
+                }
+                "/color" => {
+                    colors.pop();
+                }
+                // is_tag only lets through colors that parse.
+                color => colors.extend(parse_color(&color["color=".len()..])),
+            }
+            rest = after;
+        }
+        if !text.is_empty() || spans.is_empty() {
+            spans.push(TextSpan {
+                text,
+                color: colors.last().copied(),
+                font: if bolds > 0 { bold } else { None },
+                effect: effects.last().copied().unwrap_or_default(),
+            });
+        }
+        spans
+    }
+}
+
+fn is_tag(tag: &str) -> bool {
+    matches!(
+        tag,
+        "b" | "/b" | "wave" | "/wave" | "shake" | "/shake" | "/color"
+    ) || tag
+        .strip_prefix("color=")
+        .is_some_and(|color| parse_color(color).is_some())
+}
+
+// "#rrggbb" or "#rrggbbaa"; unlike Color::from_hex, the '#' is needed.
+fn parse_color(color: &str) -> Option<Color> {
+    color.strip_prefix('#').and_then(Color::from_hex)
+}
+
+// Laid-out rich text: its sprites, plus what moves them.
+#[derive(Clone, Default)]
+pub struct RichText {
+    // Where every glyph sits at rest, ready for a sprite group.
+    pub sprites: Vec<GPUSprite>,
+    // Indices into `sprites` of the glyphs that have an effect.
+    effects: Vec<(usize, TextEffect)>,
+}
+
+impl RichText {
+    pub fn is_animated(&self) -> bool {
+        !self.effects.is_empty()
+    }
+    // Writes where the glyphs are `time` seconds in (e.g. Engine::elapsed) into `out`, which
+    // should be these sprites' place in their group (see SpriteRender::get_sprites_mut, which
+    // also sends them to the GPU).
+    pub fn animate(&self, time: f32, out: &mut [GPUSprite]) {
+        for &(index, effect) in &self.effects {
+            let (Some(rest), Some(sprite)) = (self.sprites.get(index), out.get_mut(index)) else {
+                continue;
+            };
+            let [x, y, w, h] = rest.screen_region;
+            let (dx, dy) = match effect {
+                TextEffect::None => (0.0, 0.0),
+                TextEffect::Wave { amplitude, speed } => {
+                    (0.0, amplitude * (time * speed - index as f32 * 0.6).sin())
+                }
+                TextEffect::Shake { amplitude } => {
+                    // A new random offset 20 times a second.
+                    let step = (time * 20.0) as u32;
+                    (
+                        amplitude * jitter(index as u32, step, 0),
+                        amplitude * jitter(index as u32, step, 1),
+                    )
+                }
+            };
+            sprite.screen_region = [x + dx, y + dy, w, h];
+        }
+    }
+}
+
+// A repeatable pseudo-random number from -1 to 1.
+fn jitter(glyph: u32, step: u32, axis: u32) -> f32 {
+    let mut n = glyph
+        .wrapping_mul(0x9e37_79b9)
+        .wrapping_add(step.wrapping_mul(0x85eb_ca6b))
+        .wrapping_add(axis.wrapping_mul(0xc2b2_ae35));
+    n ^= n >> 16;
+    n = n.wrapping_mul(0x7feb_352d);
+    n ^= n >> 15;
+    n as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+// What GlyphAtlas::measure_text found out about a block of text, in pixels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextMetrics {
+    // Width of the widest line, and height from the top of the first line to the bottom of the
+    // last, like GlyphAtlas::layout_size.
+    pub size: Vec2,
+    // How far the font reaches above the baseline, and below it (negative).
+    pub ascent: f32,
+    pub descent: f32,
+    // Baseline to baseline.
+    pub line_height: f32,
+    pub lines: Vec<LineMetrics>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineMetrics {
+    // Which bytes of the text ended up on this line, without the break that ended it.
+    pub range: Range<usize>,
+    pub width: f32,
+    // How far down from the top of the block the line's baseline is.
+    pub baseline: f32,
+}
+
+// One character of laid-out text, with the font it's drawn in and the span it came from.
+#[derive(Clone, Copy)]
+pub(crate) struct StyledChar {
+    pub(crate) c: char,
+    pub(crate) font: FontId,
+    pub(crate) span: usize,
+}
+
+// A glyph placed on a line, `x` and `y` from the start of the line's baseline.
+pub(crate) struct ShapedGlyph {
+    pub(crate) font: FontId,
+    pub(crate) glyph: ab_glyph::GlyphId,
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) span: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: FontId,
+    glyph: ab_glyph::GlyphId,
+    // f32::to_bits of the size in pixels.
+    size: u32,
+}
+
+#[derive(Clone, Copy)]
+struct GlyphEntry {
+    // Where it is in the atlas image, in texels: x, y, width, height.
+    texels: [u32; 4],
+    // Where its bottom-left corner goes relative to the pen position on the baseline, in pixels
+    // with y up.
+    offset: [f32; 2],
+    // A color bitmap (emoji) rather than white coverage, so tinting it would spoil it.
+    color: bool,
+}
+
+// Rasterizes glyphs from TrueType/OpenType fonts as they're first asked for, and packs them into
+// one texture that grows as needed, so any string can be drawn at any size. Glyphs are white
+// with their coverage in alpha: draw them in a group made with add_sprite_group_with_sampler
+// (SamplerOptions::SMOOTH) and BlendMode::Alpha, and color them with GPUSprite::with_tint.
+pub struct GlyphAtlas {
+    fonts: Vec<ab_glyph::FontArc>,
+    // Per font, the fonts to look in for characters it doesn't have; see set_fallbacks.
+    fallbacks: Vec<Vec<FontId>>,
+    // The font files again, for rustybuzz.
+    #[cfg(feature = "shaping")]
+    font_data: Vec<Vec<u8>>,
+    // None for glyphs with nothing to draw, like spaces.
+    glyphs: HashMap<GlyphKey, Option<GlyphEntry>>,
+    image: image::RgbaImage,
+    texture: wgpu::Texture,
+    // Glyphs go in rows ("shelves") left to right; a glyph that doesn't fit starts a new row
+    // below the tallest glyph of the current one.
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+    // There are glyphs in `image` that aren't on the GPU yet.
+    dirty: bool,
+    max_size: u32,
+    // Glyphs are distance fields rather than coverage; see new_sdf.
+    sdf: bool,
+}
+
+impl GlyphAtlas {
+    // Starts out `size` texels square and doubles whenever it runs out of room, up to the GPU's
+    // largest texture size.
+    pub fn new(gpu: &WGPU, size: u32) -> Self {
+        let image = image::RgbaImage::from_pixel(size, size, image::Rgba([255, 255, 255, 0]));
+        let texture = gpu.create_texture_for(&image, Some("glyph atlas"), 1);
+        gpu.write_texture_data(&texture, &image);
+        Self {
+            fonts: Vec::new(),
+            fallbacks: Vec::new(),
+            #[cfg(feature = "shaping")]
+            font_data: Vec::new(),
+            glyphs: HashMap::new(),
+            image,
+            texture,
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+            dirty: false,
+            max_size: gpu.device.limits().max_texture_dimension_2d,
+            sdf: false,
+        }
+    }
+    // An atlas of signed distance fields instead, each glyph made once at one size and drawn at
+    // any size (or camera zoom) without going blurry. Draw its sprites with ShaderId::SDF_TEXT.
+    pub fn new_sdf(gpu: &WGPU, size: u32) -> Self {
+        Self {
+            sdf: true,
+            ..Self::new(gpu, size)
+        }
+    }
+    // `bytes` is the contents of a .ttf or .otf file, e.g. from Engine::load_bytes.
+    pub fn add_font(&mut self, bytes: Vec<u8>) -> Result<FontId, FontError> {
+        #[cfg(feature = "shaping")]
+        let data = bytes.clone();
+        let font = ab_glyph::FontArc::try_from_vec(bytes).map_err(|_| FontError)?;
+        #[cfg(feature = "shaping")]
+        self.font_data.push(data);
+        self.fonts.push(font);
+        self.fallbacks.push(Vec::new());
+        Ok(FontId(self.fonts.len() - 1))
+    }
+    // Characters `font` has no glyph for are drawn in the first of `fallbacks` that has one, e.g.
+    // an emoji font (color CBDT/sbix bitmaps work, in atlases that aren't SDF) or one for another
+    // script. Without one they come out as the font's missing-glyph box.
+    pub fn set_fallbacks(&mut self, font: FontId, fallbacks: &[FontId]) {
+        self.fallbacks[font.0] = fallbacks.to_vec();
+    }
+    fn plain(&self, font: FontId, text: &str) -> Vec<StyledChar> {
+        let mut chars: Vec<_> = text
+            .chars()
+            .map(|c| StyledChar { c, font, span: 0 })
+            .collect();
+        self.fall_back(&mut chars);
+        chars
+    }
+    fn styled(&self, font: FontId, spans: &[TextSpan]) -> Vec<StyledChar> {
+        let mut chars: Vec<_> = spans
+            .iter()
+            .enumerate()
+            .flat_map(|(span, s)| {
+                let font = s.font.unwrap_or(font);
+                s.text.chars().map(move |c| StyledChar { c, font, span })
+            })
+            .collect();
+        self.fall_back(&mut chars);
+        chars
+    }
+    // Moves each character to the first font in its font's fallback chain that has it. Ones no
+    // font has (variation selectors, zero width joiners) stay with the character before them, so
+    // emoji sequences aren't split across fonts.
+    fn fall_back(&self, chars: &mut [StyledChar]) {
+        let mut previous: Option<(FontId, FontId)> = None;
+        for styled in chars {
+            let base = styled.font;
+            let found = std::iter::once(base)
+                .chain(self.fallbacks[base.0].iter().copied())
+                .find(|font| self.fonts[font.0].glyph_id(styled.c).0 != 0);
+            styled.font = found
+                .or_else(|| previous.filter(|(font, _)| *font == base).map(|(_, to)| to))
+                .unwrap_or(base);
+            previous = Some((base, styled.font));
+        }
+    }
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+    // Whether this atlas came from new_sdf, so its glyphs want ShaderId::SDF_TEXT.
+    pub fn is_sdf(&self) -> bool {
+        self.sdf
+    }
+    // Distance from one baseline to the next for `font` at `size` pixels.
+    pub fn line_height(&self, font: FontId, size: f32) -> f32 {
+        let scaled = self.fonts[font.0].as_scaled(size);
+        scaled.height() + scaled.line_gap()
+    }
+    // How far `text` advances the pen on one line, in pixels.
+    pub fn measure(&self, font: FontId, size: f32, text: &str) -> f32 {
+        self.shape_line(size, &self.plain(font, text), false).1
+    }
+    // Places the glyphs of one line and says how wide it is. Without shaping that's one glyph per
+    // character, advanced and kerned left to right.
+    fn shape_line(&self, size: f32, line: &[StyledChar], shaping: bool) -> (Vec<ShapedGlyph>, f32) {
+        #[cfg(feature = "shaping")]
+        if shaping {
+            return crate::shaping::shape(&self.fonts, &self.font_data, size, line);
+        }
+        #[cfg(not(feature = "shaping"))]
+        let _ = shaping;
+        let mut glyphs = Vec::with_capacity(line.len());
+        let mut pen = 0.0;
+        let mut previous: Option<(FontId, ab_glyph::GlyphId)> = None;
+        for styled in line {
+            let scaled = self.fonts[styled.font.0].as_scaled(size);
+            let glyph = scaled.glyph_id(styled.c);
+            // Kerning pairs only mean something within one font.
+            if let Some((_, previous)) = previous.filter(|(font, _)| *font == styled.font) {
+                pen += scaled.kern(previous, glyph);
+            }
+            glyphs.push(ShapedGlyph {
+                font: styled.font,
+                glyph,
+                x: pen,
+                y: 0.0,
+                span: styled.span,
+            });
+            pen += scaled.h_advance(glyph);
+            previous = Some((styled.font, glyph));
+        }
+        (glyphs, pen)
+    }
+    // Sprites for `text` at `size` pixels (one world unit per pixel), in white, with the first
+    // line's baseline starting at `origin`. '\n' starts a new line below. Rasterizes any glyphs
+    // it hasn't seen before; call upload before drawing the sprites.
+    pub fn text_sprites(
+        &mut self,
+        font: FontId,
+        size: f32,
+        text: &str,
+        origin: [f32; 2],
+    ) -> Vec<GPUSprite> {
+        let mut sprites = Vec::with_capacity(text.len());
+        let line_height = self.line_height(font, size);
+        for (i, line) in text.split('\n').enumerate() {
+            let pen = [origin[0], origin[1] - i as f32 * line_height];
+            let line = self.plain(font, line);
+            self.push_line(size, &line, pen, false, &mut |sprite, _, _| {
+                sprites.push(sprite)
+            });
+        }
+        sprites
+    }
+    // Sprites for `text` wrapped and aligned inside `layout.bounds`.
+    pub fn layout(&mut self, layout: &TextLayout, text: &str) -> Vec<GPUSprite> {
+        self.layout_spans(layout, &[TextSpan::new(text)]).sprites
+    }
+    // Like layout, but each span can have its own color, font and effect. See TextSpan::parse for
+    // writing them as markup instead.
+    pub fn layout_spans(&mut self, layout: &TextLayout, spans: &[TextSpan]) -> RichText {
+        let chars = self.styled(layout.font, spans);
+        let lines = self.wrap(layout, &chars);
+        let [x, y, w, h] = layout.bounds.region();
+        let scaled = self.fonts[layout.font.0].as_scaled(layout.size);
+        let ascent = scaled.ascent();
+        let line_height = self.line_height(layout.font, layout.size) * layout.line_spacing;
+        let block_height = self.block_height(layout, lines.len());
+        let top = y + h;
+        let first_baseline = match layout.v_align {
+            VAlign::Top => top - ascent,
+            VAlign::Middle => top - (h - block_height) / 2.0 - ascent,
+            VAlign::Bottom => y + block_height - ascent,
+        };
+        let mut text = RichText::default();
+        for (i, line) in lines.into_iter().enumerate() {
+            let line = &chars[line];
+            let slack = if w.is_finite() {
+                w - self.shape_line(layout.size, line, layout.is_shaped()).1
+            } else {
+                0.0
+            };
+            let left = match layout.h_align {
+                HAlign::Left => x,
+                HAlign::Center => x + slack / 2.0,
+                HAlign::Right => x + slack,
+            };
+            let pen = [left, first_baseline - i as f32 * line_height];
+            self.push_line(
+                layout.size,
+                line,
+                pen,
+                layout.is_shaped(),
+                &mut |sprite, span, color| {
+                    let span = &spans[span];
+                    let sprite = match span.color {
+                        // Color glyphs keep their colors and only take the alpha, for fading.
+                        Some(tint) if color => sprite.with_tint(Color::WHITE.with_alpha(tint.a)),
+                        Some(tint) => sprite.with_tint(tint),
+                        None => sprite,
+                    };
+                    if span.effect != TextEffect::None {
+                        text.effects.push((text.sprites.len(), span.effect));
+                    }
+                    text.sprites.push(sprite);
+                },
+            );
+        }
+        text
+    }
+    // The width and height `text` takes up once wrapped by `layout`, from the top of the first
+    // line to the bottom of the last.
+    pub fn layout_size(&self, layout: &TextLayout, text: &str) -> Vec2 {
+        let chars = self.plain(layout.font, text);
+        let lines = self.wrap(layout, &chars);
+        let width = lines
+            .iter()
+            .map(|line| {
+                self.shape_line(layout.size, &chars[line.clone()], layout.is_shaped())
+                    .1
+            })
+            .fold(0.0, f32::max);
+        Vec2::new(width, self.block_height(layout, lines.len()))
+    }
+    // Everything about how `text` would wrap at `max_width` (infinite for no wrapping) with the
+    // default line spacing, without making any sprites: for centering a label or sizing a dialog
+    // box before drawing into it.
+    pub fn measure_text(&self, font: FontId, size: f32, text: &str, max_width: f32) -> TextMetrics {
+        let layout = TextLayout::new(font, size, [0.0, 0.0, max_width, f32::INFINITY]);
+        let chars = self.plain(font, text);
+        let scaled = self.fonts[font.0].as_scaled(size);
+        let line_height = self.line_height(font, size);
+        // Char index to byte offset, with one past the end for lines that finish the text.
+        let bytes: Vec<usize> = text
+            .char_indices()
+            .map(|(byte, _)| byte)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        let lines: Vec<LineMetrics> = self
+            .wrap(&layout, &chars)
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| LineMetrics {
+                width: self.shape_line(size, &chars[line.clone()], false).1,
+                baseline: scaled.ascent() + i as f32 * line_height,
+                range: bytes[line.start]..bytes[line.end],
+            })
+            .collect();
+        TextMetrics {
+            size: Vec2::new(
+                lines.iter().map(|line| line.width).fold(0.0, f32::max),
+                self.block_height(&layout, lines.len()),
+            ),
+            ascent: scaled.ascent(),
+            descent: scaled.descent(),
+            line_height,
+            lines,
+        }
+    }
+    fn block_height(&self, layout: &TextLayout, lines: usize) -> f32 {
+        let scaled = self.fonts[layout.font.0].as_scaled(layout.size);
+        let line_height = self.line_height(layout.font, layout.size) * layout.line_spacing;
+        scaled.ascent() - scaled.descent() + line_height * lines.saturating_sub(1) as f32
+    }
+    // Splits `chars` into lines at '\n' and wherever the next word would go past the bounds'
+    // width. Words wider than that on their own are broken between letters. The line breaks
+    // themselves (and the spaces they replace) aren't in any line.
+    fn wrap(&self, layout: &TextLayout, chars: &[StyledChar]) -> Vec<Range<usize>> {
+        let max_width = layout.bounds.width;
+        let fits = |range: Range<usize>| {
+            self.shape_line(layout.size, &chars[range], layout.is_shaped())
+                .1
+                <= max_width
+        };
+        let mut lines = Vec::new();
+        let mut paragraph_start = 0;
+        for paragraph in chars.split(|styled| styled.c == '\n') {
+            let mut line: Option<Range<usize>> = None;
+            let mut word_start = paragraph_start;
+            for word in paragraph.split(|styled| styled.c == ' ') {
+                let word_range = word_start..word_start + word.len();
+                word_start = word_range.end + 1;
+                let candidate = match &line {
+                    Some(line) => line.start..word_range.end,
+                    None => word_range.clone(),
+                };
+                if fits(candidate.clone()) {
+                    line = Some(candidate);
+                    continue;
+                }
+                lines.extend(line.take());
+                let mut start = word_range.start;
+                for end in word_range.start + 1..word_range.end {
+                    if !fits(start..end + 1) {
+                        lines.push(start..end);
+                        start = end;
+                    }
+                }
+                line = Some(start..word_range.end);
+            }
+            lines.push(line.unwrap_or(paragraph_start..paragraph_start));
+            paragraph_start += paragraph.len() + 1;
+        }
+        lines
+    }
+    // Makes the sprites for one line of text, starting at `pen` on its baseline, and hands each
+    // to `emit` with the span its character came from and whether it's a color glyph.
+    fn push_line(
+        &mut self,
+        size: f32,
+        line: &[StyledChar],
+        pen: [f32; 2],
+        shaping: bool,
+        emit: &mut dyn FnMut(GPUSprite, usize, bool),
+    ) {
+        let (glyphs, _) = self.shape_line(size, line, shaping);
+        for placed in glyphs {
+            let at = [pen[0] + placed.x, pen[1] + placed.y];
+            if let Some((sprite, color)) = self.glyph_sprite(placed.font, placed.glyph, size, at) {
+                emit(sprite, placed.span, color);
+            }
+        }
+    }
+    // The sprite for one glyph with its pen position at `pen`, or None if it's blank.
+    fn glyph_sprite(
+        &mut self,
+        font: FontId,
+        glyph: ab_glyph::GlyphId,
+        size: f32,
+        pen: [f32; 2],
+    ) -> Option<(GPUSprite, bool)> {
+        let entry = self.glyph(font, glyph, size)?;
+        let [x, y, w, h] = entry.texels;
+        let (width, height) = self.image.dimensions();
+        let scale = if self.sdf { size / SDF_SIZE } else { 1.0 };
+        let sprite = GPUSprite::new(
+            [
+                pen[0] + entry.offset[0] * scale,
+                pen[1] + entry.offset[1] * scale,
+                w as f32 * scale,
+                h as f32 * scale,
+            ],
+            [
+                x as f32 / width as f32,
+                y as f32 / height as f32,
+                w as f32 / width as f32,
+                h as f32 / height as f32,
+            ],
+        );
+        Some((sprite, entry.color))
+    }
+    fn glyph(&mut self, font: FontId, glyph: ab_glyph::GlyphId, size: f32) -> Option<GlyphEntry> {
+        let size = if self.sdf { SDF_SIZE } else { size };
+        let key = GlyphKey {
+            font,
+            glyph,
+            size: size.to_bits(),
+        };
+        if let Some(entry) = self.glyphs.get(&key) {
+            return *entry;
+        }
+        let entry = self.rasterize(font, glyph, size);
+        self.glyphs.insert(key, entry);
+        entry
+    }
+    fn rasterize(
+        &mut self,
+        font: FontId,
+        glyph: ab_glyph::GlyphId,
+        size: f32,
+    ) -> Option<GlyphEntry> {
+        // Distance fields only have room for one channel, so SDF atlases stick to outlines.
+        if !self.sdf {
+            if let Some(entry) = self.rasterize_color(font, glyph, size) {
+                return Some(entry);
+            }
+        }
+        let outlined = self.fonts[font.0].outline_glyph(glyph.with_scale(size))?;
+        let bounds = outlined.px_bounds();
+        let (w, h) = (bounds.width() as u32, bounds.height() as u32);
+        if w == 0 || h == 0 {
+            return None;
+        }
+        let mut coverage = vec![0.0; (w * h) as usize];
+        outlined.draw(|gx, gy, c| coverage[(gy * w + gx) as usize] = c.clamp(0.0, 1.0));
+        // Distance fields need room around the glyph to fade out in.
+        let margin = if self.sdf { SDF_SPREAD } else { 0 };
+        let (cell_w, cell_h) = (w + 2 * margin, h + 2 * margin);
+        let Some([x, y]) = self.allocate(cell_w, cell_h) else {
+            log::warn!("The glyph atlas is full; glyph {glyph:?} at {size}px won't be drawn");
+            return None;
+        };
+        for cy in 0..cell_h {
+            for cx in 0..cell_w {
+                let value = if self.sdf {
+                    distance_field(
+                        &coverage,
+                        w,
+                        h,
+                        cx as i32 - margin as i32,
+                        cy as i32 - margin as i32,
+                    )
+                } else {
+                    coverage[(cy * w + cx) as usize]
+                };
+                let alpha = (value * 255.0).round() as u8;
+                self.image
+                    .put_pixel(x + cx, y + cy, image::Rgba([255, 255, 255, alpha]));
+            }
+        }
+        self.dirty = true;
+        Some(GlyphEntry {
+            texels: [x, y, cell_w, cell_h],
+            // px_bounds are y down from the baseline.
+            offset: [bounds.min.x - margin as f32, -bounds.max.y - margin as f32],
+            color: false,
+        })
+    }
+    // The glyph's color bitmap, if the font has a PNG one (CBDT or sbix, as emoji fonts do),
+    // scaled from the nearest size the font has.
+    fn rasterize_color(
+        &mut self,
+        font: FontId,
+        glyph: ab_glyph::GlyphId,
+        size: f32,
+    ) -> Option<GlyphEntry> {
+        let bitmap = self.fonts[font.0].glyph_raster_image(glyph, size.ceil() as u16)?;
+        if !matches!(bitmap.format, ab_glyph::GlyphImageFormat::Png) {
+            return None;
+        }
+        let decoded = image::load_from_memory_with_format(bitmap.data, image::ImageFormat::Png)
+            .ok()?
+            .into_rgba8();
+        let scale = size / bitmap.scale;
+        let w = (decoded.width() as f32 * scale).round().max(1.0) as u32;
+        let h = (decoded.height() as f32 * scale).round().max(1.0) as u32;
+        let scaled = image::imageops::resize(&decoded, w, h, image::imageops::FilterType::Triangle);
+        // The bitmap's origin is its bottom-left corner, y up, at the bitmap's own size.
+        let offset = [bitmap.origin.x * scale, bitmap.origin.y * scale];
+        let Some([x, y]) = self.allocate(w, h) else {
+            log::warn!("The glyph atlas is full; glyph {glyph:?} at {size}px won't be drawn");
+            return None;
+        };
+        image::imageops::replace(&mut self.image, &scaled, x as i64, y as i64);
+        self.dirty = true;
+        Some(GlyphEntry {
+            texels: [x, y, w, h],
+            offset,
+            color: true,
+        })
+    }
+    // Finds room for a w by h glyph, growing the atlas if there isn't any.
+    fn allocate(&mut self, w: u32, h: u32) -> Option<[u32; 2]> {
+        loop {
+            let (width, height) = self.image.dimensions();
+            if self.shelf_x + w + GLYPH_PADDING > width {
+                self.shelf_x = 0;
+                self.shelf_y += self.shelf_height;
+                self.shelf_height = 0;
+            }
+            if self.shelf_x + w + GLYPH_PADDING <= width
+                && self.shelf_y + h + GLYPH_PADDING <= height
+            {
+                let at = [self.shelf_x, self.shelf_y];
+                self.shelf_x += w + GLYPH_PADDING;
+                self.shelf_height = self.shelf_height.max(h + GLYPH_PADDING);
+                return Some(at);
+            }
+            if width * 2 > self.max_size {
+                return None;
+            }
+            let mut grown = image::RgbaImage::from_pixel(
+                width * 2,
+                height * 2,
+                image::Rgba([255, 255, 255, 0]),
+            );
+            image::imageops::replace(&mut grown, &self.image, 0, 0);
+            self.image = grown;
+            self.dirty = true;
+        }
+    }
+    // Copies of `sprites` (from an SDF atlas) moved by `offset` and blurred into a `color` shadow.
+    // Draw them first, in the same group or one behind.
+    pub fn shadow_sprites(
+        sprites: &[GPUSprite],
+        offset: impl Into<Vec2>,
+        color: impl Into<Color>,
+    ) -> Vec<GPUSprite> {
+        let (offset, color) = (offset.into(), color.into());
+        sprites
+            .iter()
+            .map(|sprite| {
+                let [x, y, w, h] = sprite.screen_region;
+                let mut shadow =
+                    GPUSprite::new([x + offset[0], y + offset[1], w, h], sprite.sheet_region)
+                        .with_depth(sprite.depth)
+                        .with_tint(color);
+                shadow.flags |= GPUSprite::SOFT;
+                shadow
+            })
+            .collect()
+    }
+    // Sends new glyphs to the GPU. Returns true if the atlas grew, which replaces its texture and
+    // moves every glyph's sheet_region: give groups the new texture with
+    // SpriteRender::set_group_texture and remake their sprites with text_sprites.
+    pub fn upload(&mut self, gpu: &WGPU) -> bool {
+        if !self.dirty {
+            return false;
+        }
+        self.dirty = false;
+        let grown = self.image.dimensions() != (self.texture.width(), self.texture.height());
+        if grown {
+            self.texture = gpu.create_texture_for(&self.image, Some("glyph atlas"), 1);
+        }
+        gpu.write_texture_data(&self.texture, &self.image);
+        grown
+    }
+    pub fn usage(&self) -> crate::AtlasUsage {
+        let (width, height) = self.image.dimensions();
+        let filled = self.shelf_y * width + self.shelf_x * self.shelf_height;
+        crate::AtlasUsage {
+            size: [width, height],
+            bytes: crate::memory::texture_bytes(&self.texture),
+            used: filled as f32 / (width * height) as f32,
+        }
+    }
+    // Puts the texture back on the (new) device after WGPU::recover; the glyphs come back from
+    // the CPU-side copy of the atlas.
+    pub fn recreate(&mut self, gpu: &WGPU) {
+        self.texture = gpu.create_texture_for(&self.image, Some("glyph atlas"), 1);
+        gpu.write_texture_data(&self.texture, &self.image);
+        self.max_size = gpu.device.limits().max_texture_dimension_2d;
+    }
+}
+
+// The signed distance from texel (x, y) of a glyph's coverage bitmap to the glyph's edge, mapped
+// so that 0.5 is on the edge and SDF_SPREAD texels in (or out) is 1 (or 0). The texel may be
+// outside the bitmap, in the margin around it.
+fn distance_field(coverage: &[f32], w: u32, h: u32, x: i32, y: i32) -> f32 {
+    let inside_at = |x: i32, y: i32| {
+        x >= 0
+            && y >= 0
+            && x < w as i32
+            && y < h as i32
+            && coverage[(y as u32 * w + x as u32) as usize] >= 0.5
+    };
+    let inside = inside_at(x, y);
+    let spread = SDF_SPREAD as i32;
+    let mut nearest = SDF_SPREAD as f32;
+    for dy in -spread..=spread {
+        for dx in -spread..=spread {
+            if inside_at(x + dx, y + dy) != inside {
+                // The edge is halfway between the two texels' centers.
+                let distance = ((dx * dx + dy * dy) as f32).sqrt() - 0.5;
+                nearest = nearest.min(distance);
+            }
+        }
+    }
+    let signed = if inside { nearest } else { -nearest };
+    (0.5 + signed / (2.0 * SDF_SPREAD as f32)).clamp(0.0, 1.0)
+}
@@ -0,0 +1,110 @@
+// Frame timing. std::time::Instant isn't available in the browser, so wasm builds read
+// performance.now() instead; both give seconds since some arbitrary start point.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now() -> f64 {
+    use std::sync::OnceLock;
+    static START: OnceLock<std::time::Instant> = OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_secs_f64()
+}
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now() -> f64 {
+    web_sys::window()
+        .and_then(|w| w.performance())
+        .map(|p| p.now() / 1000.0)
+        .unwrap_or(0.0)
+}
+
+pub(crate) struct Clock {
+    start: f64,
+    last_tick: f64,
+    delta: f32,
+    elapsed: f64,
+}
+
+impl Clock {
+    pub(crate) fn new() -> Self {
+        let start = now();
+        Self {
+            start,
+            last_tick: start,
+            delta: 0.0,
+            elapsed: 0.0,
+        }
+    }
+    // Called once at the start of every frame.
+    pub(crate) fn tick(&mut self) {
+        let t = now();
+        self.delta = (t - self.last_tick) as f32;
+        self.elapsed = t - self.start;
+        self.last_tick = t;
+    }
+    pub(crate) fn delta(&self) -> f32 {
+        self.delta
+    }
+    pub(crate) fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+    // Makes this frame `delta` seconds long instead, e.g. to replay a recorded frame. Elapsed
+    // time moves along with it.
+    pub(crate) fn set_delta(&mut self, delta: f32) {
+        self.set_elapsed(self.elapsed - self.delta as f64 + delta as f64);
+        self.delta = delta;
+    }
+    // Winds the clock to `elapsed`, carrying on from there.
+    pub(crate) fn set_elapsed(&mut self, elapsed: f64) {
+        self.start = self.last_tick - elapsed;
+        self.elapsed = elapsed;
+    }
+}
+
+// Decides when the next frame should start when an fps cap is in effect.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct FramePacer {
+    next_frame: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FramePacer {
+    // How close to the deadline SleepSpin stops sleeping and starts spinning.
+    const SPIN_MARGIN: std::time::Duration = std::time::Duration::from_millis(2);
+
+    pub(crate) fn new() -> Self {
+        Self {
+            next_frame: std::time::Instant::now(),
+        }
+    }
+    // Returns None if it's time to render, or Some(instant) to sleep until before asking again.
+    // Spinning happens right here.
+    pub(crate) fn wait(
+        &mut self,
+        fps_cap: f32,
+        limiter: crate::FrameLimiter,
+    ) -> Option<std::time::Instant> {
+        use crate::FrameLimiter;
+        use std::time::{Duration, Instant};
+        let interval = Duration::from_secs_f32(1.0 / fps_cap.max(1.0));
+        let now = Instant::now();
+        if now < self.next_frame {
+            let sleep_until = match limiter {
+                FrameLimiter::Sleep => Some(self.next_frame),
+                FrameLimiter::Spin => None,
+                FrameLimiter::SleepSpin => self
+                    .next_frame
+                    .checked_sub(Self::SPIN_MARGIN)
+                    .filter(|t| *t > now),
+            };
+            if sleep_until.is_some() {
+                return sleep_until;
+            }
+            while Instant::now() < self.next_frame {
+                std::hint::spin_loop();
+            }
+        }
+        // If we fell behind by more than a frame, don't try to catch up with a burst of frames.
+        self.next_frame = (self.next_frame + interval).max(Instant::now());
+        None
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{Rect, SpriteHandle, SpriteRender};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Where a trigger zone is.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerArea {
+    Rect(Rect),
+    // Follows the sprite's screen_region, e.g. a door or a moving pressure plate.
+    Sprite(SpriteHandle),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TriggerPhase {
+    // The sprite started overlapping the zone since the last check.
+    Enter,
+    // It's still overlapping it; sent every frame after Enter.
+    Stay,
+    // It stopped overlapping it, went away, or stopped being tracked.
+    Exit,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TriggerEvent {
+    // The zone's name.
+    pub zone: String,
+    pub sprite: SpriteHandle,
+    pub phase: TriggerPhase,
+}
+
+#[derive(Clone, Debug)]
+struct Zone {
+    area: TriggerArea,
+    // The tracked sprites overlapping it as of the last check.
+    inside: BTreeSet<SpriteHandle>,
+}
+
+// Named zones that tell the game when tracked sprites (the player, say) come into them, stay, and
+// leave: checkpoints, doors, the start of a cutscene. The Engine keeps one (Engine::triggers),
+// checks it once a frame after the fixed updates, and hands the events to Game::trigger before
+// update.
+#[derive(Clone, Debug, Default)]
+pub struct Triggers {
+    zones: BTreeMap<String, Zone>,
+    tracked: BTreeSet<SpriteHandle>,
+    events: Vec<TriggerEvent>,
+}
+
+impl Triggers {
+    // Adds a zone, or moves the one with that name (which keeps track of who's in it).
+    pub fn set_zone(&mut self, name: impl Into<String>, area: TriggerArea) {
+        self.zones
+            .entry(name.into())
+            .and_modify(|zone| zone.area = area)
+            .or_insert(Zone {
+                area,
+                inside: BTreeSet::new(),
+            });
+    }
+    // Without Exit events for the sprites that were in it.
+    pub fn remove_zone(&mut self, name: &str) {
+        self.zones.remove(name);
+    }
+    pub fn zone(&self, name: &str) -> Option<TriggerArea> {
+        self.zones.get(name).map(|zone| zone.area)
+    }
+    pub fn track(&mut self, sprite: SpriteHandle) {
+        self.tracked.insert(sprite);
+    }
+    // The sprite leaves (with an Exit event) every zone it was in at the next check.
+    pub fn untrack(&mut self, sprite: SpriteHandle) {
+        self.tracked.remove(&sprite);
+    }
+    // The tracked sprites in zone `name` as of the last check.
+    pub fn inside(&self, name: &str) -> impl Iterator<Item = SpriteHandle> + '_ {
+        self.zones
+            .get(name)
+            .into_iter()
+            .flat_map(|zone| zone.inside.iter().copied())
+    }
+    // Compares where the tracked sprites are with where they were at the last check, and queues
+    // up the events for take_events.
+    pub fn update(&mut self, sprites: &SpriteRender) {
+        let bounds = |sprite: SpriteHandle| {
+            (sprite.group < sprites.group_count())
+                .then(|| sprites.get_sprites(sprite.group).get(sprite.index))
+                .flatten()
+                .map(|sprite| sprite.bounds())
+        };
+        for (name, zone) in &mut self.zones {
+            let area = match zone.area {
+                TriggerArea::Rect(rect) => Some(rect),
+                TriggerArea::Sprite(sprite) => bounds(sprite),
+            };
+            let now: BTreeSet<SpriteHandle> = match area {
+                Some(area) => self
+                    .tracked
+                    .iter()
+                    .copied()
+                    .filter(|&sprite| bounds(sprite).is_some_and(|rect| rect.overlaps(&area)))
+                    .collect(),
+                None => BTreeSet::new(),
+            };
+            let event = |sprite, phase| TriggerEvent {
+                zone: name.clone(),
+                sprite,
+                phase,
+            };
+            for &sprite in zone.inside.difference(&now) {
+                self.events.push(event(sprite, TriggerPhase::Exit));
+            }
+            for &sprite in &now {
+                let phase = if zone.inside.contains(&sprite) {
+                    TriggerPhase::Stay
+                } else {
+                    TriggerPhase::Enter
+                };
+                self.events.push(event(sprite, phase));
+            }
+            zone.inside = now;
+        }
+    }
+    // The events since the last call, zone by zone.
+    pub fn take_events(&mut self) -> Vec<TriggerEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
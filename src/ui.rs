@@ -0,0 +1,290 @@
+use crate::{GPUCamera, Rect, SpriteHandle, SpriteRender, Vec2, WGPU};
+use std::ops::{Deref, DerefMut};
+
+// Which part of the UI area a UiLayout sticks to. The Stretch ones fill the area (less margins)
+// along one axis or both, ignoring the layout's size there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+    Stretch,
+    // Full-width bars and full-height sidebars.
+    StretchTop,
+    StretchBottom,
+    StretchLeft,
+    StretchRight,
+}
+
+// Where something goes along one axis.
+#[derive(Clone, Copy)]
+enum Align {
+    Start,
+    Middle,
+    End,
+    Fill,
+}
+
+impl Anchor {
+    // Across, then up.
+    fn aligns(self) -> (Align, Align) {
+        use Align::*;
+        match self {
+            Anchor::TopLeft => (Start, End),
+            Anchor::Top => (Middle, End),
+            Anchor::TopRight => (End, End),
+            Anchor::Left => (Start, Middle),
+            Anchor::Center => (Middle, Middle),
+            Anchor::Right => (End, Middle),
+            Anchor::BottomLeft => (Start, Start),
+            Anchor::Bottom => (Middle, Start),
+            Anchor::BottomRight => (End, Start),
+            Anchor::Stretch => (Fill, Fill),
+            Anchor::StretchTop => (Fill, End),
+            Anchor::StretchBottom => (Fill, Start),
+            Anchor::StretchLeft => (Start, Fill),
+            Anchor::StretchRight => (End, Fill),
+        }
+    }
+}
+
+// Space kept clear between the edges of the UI area and whatever is anchored to them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Margins {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Margins {
+    pub fn all(margin: f32) -> Self {
+        Self::symmetric(margin, margin)
+    }
+    pub fn symmetric(horizontal: f32, vertical: f32) -> Self {
+        Self {
+            left: horizontal,
+            right: horizontal,
+            top: vertical,
+            bottom: vertical,
+        }
+    }
+}
+
+// Where a UI element goes, relative to the UI area rather than at fixed coordinates, so it stays
+// in the corner (or centered, or full width) when the window changes size:
+//
+//     // 200 by 24, 16 units in from the top-left corner.
+//     let health = UiLayout::new(Anchor::TopLeft, [200.0, 24.0]).with_margin(16.0);
+//
+// The margins shrink the area first, and the element is then lined up inside what's left, so
+// a centered element with a bigger left margin sits a little right of center.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UiLayout {
+    pub anchor: Anchor,
+    pub size: Vec2,
+    pub margins: Margins,
+}
+
+impl UiLayout {
+    pub fn new(anchor: Anchor, size: impl Into<Vec2>) -> Self {
+        Self {
+            anchor,
+            size: size.into(),
+            margins: Margins::default(),
+        }
+    }
+    pub fn with_margins(self, margins: Margins) -> Self {
+        Self { margins, ..self }
+    }
+    // The same margin on every side.
+    pub fn with_margin(self, margin: f32) -> Self {
+        self.with_margins(Margins::all(margin))
+    }
+    // Where the element goes within `area`.
+    pub fn rect(&self, area: impl Into<Rect>) -> Rect {
+        let area = area.into();
+        let m = self.margins;
+        let inner = Rect::new(
+            area.x + m.left,
+            area.y + m.bottom,
+            (area.width - m.left - m.right).max(0.0),
+            (area.height - m.bottom - m.top).max(0.0),
+        );
+        let place = |align: Align, start: f32, length: f32, size: f32| match align {
+            Align::Start => (start, size),
+            Align::Middle => (start + (length - size) / 2.0, size),
+            Align::End => (start + length - size, size),
+            Align::Fill => (start, length),
+        };
+        let (across, up) = self.anchor.aligns();
+        let (x, width) = place(across, inner.x, inner.width, self.size.x);
+        let (y, height) = place(up, inner.y, inner.height, self.size.y);
+        Rect::new(x, y, width, height)
+    }
+}
+
+// Sprites for the HUD and menus, drawn after everything else (post-processing included) and
+// kept still while the game's cameras move. Every group here gets the same camera, showing the
+// UI area: one unit per pixel of the window unless set_virtual_size says otherwise, with the
+// origin at the bottom left. It's set again whenever the area changes or a group is added, so
+// whatever camera a group is made with doesn't matter.
+//
+//     let health_bar = GPUSprite::solid([0.0; 4], Color::RED);
+//     engine.ui.add_rect_group(&engine.gpu, vec![health_bar], GPUCamera::default());
+//     engine.ui.anchor(SpriteHandle::new(0, 0), health);
+//
+// Anchored sprites are moved to their UiLayout's place in the area whenever it changes. For
+// text, lay it out in ui.place(layout) from Game::resized. It's a SpriteRender otherwise, so
+// groups, textures, shaders and blend modes work as usual.
+pub struct UiLayer {
+    sprites: SpriteRender,
+    // In pixels.
+    window: (u32, u32),
+    virtual_size: Option<Vec2>,
+    // The area and group count the cameras were last set for.
+    cameras: Option<(Rect, usize)>,
+    anchors: Vec<(SpriteHandle, UiLayout)>,
+    // The area the anchored sprites were last placed in.
+    placed: Option<Rect>,
+}
+
+impl UiLayer {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        Self {
+            sprites: SpriteRender::overlay(gpu),
+            window: (gpu.config.width, gpu.config.height),
+            virtual_size: None,
+            cameras: None,
+            anchors: Vec::new(),
+            placed: None,
+        }
+    }
+    // Lays the UI out in `size` units (say 1280 by 720) whatever the window's resolution, scaled
+    // to fit the window. If the window is a different shape, the area grows along the axis with
+    // room to spare, so it always reaches every edge of the window and anchored elements stay
+    // in the corners. None goes back to pixels.
+    pub fn set_virtual_size(&mut self, size: Option<Vec2>) {
+        self.virtual_size = size;
+    }
+    pub fn virtual_size(&self) -> Option<Vec2> {
+        self.virtual_size
+    }
+    // The part of UI space the window shows, from the origin.
+    pub fn area(&self) -> Rect {
+        let window = Vec2::new(self.window.0 as f32, self.window.1 as f32);
+        let size = match self.virtual_size {
+            Some(virtual_size) if window.x > 0.0 && window.y > 0.0 => {
+                let scale = (window.x / virtual_size.x).min(window.y / virtual_size.y);
+                window / scale
+            }
+            _ => window,
+        };
+        Rect::from_position_size(Vec2::ZERO, size)
+    }
+    // The size of area(), e.g. the top-right corner of the UI.
+    pub fn size(&self) -> Vec2 {
+        self.area().size()
+    }
+    // The camera every group is drawn with.
+    pub fn camera(&self) -> GPUCamera {
+        GPUCamera::new(Vec2::ZERO, self.size())
+    }
+    // Where `layout` puts something in the current area.
+    pub fn place(&self, layout: &UiLayout) -> Rect {
+        layout.rect(self.area())
+    }
+    // Keeps the sprite at `layout`'s place in the area, moving it there now and whenever the area
+    // changes. A sprite has one layout; anchoring it again replaces it. Like any SpriteHandle,
+    // it goes stale if sprites before it in its group are removed.
+    pub fn anchor(&mut self, sprite: SpriteHandle, layout: UiLayout) {
+        self.anchors.retain(|(anchored, _)| *anchored != sprite);
+        self.anchors.push((sprite, layout));
+        self.place_sprite(sprite, &layout);
+    }
+    // Stops moving the sprite, leaving it where it is.
+    pub fn remove_anchor(&mut self, sprite: SpriteHandle) {
+        self.anchors.retain(|(anchored, _)| *anchored != sprite);
+    }
+    pub fn anchor_of(&self, sprite: SpriteHandle) -> Option<UiLayout> {
+        self.anchors
+            .iter()
+            .find(|(anchored, _)| *anchored == sprite)
+            .map(|(_, layout)| *layout)
+    }
+    fn place_sprite(&mut self, sprite: SpriteHandle, layout: &UiLayout) {
+        let rect = self.place(layout);
+        if let Some(placed) = self.sprites.try_get_sprite_mut(sprite.group, sprite.index) {
+            placed.set_bounds(rect);
+        }
+    }
+
+    // The Engine calls this as soon as the window is resized, so Game::resized sees the new area.
+    pub(crate) fn set_window_size(&mut self, size: (u32, u32)) {
+        self.window = size;
+    }
+    // Pins the groups' cameras to the area, places the anchored sprites and sends the changed
+    // sprites.
+    pub(crate) fn prepare(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+        size: (u32, u32),
+    ) {
+        self.window = size;
+        let area = self.area();
+        let groups = self.sprites.group_count();
+        if self.cameras != Some((area, groups)) {
+            self.cameras = Some((area, groups));
+            let camera = self.camera();
+            self.sprites.set_camera_all(gpu, camera);
+        }
+        if self.placed != Some(area) {
+            self.placed = Some(area);
+            for (sprite, layout) in self.anchors.clone() {
+                self.place_sprite(sprite, &layout);
+            }
+        }
+        self.sprites.set_target_size(size.0, size.1);
+        self.sprites.flush(gpu, encoder);
+    }
+    // Draws over `frame`, keeping what's there.
+    pub(crate) fn render(&self, encoder: &mut wgpu::CommandEncoder, frame: &wgpu::TextureView) {
+        if self.sprites.group_count() == 0 {
+            return;
+        }
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("ui"),
+            color_attachments: &[Some(crate::gpu::color_attachment(
+                frame,
+                None,
+                wgpu::LoadOp::Load,
+            ))],
+            depth_stencil_attachment: None,
+        });
+        self.sprites.render(&mut rpass);
+    }
+}
+
+impl Deref for UiLayer {
+    type Target = SpriteRender;
+    fn deref(&self) -> &SpriteRender {
+        &self.sprites
+    }
+}
+
+impl DerefMut for UiLayer {
+    fn deref_mut(&mut self) -> &mut SpriteRender {
+        &mut self.sprites
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{TextureError, WGPU};
+use std::collections::{HashMap, VecDeque};
+
+// Handle for a texture that has been queued with the UploadScheduler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UploadId(u64);
+
+struct PendingUpload {
+    id: UploadId,
+    label: Option<String>,
+    image: image::RgbaImage,
+}
+
+// Spreads texture uploads over several frames so that a burst of freshly decoded
+// images (e.g. entering a new area) doesn't stall a single frame. Each frame's uploads go
+// through one staging buffer of at most the budget's size (a lone image bigger than the
+// budget gets one of its own), so the budget bounds staging memory too.
+pub struct UploadScheduler {
+    // How many bytes of staging buffer we're willing to fill per frame. Rows are padded to
+    // wgpu's copy alignment, so narrow images take a little more than their texel data.
+    frame_budget: usize,
+    pending: VecDeque<PendingUpload>,
+    ready: HashMap<UploadId, Result<wgpu::Texture, TextureError>>,
+    next_id: u64,
+    uploaded_this_frame: usize,
+}
+
+// 8MB is a couple of 1024x1024 RGBA atlases, which is about what a frame can absorb.
+pub const DEFAULT_UPLOAD_BUDGET: usize = 8 * 1024 * 1024;
+
+impl Default for UploadScheduler {
+    fn default() -> Self {
+        Self {
+            frame_budget: DEFAULT_UPLOAD_BUDGET,
+            pending: VecDeque::new(),
+            ready: HashMap::new(),
+            next_id: 0,
+            uploaded_this_frame: 0,
+        }
+    }
+}
+
+impl UploadScheduler {
+    pub fn set_frame_budget(&mut self, bytes: usize) {
+        self.frame_budget = bytes;
+    }
+    pub fn frame_budget(&self) -> usize {
+        self.frame_budget
+    }
+    // Queues a decoded image for upload. The texture becomes available from
+    // `take_texture` once a later frame has had room for it in its budget. Images the GPU can't
+    // take (too large, or empty) come out of take_texture as an error instead.
+    pub fn queue(&mut self, image: image::RgbaImage, label: Option<&str>) -> UploadId {
+        let id = UploadId(self.next_id);
+        self.next_id += 1;
+        self.pending.push_back(PendingUpload {
+            id,
+            label: label.map(str::to_owned),
+            image,
+        });
+        id
+    }
+    pub fn is_ready(&self, id: UploadId) -> bool {
+        self.ready.contains_key(&id)
+    }
+    // None until the upload has been done. Textures get mipmaps if GpuOptions::mipmaps is on,
+    // like load_texture's.
+    pub fn take_texture(&mut self, id: UploadId) -> Option<Result<wgpu::Texture, TextureError>> {
+        self.ready.remove(&id)
+    }
+    // Number of uploads still waiting for a frame with enough budget.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+    pub fn pending_bytes(&self) -> usize {
+        self.pending.iter().map(|p| p.image.as_raw().len()).sum()
+    }
+    pub fn uploaded_last_frame(&self) -> usize {
+        self.uploaded_this_frame
+    }
+
+    // Called once per frame by the Engine. Uploads queued images in order until the
+    // frame budget is spent; an image larger than the whole budget still goes through
+    // on its own frame so it can't get stuck in the queue forever.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "texture uploads", skip_all)
+    )]
+    pub(crate) fn flush(&mut self, gpu: &WGPU) {
+        self.uploaded_this_frame = 0;
+        let mut batch = Vec::new();
+        while let Some(next) = self.pending.front() {
+            let source = crate::gpu::in_memory_source(next.label.as_deref());
+            let levels = match gpu.mip_levels_for(&next.image, &source) {
+                Ok(levels) => levels,
+                Err(e) => {
+                    let upload = self.pending.pop_front().unwrap();
+                    self.ready.insert(upload.id, Err(e));
+                    continue;
+                }
+            };
+            let size = staged_size(&next.image);
+            if self.uploaded_this_frame > 0 && self.uploaded_this_frame + size > self.frame_budget {
+                break;
+            }
+            batch.push((self.pending.pop_front().unwrap(), levels));
+            self.uploaded_this_frame += size;
+        }
+        if batch.is_empty() {
+            return;
+        }
+        // wgpu's StagingBelt only copies into buffers, so the images share a buffer of our own,
+        // which wgpu frees once the copies out of it have run.
+        let staging = {
+            let staging = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("texture uploads"),
+                size: self.uploaded_this_frame as u64,
+                usage: wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: true,
+            });
+            {
+                let mut mapped = staging.slice(..).get_mapped_range_mut();
+                let mut offset = 0;
+                for (upload, _) in &batch {
+                    let row = padded_row(upload.image.width());
+                    let texels = 4 * upload.image.width() as usize;
+                    for (y, src) in upload.image.as_raw().chunks_exact(texels).enumerate() {
+                        let start = offset + y * row;
+                        mapped[start..start + texels].copy_from_slice(src);
+                    }
+                    offset += staged_size(&upload.image);
+                }
+            }
+            staging.unmap();
+            staging
+        };
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("texture uploads"),
+            });
+        let mut offset = 0;
+        let mut textures = Vec::with_capacity(batch.len());
+        for (upload, levels) in batch {
+            let texture = gpu.create_texture_for(&upload.image, upload.label.as_deref(), levels);
+            let (width, height) = upload.image.dimensions();
+            encoder.copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &staging,
+                    layout: wgpu::ImageDataLayout {
+                        offset: offset as u64,
+                        bytes_per_row: Some(padded_row(width) as u32),
+                        rows_per_image: Some(height),
+                    },
+                },
+                texture.as_image_copy(),
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+            offset += staged_size(&upload.image);
+            textures.push((upload.id, texture));
+        }
+        // Submitted now rather than with the frame, which may not get drawn (no swapchain image),
+        // so the textures are filled before anything is drawn with them either way. The mipmaps
+        // are drawn from the first levels after that.
+        gpu.queue.submit(Some(encoder.finish()));
+        for (id, texture) in textures {
+            if texture.mip_level_count() > 1 {
+                gpu.generate_mipmaps(&texture);
+            }
+            self.ready.insert(id, Ok(texture));
+        }
+    }
+}
+
+// Bytes per row in the staging buffer: copies out of buffers need rows of whole multiples of
+// COPY_BYTES_PER_ROW_ALIGNMENT.
+fn padded_row(width: u32) -> usize {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize;
+    (4 * width as usize).div_ceil(align) * align
+}
+
+fn staged_size(image: &image::RgbaImage) -> usize {
+    padded_row(image.width()) * image.height() as usize
+}
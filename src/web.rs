@@ -0,0 +1,79 @@
+// Browser-side setup for wasm builds: putting the canvas on the page and keeping it sized to it.
+use std::{cell::Cell, rc::Rc};
+use wasm_bindgen::{closure::Closure, JsCast};
+use winit::{dpi::LogicalSize, platform::web::WindowExtWebSys, window::Window};
+
+// Appends the window's canvas to the page body. With `fill_page` the body loses its margins and
+// scrollbars and is sized to the browser window, so the canvas can take all of it up.
+pub(crate) fn attach_canvas(window: &Window, fill_page: bool) {
+    let Some(body) = web_sys::window()
+        .and_then(|win| win.document())
+        .and_then(|doc| doc.body())
+    else {
+        log::error!("There's no document body to put the canvas in");
+        return;
+    };
+    let canvas = window.canvas();
+    if fill_page {
+        let _ = body
+            .style()
+            .set_property("margin", "0")
+            .and_then(|_| body.style().set_property("overflow", "hidden"))
+            .and_then(|_| body.style().set_property("width", "100vw"))
+            .and_then(|_| body.style().set_property("height", "100vh"));
+        let _ = canvas.style().set_property("display", "block");
+    }
+    if body.append_child(&web_sys::Element::from(canvas)).is_err() {
+        log::error!("Couldn't append the canvas to the document body");
+    }
+}
+
+// The browser window's inner size in CSS pixels.
+pub(crate) fn page_size() -> Option<LogicalSize<f64>> {
+    let win = web_sys::window()?;
+    let width = win.inner_width().ok()?.as_f64()?;
+    let height = win.inner_height().ok()?.as_f64()?;
+    Some(LogicalSize::new(width, height))
+}
+
+// Keeps track of the size of the element the canvas is in (the body, with fill_browser_window)
+// through a ResizeObserver, so the Engine can follow it without asking the page every frame.
+pub(crate) struct PageObserver {
+    observer: web_sys::ResizeObserver,
+    // Has to live as long as the observer can call it.
+    _callback: Closure<dyn FnMut(js_sys::Array)>,
+    // The latest size the browser reported, in CSS pixels.
+    size: Rc<Cell<LogicalSize<f64>>>,
+}
+
+impl PageObserver {
+    pub(crate) fn new(window: &Window) -> Option<Self> {
+        let parent = window.canvas().parent_element()?;
+        // Observers report once when they start, but only after the first frame or so.
+        let size = Rc::new(Cell::new(page_size()?));
+        let latest = size.clone();
+        let callback = Closure::<dyn FnMut(js_sys::Array)>::new(move |entries: js_sys::Array| {
+            let Ok(entry) = entries.at(-1).dyn_into::<web_sys::ResizeObserverEntry>() else {
+                return;
+            };
+            let rect = entry.content_rect();
+            latest.set(LogicalSize::new(rect.width(), rect.height()));
+        });
+        let observer = web_sys::ResizeObserver::new(callback.as_ref().unchecked_ref()).ok()?;
+        observer.observe(&parent);
+        Some(Self {
+            observer,
+            _callback: callback,
+            size,
+        })
+    }
+    pub(crate) fn size(&self) -> LogicalSize<f64> {
+        self.size.get()
+    }
+}
+
+impl Drop for PageObserver {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
@@ -0,0 +1,125 @@
+use crate::gpu::{color_attachment, depth_attachment};
+use crate::{Input, SpriteRender, WindowConfig, WGPU};
+use winit::window::Window;
+
+// Identifies a window opened with Engine::open_window. Handed out right away, even though the
+// window itself only appears on the next turn of the event loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SecondaryWindowId(pub(crate) u64);
+
+// An extra window (e.g. a tool palette) with its own surface, sprites, and input.
+pub struct SecondaryWindow {
+    pub sprites: SpriteRender,
+    pub input: Input,
+    id: SecondaryWindowId,
+    surface: wgpu::Surface,
+    surface_config: wgpu::SurfaceConfiguration,
+    msaa_view: Option<wgpu::TextureView>,
+    depth_view: Option<wgpu::TextureView>,
+    // Updated since it was last drawn, so the main window's next frame draws it too.
+    pub(crate) redraw: bool,
+    // Declared after the surface so that it's dropped first.
+    window: Window,
+}
+
+impl SecondaryWindow {
+    pub(crate) fn new(
+        gpu: &WGPU,
+        id: SecondaryWindowId,
+        config: &WindowConfig,
+        target: &winit::event_loop::EventLoopWindowTarget<()>,
+    ) -> Option<Self> {
+        let window = match config.builder().build(target) {
+            Ok(window) => window,
+            Err(e) => {
+                log::error!("Couldn't open window {:?}: {e}", config.title);
+                return None;
+            }
+        };
+        let (surface, surface_config) = gpu.create_window_surface(&window)?;
+        let mut window = Self {
+            sprites: SpriteRender::with_format(gpu, surface_config.format),
+            input: Input::default(),
+            id,
+            surface,
+            surface_config,
+            msaa_view: None,
+            depth_view: None,
+            redraw: false,
+            window,
+        };
+        window.recreate_render_targets(gpu);
+        Some(window)
+    }
+    pub fn id(&self) -> SecondaryWindowId {
+        self.id
+    }
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+    pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        winit::dpi::PhysicalSize::new(self.surface_config.width, self.surface_config.height)
+    }
+    pub(crate) fn winit_id(&self) -> winit::window::WindowId {
+        self.window.id()
+    }
+    pub(crate) fn resize(&mut self, gpu: &WGPU, size: winit::dpi::PhysicalSize<u32>) {
+        // A minimized window reports a zero size, which isn't a valid surface size.
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.surface_config.width = size.width;
+        self.surface_config.height = size.height;
+        self.surface.configure(&gpu.device, &self.surface_config);
+        self.recreate_render_targets(gpu);
+    }
+    fn recreate_render_targets(&mut self, gpu: &WGPU) {
+        let (width, height) = (self.surface_config.width, self.surface_config.height);
+        self.sprites.set_target_size(width, height);
+        let samples = self.sprites.sample_count();
+        self.msaa_view = gpu.create_msaa_view(self.surface_config.format, width, height, samples);
+        self.depth_view = gpu.create_depth_view(width, height, samples);
+    }
+    // Puts the window back together on the new device after the old one was lost.
+    pub(crate) fn recreate(&mut self, gpu: &WGPU) {
+        self.surface.configure(&gpu.device, &self.surface_config);
+        self.sprites.recreate(gpu);
+        self.recreate_render_targets(gpu);
+    }
+    // Draws the window into `encoder`, if it was updated since the last time, and returns the
+    // frame to present once the encoder has been submitted.
+    pub(crate) fn record(
+        &mut self,
+        gpu: &WGPU,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> Option<wgpu::SurfaceTexture> {
+        if !std::mem::take(&mut self.redraw) {
+            return None;
+        }
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::warn!("Skipping a frame of window {:?}: {e}", self.id);
+                self.surface.configure(&gpu.device, &self.surface_config);
+                return None;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.sprites.flush(gpu, encoder);
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(color_attachment(
+                    &view,
+                    self.msaa_view.as_ref(),
+                    wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                ))],
+                depth_stencil_attachment: depth_attachment(self.depth_view.as_ref()),
+            });
+            self.sprites.render(&mut rpass);
+        }
+        Some(frame)
+    }
+}